@@ -82,8 +82,7 @@ async fn pexec(db: &Db, file: &Path) -> Result<(), sqlx::Error> {
 	// -- Read the file.
 	let content = fs::read_to_string(file)?;
 
-	// FIXME: Make the split more sql proof.
-	let sqls: Vec<&str> = content.split(';').collect();
+	let sqls = split_sql_stmts(&content);
 
 	for sql in sqls {
 		sqlx::query(sql).execute(db).await.map_err(|e| {
@@ -96,6 +95,45 @@ async fn pexec(db: &Db, file: &Path) -> Result<(), sqlx::Error> {
 	Ok(())
 }
 
+/// Split the sql `content` on the `;` which are not in a dollar-quoted string
+/// (e.g., the `$$ ... $$` body of a plpgsql function).
+///
+/// Note: Still a simple split (e.g., the `;` in comments or quoted strings are not supported).
+fn split_sql_stmts(content: &str) -> Vec<&str> {
+	let mut sqls = Vec::new();
+	let mut dollar_tag: Option<&str> = None;
+	let mut start = 0;
+	let mut idx = 0;
+
+	while idx < content.len() {
+		let rest = &content[idx..];
+		if let Some(after_dollar) = rest.strip_prefix('$') {
+			// dollar quote tag, e.g., `$$` or `$body$`
+			let tag_len = after_dollar
+				.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+				.filter(|&end| rest[1 + end..].starts_with('$'))
+				.map(|end| end + 2);
+			if let Some(tag_len) = tag_len {
+				let tag = &rest[..tag_len];
+				match dollar_tag {
+					None => dollar_tag = Some(tag),
+					Some(open_tag) if open_tag == tag => dollar_tag = None,
+					Some(_) => (),
+				}
+				idx += tag_len;
+				continue;
+			}
+		} else if rest.starts_with(';') && dollar_tag.is_none() {
+			sqls.push(&content[start..idx]);
+			start = idx + 1;
+		}
+		idx += rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+	}
+	sqls.push(&content[start..]);
+
+	sqls
+}
+
 async fn new_db_pool(db_con_url: &str) -> Result<Db, sqlx::Error> {
 	PgPoolOptions::new()
 		.max_connections(1)
@@ -195,6 +195,7 @@ pub async fn seed_agent(
 		mm,
 		AgentForCreate {
 			name: name.to_string(),
+			..Default::default()
 		},
 	)
 	.await
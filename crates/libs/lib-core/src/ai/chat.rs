@@ -0,0 +1,158 @@
+use crate::ai::tool::ToolSpec;
+use crate::ai::Error;
+use crate::model::agent::AgentParams;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[derive(Debug, Clone, Default)]
+pub struct ChatRequest {
+	/// The system prompt (e.g., `AgentVersion.system_prompt`).
+	pub system: Option<String>,
+	/// The conversation messages, oldest first.
+	pub messages: Vec<ChatMessage>,
+	/// The tools the AI can call.
+	pub tools: Vec<ToolSpec>,
+	pub params: Option<AgentParams>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatMessage {
+	User(String),
+	Assistant(String),
+	System(String),
+	ToolCall(ToolCall),
+	ToolResult(ToolResult),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatResponse {
+	pub reply: ChatReply,
+	pub usage: ChatUsage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatReply {
+	/// The final reply content.
+	Content(String),
+	/// The tools to call before the AI can reply (with their results).
+	ToolCalls(Vec<ToolCall>),
+}
+
+/// The tokens of one AI call.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ChatUsage {
+	pub prompt_tokens: i64,
+	pub completion_tokens: i64,
+}
+
+impl ChatUsage {
+	pub fn total_tokens(&self) -> i64 {
+		self.prompt_tokens + self.completion_tokens
+	}
+}
+
+/// The price of a model, in millionths of USD (micros) per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+	pub prompt_micros_per_mtok: i64,
+	pub completion_micros_per_mtok: i64,
+}
+
+impl ModelPricing {
+	/// Returns the cost of the `usage` in micros (rounded up).
+	pub fn cost_micros(&self, usage: &ChatUsage) -> i64 {
+		let cost = i128::from(usage.prompt_tokens)
+			* i128::from(self.prompt_micros_per_mtok)
+			+ i128::from(usage.completion_tokens)
+				* i128::from(self.completion_micros_per_mtok);
+
+		i64::try_from((cost + 999_999) / 1_000_000).unwrap_or(i64::MAX)
+	}
+}
+
+/// The request to summarize the `messages`, following the `previous` summary
+/// (i.e., a rolling summary).
+#[derive(Debug, Clone, Default)]
+pub struct SummaryRequest {
+	pub previous: Option<String>,
+	pub messages: Vec<ChatMessage>,
+	/// The max tokens of the summary.
+	pub max_tokens: i64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SummaryResponse {
+	pub content: String,
+	pub usage: ChatUsage,
+}
+
+/// A tool call requested by the AI.
+/// (also the `ConvMsg` content of the `ToolCall` content type)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+	/// The id of the call (to match its `ToolResult`).
+	pub call_id: String,
+	/// The `Tool::name`.
+	pub name: String,
+	#[serde(default)]
+	pub args: Value,
+}
+
+/// The result of a `ToolCall`, either the `result` or the `error`.
+/// (also the `ConvMsg` content of the `ToolResult` content type)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolResult {
+	pub call_id: String,
+	pub name: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub result: Option<Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub error: Option<String>,
+}
+
+impl ChatMessage {
+	pub fn role(&self) -> &'static str {
+		match self {
+			ChatMessage::User(_) => "user",
+			ChatMessage::Assistant(_) => "assistant",
+			ChatMessage::System(_) => "system",
+			ChatMessage::ToolCall(_) => "tool_call",
+			ChatMessage::ToolResult(_) => "tool_result",
+		}
+	}
+
+	/// The text of the message, as given to the AI (json for the tool messages).
+	pub fn text(&self) -> String {
+		match self {
+			ChatMessage::User(content)
+			| ChatMessage::Assistant(content)
+			| ChatMessage::System(content) => content.clone(),
+			ChatMessage::ToolCall(tool_call) => {
+				serde_json::to_string(tool_call).unwrap_or_default()
+			}
+			ChatMessage::ToolResult(tool_result) => {
+				serde_json::to_string(tool_result).unwrap_or_default()
+			}
+		}
+	}
+}
+
+impl ToolResult {
+	pub fn from_result(call: &ToolCall, result: Value) -> Self {
+		Self {
+			call_id: call.call_id.clone(),
+			name: call.name.clone(),
+			result: Some(result),
+			error: None,
+		}
+	}
+
+	pub fn from_error(call: &ToolCall, error: Error) -> Self {
+		Self {
+			call_id: call.call_id.clone(),
+			name: call.name.clone(),
+			result: None,
+			error: Some(error.to_string()),
+		}
+	}
+}
@@ -0,0 +1,200 @@
+//! The `dev` provider, for development and tests (no network, deterministic replies).
+//!
+//! The `parrot` model:
+//!   - Replies the last user message content as is.
+//!   - But when the last user message is `/tool <name> <json args>` (e.g., `/tool calculator {"expr": "1+2"}`)
+//!     and the tool is in the request tools, it calls the tool.
+//!   - After tool results, it replies them as `<name>: <result json>` lines.
+//!   - But after tool errors, it calls the failed tools again (as a model may retry),
+//!     so a call which always fails ends with the reply steps max.
+//!   - Its summaries are the first words of each message, appended to the previous summary,
+//!     and truncated to their max tokens (keeping the most recent).
+//!   - The usage is computed with `ai::count_tokens`.
+
+use crate::ai::{
+	count_tokens, ChatMessage, ChatReply, ChatRequest, ChatResponse, ChatUsage,
+	Error, ModelPricing, Result, SummaryRequest, SummaryResponse, ToolCall,
+	ToolResult,
+};
+use serde_json::Value;
+use uuid::Uuid;
+
+pub(super) const PARROT_CONTEXT_WINDOW: i64 = 4096;
+
+/// The number of first words of each message in the parrot summaries.
+const PARROT_SUMMARY_WORDS: usize = 8;
+
+/// Note: Not free, so that the cost accounting can be tested.
+pub(super) const PARROT_PRICING: ModelPricing = ModelPricing {
+	prompt_micros_per_mtok: 1_000_000,
+	completion_micros_per_mtok: 2_000_000,
+};
+
+pub(super) fn exec_parrot_chat(chat_req: &ChatRequest) -> Result<ChatResponse> {
+	let reply = parrot_reply(chat_req)?;
+
+	let prompt_tokens = chat_req.system.as_deref().map(count_tokens).unwrap_or(0)
+		+ chat_req
+			.messages
+			.iter()
+			.map(|msg| count_tokens(&msg.text()))
+			.sum::<i64>();
+	let completion_tokens = match &reply {
+		ChatReply::Content(content) => count_tokens(content),
+		ChatReply::ToolCalls(tool_calls) => tool_calls
+			.iter()
+			.map(|tool_call| {
+				count_tokens(&serde_json::to_string(tool_call).unwrap_or_default())
+			})
+			.sum(),
+	};
+
+	Ok(ChatResponse {
+		reply,
+		usage: ChatUsage {
+			prompt_tokens,
+			completion_tokens,
+		},
+	})
+}
+
+pub(super) fn exec_parrot_summary(summary_req: &SummaryRequest) -> SummaryResponse {
+	let mut lines: Vec<String> = summary_req.previous.iter().cloned().collect();
+	for msg in summary_req.messages.iter() {
+		let text = msg.text();
+		let words: Vec<&str> =
+			text.split_whitespace().take(PARROT_SUMMARY_WORDS).collect();
+		lines.push(format!("{}: {}", msg.role(), words.join(" ")));
+	}
+	let content = lines.join("\n");
+
+	// Keep the most recent chars which fit in the max tokens (~4 chars per token).
+	let max_chars = usize::try_from(summary_req.max_tokens.max(1) * 4).unwrap_or(0);
+	let char_count = content.chars().count();
+	let content: String = content
+		.chars()
+		.skip(char_count.saturating_sub(max_chars))
+		.collect();
+
+	let prompt_tokens = summary_req
+		.previous
+		.as_deref()
+		.map(count_tokens)
+		.unwrap_or(0)
+		+ summary_req
+			.messages
+			.iter()
+			.map(|msg| count_tokens(&msg.text()))
+			.sum::<i64>();
+	let completion_tokens = count_tokens(&content);
+
+	SummaryResponse {
+		content,
+		usage: ChatUsage {
+			prompt_tokens,
+			completion_tokens,
+		},
+	}
+}
+
+fn parrot_reply(chat_req: &ChatRequest) -> Result<ChatReply> {
+	let last_tool_results: Vec<&ToolResult> = chat_req
+		.messages
+		.iter()
+		.rev()
+		.map_while(|msg| match msg {
+			ChatMessage::ToolResult(tool_result) => Some(tool_result),
+			_ => None,
+		})
+		.collect();
+
+	// -- Call again the failed tool calls
+	let retry_calls: Vec<ToolCall> = last_tool_results
+		.iter()
+		.rev()
+		.filter(|tool_result| tool_result.error.is_some())
+		.filter_map(|tool_result| {
+			chat_req.messages.iter().find_map(|msg| match msg {
+				ChatMessage::ToolCall(tool_call)
+					if tool_call.call_id == tool_result.call_id =>
+				{
+					Some(ToolCall {
+						call_id: new_call_id(),
+						..tool_call.clone()
+					})
+				}
+				_ => None,
+			})
+		})
+		.collect();
+	if !retry_calls.is_empty() {
+		return Ok(ChatReply::ToolCalls(retry_calls));
+	}
+
+	// -- Reply the tool results
+	let tool_results: Vec<String> = last_tool_results
+		.into_iter()
+		.map(|tool_result| {
+			let value = match &tool_result.result {
+				Some(result) => result.to_string(),
+				None => "null".to_string(),
+			};
+			format!("{}: {value}", tool_result.name)
+		})
+		.collect();
+	if !tool_results.is_empty() {
+		let content = tool_results
+			.into_iter()
+			.rev()
+			.collect::<Vec<_>>()
+			.join("\n");
+		return Ok(ChatReply::Content(content));
+	}
+
+	// -- Call the tool of the last user message `/tool ...`
+	if let Some(ChatMessage::User(content)) = chat_req.messages.last() {
+		if let Some(tool_call) = parse_tool_command(chat_req, content) {
+			return Ok(ChatReply::ToolCalls(vec![tool_call]));
+		}
+	}
+
+	// -- Otherwise, parrot the last user message
+	chat_req
+		.messages
+		.iter()
+		.rev()
+		.find_map(|msg| match msg {
+			ChatMessage::User(content) => Some(content.clone()),
+			_ => None,
+		})
+		.map(ChatReply::Content)
+		.ok_or(Error::ChatNoUserMessage)
+}
+
+/// Returns the `ToolCall` of a `/tool <name> <json args>` content,
+/// if the tool is in the request tools and the args are a valid json (or empty).
+fn parse_tool_command(chat_req: &ChatRequest, content: &str) -> Option<ToolCall> {
+	let command = content.trim().strip_prefix("/tool ")?.trim();
+	let (name, args) = command
+		.split_once(char::is_whitespace)
+		.unwrap_or((command, ""));
+
+	if !chat_req.tools.iter().any(|tool| tool.name == name) {
+		return None;
+	}
+
+	let args = match args.trim() {
+		"" => Value::Object(Default::default()),
+		args => serde_json::from_str(args).ok()?,
+	};
+
+	Some(ToolCall {
+		call_id: new_call_id(),
+		name: name.to_string(),
+		args,
+	})
+}
+
+fn new_call_id() -> String {
+	format!("call_{}", Uuid::new_v4().simple())
+}
@@ -0,0 +1,43 @@
+use serde::Serialize;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug, Serialize)]
+pub enum Error {
+	ProviderNotSupported {
+		ai_provider: String,
+		ai_model: String,
+	},
+	ChatNoUserMessage,
+
+	// -- Tool
+	ToolUnknown {
+		name: String,
+	},
+	ToolNotEnabled {
+		name: String,
+	},
+	ToolArgsInvalid {
+		tool: &'static str,
+		cause: String,
+	},
+	ToolExecFail {
+		tool: &'static str,
+		cause: String,
+	},
+}
+
+// region:    --- Error Boilerplate
+
+impl core::fmt::Display for Error {
+	fn fmt(
+		&self,
+		fmt: &mut core::fmt::Formatter,
+	) -> core::result::Result<(), core::fmt::Error> {
+		write!(fmt, "{self:?}")
+	}
+}
+
+impl std::error::Error for Error {}
+
+// endregion: --- Error Boilerplate
@@ -0,0 +1,85 @@
+//! AI Layer
+//!
+//! Design:
+//!
+//! - The AI layer abstracts the AI providers (e.g., `dev/parrot`) behind
+//!   a provider agnostic chat api (`ChatRequest` -> `ChatResponse`).
+//! - It does not persist anything. The Model layer builds the `ChatRequest`
+//!   from the `ConvMsg` history, and persists the responses
+//!   (see `ConvBmc::generate_reply`).
+//! - The agent tools (see `tool::Tool`) are called by the Model layer on the
+//!   `ChatReply::ToolCalls`, with the ctx of the user requesting the reply.
+//!
+
+// region:    --- Modules
+
+mod chat;
+mod dev;
+mod error;
+pub mod tool;
+
+pub use self::chat::{
+	ChatMessage, ChatReply, ChatRequest, ChatResponse, ChatUsage, ModelPricing,
+	SummaryRequest, SummaryResponse, ToolCall, ToolResult,
+};
+pub use self::error::{Error, Result};
+
+// endregion: --- Modules
+
+/// Execute the chat request with the `ai_provider` / `ai_model`
+/// (e.g., the `AgentVersion` ones).
+pub async fn exec_chat(
+	ai_provider: &str,
+	ai_model: &str,
+	chat_req: &ChatRequest,
+) -> Result<ChatResponse> {
+	match (ai_provider, ai_model) {
+		("dev", "parrot") => dev::exec_parrot_chat(chat_req),
+		_ => Err(Error::ProviderNotSupported {
+			ai_provider: ai_provider.to_string(),
+			ai_model: ai_model.to_string(),
+		}),
+	}
+}
+
+/// Summarize the messages with the `ai_provider` / `ai_model`
+/// (e.g., for the conv msgs which do not fit in the agent context).
+pub async fn exec_summary(
+	ai_provider: &str,
+	ai_model: &str,
+	summary_req: &SummaryRequest,
+) -> Result<SummaryResponse> {
+	match (ai_provider, ai_model) {
+		("dev", "parrot") => Ok(dev::exec_parrot_summary(summary_req)),
+		_ => Err(Error::ProviderNotSupported {
+			ai_provider: ai_provider.to_string(),
+			ai_model: ai_model.to_string(),
+		}),
+	}
+}
+
+/// Returns the context window, in tokens, of the `ai_provider` / `ai_model`
+/// (None if not supported).
+pub fn model_context_window(ai_provider: &str, ai_model: &str) -> Option<i64> {
+	match (ai_provider, ai_model) {
+		("dev", "parrot") => Some(dev::PARROT_CONTEXT_WINDOW),
+		_ => None,
+	}
+}
+
+/// Returns the pricing of the `ai_provider` / `ai_model` (None if not supported).
+pub fn model_pricing(ai_provider: &str, ai_model: &str) -> Option<ModelPricing> {
+	match (ai_provider, ai_model) {
+		("dev", "parrot") => Some(dev::PARROT_PRICING),
+		_ => None,
+	}
+}
+
+/// Returns the approximate number of tokens of the `text` (~4 chars per token).
+///
+/// Note: For the providers which do not report their usage (e.g., `dev/parrot`),
+///       and the estimates before the AI calls.
+pub fn count_tokens(text: &str) -> i64 {
+	let chars = text.chars().count() as i64;
+	(chars + 3) / 4
+}
@@ -0,0 +1,260 @@
+use crate::ai::tool::{parse_args, Tool, ToolFuture};
+use crate::ai::Error;
+use crate::ctx::Ctx;
+use crate::model::ModelManager;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const NAME: &str = "calculator";
+const EXPR_MAX_LEN: usize = 256;
+const EXPR_MAX_DEPTH: usize = 32;
+
+/// Evaluate an arithmetic expression.
+///
+/// Note: Sandboxed, as this is a dedicated arithmetic parser
+///       (numbers, `+ - * / % ^`, and parentheses), with bounded length and nesting.
+pub(super) struct CalculatorTool;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CalculatorArgs {
+	expr: String,
+}
+
+impl Tool for CalculatorTool {
+	fn name(&self) -> &'static str {
+		NAME
+	}
+
+	fn description(&self) -> &'static str {
+		"Evaluate an arithmetic expression with numbers, + - * / % ^ and parentheses \
+		 (e.g., `(1.5 + 2) * 3^2`)."
+	}
+
+	fn args_schema(&self) -> Value {
+		json!({
+			"type": "object",
+			"properties": {
+				"expr": { "type": "string", "maxLength": EXPR_MAX_LEN }
+			},
+			"required": ["expr"]
+		})
+	}
+
+	fn call<'a>(
+		&'a self,
+		_ctx: &'a Ctx,
+		_mm: &'a ModelManager,
+		args: Value,
+	) -> ToolFuture<'a> {
+		Box::pin(async move {
+			let CalculatorArgs { expr } = parse_args(NAME, args)?;
+
+			let result = eval(&expr)
+				.map_err(|cause| Error::ToolExecFail { tool: NAME, cause })?;
+
+			Ok(json!({ "expr": expr, "result": result }))
+		})
+	}
+}
+
+// region:    --- Evaluator
+
+fn eval(expr: &str) -> core::result::Result<f64, String> {
+	if expr.len() > EXPR_MAX_LEN {
+		return Err(format!("expression longer than {EXPR_MAX_LEN} characters"));
+	}
+
+	let mut parser = Parser {
+		chars: expr.chars().collect(),
+		pos: 0,
+		depth: 0,
+	};
+	let value = parser.parse_expr()?;
+	if let Some(c) = parser.peek() {
+		return Err(format!("unexpected '{c}' at {}", parser.pos));
+	}
+	if !value.is_finite() {
+		return Err("result is not a finite number".to_string());
+	}
+
+	Ok(value)
+}
+
+/// A recursive descent parser/evaluator of:
+///
+/// ```text
+/// expr  := term (('+' | '-') term)*
+/// term  := unary (('*' | '/' | '%') unary)*
+/// unary := ('-' | '+') unary | power
+/// power := atom ('^' unary)?
+/// atom  := number | '(' expr ')'
+/// ```
+struct Parser {
+	chars: Vec<char>,
+	pos: usize,
+	depth: usize,
+}
+
+impl Parser {
+	/// Returns the next non-whitespace char (without consuming it).
+	fn peek(&mut self) -> Option<char> {
+		while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+			self.pos += 1;
+		}
+		self.chars.get(self.pos).copied()
+	}
+
+	fn parse_expr(&mut self) -> core::result::Result<f64, String> {
+		let mut value = self.parse_term()?;
+		while let Some(op @ ('+' | '-')) = self.peek() {
+			self.pos += 1;
+			let rhs = self.parse_term()?;
+			value = if op == '+' { value + rhs } else { value - rhs };
+		}
+		Ok(value)
+	}
+
+	fn parse_term(&mut self) -> core::result::Result<f64, String> {
+		let mut value = self.parse_unary()?;
+		while let Some(op @ ('*' | '/' | '%')) = self.peek() {
+			self.pos += 1;
+			let rhs = self.parse_unary()?;
+			if op != '*' && rhs == 0.0 {
+				return Err("division by zero".to_string());
+			}
+			value = match op {
+				'*' => value * rhs,
+				'/' => value / rhs,
+				_ => value % rhs,
+			};
+		}
+		Ok(value)
+	}
+
+	fn parse_unary(&mut self) -> core::result::Result<f64, String> {
+		match self.peek() {
+			Some(op @ ('-' | '+')) => {
+				self.pos += 1;
+				let value = self.nested(Self::parse_unary)?;
+				Ok(if op == '-' { -value } else { value })
+			}
+			_ => self.parse_power(),
+		}
+	}
+
+	fn parse_power(&mut self) -> core::result::Result<f64, String> {
+		let base = self.parse_atom()?;
+		if self.peek() == Some('^') {
+			self.pos += 1;
+			let exp = self.nested(Self::parse_unary)?;
+			return Ok(base.powf(exp));
+		}
+		Ok(base)
+	}
+
+	fn parse_atom(&mut self) -> core::result::Result<f64, String> {
+		match self.peek() {
+			Some('(') => {
+				self.pos += 1;
+				let value = self.nested(Self::parse_expr)?;
+				if self.peek() != Some(')') {
+					return Err(format!("missing ')' at {}", self.pos));
+				}
+				self.pos += 1;
+				Ok(value)
+			}
+			Some(c) if c.is_ascii_digit() || c == '.' => {
+				let start = self.pos;
+				while self
+					.chars
+					.get(self.pos)
+					.is_some_and(|c| c.is_ascii_digit() || *c == '.')
+				{
+					self.pos += 1;
+				}
+				let number: String = self.chars[start..self.pos].iter().collect();
+				number
+					.parse::<f64>()
+					.map_err(|_| format!("invalid number '{number}'"))
+			}
+			Some(c) => Err(format!("unexpected '{c}' at {}", self.pos)),
+			None => Err("unexpected end of expression".to_string()),
+		}
+	}
+
+	/// Parse with `parse_fn` one nesting level deeper (bounded by `EXPR_MAX_DEPTH`).
+	fn nested(
+		&mut self,
+		parse_fn: fn(&mut Self) -> core::result::Result<f64, String>,
+	) -> core::result::Result<f64, String> {
+		if self.depth >= EXPR_MAX_DEPTH {
+			return Err(format!("expression nested deeper than {EXPR_MAX_DEPTH}"));
+		}
+		self.depth += 1;
+		let res = parse_fn(self);
+		self.depth -= 1;
+		res
+	}
+}
+
+// endregion: --- Evaluator
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+
+	#[test]
+	fn test_eval_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_cases = [
+			("1 + 2 * 3", 7.0),
+			("(1 + 2) * 3", 9.0),
+			("-2^2", -4.0),
+			("2^3^2", 512.0),
+			("10 % 4 - 1.5", 0.5),
+			("--3", 3.0),
+		];
+
+		// -- Exec & Check
+		for (fx_expr, fx_result) in fx_cases {
+			assert_eq!(eval(fx_expr)?, fx_result, "expr '{fx_expr}'");
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_eval_err() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_too_deep = format!("{}1{}", "(".repeat(40), ")".repeat(40));
+		let fx_too_long = "1+".repeat(200) + "1";
+		let fx_exprs = [
+			"",
+			"1 +",
+			"1 / 0",
+			"(1 + 2",
+			"2 ** 3",
+			"1.2.3",
+			"1 2",
+			"abs(1)",
+			"10^400",
+			fx_too_deep.as_str(),
+			fx_too_long.as_str(),
+		];
+
+		// -- Exec & Check
+		for fx_expr in fx_exprs {
+			assert!(eval(fx_expr).is_err(), "expr '{fx_expr}' should fail");
+		}
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
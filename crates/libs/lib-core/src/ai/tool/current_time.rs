@@ -0,0 +1,45 @@
+use crate::ai::tool::{parse_args, Tool, ToolFuture};
+use crate::ctx::Ctx;
+use crate::model::ModelManager;
+use lib_utils::time::{format_time, now_utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const NAME: &str = "get_current_time";
+
+/// Returns the current UTC time (RFC 3339).
+pub(super) struct CurrentTimeTool;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CurrentTimeArgs {}
+
+impl Tool for CurrentTimeTool {
+	fn name(&self) -> &'static str {
+		NAME
+	}
+
+	fn description(&self) -> &'static str {
+		"Get the current date and time (UTC, RFC 3339)."
+	}
+
+	fn args_schema(&self) -> Value {
+		json!({
+			"type": "object",
+			"properties": {}
+		})
+	}
+
+	fn call<'a>(
+		&'a self,
+		_ctx: &'a Ctx,
+		_mm: &'a ModelManager,
+		args: Value,
+	) -> ToolFuture<'a> {
+		Box::pin(async move {
+			let CurrentTimeArgs {} = parse_args(NAME, args)?;
+
+			Ok(json!({ "utc": format_time(now_utc()) }))
+		})
+	}
+}
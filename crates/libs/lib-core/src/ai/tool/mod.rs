@@ -0,0 +1,134 @@
+//! The agent tools, which the AI can call during a reply (see `ConvBmc::generate_reply`).
+//!
+//! Notes:
+//!   - The tools are enabled per agent (see `Agent.tools`), by their name in the `ToolRegistry`.
+//!   - A tool is called with the ctx of the user requesting the reply, so it can only
+//!     access what this user can access (e.g., `search_convs`).
+//!   - The tool errors do not fail the reply, they are given to the AI as the `ToolResult`.
+
+// region:    --- Modules
+
+mod calculator;
+mod current_time;
+mod search_convs;
+
+use crate::ai::{Error, Result, ToolCall, ToolResult};
+use crate::ctx::Ctx;
+use crate::model::ModelManager;
+use serde::Serialize;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::OnceLock;
+
+// endregion: --- Modules
+
+pub type ToolFuture<'a> = Pin<Box<dyn Future<Output = Result<Value>> + Send + 'a>>;
+
+pub trait Tool: Send + Sync {
+	/// The unique name (as enabled in `Agent.tools` and called by the AI).
+	fn name(&self) -> &'static str;
+
+	/// The description for the AI (when and how to use the tool).
+	fn description(&self) -> &'static str;
+
+	/// The json schema of the call `args`.
+	fn args_schema(&self) -> Value;
+
+	/// Note: Returns a boxed future so that the tools can be `dyn Tool` (see `ToolRegistry`).
+	fn call<'a>(
+		&'a self,
+		ctx: &'a Ctx,
+		mm: &'a ModelManager,
+		args: Value,
+	) -> ToolFuture<'a>;
+
+	fn spec(&self) -> ToolSpec {
+		ToolSpec {
+			name: self.name(),
+			description: self.description(),
+			args_schema: self.args_schema(),
+		}
+	}
+}
+
+/// The tool description given to the AI (in the `ChatRequest`).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolSpec {
+	pub name: &'static str,
+	pub description: &'static str,
+	pub args_schema: Value,
+}
+
+pub struct ToolRegistry {
+	tools: Vec<Box<dyn Tool>>,
+}
+
+impl ToolRegistry {
+	/// The registry of the built-in tools.
+	pub fn builtin() -> &'static ToolRegistry {
+		static INSTANCE: OnceLock<ToolRegistry> = OnceLock::new();
+
+		INSTANCE.get_or_init(|| ToolRegistry {
+			tools: vec![
+				Box::new(search_convs::SearchConvsTool),
+				Box::new(current_time::CurrentTimeTool),
+				Box::new(calculator::CalculatorTool),
+			],
+		})
+	}
+
+	pub fn get(&self, name: &str) -> Result<&dyn Tool> {
+		self.tools
+			.iter()
+			.find(|tool| tool.name() == name)
+			.map(|tool| tool.as_ref())
+			.ok_or_else(|| Error::ToolUnknown {
+				name: name.to_string(),
+			})
+	}
+
+	pub fn specs(&self) -> Vec<ToolSpec> {
+		self.tools.iter().map(|tool| tool.spec()).collect()
+	}
+
+	/// Call the tool of the `tool_call`.
+	///
+	/// Note: Never fails, as the errors are returned as the `ToolResult.error`.
+	pub async fn exec_call(
+		&self,
+		ctx: &Ctx,
+		mm: &ModelManager,
+		tool_call: &ToolCall,
+	) -> ToolResult {
+		let res = match self.get(&tool_call.name) {
+			Ok(tool) => tool.call(ctx, mm, tool_call.args.clone()).await,
+			Err(err) => Err(err),
+		};
+
+		match res {
+			Ok(result) => ToolResult::from_result(tool_call, result),
+			Err(err) => ToolResult::from_error(tool_call, err),
+		}
+	}
+}
+
+// region:    --- Support
+
+/// Deserialize the tool call `args` (a missing args being `{}`).
+fn parse_args<T>(tool: &'static str, args: Value) -> Result<T>
+where
+	T: serde::de::DeserializeOwned,
+{
+	let args = match args {
+		Value::Null => Value::Object(Default::default()),
+		args => args,
+	};
+
+	serde_json::from_value(args).map_err(|ex| Error::ToolArgsInvalid {
+		tool,
+		cause: ex.to_string(),
+	})
+}
+
+// endregion: --- Support
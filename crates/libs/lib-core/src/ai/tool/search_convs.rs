@@ -0,0 +1,84 @@
+use crate::ai::tool::{parse_args, Tool, ToolFuture};
+use crate::ai::Error;
+use crate::ctx::Ctx;
+use crate::model::conv::ConvBmc;
+use crate::model::ModelManager;
+use modql::filter::ListOptions;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const NAME: &str = "search_convs";
+const SEARCH_LIMIT_DEFAULT: i64 = 5;
+const SEARCH_LIMIT_MAX: i64 = 20;
+
+/// Full-text search of the convs of the ctx user (see `ConvBmc::search_msgs`).
+pub(super) struct SearchConvsTool;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SearchConvsArgs {
+	query: String,
+	limit: Option<i64>,
+}
+
+impl Tool for SearchConvsTool {
+	fn name(&self) -> &'static str {
+		NAME
+	}
+
+	fn description(&self) -> &'static str {
+		"Search the messages and titles of the user conversations. \
+		 The query is in the web search syntax (e.g., `rust \"web app\" -java`)."
+	}
+
+	fn args_schema(&self) -> Value {
+		json!({
+			"type": "object",
+			"properties": {
+				"query": { "type": "string" },
+				"limit": { "type": "integer", "minimum": 1, "maximum": SEARCH_LIMIT_MAX }
+			},
+			"required": ["query"]
+		})
+	}
+
+	fn call<'a>(
+		&'a self,
+		ctx: &'a Ctx,
+		mm: &'a ModelManager,
+		args: Value,
+	) -> ToolFuture<'a> {
+		Box::pin(async move {
+			let SearchConvsArgs { query, limit } = parse_args(NAME, args)?;
+			let limit = limit
+				.unwrap_or(SEARCH_LIMIT_DEFAULT)
+				.clamp(1, SEARCH_LIMIT_MAX);
+
+			let list_options = ListOptions {
+				limit: Some(limit),
+				..Default::default()
+			};
+			let page = ConvBmc::search_msgs(ctx, mm, &query, Some(list_options))
+				.await
+				.map_err(|err| Error::ToolExecFail {
+					tool: NAME,
+					cause: err.to_string(),
+				})?;
+
+			let hits: Vec<Value> = page
+				.items
+				.into_iter()
+				.map(|hit| {
+					json!({
+						"conv_id": hit.conv.id,
+						"conv_title": hit.conv.title,
+						"msg_id": hit.msg.map(|msg| msg.id),
+						"snippet": hit.snippet.or(hit.title_snippet),
+					})
+				})
+				.collect();
+
+			Ok(json!({ "total": page.total, "hits": hits }))
+		})
+	}
+}
@@ -1,3 +1,4 @@
+pub mod ai;
 pub mod config;
 pub mod ctx;
 pub mod model;
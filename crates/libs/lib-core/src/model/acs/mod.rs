@@ -2,4 +2,239 @@
 //!
 //! `acs` Access Control System based on PBAC (Privilege Based Access Control)
 //!
+//! For now, only the coarse "sys" requirement (root or `UserTyp::Sys` users), the
+//! conv membership (owner or `ConvUser`), the agent visibility (`AgentVisibility`),
+//! and the conv / agent / user owned entity ownership are supported.
+//!
 //! (more to come)
+
+use crate::ctx::Ctx;
+use crate::model::user::{User, UserBmc, UserTyp};
+use crate::model::ModelManager;
+use crate::model::{Error, Result};
+
+/// The privileges which can be required by the Bmc methods
+/// (e.g., `#[bmc(privileges(delete = Sys))]` of the `derive(Bmc)`).
+#[derive(Debug, Clone, Copy)]
+pub enum Privilege {
+	/// Root or `UserTyp::Sys` users.
+	Sys,
+}
+
+/// Assert that the ctx user has the `privilege`.
+pub(in crate::model) async fn assert_privilege(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	privilege: Privilege,
+) -> Result<()> {
+	match privilege {
+		Privilege::Sys => assert_sys_user(ctx, mm).await,
+	}
+}
+
+/// Assert that the ctx user is root (`UserTyp::Sys` users are denied).
+pub(in crate::model) fn assert_root(ctx: &Ctx) -> Result<()> {
+	let user_id = ctx.user_id();
+	if user_id == 0 {
+		Ok(())
+	} else {
+		Err(Error::AccessDenied {
+			user_id,
+			required: "root",
+		})
+	}
+}
+
+/// Assert that the ctx user is root or a `UserTyp::Sys` user.
+pub(in crate::model) async fn assert_sys_user(
+	ctx: &Ctx,
+	mm: &ModelManager,
+) -> Result<()> {
+	let user_id = ctx.user_id();
+
+	// root ctx
+	if user_id == 0 {
+		return Ok(());
+	}
+
+	let user: User = UserBmc::get(ctx, mm, user_id).await?;
+	match user.typ {
+		UserTyp::Sys => Ok(()),
+		UserTyp::User => Err(Error::AccessDenied {
+			user_id,
+			required: "sys",
+		}),
+	}
+}
+
+/// Returns the SQL condition of the convs visible by the user of the `user_param`
+/// (e.g., `$2`), for the queries on the `conv` table.
+///
+/// Visible: owned, or with a `ConvUser` for the user.
+pub(in crate::model) fn conv_visible_sql(user_param: &str) -> String {
+	format!(
+		r#"(conv.owner_id = {user_param}
+		OR EXISTS (
+			SELECT 1 FROM conv_user cu
+			WHERE cu.conv_id = conv.id AND cu.user_id = {user_param}))"#
+	)
+}
+
+/// Assert that the ctx user is root, the owner of the conv, or one of its `ConvUser`.
+pub(in crate::model) async fn assert_conv_user(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	conv_id: i64,
+) -> Result<()> {
+	let user_id = ctx.user_id();
+
+	// root ctx
+	if user_id == 0 {
+		return Ok(());
+	}
+
+	let sql = format!(
+		"SELECT EXISTS (SELECT 1 FROM conv WHERE conv.id = $1 AND {})",
+		conv_visible_sql("$2")
+	);
+	let sqlx_query = sqlx::query_as::<_, (bool,)>(&sql)
+		.bind(conv_id)
+		.bind(user_id);
+	let (is_conv_user,) = mm.dbx().fetch_one(sqlx_query).await?;
+
+	if is_conv_user {
+		Ok(())
+	} else {
+		Err(Error::AccessDenied {
+			user_id,
+			required: "conv_user",
+		})
+	}
+}
+
+/// Assert that the ctx user is root or the owner of the conv.
+pub(in crate::model) async fn assert_conv_owner(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	conv_id: i64,
+) -> Result<()> {
+	let user_id = ctx.user_id();
+
+	// root ctx
+	if user_id == 0 {
+		return Ok(());
+	}
+
+	let sqlx_query = sqlx::query_as::<_, (bool,)>(
+		"SELECT EXISTS (SELECT 1 FROM conv WHERE id = $1 AND owner_id = $2)",
+	)
+	.bind(conv_id)
+	.bind(user_id);
+	let (is_conv_owner,) = mm.dbx().fetch_one(sqlx_query).await?;
+
+	if is_conv_owner {
+		Ok(())
+	} else {
+		Err(Error::AccessDenied {
+			user_id,
+			required: "conv_owner",
+		})
+	}
+}
+
+/// Returns the SQL condition of the agents visible by the user of the `user_param`
+/// (e.g., `$2`), for the queries on the `agent` table.
+///
+/// Visible: owned, `Public`, `Org` of the user org, or `Shared` / `Org` with
+/// an `agent_share` for the user.
+pub(in crate::model) fn agent_visible_sql(user_param: &str) -> String {
+	format!(
+		r#"(agent.owner_id = {user_param}
+		OR agent.visibility = 'Public'
+		OR (agent.visibility IN ('Shared', 'Org') AND EXISTS (
+			SELECT 1 FROM agent_share s
+			WHERE s.agent_id = agent.id AND s.user_id = {user_param}))
+		OR (agent.visibility = 'Org' AND EXISTS (
+			SELECT 1 FROM "user" o JOIN "user" u ON u.org_id = o.org_id
+			WHERE o.id = agent.owner_id AND u.id = {user_param})))"#
+	)
+}
+
+/// Assert that the ctx user is root, or can see and use the agent
+/// (see `agent_visible_sql`).
+pub(in crate::model) async fn assert_agent_user(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	agent_id: i64,
+) -> Result<()> {
+	let user_id = ctx.user_id();
+
+	// root ctx
+	if user_id == 0 {
+		return Ok(());
+	}
+
+	let sql = format!(
+		"SELECT EXISTS (SELECT 1 FROM agent WHERE agent.id = $1 AND {})",
+		agent_visible_sql("$2")
+	);
+	let sqlx_query = sqlx::query_as::<_, (bool,)>(&sql)
+		.bind(agent_id)
+		.bind(user_id);
+	let (is_agent_user,) = mm.dbx().fetch_one(sqlx_query).await?;
+
+	if is_agent_user {
+		Ok(())
+	} else {
+		Err(Error::AccessDenied {
+			user_id,
+			required: "agent_user",
+		})
+	}
+}
+
+/// Assert that the ctx user is root or the owner of the agent.
+pub(in crate::model) async fn assert_agent_owner(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	agent_id: i64,
+) -> Result<()> {
+	let user_id = ctx.user_id();
+
+	// root ctx
+	if user_id == 0 {
+		return Ok(());
+	}
+
+	let sqlx_query = sqlx::query_as::<_, (bool,)>(
+		"SELECT EXISTS (SELECT 1 FROM agent WHERE id = $1 AND owner_id = $2)",
+	)
+	.bind(agent_id)
+	.bind(user_id);
+	let (is_agent_owner,) = mm.dbx().fetch_one(sqlx_query).await?;
+
+	if is_agent_owner {
+		Ok(())
+	} else {
+		Err(Error::AccessDenied {
+			user_id,
+			required: "agent_owner",
+		})
+	}
+}
+
+/// Assert that the ctx user is root or the `owner_id` of a user owned entity
+/// (e.g., `Tag`, `Folder`).
+pub(in crate::model) fn assert_owner(
+	ctx: &Ctx,
+	owner_id: i64,
+	required: &'static str,
+) -> Result<()> {
+	let user_id = ctx.user_id();
+
+	if user_id == 0 || user_id == owner_id {
+		Ok(())
+	} else {
+		Err(Error::AccessDenied { user_id, required })
+	}
+}
@@ -1,22 +1,66 @@
+use crate::ai::tool::ToolRegistry;
 use crate::ctx::Ctx;
-use crate::generate_common_bmc_fns;
+use crate::model::acs::assert_agent_user;
+use crate::model::agent_share::agent_visible_to_sea_condition;
 use crate::model::base::{self, DbBmc};
 use crate::model::modql_utils::time_to_sea_value;
 use crate::model::ModelManager;
 use crate::model::Result;
+use lib_macros::Bmc;
 use lib_utils::time::Rfc3339;
-use modql::field::Fields;
-use modql::filter::{FilterNodes, OpValsString, OpValsValue};
-use modql::filter::{ListOptions, OpValsInt64};
+use modql::field::{Fields, SeaFieldValue};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString, OpValsValue};
+use sea_query::Nullable;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use serde_with::serde_as;
 use sqlx::types::time::OffsetDateTime;
 use sqlx::FromRow;
 
+const AGENT_PARAMS_MAX_TOKENS: u32 = 1_000_000;
+const AGENT_PARAMS_MAX_STOPS: usize = 4;
+const AGENT_CONTEXT_MIN_TOKENS: u32 = 64;
+const AGENT_CONTEXT_SUMMARY_MIN_TOKENS: u32 = 16;
+const AGENT_STARTER_PROMPTS_MAX: usize = 8;
+const AGENT_STARTER_PROMPT_MAX_CHARS: usize = 512;
+
 // region:    --- Agent Types
 
+/// Note: The `derive(Bmc)` generates the `AgentBmc` with the default CRUD functions,
+///       and the `generate_agent_rpc_fns!()` for the rpc handlers.
+///
+/// Access: The default CRUD functions do not check the agent access, so the rpc handlers
+///         use the checked functions of `model::agent_share` (e.g., `AgentBmc::get_visible`,
+///         `AgentBmc::update_owned`), following the `Agent.visibility`.
+///
+/// Versioning: The agent row is the current configuration, and each change of the
+///             configuration (ai provider/model, system prompt, params, tools, context)
+///             creates a new immutable `AgentVersion` (by the db triggers, see `agent_version`
+///             in the schema), so the convs keep the version they ran with
+///             (`Conv.agent_version_id`).
 #[serde_as]
-#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+#[derive(Debug, Clone, Fields, FromRow, Serialize, Bmc)]
+#[bmc(
+	table = "agent",
+	owner_id,
+	for_create = AgentForCreate,
+	for_update = AgentForUpdate,
+	filter = AgentFilter,
+	privileges(update_by_filter = Sys),
+	rpc(
+		suffix = "agent",
+		plural = "agents",
+		skip(
+			get_agent,
+			list_agents,
+			count_agents,
+			update_agent,
+			update_many_agents,
+			delete_agent,
+			delete_many_agents
+		)
+	)
+)]
 pub struct Agent {
 	pub id: i64,
 
@@ -25,8 +69,29 @@ pub struct Agent {
 
 	// -- Properties
 	pub name: String,
+	pub visibility: AgentVisibility,
 	pub ai_provider: String,
 	pub ai_model: String,
+	/// The system prompt / instructions.
+	pub system_prompt: Option<String>,
+	#[sqlx(json(nullable))]
+	pub params: Option<AgentParams>,
+	/// The names of the enabled tools (see `ai::tool::ToolRegistry`).
+	#[sqlx(json)]
+	pub tools: AgentTools,
+	#[sqlx(json(nullable))]
+	pub context: Option<AgentContext>,
+	/// The suggested first user msgs of its convs (not part of the versioned configuration).
+	#[sqlx(json)]
+	pub starter_prompts: AgentStarterPrompts,
+	/// The current `AgentVersion.version` (starts at 1).
+	pub version: i32,
+
+	/// If the agent is pinned by the ctx user (only set by `AgentBmc::list_page_pinned_first`).
+	#[field(skip)]
+	#[sqlx(skip)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub pinned: Option<bool>,
 
 	// -- Timestamps
 	//    (creator and last modified user_id/time)
@@ -38,20 +103,405 @@ pub struct Agent {
 	pub mtime: OffsetDateTime,
 }
 
-#[derive(Fields, Deserialize)]
+#[derive(Fields, Deserialize, Default)]
 pub struct AgentForCreate {
 	pub name: String,
+	/// Default: `Private`
+	#[field(cast_as = "agent_visibility")]
+	pub visibility: Option<AgentVisibility>,
+	pub system_prompt: Option<String>,
+	pub params: Option<AgentParams>,
+	pub tools: Option<AgentTools>,
+	pub context: Option<AgentContext>,
+	pub starter_prompts: Option<AgentStarterPrompts>,
 }
 
-#[derive(Fields, Deserialize)]
+/// Note: Changing the `system_prompt`, `params`, `tools`, or `context` creates a new `AgentVersion`.
+#[derive(Fields, Deserialize, Default)]
 pub struct AgentForUpdate {
 	pub name: Option<String>,
+	/// Note: As the `starter_prompts`, not part of the configuration
+	///       (does not create a new `AgentVersion`).
+	#[field(cast_as = "agent_visibility")]
+	pub visibility: Option<AgentVisibility>,
+	pub system_prompt: Option<String>,
+	pub params: Option<AgentParams>,
+	pub tools: Option<AgentTools>,
+	pub context: Option<AgentContext>,
+	pub starter_prompts: Option<AgentStarterPrompts>,
+}
+
+/// Who, besides its owner (and root), can see the agent, start convs with it, and clone it.
+///
+/// Note: The `agent_share` users only apply to the `Shared` and `Org` visibilities,
+///       so a `Private` agent keeps its shares for when it is shared again.
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	PartialEq,
+	sqlx::Type,
+	SeaFieldValue,
+	derive_more::Display,
+	Deserialize,
+	Serialize,
+)]
+#[sqlx(type_name = "agent_visibility")]
+pub enum AgentVisibility {
+	Private,
+	/// The users of the `agent_share` of the agent.
+	Shared,
+	/// The users of the owner org (and the `agent_share` users).
+	Org,
+	/// All the users.
+	Public,
+}
+
+/// The names of the tools enabled for the agent (stored as a json array).
+///
+/// Note: Validated on deserialization (e.g., rpc params), the names must be
+///       in the `ToolRegistry::builtin()`, without duplicates.
+///       So, removing a built-in tool requires removing it from the agents first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct AgentTools(Vec<String>);
+
+impl AgentTools {
+	pub fn names(&self) -> &[String] {
+		&self.0
+	}
+}
+
+impl TryFrom<Vec<String>> for AgentTools {
+	type Error = String;
+
+	fn try_from(names: Vec<String>) -> core::result::Result<Self, Self::Error> {
+		let registry = ToolRegistry::builtin();
+		for (idx, name) in names.iter().enumerate() {
+			if registry.get(name).is_err() {
+				return Err(format!("invalid agent tools - unknown tool '{name}'"));
+			}
+			if names[..idx].contains(name) {
+				return Err(format!(
+					"invalid agent tools - duplicate tool '{name}'"
+				));
+			}
+		}
+
+		Ok(AgentTools(names))
+	}
+}
+
+/// Note: Manual implementation (json), required for a modql::field::Fields.
+impl From<AgentTools> for sea_query::Value {
+	fn from(val: AgentTools) -> Self {
+		sea_query::Value::Json(Some(Box::new(val.0.into())))
+	}
+}
+
+/// Note: Manual implementation (see `ConvKind`).
+impl Nullable for AgentTools {
+	fn null() -> sea_query::Value {
+		sea_query::Value::Json(None)
+	}
+}
+
+/// The starter prompts of the agent (stored as a json array).
+///
+/// Note: Validated on deserialization (e.g., rpc params), at most 8 non-empty
+///       prompts of at most 512 chars.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<String>")]
+pub struct AgentStarterPrompts(Vec<String>);
+
+impl AgentStarterPrompts {
+	pub fn prompts(&self) -> &[String] {
+		&self.0
+	}
+}
+
+impl TryFrom<Vec<String>> for AgentStarterPrompts {
+	type Error = String;
+
+	fn try_from(prompts: Vec<String>) -> core::result::Result<Self, Self::Error> {
+		if prompts.len() > AGENT_STARTER_PROMPTS_MAX {
+			return Err(format!(
+				"invalid agent starter prompts - more than {AGENT_STARTER_PROMPTS_MAX}"
+			));
+		}
+		for prompt in prompts.iter() {
+			if prompt.trim().is_empty()
+				|| prompt.chars().count() > AGENT_STARTER_PROMPT_MAX_CHARS
+			{
+				return Err(format!(
+					"invalid agent starter prompts - must be non-empty and at most {AGENT_STARTER_PROMPT_MAX_CHARS} chars"
+				));
+			}
+		}
+
+		Ok(AgentStarterPrompts(prompts))
+	}
+}
+
+/// Note: Manual implementation (json), required for a modql::field::Fields.
+impl From<AgentStarterPrompts> for sea_query::Value {
+	fn from(val: AgentStarterPrompts) -> Self {
+		sea_query::Value::Json(Some(Box::new(val.0.into())))
+	}
+}
+
+/// Note: Manual implementation (see `ConvKind`).
+impl Nullable for AgentStarterPrompts {
+	fn null() -> sea_query::Value {
+		sea_query::Value::Json(None)
+	}
+}
+
+/// The generation parameters of the agent (stored as json).
+///
+/// Notes:
+///   - Validated on deserialization (e.g., rpc params), with the unknown
+///     properties rejected (the provider specific ones go in `provider`).
+///   - All the properties are optional (provider defaults).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "AgentParamsRaw")]
+pub struct AgentParams {
+	/// 0.0 to 2.0
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub temperature: Option<f64>,
+	/// 0.0 to 1.0
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub top_p: Option<f64>,
+	/// The max number of generated tokens.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_tokens: Option<u32>,
+	/// The stop sequences (max 4).
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub stop: Option<Vec<String>>,
+	/// The other provider specific parameters.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub provider: Option<Map<String, Value>>,
+}
+
+impl AgentParams {
+	/// Returns the description of the first invalid param, if any.
+	pub fn invalid_reason(&self) -> Option<String> {
+		if let Some(temperature) = self.temperature {
+			if !(0.0..=2.0).contains(&temperature) {
+				return Some(format!("temperature {temperature} not in 0.0..=2.0"));
+			}
+		}
+		if let Some(top_p) = self.top_p {
+			if !(0.0..=1.0).contains(&top_p) {
+				return Some(format!("top_p {top_p} not in 0.0..=1.0"));
+			}
+		}
+		if let Some(max_tokens) = self.max_tokens {
+			if max_tokens == 0 || max_tokens > AGENT_PARAMS_MAX_TOKENS {
+				return Some(format!(
+					"max_tokens {max_tokens} not in 1..={AGENT_PARAMS_MAX_TOKENS}"
+				));
+			}
+		}
+		if let Some(stop) = self.stop.as_ref() {
+			if stop.len() > AGENT_PARAMS_MAX_STOPS
+				|| stop.iter().any(|s| s.is_empty())
+			{
+				return Some(format!(
+					"stop must have at most {AGENT_PARAMS_MAX_STOPS} non-empty sequences"
+				));
+			}
+		}
+
+		None
+	}
+}
+
+/// The unvalidated `AgentParams` (for its `serde(try_from)`).
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AgentParamsRaw {
+	temperature: Option<f64>,
+	top_p: Option<f64>,
+	max_tokens: Option<u32>,
+	stop: Option<Vec<String>>,
+	provider: Option<Map<String, Value>>,
+}
+
+impl TryFrom<AgentParamsRaw> for AgentParams {
+	type Error = String;
+
+	fn try_from(raw: AgentParamsRaw) -> core::result::Result<Self, Self::Error> {
+		let params = AgentParams {
+			temperature: raw.temperature,
+			top_p: raw.top_p,
+			max_tokens: raw.max_tokens,
+			stop: raw.stop,
+			provider: raw.provider,
+		};
+
+		match params.invalid_reason() {
+			Some(reason) => Err(format!("invalid agent params - {reason}")),
+			None => Ok(params),
+		}
+	}
+}
+
+/// Note: Manual implementation (json), required for a modql::field::Fields.
+impl From<AgentParams> for sea_query::Value {
+	fn from(val: AgentParams) -> Self {
+		// Note: Cannot fail, as all the AgentParams types serialize to json.
+		let json = serde_json::to_value(val).unwrap_or_default();
+		sea_query::Value::Json(Some(Box::new(json)))
+	}
+}
+
+/// Note: Manual implementation (see `ConvKind`).
+impl Nullable for AgentParams {
+	fn null() -> sea_query::Value {
+		sea_query::Value::Json(None)
+	}
+}
+
+/// How the conv msgs which do not fit in the agent context are handled
+/// (see `model::conv_context`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum ContextStrategy {
+	/// The oldest msgs are dropped.
+	#[default]
+	Truncate,
+	/// The oldest msgs are replaced by a rolling summary (`ConvSummary`).
+	Summarize,
+}
+
+/// The context window configuration of the agent (stored as json).
+///
+/// Note: Validated on deserialization (e.g., rpc params), as the `AgentParams`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "AgentContextRaw")]
+pub struct AgentContext {
+	/// The max prompt tokens (default: the model context window).
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub max_tokens: Option<u32>,
+	pub strategy: ContextStrategy,
+	/// The max tokens of the summary (default: a quarter of the max prompt tokens).
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub summary_max_tokens: Option<u32>,
+}
+
+impl AgentContext {
+	/// Returns the description of the first invalid property, if any.
+	pub fn invalid_reason(&self) -> Option<String> {
+		if let Some(max_tokens) = self.max_tokens {
+			if max_tokens < AGENT_CONTEXT_MIN_TOKENS {
+				return Some(format!(
+					"max_tokens {max_tokens} lower than {AGENT_CONTEXT_MIN_TOKENS}"
+				));
+			}
+		}
+		if let Some(summary_max_tokens) = self.summary_max_tokens {
+			if summary_max_tokens < AGENT_CONTEXT_SUMMARY_MIN_TOKENS {
+				return Some(format!(
+					"summary_max_tokens {summary_max_tokens} lower than {AGENT_CONTEXT_SUMMARY_MIN_TOKENS}"
+				));
+			}
+			if let Some(max_tokens) = self.max_tokens {
+				if summary_max_tokens >= max_tokens / 2 {
+					return Some(format!(
+						"summary_max_tokens {summary_max_tokens} not lower than half of max_tokens {max_tokens}"
+					));
+				}
+			}
+		}
+
+		None
+	}
+}
+
+/// The unvalidated `AgentContext` (for its `serde(try_from)`).
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AgentContextRaw {
+	max_tokens: Option<u32>,
+	#[serde(default)]
+	strategy: ContextStrategy,
+	summary_max_tokens: Option<u32>,
+}
+
+impl TryFrom<AgentContextRaw> for AgentContext {
+	type Error = String;
+
+	fn try_from(raw: AgentContextRaw) -> core::result::Result<Self, Self::Error> {
+		let context = AgentContext {
+			max_tokens: raw.max_tokens,
+			strategy: raw.strategy,
+			summary_max_tokens: raw.summary_max_tokens,
+		};
+
+		match context.invalid_reason() {
+			Some(reason) => Err(format!("invalid agent context - {reason}")),
+			None => Ok(context),
+		}
+	}
+}
+
+/// Note: Manual implementation (json), required for a modql::field::Fields.
+impl From<AgentContext> for sea_query::Value {
+	fn from(val: AgentContext) -> Self {
+		// Note: Cannot fail, as all the AgentContext types serialize to json.
+		let json = serde_json::to_value(val).unwrap_or_default();
+		sea_query::Value::Json(Some(Box::new(json)))
+	}
+}
+
+/// Note: Manual implementation (see `ConvKind`).
+impl Nullable for AgentContext {
+	fn null() -> sea_query::Value {
+		sea_query::Value::Json(None)
+	}
+}
+
+/// An immutable snapshot of the agent configuration.
+/// (the `cid`/`ctime` are the user and time of the agent change)
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct AgentVersion {
+	pub id: i64,
+
+	// -- Relations
+	pub agent_id: i64,
+
+	// -- Properties
+	pub version: i32,
+	pub ai_provider: String,
+	pub ai_model: String,
+	pub system_prompt: Option<String>,
+	#[sqlx(json(nullable))]
+	pub params: Option<AgentParams>,
+	#[sqlx(json)]
+	pub tools: AgentTools,
+	#[sqlx(json(nullable))]
+	pub context: Option<AgentContext>,
+
+	// -- Timestamps
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
 }
 
-#[derive(FilterNodes, Default, Deserialize)]
+#[derive(FilterNodes, Default, Deserialize, Clone)]
 pub struct AgentFilter {
 	pub id: Option<OpValsInt64>,
+	pub owner_id: Option<OpValsInt64>,
 	pub name: Option<OpValsString>,
+	#[modql(cast_as = "agent_visibility")]
+	pub visibility: Option<OpValsString>,
+
+	/// The agents visible by the user id
+	/// (overwritten by `AgentBmc::visible_filters`, and not deserialized).
+	#[serde(skip)]
+	#[modql(to_sea_condition_fn = "agent_visible_to_sea_condition")]
+	pub visible_to: Option<OpValsValue>,
 
 	pub cid: Option<OpValsInt64>,
 	#[modql(to_sea_value_fn = "time_to_sea_value")]
@@ -63,28 +513,58 @@ pub struct AgentFilter {
 
 // endregion: --- Agent Types
 
-// region:    --- AgentBmc
+// region:    --- AgentVersionBmc
 
-pub struct AgentBmc;
+pub struct AgentVersionBmc;
 
-impl DbBmc for AgentBmc {
-	const TABLE: &'static str = "agent";
+impl DbBmc for AgentVersionBmc {
+	const TABLE: &'static str = "agent_version";
 
-	fn has_owner_id() -> bool {
-		true
+	// Note: Created by the db triggers (no mid/mtime, immutable).
+	fn has_timestamps() -> bool {
+		false
+	}
+
+	fn has_audit_log() -> bool {
+		false
 	}
 }
 
-// This will generate the `impl AgentBmc {...}` with the default CRUD functions.
-generate_common_bmc_fns!(
-	Bmc: AgentBmc,
-	Entity: Agent,
-	ForCreate: AgentForCreate,
-	ForUpdate: AgentForUpdate,
-	Filter: AgentFilter,
-);
+// Additional AgentBmc methods to read the `AgentVersion`
+// (which are only created by the agent changes).
+impl AgentBmc {
+	pub async fn get_version(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		agent_version_id: i64,
+	) -> Result<AgentVersion> {
+		let version: AgentVersion =
+			base::get::<AgentVersionBmc, _>(ctx, mm, agent_version_id).await?;
+		assert_agent_user(ctx, mm, version.agent_id).await?;
+
+		Ok(version)
+	}
+
+	/// Returns the versions of the agent (ordered by version).
+	pub async fn list_versions(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		agent_id: i64,
+	) -> Result<Vec<AgentVersion>> {
+		// Note: Make sure the agent exists (EntityNotFound otherwise).
+		Self::get(ctx, mm, agent_id).await?;
+		assert_agent_user(ctx, mm, agent_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, AgentVersion>(
+			"SELECT * FROM agent_version WHERE agent_id = $1 ORDER BY version",
+		)
+		.bind(agent_id);
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+}
 
-// endregion: --- AgentBmc
+// endregion: --- AgentVersionBmc
 
 // region:    --- Tests
 
@@ -94,8 +574,13 @@ mod tests {
 	type Result<T> = core::result::Result<T, Error>; // For tests.
 
 	use super::*;
-	use crate::_dev_utils::{self, clean_agents, seed_agent, seed_agents};
+	use crate::_dev_utils::{
+		self, clean_agents, clean_users, seed_agent, seed_agents, seed_user,
+	};
+	use crate::ctx::Ctx;
 	use crate::model;
+	use crate::model::conv::ConvBmc;
+	use modql::filter::{ListOptions, OpValString};
 	use serde_json::json;
 	use serial_test::serial;
 
@@ -110,6 +595,7 @@ mod tests {
 		// -- Exec
 		let fx_agent_c = AgentForCreate {
 			name: fx_name.to_string(),
+			..Default::default()
 		};
 		let agent_id = AgentBmc::create(&ctx, &mm, fx_agent_c).await?;
 
@@ -135,9 +621,11 @@ mod tests {
 		// -- Exec
 		let fx_agent_c = AgentForCreate {
 			name: fx_name.to_string(),
+			..Default::default()
 		};
 		let fx_agent_c2 = AgentForCreate {
 			name: fx_name.to_string(),
+			..Default::default()
 		};
 
 		let agent_ids =
@@ -171,6 +659,7 @@ mod tests {
 		// -- Exec
 		let fx_agent_u = AgentForUpdate {
 			name: Some(fx_name_updated.to_string()),
+			..Default::default()
 		};
 		AgentBmc::update(&ctx, &mm, fx_agent_id, fx_agent_u).await?;
 
@@ -221,9 +710,11 @@ mod tests {
 		// -- Exec
 		let fx_agent_c = AgentForCreate {
 			name: fx_name.to_string(),
+			..Default::default()
 		};
 		let fx_agent_c2 = AgentForCreate {
 			name: fx_name.to_string(),
+			..Default::default()
 		};
 
 		let agent_ids =
@@ -247,6 +738,275 @@ mod tests {
 		Ok(())
 	}
 
+	#[serial]
+	#[tokio::test]
+	async fn test_update_by_filter_err_not_sys() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_user_id =
+			seed_user(&ctx, &mm, "test_update_by_filter_err_not_sys-user-01")
+				.await?;
+		let user_ctx = Ctx::new(fx_user_id)?;
+		let fx_name = "test_update_by_filter_err_not_sys agent 01";
+		let agent_id = seed_agent(&user_ctx, &mm, fx_name).await?;
+
+		// -- Exec
+		let filter: AgentFilter = serde_json::from_value(json!({
+			"name": {"$startsWith": "test_update_by_filter_err_not_sys"}
+		}))?;
+		let res = AgentBmc::update_by_filter(
+			&user_ctx,
+			&mm,
+			filter,
+			AgentForUpdate {
+				name: Some("should not be updated".to_string()),
+				..Default::default()
+			},
+		)
+		.await;
+
+		// -- Check
+		assert!(
+			matches!(&res, Err(model::Error::AccessDenied { .. })),
+			"should return a AccessDenied"
+		);
+		let agent = AgentBmc::get(&user_ctx, &mm, agent_id).await?;
+		assert_eq!(agent.name, fx_name);
+
+		// -- Clean
+		clean_agents(&ctx, &mm, "test_update_by_filter_err_not_sys").await?;
+		clean_users(&ctx, &mm, "test_update_by_filter_err_not_sys").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_delete_many_err_not_found() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_name = "test_delete_many_err_not_found agent 01";
+		let agent_id = seed_agent(&ctx, &mm, fx_name).await?;
+
+		// -- Exec
+		let res =
+			AgentBmc::delete_many(&ctx, &mm, vec![agent_id, 999_998, 999_999]).await;
+
+		// -- Check
+		match res {
+			Err(model::Error::EntitiesNotFound { entity, ids }) => {
+				assert_eq!(entity, "agent");
+				assert_eq!(ids, &[999_998, 999_999]);
+			}
+			other => {
+				return Err(
+					format!("should be EntitiesNotFound, was {other:?}").into()
+				)
+			}
+		}
+		// the found agent should not have been deleted
+		AgentBmc::get(&ctx, &mm, agent_id).await?;
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_delete_many_partial_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_names = &[
+			"test_delete_many_partial_ok agent 01",
+			"test_delete_many_partial_ok agent 02",
+		];
+		let agent_ids = seed_agents(&ctx, &mm, fx_names).await?;
+
+		// -- Exec
+		let results = AgentBmc::delete_many_partial(
+			&ctx,
+			&mm,
+			vec![agent_ids[0], 999_999, agent_ids[1]],
+		)
+		.await?;
+
+		// -- Check
+		assert_eq!(results.len(), 3);
+		assert!(results[0].is_ok());
+		assert!(matches!(
+			results[1],
+			Err(model::Error::EntityNotFound { id: 999_999, .. })
+		));
+		assert!(results[2].is_ok());
+		let count = clean_agents(&ctx, &mm, "test_delete_many_partial_ok").await?;
+		assert_eq!(count, 0, "agents should have been deleted");
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_many_partial_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_name = "test_create_many_partial_ok agent 01";
+		let fx_name_too_long = "x".repeat(300);
+
+		// -- Exec
+		let results = AgentBmc::create_many_partial(
+			&ctx,
+			&mm,
+			vec![
+				AgentForCreate {
+					name: fx_name.to_string(),
+					..Default::default()
+				},
+				AgentForCreate {
+					name: fx_name_too_long,
+					..Default::default()
+				},
+			],
+		)
+		.await?;
+
+		// -- Check
+		assert_eq!(results.len(), 2);
+		let agent_id = results[0].as_ref().map_err(|err| format!("{err:?}"))?;
+		assert!(results[1].is_err(), "too long name should fail");
+		let agent = AgentBmc::get(&ctx, &mm, *agent_id).await?;
+		assert_eq!(agent.name, fx_name);
+
+		// -- Clean
+		clean_agents(&ctx, &mm, "test_create_many_partial_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_update_many_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_names = &[
+			"test_update_many_ok agent 01",
+			"test_update_many_ok agent 02",
+		];
+		let fx_name_updated = "test_update_many_ok agent - updated";
+		let agent_ids = seed_agents(&ctx, &mm, fx_names).await?;
+
+		// -- Exec
+		let count = AgentBmc::update_many(
+			&ctx,
+			&mm,
+			agent_ids.clone(),
+			AgentForUpdate {
+				name: Some(fx_name_updated.to_string()),
+				..Default::default()
+			},
+		)
+		.await?;
+
+		// -- Check
+		assert_eq!(count, 2);
+		for agent_id in agent_ids {
+			let agent = AgentBmc::get(&ctx, &mm, agent_id).await?;
+			assert_eq!(agent.name, fx_name_updated);
+		}
+
+		// -- Clean
+		clean_agents(&ctx, &mm, "test_update_many_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_update_many_err_not_found() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_name = "test_update_many_err_not_found agent 01";
+		let agent_id = seed_agent(&ctx, &mm, fx_name).await?;
+
+		// -- Exec
+		let res = AgentBmc::update_many(
+			&ctx,
+			&mm,
+			vec![agent_id, 999_999],
+			AgentForUpdate {
+				name: Some("should not be updated".to_string()),
+				..Default::default()
+			},
+		)
+		.await;
+
+		// -- Check
+		assert!(
+			matches!(
+				res,
+				Err(model::Error::EntitiesNotFound { ref ids, .. }) if ids == &[999_999]
+			),
+			"should return a EntitiesNotFound with the missing id"
+		);
+		let agent = AgentBmc::get(&ctx, &mm, agent_id).await?;
+		assert_eq!(agent.name, fx_name, "update should have been rolled back");
+
+		// -- Clean
+		clean_agents(&ctx, &mm, "test_update_many_err_not_found").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_update_by_filter_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_names = &[
+			"test_update_by_filter_ok agent 01",
+			"test_update_by_filter_ok agent 02",
+			"test_update_by_filter_ok other 03",
+		];
+		seed_agents(&ctx, &mm, fx_names).await?;
+
+		// -- Exec
+		let agent_filter: AgentFilter = serde_json::from_value(json!({
+			"name": {"$startsWith": "test_update_by_filter_ok agent"}
+		}))?;
+		let count = AgentBmc::update_by_filter(
+			&ctx,
+			&mm,
+			vec![agent_filter],
+			AgentForUpdate {
+				name: Some("test_update_by_filter_ok updated".to_string()),
+				..Default::default()
+			},
+		)
+		.await?;
+
+		// -- Check
+		assert_eq!(count, 2);
+		let agent_filter: AgentFilter = serde_json::from_value(json!({
+			"name": {"$eq": "test_update_by_filter_ok updated"}
+		}))?;
+		let agents =
+			AgentBmc::list(&ctx, &mm, Some(vec![agent_filter]), None).await?;
+		assert_eq!(agents.len(), 2);
+
+		// -- Clean
+		clean_agents(&ctx, &mm, "test_update_by_filter_ok").await?;
+
+		Ok(())
+	}
+
 	#[serial]
 	#[tokio::test]
 	async fn test_first_ok() -> Result<()> {
@@ -342,6 +1102,395 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_page_cursor_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_agent_names = &[
+			"test_list_page_cursor_ok agent 03",
+			"test_list_page_cursor_ok agent 01",
+			"test_list_page_cursor_ok agent 05",
+			"test_list_page_cursor_ok agent 02",
+			"test_list_page_cursor_ok agent 04",
+		];
+		seed_agents(&ctx, &mm, fx_agent_names).await?;
+		let agent_filter =
+			json!({"name": {"$contains": "test_list_page_cursor_ok"}});
+		let list_options: ListOptions = serde_json::from_value(json!({
+			"limit": 2,
+			"order_bys": "!name"
+		}))?;
+
+		// -- Exec
+		let mut names: Vec<String> = Vec::new();
+		let mut totals: Vec<i64> = Vec::new();
+		let mut cursor = None;
+		loop {
+			let page = AgentBmc::list_page(
+				&ctx,
+				&mm,
+				Some(vec![serde_json::from_value(agent_filter.clone())?]),
+				Some(list_options.clone()),
+				cursor,
+			)
+			.await?;
+			names.extend(page.items.into_iter().map(|a| a.name));
+			totals.push(page.total);
+			cursor = page.next_cursor;
+			if cursor.is_none() {
+				break;
+			}
+		}
+
+		// -- Check
+		let mut fx_names_desc = fx_agent_names.to_vec();
+		fx_names_desc.sort();
+		fx_names_desc.reverse();
+		assert_eq!(names, fx_names_desc);
+		assert_eq!(totals, &[5, 5, 5]);
+
+		// -- Clean
+		clean_agents(&ctx, &mm, "test_list_page_cursor_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_page_cursor_other_filter_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_agent_names = &[
+			"test_list_page_cursor_other_filter_ok agent A 01",
+			"test_list_page_cursor_other_filter_ok agent A 02",
+			"test_list_page_cursor_other_filter_ok agent B 01",
+			"test_list_page_cursor_other_filter_ok agent B 02",
+		];
+		seed_agents(&ctx, &mm, fx_agent_names).await?;
+		let filter_a: AgentFilter = serde_json::from_value(json!(
+			{"name": {"$contains": "test_list_page_cursor_other_filter_ok agent A"}}
+		))?;
+		let filter_b: AgentFilter = serde_json::from_value(json!(
+			{"name": {"$contains": "test_list_page_cursor_other_filter_ok agent B"}}
+		))?;
+		let list_options = ListOptions {
+			limit: Some(1),
+			order_bys: Some("name".into()),
+			..Default::default()
+		};
+		let page_a = AgentBmc::list_page(
+			&ctx,
+			&mm,
+			Some(vec![filter_a]),
+			Some(list_options.clone()),
+			None,
+		)
+		.await?;
+
+		// -- Exec
+		// The cursor of the "A" listing, replayed against the "B" listing.
+		let page_b = AgentBmc::list_page(
+			&ctx,
+			&mm,
+			Some(vec![filter_b]),
+			Some(list_options),
+			page_a.next_cursor,
+		)
+		.await?;
+
+		// -- Check
+		assert!(
+			page_b.items.is_empty(),
+			"should not position the page on a cursor row outside the filter"
+		);
+		assert!(page_b.next_cursor.is_none());
+		assert_eq!(page_b.total, 2);
+
+		// -- Clean
+		clean_agents(&ctx, &mm, "test_list_page_cursor_other_filter_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_page_cursor_err_order_mismatch() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_agent_names = &[
+			"test_list_page_cursor_err_order_mismatch agent 01",
+			"test_list_page_cursor_err_order_mismatch agent 02",
+		];
+		seed_agents(&ctx, &mm, fx_agent_names).await?;
+		let list_options = |order_bys: &str| ListOptions {
+			limit: Some(1),
+			order_bys: Some(order_bys.into()),
+			..Default::default()
+		};
+		let page =
+			AgentBmc::list_page(&ctx, &mm, None, Some(list_options("name")), None)
+				.await?;
+
+		// -- Exec
+		let res = AgentBmc::list_page(
+			&ctx,
+			&mm,
+			None,
+			Some(list_options("!ctime")),
+			page.next_cursor,
+		)
+		.await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(model::Error::ListCursorInvalid)),
+			"should return a ListCursorInvalid"
+		);
+
+		// -- Clean
+		clean_agents(&ctx, &mm, "test_list_page_cursor_err_order_mismatch").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_count_in_txn_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = &Ctx::root_ctx();
+		let fx_agent_names = &["test_count_in_txn_ok agent 01"];
+
+		// -- Exec
+		let count_in_txn = mm
+			.in_txn(|mm| async move {
+				seed_agents(ctx, &mm, fx_agent_names).await?;
+				let agent_filter = AgentFilter {
+					name: Some(
+						OpValString::Contains("count_in_txn_ok agent".to_string())
+							.into(),
+					),
+					..Default::default()
+				};
+				AgentBmc::count(ctx, &mm, Some(vec![agent_filter])).await
+			})
+			.await?;
+
+		// -- Check
+		assert_eq!(count_in_txn, 1, "count should see the uncommitted agent");
+
+		// -- Clean
+		let count = clean_agents(ctx, &mm, "test_count_in_txn_ok agent").await?;
+		assert_eq!(count, 1, "Should have cleaned 1 agent");
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_versions_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = &Ctx::root_ctx();
+		let fx_params = AgentParams {
+			temperature: Some(0.2),
+			max_tokens: Some(512),
+			..Default::default()
+		};
+		let agent_id = AgentBmc::create(
+			ctx,
+			&mm,
+			AgentForCreate {
+				name: "test_versions_ok agent 01".to_string(),
+				system_prompt: Some("You are helpful.".to_string()),
+				params: Some(fx_params.clone()),
+				..Default::default()
+			},
+		)
+		.await?;
+		let conv_v1_id =
+			_dev_utils::seed_conv(ctx, &mm, agent_id, "test_versions_ok conv 01")
+				.await?;
+
+		// -- Exec
+		// name only change, same version
+		AgentBmc::update(
+			ctx,
+			&mm,
+			agent_id,
+			AgentForUpdate {
+				name: Some("test_versions_ok agent 01 renamed".to_string()),
+				..Default::default()
+			},
+		)
+		.await?;
+		// system prompt change, new version
+		AgentBmc::update(
+			ctx,
+			&mm,
+			agent_id,
+			AgentForUpdate {
+				system_prompt: Some("You are concise.".to_string()),
+				..Default::default()
+			},
+		)
+		.await?;
+		let conv_v2_id =
+			_dev_utils::seed_conv(ctx, &mm, agent_id, "test_versions_ok conv 02")
+				.await?;
+
+		// -- Check
+		let agent = AgentBmc::get(ctx, &mm, agent_id).await?;
+		assert_eq!(agent.version, 2);
+		assert_eq!(agent.params.as_ref(), Some(&fx_params));
+
+		let versions = AgentBmc::list_versions(ctx, &mm, agent_id).await?;
+		assert_eq!(versions.len(), 2, "number of versions");
+		let (v1, v2) = (&versions[0], &versions[1]);
+		assert_eq!(v1.version, 1);
+		assert_eq!(v1.system_prompt.as_deref(), Some("You are helpful."));
+		assert_eq!(v2.version, 2);
+		assert_eq!(v2.system_prompt.as_deref(), Some("You are concise."));
+		assert_eq!(v2.params.as_ref(), Some(&fx_params));
+
+		let conv_v1 = ConvBmc::get(ctx, &mm, conv_v1_id).await?;
+		let conv_v2 = ConvBmc::get(ctx, &mm, conv_v2_id).await?;
+		assert_eq!(conv_v1.agent_version_id, v1.id);
+		assert_eq!(conv_v2.agent_version_id, v2.id);
+		let v1_got =
+			AgentBmc::get_version(ctx, &mm, conv_v1.agent_version_id).await?;
+		assert_eq!(v1_got.system_prompt.as_deref(), Some("You are helpful."));
+
+		// -- Clean
+		AgentBmc::delete(ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_versions_err_update() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = &Ctx::root_ctx();
+		let agent_id =
+			seed_agent(ctx, &mm, "test_versions_err_update agent").await?;
+
+		// -- Exec
+		let res = sqlx::query(
+			"UPDATE agent_version SET system_prompt = 'tampered' WHERE agent_id = $1",
+		)
+		.bind(agent_id)
+		.execute(mm.dbx().db())
+		.await;
+
+		// -- Check
+		assert!(res.is_err(), "update should fail");
+		let versions = AgentBmc::list_versions(ctx, &mm, agent_id).await?;
+		assert_ne!(versions[0].system_prompt.as_deref(), Some("tampered"));
+
+		// -- Clean
+		AgentBmc::delete(ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_versions_err_not_visible() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = &Ctx::root_ctx();
+		let owner_id =
+			seed_user(ctx, &mm, "test_versions_err_not_visible-user-owner").await?;
+		let other_id =
+			seed_user(ctx, &mm, "test_versions_err_not_visible-user-other").await?;
+		let owner_ctx = Ctx::new(owner_id)?;
+		let other_ctx = Ctx::new(other_id)?;
+		let agent_id =
+			seed_agent(&owner_ctx, &mm, "test_versions_err_not_visible agent")
+				.await?;
+		let versions = AgentBmc::list_versions(&owner_ctx, &mm, agent_id).await?;
+		let version_id = versions[0].id;
+
+		// -- Exec
+		let list_res = AgentBmc::list_versions(&other_ctx, &mm, agent_id).await;
+		let get_res = AgentBmc::get_version(&other_ctx, &mm, version_id).await;
+
+		// -- Check
+		assert!(
+			matches!(list_res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {list_res:?}"
+		);
+		assert!(
+			matches!(get_res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {get_res:?}"
+		);
+		let version = AgentBmc::get_version(&owner_ctx, &mm, version_id).await?;
+		assert_eq!(version.agent_id, agent_id);
+
+		// -- Clean
+		AgentBmc::delete(ctx, &mm, agent_id).await?;
+		clean_users(ctx, &mm, "test_versions_err_not_visible").await?;
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_params_err_invalid() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_invalids = [
+			json!({"temperature": 2.5}),
+			json!({"top_p": -0.1}),
+			json!({"max_tokens": 0}),
+			json!({"stop": ["a", ""]}),
+			json!({"unknown_param": 1}),
+		];
+
+		// -- Exec & Check
+		for fx_invalid in fx_invalids {
+			let res = serde_json::from_value::<AgentParams>(fx_invalid.clone());
+			assert!(res.is_err(), "params {fx_invalid} should be invalid");
+		}
+		let params: AgentParams = serde_json::from_value(json!({
+			"temperature": 0.7,
+			"stop": ["END"],
+			"provider": {"seed": 42}
+		}))?;
+		assert_eq!(params.temperature, Some(0.7));
+		assert_eq!(
+			params.provider.and_then(|p| p.get("seed").cloned()),
+			Some(json!(42))
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_tools_err_invalid() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_invalids = [
+			json!(["calculator", "unknown_tool"]),
+			json!(["calculator", "calculator"]),
+			json!("calculator"),
+		];
+
+		// -- Exec & Check
+		for fx_invalid in fx_invalids {
+			let res = serde_json::from_value::<AgentTools>(fx_invalid.clone());
+			assert!(res.is_err(), "tools {fx_invalid} should be invalid");
+		}
+		let tools: AgentTools =
+			serde_json::from_value(json!(["calculator", "search_convs"]))?;
+		assert_eq!(tools.names(), ["calculator", "search_convs"]);
+
+		Ok(())
+	}
 }
 
 // endregion: --- Tests
@@ -0,0 +1,586 @@
+use crate::ctx::Ctx;
+use crate::model::acs::{agent_visible_sql, assert_agent_owner, assert_agent_user};
+use crate::model::agent::{
+	Agent, AgentBmc, AgentContext, AgentFilter, AgentForUpdate, AgentParams,
+	AgentStarterPrompts, AgentTools,
+};
+use crate::model::base::{self, DbBmc};
+use crate::model::user::{User, UserBmc};
+use crate::model::{Error, ModelManager, Result};
+use lib_utils::time::Rfc3339;
+use modql::field::Fields;
+use modql::filter::{IntoSeaError, OpValValue, OpValsValue, SeaResult};
+use sea_query::{ColumnRef, ConditionExpression, Expr};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+// region:    --- Types
+
+/// A user an agent is shared with (effective for the `Shared` and `Org` visibilities).
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct AgentShare {
+	pub id: i64,
+
+	// -- FK
+	pub agent_id: i64,
+	pub user_id: i64,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+#[derive(Fields, Deserialize, Default)]
+pub struct AgentShareForCreate {
+	pub agent_id: i64,
+	pub user_id: i64,
+}
+
+#[derive(Deserialize, Default)]
+pub struct AgentForClone {
+	pub agent_id: i64,
+	/// Default: The name of the cloned agent.
+	pub name: Option<String>,
+}
+
+/// The configuration of the cloned agent (the clone is `Private`, at version 1).
+#[derive(Fields)]
+pub(in crate::model) struct AgentForCloneInsert {
+	pub name: String,
+	pub ai_provider: String,
+	pub ai_model: String,
+	pub system_prompt: Option<String>,
+	pub params: Option<AgentParams>,
+	pub tools: AgentTools,
+	pub context: Option<AgentContext>,
+	pub starter_prompts: AgentStarterPrompts,
+}
+
+// endregion: --- Types
+
+// region:    --- AgentShareBmc
+
+pub struct AgentShareBmc;
+
+impl DbBmc for AgentShareBmc {
+	const TABLE: &'static str = "agent_share";
+
+	fn unique_key() -> Option<&'static [&'static str]> {
+		Some(&["agent_id", "user_id"])
+	}
+}
+
+// Note: The agent shares are managed by the `AgentBmc` container.
+
+// endregion: --- AgentShareBmc
+
+// region:    --- AgentBmc Access
+
+/// The agent functions checking the ctx user access
+/// (the rpc handlers use those, rather than the default CRUD functions).
+impl AgentBmc {
+	/// Returns the agent if visible by the ctx user.
+	pub async fn get_visible(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+	) -> Result<Agent> {
+		let agent = Self::get(ctx, mm, id).await?;
+		assert_agent_user(ctx, mm, id).await?;
+
+		Ok(agent)
+	}
+
+	/// Returns the number of agents matching the filters, and visible by the ctx user.
+	pub async fn count_visible(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: Option<Vec<AgentFilter>>,
+	) -> Result<i64> {
+		Self::count(ctx, mm, Self::visible_filters(ctx, filters)).await
+	}
+
+	/// Update the agent (owner only).
+	pub async fn update_owned(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		agent_u: AgentForUpdate,
+	) -> Result<()> {
+		assert_agent_owner(ctx, mm, id).await?;
+
+		Self::update(ctx, mm, id, agent_u).await
+	}
+
+	/// Delete the agent (owner only).
+	///
+	/// Note: Deletes its convs as well, so the delete is refused while convs of
+	///       other users than the agent owner use it (`Error::AgentHasOtherUsersConvs`).
+	pub async fn delete_owned(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		assert_agent_owner(ctx, mm, id).await?;
+
+		mm.in_txn(|mm| async move {
+			// Note: Lock the agent row, so that no conv is created on it in between
+			//       (the conv insert takes a key share lock on the agent row).
+			let sqlx_query = sqlx::query_as::<_, (i64,)>(
+				"SELECT owner_id FROM agent WHERE id = $1 FOR UPDATE",
+			)
+			.bind(id);
+			let (owner_id,) = mm.dbx().fetch_optional(sqlx_query).await?.ok_or(
+				Error::EntityNotFound {
+					entity: Self::TABLE,
+					id,
+				},
+			)?;
+
+			let sqlx_query = sqlx::query_as::<_, (i64,)>(
+				"SELECT count(*) FROM conv WHERE agent_id = $1 AND owner_id <> $2",
+			)
+			.bind(id)
+			.bind(owner_id);
+			let (count,) = mm.dbx().fetch_one(sqlx_query).await?;
+			if count > 0 {
+				return Err(Error::AgentHasOtherUsersConvs {
+					agent_id: id,
+					count,
+				});
+			}
+
+			Self::delete(ctx, &mm, id).await
+		})
+		.await
+	}
+
+	/// Returns the filter groups (or a default one) restricted to the agents
+	/// visible by the ctx user (unchanged for root).
+	pub(in crate::model) fn visible_filters(
+		ctx: &Ctx,
+		filters: Option<Vec<AgentFilter>>,
+	) -> Option<Vec<AgentFilter>> {
+		let user_id = ctx.user_id();
+		if user_id == 0 {
+			return filters;
+		}
+
+		let mut filters = filters.unwrap_or_default();
+		if filters.is_empty() {
+			filters.push(AgentFilter::default());
+		}
+		for filter in filters.iter_mut() {
+			filter.visible_to =
+				Some(OpValsValue(vec![OpValValue::Eq(user_id.into())]));
+		}
+
+		Some(filters)
+	}
+
+	/// Clone the (visible) agent into a new `Private` agent of the ctx user.
+	///
+	/// Note: The clone starts at version 1 with the current configuration of the agent
+	///       (its shares, versions, and conv templates are not cloned).
+	pub async fn clone_agent(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		agent_clone: AgentForClone,
+	) -> Result<i64> {
+		let AgentForClone { agent_id, name } = agent_clone;
+		let agent = Self::get_visible(ctx, mm, agent_id).await?;
+
+		let agent_i = AgentForCloneInsert {
+			name: name.unwrap_or(agent.name),
+			ai_provider: agent.ai_provider,
+			ai_model: agent.ai_model,
+			system_prompt: agent.system_prompt,
+			params: agent.params,
+			tools: agent.tools,
+			context: agent.context,
+			starter_prompts: agent.starter_prompts,
+		};
+
+		base::create::<Self, _>(ctx, mm, agent_i).await
+	}
+}
+
+// endregion: --- AgentBmc Access
+
+// region:    --- AgentBmc Shares
+
+/// Note: Only the agent owner (or root) can manage and list its shares.
+impl AgentBmc {
+	/// Share the agent with the user (no-op if already shared).
+	pub async fn share(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		share_c: AgentShareForCreate,
+	) -> Result<()> {
+		assert_agent_owner(ctx, mm, share_c.agent_id).await?;
+		// Note: Make sure the user exists (EntityNotFound otherwise).
+		let _: User = UserBmc::get(ctx, mm, share_c.user_id).await?;
+
+		base::upsert::<AgentShareBmc, _>(ctx, mm, share_c).await?;
+
+		Ok(())
+	}
+
+	/// Unshare the agent with the user (no-op if not shared).
+	pub async fn unshare(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		share_c: AgentShareForCreate,
+	) -> Result<()> {
+		let AgentShareForCreate { agent_id, user_id } = share_c;
+		assert_agent_owner(ctx, mm, agent_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, (i64,)>(
+			"SELECT id FROM agent_share WHERE agent_id = $1 AND user_id = $2",
+		)
+		.bind(agent_id)
+		.bind(user_id);
+		if let Some((share_id,)) = mm.dbx().fetch_optional(sqlx_query).await? {
+			base::delete::<AgentShareBmc>(ctx, mm, share_id).await?;
+		}
+
+		Ok(())
+	}
+
+	pub async fn list_shares(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		agent_id: i64,
+	) -> Result<Vec<AgentShare>> {
+		assert_agent_owner(ctx, mm, agent_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, AgentShare>(
+			"SELECT * FROM agent_share WHERE agent_id = $1 ORDER BY id",
+		)
+		.bind(agent_id);
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+}
+
+// endregion: --- AgentBmc Shares
+
+// region:    --- AgentFilter visible_to
+
+/// The `AgentFilter.visible_to` condition (see `acs::agent_visible_sql`).
+///
+/// Note: Only `Eq` with the user id (set by `AgentBmc::visible_filters`).
+pub(in crate::model) fn agent_visible_to_sea_condition(
+	_col: &ColumnRef,
+	op_value: OpValValue,
+) -> SeaResult<ConditionExpression> {
+	let user_id = match op_value {
+		OpValValue::Eq(user_id) => user_id.as_i64(),
+		_ => None,
+	}
+	.ok_or_else(|| {
+		IntoSeaError::Custom("visible_to must be a user id $eq".to_string())
+	})?;
+
+	let cond = Expr::cust_with_values(agent_visible_sql("$1"), [user_id]);
+
+	Ok(ConditionExpression::SimpleExpr(cond))
+}
+
+// endregion: --- AgentFilter visible_to
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, clean_users, seed_agent, seed_user};
+	use crate::model;
+	use crate::model::agent::AgentVisibility;
+	use crate::model::conv::{ConvBmc, ConvForCreate};
+	use modql::filter::OpValString;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_agent_visibility_share_clone_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_name = "test_agent_visibility_share_clone_ok agent";
+		let owner_id =
+			seed_user(&ctx, &mm, "test_agent_visibility_share_clone_ok-user-owner")
+				.await?;
+		let peer_id =
+			seed_user(&ctx, &mm, "test_agent_visibility_share_clone_ok-user-peer")
+				.await?;
+		let other_id =
+			seed_user(&ctx, &mm, "test_agent_visibility_share_clone_ok-user-other")
+				.await?;
+		let owner_ctx = Ctx::new(owner_id)?;
+		let peer_ctx = Ctx::new(peer_id)?;
+		let other_ctx = Ctx::new(other_id)?;
+		// The owner and the peer are in the same org (the other is not).
+		let (org_id,) = sqlx::query_as::<_, (i64,)>(
+			"INSERT INTO org (name, cid, ctime, mid, mtime)
+			VALUES ('test_agent_visibility_share_clone_ok org', 0, now(), 0, now())
+			RETURNING id",
+		)
+		.fetch_one(mm.dbx().db())
+		.await?;
+		sqlx::query(r#"UPDATE "user" SET org_id = $1 WHERE id = ANY($2)"#)
+			.bind(org_id)
+			.bind(vec![owner_id, peer_id])
+			.execute(mm.dbx().db())
+			.await?;
+		let agent_id = seed_agent(&owner_ctx, &mm, fx_name).await?;
+		let set_visibility = |visibility: AgentVisibility| AgentForUpdate {
+			visibility: Some(visibility),
+			..Default::default()
+		};
+		let visible_ids = |ctx: Ctx| {
+			let mm = mm.clone();
+			async move {
+				let filters = vec![AgentFilter {
+					name: Some(OpValString::Contains(fx_name.to_string()).into()),
+					..Default::default()
+				}];
+				let page = AgentBmc::list_page_pinned_first(
+					&ctx,
+					&mm,
+					Some(filters),
+					None,
+					None,
+				)
+				.await?;
+				Ok::<_, model::Error>(
+					page.items.into_iter().map(|a| a.id).collect::<Vec<_>>(),
+				)
+			}
+		};
+
+		// -- Exec & Check - Private
+		assert_eq!(visible_ids(owner_ctx.clone()).await?, [agent_id]);
+		assert!(visible_ids(peer_ctx.clone()).await?.is_empty());
+		let res = AgentBmc::get_visible(&peer_ctx, &mm, agent_id).await;
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+		let res = ConvBmc::start(
+			&peer_ctx,
+			&mm,
+			ConvForCreate {
+				agent_id,
+				..Default::default()
+			},
+		)
+		.await;
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+
+		// -- Exec & Check - Shared
+		AgentBmc::update_owned(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			set_visibility(AgentVisibility::Shared),
+		)
+		.await?;
+		AgentBmc::share(
+			&owner_ctx,
+			&mm,
+			AgentShareForCreate {
+				agent_id,
+				user_id: other_id,
+			},
+		)
+		.await?;
+		assert!(visible_ids(peer_ctx.clone()).await?.is_empty());
+		assert_eq!(visible_ids(other_ctx.clone()).await?, [agent_id]);
+		let conv_id = ConvBmc::start(
+			&other_ctx,
+			&mm,
+			ConvForCreate {
+				agent_id,
+				..Default::default()
+			},
+		)
+		.await?;
+		let res = AgentBmc::list_shares(&other_ctx, &mm, agent_id).await;
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+
+		// -- Exec & Check - Org
+		AgentBmc::update_owned(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			set_visibility(AgentVisibility::Org),
+		)
+		.await?;
+		assert_eq!(visible_ids(peer_ctx.clone()).await?, [agent_id]);
+		assert_eq!(visible_ids(other_ctx.clone()).await?, [agent_id]);
+		AgentBmc::unshare(
+			&owner_ctx,
+			&mm,
+			AgentShareForCreate {
+				agent_id,
+				user_id: other_id,
+			},
+		)
+		.await?;
+		assert!(visible_ids(other_ctx.clone()).await?.is_empty());
+		let name_filters = vec![AgentFilter {
+			name: Some(OpValString::Contains(fx_name.to_string()).into()),
+			..Default::default()
+		}];
+		assert_eq!(
+			AgentBmc::count_visible(&other_ctx, &mm, Some(name_filters)).await?,
+			0
+		);
+
+		// -- Exec & Check - Public
+		AgentBmc::update_owned(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			set_visibility(AgentVisibility::Public),
+		)
+		.await?;
+		assert_eq!(visible_ids(other_ctx.clone()).await?, [agent_id]);
+		let res = AgentBmc::update_owned(
+			&other_ctx,
+			&mm,
+			agent_id,
+			set_visibility(AgentVisibility::Private),
+		)
+		.await;
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+
+		// -- Exec & Check - Clone
+		let clone_id = AgentBmc::clone_agent(
+			&other_ctx,
+			&mm,
+			AgentForClone {
+				agent_id,
+				name: Some(format!("{fx_name} clone")),
+			},
+		)
+		.await?;
+		let clone = AgentBmc::get_visible(&other_ctx, &mm, clone_id).await?;
+		assert_eq!(clone.owner_id, other_id);
+		assert_eq!(clone.visibility, AgentVisibility::Private);
+		assert_eq!(clone.version, 1);
+		assert_eq!(
+			clone.ai_model,
+			AgentBmc::get(&ctx, &mm, agent_id).await?.ai_model
+		);
+		let res = AgentBmc::get_visible(&owner_ctx, &mm, clone_id).await;
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+
+		// -- Clean
+		ConvBmc::delete(&ctx, &mm, conv_id).await?;
+		AgentBmc::delete(&ctx, &mm, clone_id).await?;
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+		clean_users(&ctx, &mm, "test_agent_visibility_share_clone_ok").await?;
+		sqlx::query("DELETE FROM org WHERE id = $1")
+			.bind(org_id)
+			.execute(mm.dbx().db())
+			.await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_delete_owned_err_other_users_convs() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let owner_id = seed_user(
+			&ctx,
+			&mm,
+			"test_delete_owned_err_other_users_convs-user-owner",
+		)
+		.await?;
+		let other_id = seed_user(
+			&ctx,
+			&mm,
+			"test_delete_owned_err_other_users_convs-user-other",
+		)
+		.await?;
+		let owner_ctx = Ctx::new(owner_id)?;
+		let other_ctx = Ctx::new(other_id)?;
+		let agent_id = seed_agent(
+			&owner_ctx,
+			&mm,
+			"test_delete_owned_err_other_users_convs agent",
+		)
+		.await?;
+		AgentBmc::update_owned(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			AgentForUpdate {
+				visibility: Some(AgentVisibility::Public),
+				..Default::default()
+			},
+		)
+		.await?;
+		let conv_c = || ConvForCreate {
+			agent_id,
+			..Default::default()
+		};
+		ConvBmc::start(&owner_ctx, &mm, conv_c()).await?;
+		let other_conv_id = ConvBmc::start(&other_ctx, &mm, conv_c()).await?;
+
+		// -- Exec
+		let res = AgentBmc::delete_owned(&owner_ctx, &mm, agent_id).await;
+
+		// -- Check
+		assert!(
+			matches!(
+				res,
+				Err(model::Error::AgentHasOtherUsersConvs { count: 1, .. })
+			),
+			"should be AgentHasOtherUsersConvs, but was {res:?}"
+		);
+		// the agent and the other user conv are still there
+		AgentBmc::get(&ctx, &mm, agent_id).await?;
+		ConvBmc::get_visible(&other_ctx, &mm, other_conv_id).await?;
+		// deletable once the other user conv is deleted (with the owner convs)
+		ConvBmc::delete_owned(&other_ctx, &mm, other_conv_id).await?;
+		AgentBmc::delete_owned(&owner_ctx, &mm, agent_id).await?;
+		let res = AgentBmc::get(&ctx, &mm, agent_id).await;
+		assert!(
+			matches!(res, Err(model::Error::EntityNotFound { .. })),
+			"should be EntityNotFound, but was {res:?}"
+		);
+
+		// -- Clean
+		clean_users(&ctx, &mm, "test_delete_owned_err_other_users_convs").await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,485 @@
+use crate::ctx::Ctx;
+use crate::model::acs::assert_conv_user;
+use crate::model::base::{self, DbBmc};
+use crate::model::conv::ConvScoped;
+use crate::model::conv_msg::{ConvMsg, ConvMsgBmc};
+use crate::model::ModelManager;
+use crate::model::{Error, Result};
+use lib_utils::time::Rfc3339;
+use modql::field::Fields;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sha2::{Digest, Sha256};
+use sqlx::types::time::OffsetDateTime;
+use sqlx::FromRow;
+use std::collections::HashMap;
+use tracing::warn;
+use uuid::Uuid;
+
+/// The maximum size, in bytes, of an attachment content (10 MiB).
+pub const ATTACHMENT_MAX_SIZE: usize = 10 * 1024 * 1024;
+
+const ATTACHMENT_NAME_MAX_LEN: usize = 256;
+
+// region:    --- Attachment Types
+
+/// The metadata of a file attached to a `ConvMsg`.
+/// (the content is in the `ModelManager` blob store).
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct Attachment {
+	pub id: i64,
+
+	// -- FK
+	pub conv_id: i64,
+	pub msg_id: i64,
+	pub owner_id: i64,
+
+	// -- Properties
+	pub name: String,
+	pub content_type: String,
+	/// The content size in bytes.
+	pub size: i64,
+	/// The sha256 of the content (lowercase hex).
+	pub checksum: String,
+	#[serde(skip)]
+	blob_key: String,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+impl ConvScoped for Attachment {
+	fn conv_id(&self) -> i64 {
+		self.conv_id
+	}
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AttachmentForCreate {
+	pub msg_id: i64,
+	/// The file name (e.g., `report.pdf`).
+	pub name: String,
+	/// The mime type (default: `application/octet-stream`).
+	pub content_type: Option<String>,
+}
+
+impl AttachmentForCreate {
+	/// Validate the name and content type, and the `content` size.
+	pub fn validate(&self, content: &[u8]) -> Result<()> {
+		if content.is_empty() {
+			return Err(Error::AttachmentEmpty);
+		}
+		if content.len() > ATTACHMENT_MAX_SIZE {
+			return Err(Error::AttachmentTooLarge {
+				max: ATTACHMENT_MAX_SIZE,
+				actual: content.len(),
+			});
+		}
+
+		// Note: The name ends up in the download `Content-Disposition` header.
+		let name = &self.name;
+		if name.trim().is_empty()
+			|| name.chars().count() > ATTACHMENT_NAME_MAX_LEN
+			|| name
+				.chars()
+				.any(|c| c.is_control() || matches!(c, '/' | '\\' | '"'))
+		{
+			return Err(Error::AttachmentNameInvalid { name: name.clone() });
+		}
+
+		if let Some(content_type) = self.content_type.as_ref() {
+			if !is_valid_content_type(content_type) {
+				return Err(Error::AttachmentContentTypeInvalid {
+					content_type: content_type.clone(),
+				});
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Attachment for Insert, which is derived from the public `AttachmentForCreate`
+/// and its content.
+#[derive(Fields)]
+struct AttachmentForInsert {
+	conv_id: i64,
+	msg_id: i64,
+	name: String,
+	content_type: String,
+	size: i64,
+	checksum: String,
+	blob_key: String,
+}
+
+// endregion: --- Attachment Types
+
+// region:    --- AttachmentBmc
+
+pub struct AttachmentBmc;
+
+impl DbBmc for AttachmentBmc {
+	const TABLE: &'static str = "attachment";
+
+	fn has_owner_id() -> bool {
+		true
+	}
+}
+
+impl AttachmentBmc {
+	/// Attach the `content` to the `ConvMsg` of `att_c.msg_id`.
+	///
+	/// Requires the ctx user to be a conv user (owner or `ConvUser`) of the msg conv.
+	pub async fn create(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		att_c: AttachmentForCreate,
+		content: &[u8],
+	) -> Result<i64> {
+		att_c.validate(content)?;
+
+		let msg: ConvMsg = base::get::<ConvMsgBmc, _>(ctx, mm, att_c.msg_id).await?;
+		assert_conv_user(ctx, mm, msg.conv_id).await?;
+
+		// -- Store the content
+		let blob_key = format!("attachments/{}", Uuid::new_v4());
+		mm.blob().put(&blob_key, content).await?;
+
+		// -- Insert the attachment (and delete the blob if it fails)
+		let att_i = AttachmentForInsert {
+			conv_id: msg.conv_id,
+			msg_id: msg.id,
+			name: att_c.name,
+			content_type: att_c
+				.content_type
+				.unwrap_or_else(|| "application/octet-stream".to_string()),
+			size: content.len() as i64,
+			checksum: hex::encode(Sha256::digest(content)),
+			blob_key: blob_key.clone(),
+		};
+		match base::create::<Self, _>(ctx, mm, att_i).await {
+			Ok(id) => Ok(id),
+			Err(err) => {
+				if let Err(blob_err) = mm.blob().delete(&blob_key).await {
+					warn!("AttachmentBmc::create blob cleanup fail: {blob_err:?}");
+				}
+				Err(err)
+			}
+		}
+	}
+
+	/// Requires the ctx user to be a conv user of the attachment conv.
+	pub async fn get(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<Attachment> {
+		let attachment: Attachment = base::get::<Self, _>(ctx, mm, id).await?;
+		assert_conv_user(ctx, mm, attachment.conv_id).await?;
+
+		Ok(attachment)
+	}
+
+	/// Returns the attachment with its content.
+	pub async fn get_with_content(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+	) -> Result<(Attachment, Vec<u8>)> {
+		let attachment = Self::get(ctx, mm, id).await?;
+		let content = mm.blob().get(&attachment.blob_key).await?;
+
+		Ok((attachment, content))
+	}
+
+	/// Delete the attachment and its content.
+	///
+	/// Note: The blob is deleted after the row, so a failure leaves, at worst,
+	///       an orphan blob (rather than an attachment without content).
+	pub async fn delete(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		let attachment = Self::get(ctx, mm, id).await?;
+		base::delete::<Self>(ctx, mm, id).await?;
+
+		if let Err(blob_err) = mm.blob().delete(&attachment.blob_key).await {
+			warn!("AttachmentBmc::delete blob delete fail: {blob_err:?}");
+		}
+
+		Ok(())
+	}
+
+	/// Set the `attachments` of the `msgs` (ordered by id).
+	///
+	/// Note: The access to the msgs must be validated by the caller.
+	pub(in crate::model) async fn load_into_msgs(
+		_ctx: &Ctx,
+		mm: &ModelManager,
+		msgs: &mut [ConvMsg],
+	) -> Result<()> {
+		if msgs.is_empty() {
+			return Ok(());
+		}
+
+		let msg_ids: Vec<i64> = msgs.iter().map(|msg| msg.id).collect();
+		let sqlx_query = sqlx::query_as::<_, Attachment>(
+			"SELECT * FROM attachment WHERE msg_id = ANY($1) ORDER BY id",
+		)
+		.bind(msg_ids);
+		let attachments = mm.dbx().fetch_all(sqlx_query).await?;
+
+		let mut attachments_by_msg_id: HashMap<i64, Vec<Attachment>> =
+			HashMap::new();
+		for attachment in attachments {
+			attachments_by_msg_id
+				.entry(attachment.msg_id)
+				.or_default()
+				.push(attachment);
+		}
+		for msg in msgs.iter_mut() {
+			msg.attachments =
+				attachments_by_msg_id.remove(&msg.id).unwrap_or_default();
+		}
+
+		Ok(())
+	}
+}
+
+// endregion: --- AttachmentBmc
+
+// region:    --- Support
+
+/// Loose check of the `type/subtype` (with eventual `; params`) mime format.
+fn is_valid_content_type(content_type: &str) -> bool {
+	let essence = content_type.split(';').next().unwrap_or_default().trim();
+	let Some((typ, subtype)) = essence.split_once('/') else {
+		return false;
+	};
+	let is_token = |s: &str| {
+		!s.is_empty()
+			&& s.chars()
+				.all(|c| c.is_ascii_alphanumeric() || "!#$&-^_.+".contains(c))
+	};
+
+	content_type.len() <= 256
+		&& is_token(typ)
+		&& is_token(subtype)
+		&& !content_type.chars().any(|c| c.is_control())
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, clean_users, seed_agent, seed_conv, seed_user};
+	use crate::model;
+	use crate::model::agent::AgentBmc;
+	use crate::model::conv::ConvBmc;
+	use crate::model::conv_msg::ConvMsgForCreate;
+	use crate::model::store::blob;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_get_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_content = b"Hello attachment";
+		let agent_id = seed_agent(&ctx, &mm, "test_create_get_ok agent 01").await?;
+		let msg_id = seed_msg(&ctx, &mm, agent_id, "test_create_get_ok").await?;
+
+		// -- Exec
+		let att_id = AttachmentBmc::create(
+			&ctx,
+			&mm,
+			AttachmentForCreate {
+				msg_id,
+				name: "hello.txt".to_string(),
+				content_type: Some("text/plain; charset=utf-8".to_string()),
+			},
+			fx_content,
+		)
+		.await?;
+
+		// -- Check
+		let (attachment, content) =
+			AttachmentBmc::get_with_content(&ctx, &mm, att_id).await?;
+		assert_eq!(content, fx_content);
+		assert_eq!(attachment.msg_id, msg_id);
+		assert_eq!(attachment.size, fx_content.len() as i64);
+		assert_eq!(attachment.checksum, hex::encode(Sha256::digest(fx_content)));
+		// the msg is returned with its attachments
+		let msg = ConvBmc::get_msg(&ctx, &mm, msg_id).await?;
+		assert_eq!(msg.attachments.len(), 1);
+		assert_eq!(msg.attachments[0].id, att_id);
+
+		// -- Exec & Check - delete
+		AttachmentBmc::delete(&ctx, &mm, att_id).await?;
+		let res = mm.blob().get(&attachment.blob_key).await;
+		assert!(
+			matches!(res, Err(blob::Error::BlobNotFound { .. })),
+			"the blob should have been deleted"
+		);
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_err_invalid() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_too_large = vec![0u8; ATTACHMENT_MAX_SIZE + 1];
+		let fx_att_c =
+			|name: &str, content_type: Option<&str>| AttachmentForCreate {
+				msg_id: 0, // validated before the msg get
+				name: name.to_string(),
+				content_type: content_type.map(|v| v.to_string()),
+			};
+
+		// -- Exec & Check
+		let res =
+			AttachmentBmc::create(&ctx, &mm, fx_att_c("a.bin", None), b"").await;
+		assert!(matches!(res, Err(model::Error::AttachmentEmpty)));
+
+		let res =
+			AttachmentBmc::create(&ctx, &mm, fx_att_c("a.bin", None), &fx_too_large)
+				.await;
+		assert!(matches!(
+			res,
+			Err(model::Error::AttachmentTooLarge {
+				max: ATTACHMENT_MAX_SIZE,
+				..
+			})
+		));
+
+		for fx_name in ["", "  ", "../a.txt", "a\"b.txt", "a\nb.txt"] {
+			let res = AttachmentBmc::create(
+				&ctx,
+				&mm,
+				fx_att_c(fx_name, None),
+				b"content",
+			)
+			.await;
+			assert!(
+				matches!(res, Err(model::Error::AttachmentNameInvalid { .. })),
+				"name {fx_name:?} should be invalid"
+			);
+		}
+
+		for fx_content_type in ["text", "text/", "text/plain\r\nX-Evil: 1"] {
+			let res = AttachmentBmc::create(
+				&ctx,
+				&mm,
+				fx_att_c("a.txt", Some(fx_content_type)),
+				b"content",
+			)
+			.await;
+			assert!(
+				matches!(
+					res,
+					Err(model::Error::AttachmentContentTypeInvalid { .. })
+				),
+				"content type {fx_content_type:?} should be invalid"
+			);
+		}
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_get_err_not_conv_user() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_username = "test_create_get_err_not_conv_user-user-01";
+		let agent_id =
+			seed_agent(&root_ctx, &mm, "test_create_get_err_not_conv_user agent 01")
+				.await?;
+		let msg_id = seed_msg(
+			&root_ctx,
+			&mm,
+			agent_id,
+			"test_create_get_err_not_conv_user",
+		)
+		.await?;
+		let att_id = AttachmentBmc::create(
+			&root_ctx,
+			&mm,
+			AttachmentForCreate {
+				msg_id,
+				name: "secret.txt".to_string(),
+				content_type: None,
+			},
+			b"secret",
+		)
+		.await?;
+		let user_ctx = Ctx::new(seed_user(&root_ctx, &mm, fx_username).await?)?;
+
+		// -- Exec
+		let create_res = AttachmentBmc::create(
+			&user_ctx,
+			&mm,
+			AttachmentForCreate {
+				msg_id,
+				name: "other.txt".to_string(),
+				content_type: None,
+			},
+			b"other",
+		)
+		.await;
+		let get_res = AttachmentBmc::get_with_content(&user_ctx, &mm, att_id).await;
+
+		// -- Check
+		assert!(matches!(create_res, Err(model::Error::AccessDenied { .. })));
+		assert!(matches!(get_res, Err(model::Error::AccessDenied { .. })));
+
+		// -- Clean
+		AttachmentBmc::delete(&root_ctx, &mm, att_id).await?;
+		AgentBmc::delete(&root_ctx, &mm, agent_id).await?;
+		clean_users(&root_ctx, &mm, "test_create_get_err_not_conv_user").await?;
+
+		Ok(())
+	}
+
+	// region:    --- Support
+
+	async fn seed_msg(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		agent_id: i64,
+		title: &str,
+	) -> Result<i64> {
+		let conv_id = seed_conv(ctx, mm, agent_id, title).await?;
+		let msg_id = ConvBmc::add_msg(
+			ctx,
+			mm,
+			ConvMsgForCreate {
+				conv_id,
+				content: "See attached".to_string(),
+				..Default::default()
+			},
+		)
+		.await?;
+
+		Ok(msg_id)
+	}
+
+	// endregion: --- Support
+}
+
+// endregion: --- Tests
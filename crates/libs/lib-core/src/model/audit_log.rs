@@ -0,0 +1,418 @@
+//! The `audit_log` is the append-only history of the model mutations.
+//!
+//! Notes:
+//!   - Entries are recorded by the `base::` create/update/delete functions (see `DbBmc::has_audit_log`),
+//!     within the same transaction as the mutation itself.
+//!   - The `diff` is computed from the `to_jsonb` row snapshots before and after the mutation,
+//!     and only contains the changed fields (minus the timestamps and `DbBmc::audit_redacted_fields`).
+//!   - Rows deleted by database cascades (e.g., `conv_msg` on `conv` delete) are not recorded.
+
+use crate::ctx::Ctx;
+use crate::model::acs;
+use crate::model::base::{self, CommonIden, DbBmc};
+use crate::model::modql_utils::time_to_sea_value;
+use crate::model::ModelManager;
+use crate::model::Result;
+use lib_utils::time::{now_utc, Rfc3339};
+use modql::field::{Fields, HasSeaFields, SeaFieldValue};
+use modql::filter::{
+	FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue,
+};
+use sea_query::{Expr, LockType, PostgresQueryBuilder, Query};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use serde_with::serde_as;
+use sqlx::FromRow;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+// region:    --- AuditLog Types
+
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	sqlx::Type,
+	SeaFieldValue,
+	derive_more::Display,
+	Deserialize,
+	Serialize,
+)]
+#[sqlx(type_name = "audit_action")]
+#[cfg_attr(test, derive(PartialEq))]
+pub enum AuditAction {
+	Create,
+	Update,
+	Delete,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct AuditLog {
+	pub id: i64,
+
+	// -- Properties
+	pub entity: String,
+	pub entity_id: i64,
+	pub user_id: i64,
+	pub action: AuditAction,
+	/// The changed fields, as `{"field_name": {"old": ..., "new": ...}}`
+	pub diff: Value,
+
+	// -- Timestamp
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+}
+
+#[derive(Fields)]
+struct AuditLogForInsert {
+	entity: String,
+	entity_id: i64,
+	user_id: i64,
+	#[field(cast_as = "audit_action")]
+	action: AuditAction,
+	diff: Value,
+	ctime: OffsetDateTime,
+}
+
+#[derive(FilterNodes, Deserialize, Default, Debug)]
+pub struct AuditLogFilter {
+	pub id: Option<OpValsInt64>,
+
+	pub entity: Option<OpValsString>,
+	pub entity_id: Option<OpValsInt64>,
+	pub user_id: Option<OpValsInt64>,
+
+	#[modql(cast_as = "audit_action")]
+	pub action: Option<OpValsString>,
+
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	pub ctime: Option<OpValsValue>,
+}
+
+/// The `to_jsonb` row snapshots by entity id.
+pub(in crate::model) type Snapshots = HashMap<i64, Value>;
+
+// endregion: --- AuditLog Types
+
+// region:    --- AuditLogBmc
+
+pub struct AuditLogBmc;
+
+impl DbBmc for AuditLogBmc {
+	const TABLE: &'static str = "audit_log";
+
+	fn has_timestamps() -> bool {
+		false
+	}
+
+	fn has_audit_log() -> bool {
+		false
+	}
+}
+
+impl AuditLogBmc {
+	/// Note: Restricted to root.
+	pub async fn list(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filter: Option<Vec<AuditLogFilter>>,
+		list_options: Option<ListOptions>,
+	) -> Result<Vec<AuditLog>> {
+		acs::assert_root(ctx)?;
+
+		base::list::<Self, _, _>(ctx, mm, filter, list_options).await
+	}
+}
+
+/// Audit functions used by the `base::` crud functions.
+///
+/// Note: Should be called with the same transactional `ModelManager` as the mutation.
+impl AuditLogBmc {
+	/// Returns the row snapshots for the given entity ids (locked for update).
+	/// Returns empty snapshots if the `MC` does not have an audit log.
+	pub(in crate::model) async fn snapshots<MC>(
+		mm: &ModelManager,
+		ids: &[i64],
+	) -> Result<Snapshots>
+	where
+		MC: DbBmc,
+	{
+		if !MC::has_audit_log() || ids.is_empty() {
+			return Ok(Snapshots::new());
+		}
+
+		// -- Build query
+		let mut query = Query::select();
+		query
+			.from(MC::table_ref())
+			.column(CommonIden::Id)
+			.expr(Expr::cust(format!(r#"to_jsonb("{}".*)"#, MC::TABLE)))
+			.and_where(Expr::col(CommonIden::Id).is_in(ids.iter().copied()))
+			.lock(LockType::Update);
+
+		// -- Exec query
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let sqlx_query = sqlx::query_as_with::<_, (i64, Value), _>(&sql, values);
+		let rows = mm.dbx().fetch_all(sqlx_query).await?;
+
+		Ok(rows.into_iter().collect())
+	}
+
+	/// Records one `audit_log` entry per entity id, with the diff
+	/// between its `before` and `after` snapshots.
+	pub(in crate::model) async fn log_changes<MC>(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		action: AuditAction,
+		ids: &[i64],
+		before: &Snapshots,
+		after: &Snapshots,
+	) -> Result<()>
+	where
+		MC: DbBmc,
+	{
+		if !MC::has_audit_log() || ids.is_empty() {
+			return Ok(());
+		}
+
+		let ctime = now_utc();
+
+		// -- Build query
+		let mut query = Query::insert();
+		query.into_table(Self::table_ref());
+		for &entity_id in ids {
+			let log_i = AuditLogForInsert {
+				entity: MC::TABLE.to_string(),
+				entity_id,
+				user_id: ctx.user_id(),
+				action,
+				diff: diff::<MC>(before.get(&entity_id), after.get(&entity_id)),
+				ctime,
+			};
+			let (columns, sea_values) = log_i.not_none_sea_fields().for_sea_insert();
+			query.columns(columns).values(sea_values)?;
+		}
+
+		// -- Exec query
+		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+		let sqlx_query = sqlx::query_with(&sql, values);
+		mm.dbx().execute(sqlx_query).await?;
+
+		Ok(())
+	}
+}
+
+/// Compute the `{"field_name": {"old": ..., "new": ...}}` diff of two row snapshots.
+/// The `old`/`new` property is omitted when the corresponding snapshot is absent (create/delete).
+fn diff<MC>(before: Option<&Value>, after: Option<&Value>) -> Value
+where
+	MC: DbBmc,
+{
+	const SKIP_FIELDS: &[&str] = &["id", "cid", "ctime", "mid", "mtime"];
+
+	let empty = Map::new();
+	let before = before.and_then(Value::as_object).unwrap_or(&empty);
+	let after = after.and_then(Value::as_object).unwrap_or(&empty);
+
+	let mut diff = Map::new();
+	for name in before.keys().chain(after.keys()) {
+		if diff.contains_key(name)
+			|| SKIP_FIELDS.contains(&name.as_str())
+			|| MC::audit_redacted_fields().contains(&name.as_str())
+		{
+			continue;
+		}
+
+		let (old, new) = (before.get(name), after.get(name));
+		if old == new {
+			continue;
+		}
+
+		let mut change = Map::new();
+		if let Some(old) = old {
+			change.insert("old".to_string(), old.clone());
+		}
+		if let Some(new) = new {
+			change.insert("new".to_string(), new.clone());
+		}
+		diff.insert(name.clone(), Value::Object(change));
+	}
+
+	Value::Object(diff)
+}
+
+// endregion: --- AuditLogBmc
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, clean_users, seed_agent, seed_user};
+	use crate::model;
+	use crate::model::agent::{AgentBmc, AgentForUpdate};
+	use crate::model::user::UserBmc;
+	use serde_json::json;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_audit_log_agent_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_name = "test_audit_log_agent_ok agent 01";
+		let fx_name_updated = "test_audit_log_agent_ok agent 01 - updated";
+
+		// -- Exec
+		let agent_id = seed_agent(&ctx, &mm, fx_name).await?;
+		AgentBmc::update(
+			&ctx,
+			&mm,
+			agent_id,
+			AgentForUpdate {
+				name: Some(fx_name_updated.to_string()),
+				..Default::default()
+			},
+		)
+		.await?;
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		// -- Check
+		let filter: AuditLogFilter = serde_json::from_value(json!({
+			"entity": "agent",
+			"entity_id": agent_id
+		}))?;
+		let logs = AuditLogBmc::list(&ctx, &mm, Some(vec![filter]), None).await?;
+		let actions: Vec<AuditAction> = logs.iter().map(|l| l.action).collect();
+		assert_eq!(
+			actions,
+			&[
+				AuditAction::Create,
+				AuditAction::Update,
+				AuditAction::Delete
+			]
+		);
+		assert_eq!(logs[0].diff["name"], json!({"new": fx_name}));
+		assert_eq!(
+			logs[1].diff,
+			json!({"name": {"old": fx_name, "new": fx_name_updated}})
+		);
+		assert_eq!(logs[2].diff["name"], json!({"old": fx_name_updated}));
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_audit_log_user_secrets_redacted_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_username = "test_audit_log_user_secrets_redacted_ok-user-01";
+
+		// -- Exec
+		let user_id = seed_user(&ctx, &mm, fx_username).await?;
+		UserBmc::update_pwd(&ctx, &mm, user_id, "new pwd").await?;
+
+		// -- Check
+		let filter: AuditLogFilter = serde_json::from_value(json!({
+			"entity": "user",
+			"entity_id": user_id
+		}))?;
+		let logs = AuditLogBmc::list(&ctx, &mm, Some(vec![filter]), None).await?;
+		let actions: Vec<AuditAction> = logs.iter().map(|l| l.action).collect();
+		// Note: `UserBmc::create` sets the pwd (update) after the insert.
+		assert_eq!(
+			actions,
+			vec![
+				AuditAction::Create,
+				AuditAction::Update,
+				AuditAction::Update
+			]
+		);
+		assert_eq!(logs[0].diff["username"], json!({"new": fx_username}));
+		for log in logs.iter() {
+			for field in ["pwd", "pwd_salt", "token_salt"] {
+				assert!(
+					log.diff.get(field).is_none(),
+					"audit log {:?} should not have '{field}' in its diff",
+					log.action
+				);
+			}
+		}
+
+		// -- Clean
+		clean_users(&ctx, &mm, "test_audit_log_user_secrets_redacted_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_audit_log_err_append_only() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&ctx, &mm, "test_audit_log_err_append_only agent 01").await?;
+
+		// -- Exec
+		let res_update =
+			sqlx::query("UPDATE audit_log SET diff = '{}' WHERE entity_id = $1")
+				.bind(agent_id)
+				.execute(mm.dbx().db())
+				.await;
+		let res_delete = sqlx::query("DELETE FROM audit_log WHERE entity_id = $1")
+			.bind(agent_id)
+			.execute(mm.dbx().db())
+			.await;
+
+		// -- Check
+		assert!(res_update.is_err(), "update should fail");
+		assert!(res_delete.is_err(), "delete should fail");
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_audit_log_list_err_not_root() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_user_id =
+			seed_user(&ctx, &mm, "test_audit_log_list_err_not_root-user-01").await?;
+		let fx_sys_user_id =
+			seed_user(&ctx, &mm, "test_audit_log_list_err_not_root-user-02").await?;
+		sqlx::query(r#"UPDATE "user" SET typ = 'Sys' WHERE id = $1"#)
+			.bind(fx_sys_user_id)
+			.execute(mm.dbx().db())
+			.await?;
+
+		// -- Exec & Check
+		for user_id in [fx_user_id, fx_sys_user_id] {
+			let user_ctx = Ctx::new(user_id)?;
+			let res = AuditLogBmc::list(&user_ctx, &mm, None, None).await;
+			assert!(
+				matches!(&res, Err(model::Error::AccessDenied { .. })),
+				"should return a AccessDenied for user {user_id}"
+			);
+		}
+
+		// -- Clean
+		clean_users(&ctx, &mm, "test_audit_log_list_err_not_root").await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
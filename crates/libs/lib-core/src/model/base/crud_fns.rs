@@ -1,17 +1,26 @@
 use crate::ctx::Ctx;
+use crate::model::audit_log::{AuditAction, AuditLogBmc, Snapshots};
 use crate::model::base::{
 	prep_fields_for_create, prep_fields_for_update, CommonIden, DbBmc,
 	LIST_LIMIT_DEFAULT, LIST_LIMIT_MAX,
 };
 use crate::model::ModelManager;
 use crate::model::{Error, Result};
-use modql::field::HasSeaFields;
+use modql::field::{HasSeaFields, SeaFields};
 use modql::filter::{FilterGroups, ListOptions};
-use sea_query::{Condition, Expr, PostgresQueryBuilder, Query};
+use modql::SIden;
+use sea_query::{
+	CommonTableExpression, Condition, DynIden, Expr, IntoIden, LockType, OnConflict,
+	PostgresQueryBuilder, Query, WithClause,
+};
 use sea_query_binder::SqlxBinder;
+use serde_json::Value;
 use sqlx::postgres::PgRow;
 use sqlx::FromRow;
-use sqlx::Row;
+use std::collections::HashSet;
+
+/// The columns which are never updated by an `upsert` conflict.
+const UPSERT_KEEP_COLUMNS: &[&str] = &["id", "owner_id", "cid", "ctime"];
 
 pub async fn create<MC, E>(ctx: &Ctx, mm: &ModelManager, data: E) -> Result<i64>
 where
@@ -33,14 +42,27 @@ where
 		.values(sea_values)?
 		.returning(Query::returning().columns([CommonIden::Id]));
 
-	// -- Exec query
+	// -- Exec query (and audit log) in a transaction (savepoint if nested)
 	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 	let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
-	// NOTE: For now, we will use the _txn for all create.
-	//       We could have a with_txn as function argument if perf is an issue (it should not be)
-	let (id,) = mm.dbx().fetch_one(sqlx_query).await?;
-
-	Ok(id)
+	mm.in_txn(|mm| async move {
+		let (id,) = mm.dbx().fetch_one(sqlx_query).await?;
+
+		let after = AuditLogBmc::snapshots::<MC>(&mm, &[id]).await?;
+		let before = Snapshots::new();
+		AuditLogBmc::log_changes::<MC>(
+			ctx,
+			&mm,
+			AuditAction::Create,
+			&[id],
+			&before,
+			&after,
+		)
+		.await?;
+
+		Ok(id)
+	})
+	.await
 }
 
 pub async fn create_many<MC, E>(
@@ -72,18 +94,55 @@ where
 
 	query.returning(Query::returning().columns([CommonIden::Id]));
 
-	// Execute query
+	// Execute query (and audit log) in a transaction (savepoint if nested)
 	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 	let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
+	mm.in_txn(|mm| async move {
+		let rows = mm.dbx().fetch_all(sqlx_query).await?;
+
+		for row in rows {
+			let (id,): (i64,) = row;
+			ids.push(id);
+		}
 
-	let rows = mm.dbx().fetch_all(sqlx_query).await?;
+		let after = AuditLogBmc::snapshots::<MC>(&mm, &ids).await?;
+		let before = Snapshots::new();
+		AuditLogBmc::log_changes::<MC>(
+			ctx,
+			&mm,
+			AuditAction::Create,
+			&ids,
+			&before,
+			&after,
+		)
+		.await?;
+
+		Ok(ids)
+	})
+	.await
+}
 
-	for row in rows {
-		let (id,): (i64,) = row;
-		ids.push(id);
-	}
+/// Create each item in its own savepoint, so that the failing items
+/// do not prevent the other ones from being created.
+/// Returns the per item results (same order as `data`).
+pub async fn create_many_partial<MC, E>(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	data: Vec<E>,
+) -> Result<Vec<Result<i64>>>
+where
+	MC: DbBmc,
+	E: HasSeaFields,
+{
+	mm.in_txn(|mm| async move {
+		let mut results = Vec::with_capacity(data.len());
+		for item in data {
+			results.push(create::<MC, _>(ctx, &mm, item).await);
+		}
 
-	Ok(ids)
+		Ok(results)
+	})
+	.await
 }
 
 pub async fn get<MC, E>(_ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<E>
@@ -195,12 +254,11 @@ where
 	MC: DbBmc,
 	F: Into<FilterGroups>,
 {
-	let db = mm.dbx().db();
 	// -- Build the query
-	let mut query = Query::select()
+	let mut query = Query::select();
+	query
 		.from(MC::table_ref())
-		.expr(Expr::col(sea_query::Asterisk).count())
-		.to_owned();
+		.expr(Expr::col(sea_query::Asterisk).count());
 
 	// condition from filter
 	if let Some(filter) = filter {
@@ -209,14 +267,11 @@ where
 		query.cond_where(cond);
 	}
 
-	let query_str = query.to_string(PostgresQueryBuilder);
-
-	let result = sqlx::query(&query_str)
-		.fetch_one(db)
-		.await
-		.map_err(|_| Error::CountFail)?;
-
-	let count: i64 = result.try_get("count").map_err(|_| Error::CountFail)?;
+	// -- Execute the query
+	// Note: Through the Dbx, so that the count sees the eventual ongoing transaction.
+	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+	let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
+	let (count,) = mm.dbx().fetch_one(sqlx_query).await?;
 
 	Ok(count)
 }
@@ -243,23 +298,225 @@ where
 		.values(fields)
 		.and_where(Expr::col(CommonIden::Id).eq(id));
 
-	// -- Execute query
+	// -- Execute query (and audit log) in a transaction (savepoint if nested)
 	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 	let sqlx_query = sqlx::query_with(&sql, values);
-	let count = mm.dbx().execute(sqlx_query).await?;
+	mm.in_txn(|mm| async move {
+		let before = AuditLogBmc::snapshots::<MC>(&mm, &[id]).await?;
+		let count = mm.dbx().execute(sqlx_query).await?;
+
+		// -- Check result
+		if count == 0 {
+			return Err(Error::EntityNotFound {
+				entity: MC::TABLE,
+				id,
+			});
+		}
+
+		let after = AuditLogBmc::snapshots::<MC>(&mm, &[id]).await?;
+		AuditLogBmc::log_changes::<MC>(
+			ctx,
+			&mm,
+			AuditAction::Update,
+			&[id],
+			&before,
+			&after,
+		)
+		.await
+	})
+	.await
+}
+
+/// Update the entities of `ids` with the same `data` (atomically).
+/// Returns an `EntitiesNotFound` error with the missing ids (and nothing is updated)
+/// if some of the ids do not exist.
+pub async fn update_many<MC, E>(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	ids: Vec<i64>,
+	data: E,
+) -> Result<u64>
+where
+	MC: DbBmc,
+	E: HasSeaFields,
+{
+	if ids.is_empty() {
+		return Ok(0);
+	}
+
+	let fields = data.not_none_sea_fields();
+
+	mm.in_txn(|mm| async move {
+		let updated_ids = update_ids::<MC>(ctx, &mm, &ids, fields).await?;
+
+		// -- Check result
+		let missing_ids = missing_ids(&ids, &updated_ids);
+		if !missing_ids.is_empty() {
+			return Err(Error::EntitiesNotFound {
+				entity: MC::TABLE,
+				ids: missing_ids,
+			});
+		}
+
+		Ok(updated_ids.len() as u64)
+	})
+	.await
+}
+
+/// Update all of the entities matching the `filter` with the same `data`.
+/// Returns the number of updated entities.
+pub async fn update_by_filter<MC, E, F>(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	filter: F,
+	data: E,
+) -> Result<u64>
+where
+	MC: DbBmc,
+	E: HasSeaFields,
+	F: Into<FilterGroups>,
+{
+	let filters: FilterGroups = filter.into();
+	let cond: Condition = filters.try_into()?;
+	let fields = data.not_none_sea_fields();
+
+	// -- Build the ids query
+	// Note: The ids are selected (and locked) first, so that the changes
+	//       can be recorded in the audit log.
+	let mut query = Query::select();
+	query
+		.from(MC::table_ref())
+		.column(CommonIden::Id)
+		.cond_where(cond)
+		.lock(LockType::Update);
+	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+
+	mm.in_txn(|mm| async move {
+		let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
+		let ids: Vec<i64> = mm
+			.dbx()
+			.fetch_all(sqlx_query)
+			.await?
+			.into_iter()
+			.map(|(id,)| id)
+			.collect();
+
+		let updated_ids = update_ids::<MC>(ctx, &mm, &ids, fields).await?;
+
+		Ok(updated_ids.len() as u64)
+	})
+	.await
+}
+
+/// Insert the entity, or update it if an entity with the same `DbBmc::unique_key` exists
+/// (i.e., `INSERT ... ON CONFLICT (unique_key) DO UPDATE`).
+///
+/// Notes:
+///   - The `data` must have all the unique key fields.
+///   - On update, only the `data` fields (other than the unique key ones) and the
+///     `mid`/`mtime` are updated (`cid`, `ctime`, and `owner_id` are left as is).
+///   - Whether the entity was inserted or updated is decided by the upsert statement
+///     itself (`xmax = 0` for an inserted row), which also returns the snapshot of the
+///     existing row for the audit log (absent if that row was inserted concurrently).
+///
+/// Returns the id of the inserted or updated entity.
+pub async fn upsert<MC, E>(ctx: &Ctx, mm: &ModelManager, data: E) -> Result<i64>
+where
+	MC: DbBmc,
+	E: HasSeaFields,
+{
+	let unique_key =
+		MC::unique_key().ok_or(Error::UpsertNoUniqueKey { entity: MC::TABLE })?;
+	let user_id = ctx.user_id();
 
-	// -- Check result
-	if count == 0 {
-		Err(Error::EntityNotFound {
-			entity: MC::TABLE,
-			id,
+	// -- Prep the fields
+	let mut fields = data.not_none_sea_fields();
+	prep_fields_for_create::<MC>(&mut fields, user_id);
+	let fields = fields.into_vec();
+
+	// -- Build the existing row snapshot query (from the unique key values)
+	let mut before_query = Query::select();
+	before_query.from(MC::table_ref()).expr_as(
+		Expr::cust(format!(r#"to_jsonb("{}".*)"#, MC::TABLE)),
+		SIden("snapshot"),
+	);
+	for key in unique_key {
+		let field = fields
+			.iter()
+			.find(|field| field.iden.to_string() == *key)
+			.ok_or(Error::UpsertMissingKeyField {
+				entity: MC::TABLE,
+				field: key,
+			})?;
+		before_query
+			.and_where(Expr::col(field.iden.clone()).eq(field.value.clone()));
+	}
+	let before_cte = CommonTableExpression::new()
+		.query(before_query)
+		.table_name(SIden("upsert_before"))
+		.to_owned();
+
+	// -- Build the upsert query
+	// Note: Without columns to update, the unique key columns are set to themselves,
+	//       so that the existing row is still returned (`DO NOTHING` returns no row).
+	let mut update_columns: Vec<DynIden> = fields
+		.iter()
+		.map(|field| field.iden.clone())
+		.filter(|iden| {
+			let name = iden.to_string();
+			!unique_key.contains(&name.as_str())
+				&& !UPSERT_KEEP_COLUMNS.contains(&name.as_str())
 		})
-	} else {
-		Ok(())
+		.collect();
+	if update_columns.is_empty() {
+		update_columns = unique_key
+			.iter()
+			.map(|key| SIden(key).into_iden())
+			.collect();
 	}
+	let on_conflict = OnConflict::columns(unique_key.iter().map(|key| SIden(key)))
+		.update_columns(update_columns)
+		.to_owned();
+	let (columns, sea_values) = SeaFields::new(fields).for_sea_insert();
+	let mut query = Query::insert();
+	query
+		.into_table(MC::table_ref())
+		.columns(columns)
+		.values(sea_values)?
+		.on_conflict(on_conflict)
+		.returning(Query::returning().exprs([
+			Expr::col(CommonIden::Id).into(),
+			Expr::cust(format!(r#""{}".xmax = 0"#, MC::TABLE)),
+			Expr::cust(r#"(SELECT "snapshot" FROM "upsert_before")"#),
+		]));
+	let (sql, values) = query
+		.with(WithClause::new().cte(before_cte).to_owned())
+		.build_sqlx(PostgresQueryBuilder);
+
+	// -- Exec query (and audit log) in a transaction (savepoint if nested)
+	mm.in_txn(|mm| async move {
+		let sqlx_query =
+			sqlx::query_as_with::<_, (i64, bool, Option<Value>), _>(&sql, values);
+		let (id, inserted, before) = mm.dbx().fetch_one(sqlx_query).await?;
+
+		let (action, before) = if inserted {
+			(AuditAction::Create, Snapshots::new())
+		} else {
+			(
+				AuditAction::Update,
+				before.map(|v| (id, v)).into_iter().collect(),
+			)
+		};
+		let after = AuditLogBmc::snapshots::<MC>(&mm, &[id]).await?;
+		AuditLogBmc::log_changes::<MC>(ctx, &mm, action, &[id], &before, &after)
+			.await?;
+
+		Ok(id)
+	})
+	.await
 }
 
-pub async fn delete<MC>(_ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()>
+pub async fn delete<MC>(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()>
 where
 	MC: DbBmc,
 {
@@ -269,24 +526,40 @@ where
 		.from_table(MC::table_ref())
 		.and_where(Expr::col(CommonIden::Id).eq(id));
 
-	// -- Execute query
+	// -- Execute query (and audit log) in a transaction (savepoint if nested)
 	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
 	let sqlx_query = sqlx::query_with(&sql, values);
-	let count = mm.dbx().execute(sqlx_query).await?;
+	mm.in_txn(|mm| async move {
+		let before = AuditLogBmc::snapshots::<MC>(&mm, &[id]).await?;
+		let count = mm.dbx().execute(sqlx_query).await?;
 
-	// -- Check result
-	if count == 0 {
-		Err(Error::EntityNotFound {
-			entity: MC::TABLE,
-			id,
-		})
-	} else {
-		Ok(())
-	}
+		// -- Check result
+		if count == 0 {
+			return Err(Error::EntityNotFound {
+				entity: MC::TABLE,
+				id,
+			});
+		}
+
+		let after = Snapshots::new();
+		AuditLogBmc::log_changes::<MC>(
+			ctx,
+			&mm,
+			AuditAction::Delete,
+			&[id],
+			&before,
+			&after,
+		)
+		.await
+	})
+	.await
 }
 
+/// Delete the entities of `ids` (atomically).
+/// Returns an `EntitiesNotFound` error with the missing ids (and nothing is deleted)
+/// if some of the ids do not exist.
 pub async fn delete_many<MC>(
-	_ctx: &Ctx,
+	ctx: &Ctx,
 	mm: &ModelManager,
 	ids: Vec<i64>,
 ) -> Result<u64>
@@ -297,26 +570,163 @@ where
 		return Ok(0);
 	}
 
+	mm.in_txn(|mm| async move {
+		let deleted_ids = delete_ids::<MC>(ctx, &mm, &ids).await?;
+
+		// -- Check result
+		let missing_ids = missing_ids(&ids, &deleted_ids);
+		if !missing_ids.is_empty() {
+			return Err(Error::EntitiesNotFound {
+				entity: MC::TABLE,
+				ids: missing_ids,
+			});
+		}
+
+		Ok(deleted_ids.len() as u64)
+	})
+	.await
+}
+
+/// Delete the entities of `ids` which exist.
+/// Returns the per id results (`EntityNotFound` error for the missing ids).
+pub async fn delete_many_partial<MC>(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	ids: Vec<i64>,
+) -> Result<Vec<Result<()>>>
+where
+	MC: DbBmc,
+{
+	if ids.is_empty() {
+		return Ok(Vec::new());
+	}
+
+	let ids = ids.as_slice();
+	let deleted_ids = mm
+		.in_txn(|mm| async move { delete_ids::<MC>(ctx, &mm, ids).await })
+		.await?;
+
+	let results = ids
+		.iter()
+		.map(|&id| {
+			if deleted_ids.contains(&id) {
+				Ok(())
+			} else {
+				Err(Error::EntityNotFound {
+					entity: MC::TABLE,
+					id,
+				})
+			}
+		})
+		.collect();
+
+	Ok(results)
+}
+
+/// Delete the `ids` entities, and record the changes in the audit log.
+/// Returns the ids of the deleted entities.
+///
+/// Note: Must be called with a transactional `ModelManager`.
+async fn delete_ids<MC>(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	ids: &[i64],
+) -> Result<Vec<i64>>
+where
+	MC: DbBmc,
+{
 	// -- Build query
 	let mut query = Query::delete();
 	query
 		.from_table(MC::table_ref())
-		.and_where(Expr::col(CommonIden::Id).is_in(ids.clone()));
+		.and_where(Expr::col(CommonIden::Id).is_in(ids.iter().copied()))
+		.returning(Query::returning().columns([CommonIden::Id]));
 
-	// -- Execute query
+	// -- Execute query (and audit log)
 	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-	let sqlx_query = sqlx::query_with(&sql, values);
-	let result = mm.dbx().execute(sqlx_query).await?;
+	let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
 
-	// -- Check result
-	if result as usize != ids.len() {
-		Err(Error::EntityNotFound {
-			entity: MC::TABLE,
-			id: 0, // Using 0 because multiple IDs could be not found, you may want to improve error handling here
-		})
-	} else {
-		Ok(result)
+	let before = AuditLogBmc::snapshots::<MC>(mm, ids).await?;
+	let deleted_ids: Vec<i64> = mm
+		.dbx()
+		.fetch_all(sqlx_query)
+		.await?
+		.into_iter()
+		.map(|(id,)| id)
+		.collect();
+
+	let after = Snapshots::new();
+	AuditLogBmc::log_changes::<MC>(
+		ctx,
+		mm,
+		AuditAction::Delete,
+		&deleted_ids,
+		&before,
+		&after,
+	)
+	.await?;
+
+	Ok(deleted_ids)
+}
+
+/// Update the `ids` entities with the `fields` (plus the update timestamps),
+/// and record the changes in the audit log.
+///
+/// Note: Must be called with a transactional `ModelManager`.
+async fn update_ids<MC>(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	ids: &[i64],
+	mut fields: SeaFields,
+) -> Result<Vec<i64>>
+where
+	MC: DbBmc,
+{
+	if ids.is_empty() {
+		return Ok(Vec::new());
 	}
+
+	prep_fields_for_update::<MC>(&mut fields, ctx.user_id());
+
+	// -- Build query
+	let fields = fields.for_sea_update();
+	let mut query = Query::update();
+	query
+		.table(MC::table_ref())
+		.values(fields)
+		.and_where(Expr::col(CommonIden::Id).is_in(ids.iter().copied()))
+		.returning(Query::returning().columns([CommonIden::Id]));
+
+	// -- Execute query (and audit log)
+	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+	let sqlx_query = sqlx::query_as_with::<_, (i64,), _>(&sql, values);
+
+	let before = AuditLogBmc::snapshots::<MC>(mm, ids).await?;
+	let updated_ids: Vec<i64> = mm
+		.dbx()
+		.fetch_all(sqlx_query)
+		.await?
+		.into_iter()
+		.map(|(id,)| id)
+		.collect();
+	let after = AuditLogBmc::snapshots::<MC>(mm, &updated_ids).await?;
+	AuditLogBmc::log_changes::<MC>(
+		ctx,
+		mm,
+		AuditAction::Update,
+		&updated_ids,
+		&before,
+		&after,
+	)
+	.await?;
+
+	Ok(updated_ids)
+}
+
+/// Returns the (deduplicated) `ids` which are not in `found_ids`.
+fn missing_ids(ids: &[i64], found_ids: &[i64]) -> Vec<i64> {
+	let mut seen: HashSet<i64> = found_ids.iter().copied().collect();
+	ids.iter().copied().filter(|id| seen.insert(*id)).collect()
 }
 
 pub fn compute_list_options(
@@ -0,0 +1,268 @@
+//! Paginated list, with the total count and the keyset pagination cursor.
+//!
+//! Notes:
+//!   - The `next_cursor` is an opaque (b64u json) `ListCursor` with the `order_by` and the
+//!     `id` of the last item of the page. The next page is selected with
+//!     `(order_by_col, id) > (SELECT order_by_col, id FROM table WHERE id = cursor.id AND <filter>)`
+//!     (or `<` for a descending order), which does not degrade like deep offsets.
+//!   - The cursor row is selected with the same filter as the page, so a cursor replayed
+//!     against another listing (or a row the caller cannot see) does not position the page.
+//!   - Keyset pagination requires at most one `order_bys` column (`id` is appended as tie breaker).
+//!     With more, the page is still returned (offset paging), but without `next_cursor`.
+//!   - Rows with a NULL `order_by` value, or a cursor row deleted (or not matching the filter)
+//!     in between, end the keyset iteration.
+
+use crate::ctx::Ctx;
+use crate::model::base::{compute_list_options, CommonIden, DbBmc};
+use crate::model::ModelManager;
+use crate::model::{Error, Result};
+use lib_utils::b64::{b64u_decode_to_string, b64u_encode};
+use modql::field::{HasFields, HasSeaFields};
+use modql::filter::{FilterGroups, ListOptions, OrderBy, OrderBys};
+use modql::StringIden;
+use sea_query::{
+	Condition, Expr, IntoIden, PostgresQueryBuilder, Query, SimpleExpr,
+	SubQueryStatement,
+};
+use sea_query_binder::SqlxBinder;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::{FromRow, Row};
+
+// region:    --- Types
+
+/// One page of a list, with the total of the matching entities (regardless of the page).
+#[derive(Debug)]
+pub struct ListPage<E> {
+	pub items: Vec<E>,
+	pub total: i64,
+	pub limit: i64,
+	pub offset: i64,
+	pub next_cursor: Option<String>,
+}
+
+/// The decoded `next_cursor`.
+#[derive(Debug, Serialize, Deserialize)]
+struct ListCursor {
+	/// The order by (modql format, e.g., `"!ctime"`) of the page which issued the cursor.
+	order_by: String,
+	/// The id of the last item of the page which issued the cursor.
+	id: i64,
+}
+
+impl ListCursor {
+	fn encode(&self) -> Result<String> {
+		let json =
+			serde_json::to_string(self).map_err(|_| Error::ListCursorInvalid)?;
+		Ok(b64u_encode(json))
+	}
+
+	fn decode(cursor: &str) -> Result<Self> {
+		let json =
+			b64u_decode_to_string(cursor).map_err(|_| Error::ListCursorInvalid)?;
+		serde_json::from_str(&json).map_err(|_| Error::ListCursorInvalid)
+	}
+}
+
+/// Wrap the entity `E` with its row id, to build the `next_cursor`.
+struct WithId<E>(i64, E);
+
+impl<'r, E> FromRow<'r, PgRow> for WithId<E>
+where
+	E: FromRow<'r, PgRow>,
+{
+	fn from_row(row: &'r PgRow) -> sqlx::Result<Self> {
+		Ok(WithId(row.try_get("id")?, E::from_row(row)?))
+	}
+}
+
+// endregion: --- Types
+
+pub async fn list_page<MC, E, F>(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	filter: Option<F>,
+	list_options: Option<ListOptions>,
+	cursor: Option<String>,
+) -> Result<ListPage<E>>
+where
+	MC: DbBmc,
+	F: Into<FilterGroups>,
+	E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
+	E: HasFields + HasSeaFields,
+{
+	let filter: Option<FilterGroups> = filter.map(Into::into);
+	let mut list_options = compute_list_options(list_options)?;
+	let keyset_order = keyset_order_by::<E>(list_options.order_bys.as_ref())?;
+
+	// -- Build the query
+	let mut query = Query::select();
+	query.from(MC::table_ref()).columns(E::sea_column_refs());
+
+	// condition from filter
+	let filter_cond: Option<Condition> =
+		filter.clone().map(TryInto::try_into).transpose()?;
+	if let Some(cond) = filter_cond.clone() {
+		query.cond_where(cond);
+	}
+
+	// condition from cursor
+	if let Some(cursor) = cursor {
+		let cursor = ListCursor::decode(&cursor)?;
+		let order_by = keyset_order.as_ref().ok_or_else(|| {
+			Error::ListCursorOrderByNotSupported {
+				order_by: order_bys_to_string(list_options.order_bys.as_ref()),
+			}
+		})?;
+		if cursor.order_by != order_by_to_string(order_by) {
+			return Err(Error::ListCursorInvalid);
+		}
+		query.and_where(keyset_cond::<MC>(order_by, cursor.id, filter_cond));
+		list_options.offset = None;
+	}
+
+	// list options (with `id` tie breaker for keyset pagination)
+	if let Some(order_by) = keyset_order.as_ref() {
+		list_options.order_bys = Some(with_id_tie_breaker(order_by));
+	}
+	let limit = list_options.limit.unwrap_or_default();
+	let offset = list_options.offset.unwrap_or_default();
+	list_options.apply_to_sea_query(&mut query);
+
+	// -- Execute the query
+	let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
+	let sqlx_query = sqlx::query_as_with::<_, WithId<E>, _>(&sql, values);
+	let rows = mm.dbx().fetch_all(sqlx_query).await?;
+
+	// -- Build the next cursor (only if the page is full)
+	let next_cursor = match (keyset_order.as_ref(), rows.last()) {
+		(Some(order_by), Some(WithId(last_id, _))) if rows.len() as i64 == limit => {
+			let cursor = ListCursor {
+				order_by: order_by_to_string(order_by),
+				id: *last_id,
+			};
+			Some(cursor.encode()?)
+		}
+		_ => None,
+	};
+
+	let items = rows.into_iter().map(|WithId(_, entity)| entity).collect();
+	let total = super::count::<MC, _>(ctx, mm, filter).await?;
+
+	Ok(ListPage {
+		items,
+		total,
+		limit,
+		offset,
+		next_cursor,
+	})
+}
+
+// region:    --- Support
+
+/// Returns the single order by usable for keyset pagination (default `id`),
+/// or None if there are multiple order bys.
+/// Returns an error if the order by column is not a field of `E`.
+fn keyset_order_by<E: HasFields>(
+	order_bys: Option<&OrderBys>,
+) -> Result<Option<OrderBy>> {
+	let Some(order_bys) = order_bys else {
+		return Ok(Some(OrderBy::Asc("id".to_string())));
+	};
+
+	let mut iter = order_bys.into_iter();
+	match (iter.next(), iter.next()) {
+		(Some(order_by), None) => {
+			let (OrderBy::Asc(col) | OrderBy::Desc(col)) = order_by;
+			if E::field_names().contains(&col.as_str()) {
+				Ok(Some(order_by.clone()))
+			} else {
+				Err(Error::ListOrderByUnknown {
+					order_by: col.to_string(),
+				})
+			}
+		}
+		(None, _) => Ok(Some(OrderBy::Asc("id".to_string()))),
+		_ => Ok(None),
+	}
+}
+
+/// Returns the condition selecting the rows after the cursor row.
+/// The cursor row is selected with the page filter, so that a cursor row not part of
+/// the listing returns NULL (i.e., no rows) rather than positioning the page.
+fn keyset_cond<MC: DbBmc>(
+	order_by: &OrderBy,
+	cursor_id: i64,
+	filter_cond: Option<Condition>,
+) -> SimpleExpr {
+	let (col, is_asc) = match order_by {
+		OrderBy::Asc(col) => (col.as_str(), true),
+		OrderBy::Desc(col) => (col.as_str(), false),
+	};
+
+	// -- The cursor row (with the page filter)
+	let mut cursor_row = Query::select();
+	cursor_row
+		.from(MC::table_ref())
+		.and_where(Expr::col(CommonIden::Id).eq(cursor_id));
+	if let Some(cond) = filter_cond {
+		cursor_row.cond_where(cond);
+	}
+
+	// -- The page row (`id` only, or `(col, id)` row comparison)
+	let page_row: SimpleExpr = if col == "id" {
+		cursor_row.column(CommonIden::Id);
+		Expr::col(CommonIden::Id).into()
+	} else {
+		let col = StringIden(col.to_string()).into_iden();
+		cursor_row.column(col.clone()).column(CommonIden::Id);
+		Expr::tuple([Expr::col(col).into(), Expr::col(CommonIden::Id).into()]).into()
+	};
+
+	let cursor_row = SimpleExpr::SubQuery(
+		None,
+		Box::new(SubQueryStatement::SelectStatement(cursor_row.to_owned())),
+	);
+	let page_row = Expr::expr(page_row);
+	if is_asc {
+		page_row.gt(cursor_row)
+	} else {
+		page_row.lt(cursor_row)
+	}
+}
+
+fn with_id_tie_breaker(order_by: &OrderBy) -> OrderBys {
+	let id_order_by = match order_by {
+		OrderBy::Asc(_) => OrderBy::Asc("id".to_string()),
+		OrderBy::Desc(_) => OrderBy::Desc("id".to_string()),
+	};
+	let (OrderBy::Asc(col) | OrderBy::Desc(col)) = order_by;
+
+	if col == "id" {
+		OrderBys::new(vec![order_by.clone()])
+	} else {
+		OrderBys::new(vec![order_by.clone(), id_order_by])
+	}
+}
+
+/// Format the order by as the modql `"!col"` / `"col"` format.
+fn order_by_to_string(order_by: &OrderBy) -> String {
+	match order_by {
+		OrderBy::Asc(col) => col.to_string(),
+		OrderBy::Desc(col) => format!("!{col}"),
+	}
+}
+
+fn order_bys_to_string(order_bys: Option<&OrderBys>) -> String {
+	order_bys
+		.map(|order_bys| {
+			order_bys
+				.into_iter()
+				.map(order_by_to_string)
+				.collect::<Vec<_>>()
+				.join(",")
+		})
+		.unwrap_or_default()
+}
+
+// endregion: --- Support
@@ -1,11 +1,12 @@
 // region:    --- Modules
 
 mod crud_fns;
-mod macro_utils;
+mod list_page;
 mod utils;
 
 // -- Flatten hierarchy for user code.
 pub use crud_fns::*;
+pub use list_page::*;
 pub use utils::*;
 
 use modql::SIden;
@@ -67,4 +68,28 @@ pub trait DbBmc {
 	fn has_owner_id() -> bool {
 		false
 	}
+
+	/// Specifies if the `base::` create/update/delete functions must record
+	/// their changes in the `audit_log` table.
+	///
+	/// default: true
+	fn has_audit_log() -> bool {
+		true
+	}
+
+	/// The column names that must never be recorded in the `audit_log` diffs
+	/// (e.g., password hashes, salts).
+	///
+	/// default: none
+	fn audit_redacted_fields() -> &'static [&'static str] {
+		&[]
+	}
+
+	/// The column names of the unique key used by `base::upsert`
+	/// for its `ON CONFLICT` (must match a unique constraint of the table).
+	///
+	/// default: None (no upsert)
+	fn unique_key() -> Option<&'static [&'static str]> {
+		None
+	}
 }
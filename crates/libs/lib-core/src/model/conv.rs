@@ -1,22 +1,46 @@
+use crate::ai::tool::ToolRegistry;
+use crate::ai::{self, ChatMessage, ChatReply, ChatResponse, ToolResult};
 use crate::ctx::Ctx;
-use crate::generate_common_bmc_fns;
-use crate::model::base::{self, DbBmc};
+use crate::model::acs::{
+	assert_agent_user, assert_conv_owner, assert_conv_user, conv_visible_sql,
+};
+use crate::model::agent::{AgentVersion, AgentVersionBmc};
+use crate::model::attachment::AttachmentBmc;
+use crate::model::base;
+use crate::model::conv_context;
 use crate::model::conv_msg::{
-	ConvMsg, ConvMsgBmc, ConvMsgForCreate, ConvMsgForInsert,
+	validate_content, ConvMsg, ConvMsgBmc, ConvMsgContentType, ConvMsgFilter,
+	ConvMsgForCreate, ConvMsgForEdit, ConvMsgForInsert, ConvMsgForUpdate,
+	ConvMsgHit, ConvMsgRole,
+};
+use crate::model::conv_msg_reaction::{
+	ConvMsgReaction, ConvMsgReactionBmc, ConvMsgReactionForCreate,
+	ConvMsgReactionForInsert,
+};
+use crate::model::conv_msg_revision::{
+	ConvMsgRevision, ConvMsgRevisionBmc, ConvMsgRevisionForInsert,
+};
+use crate::model::conv_user::{
+	ConvUser, ConvUserBmc, ConvUserForCreate, ConvUserForMarkRead,
 };
 use crate::model::modql_utils::time_to_sea_value;
-use crate::model::ModelManager;
-use crate::model::Result;
-use lib_utils::time::Rfc3339;
+use crate::model::tag::{conv_tag_id_to_sea_condition, scope_conv_tag_ids};
+use crate::model::usage::UsageBmc;
+use crate::model::{Error, Result};
+use crate::model::{ListPage, ModelManager};
+use lib_macros::Bmc;
+use lib_utils::time::{now_utc, Rfc3339};
 use modql::field::{Fields, SeaFieldValue};
 use modql::filter::{
-	FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue,
+	FilterNodes, IntoSeaError, ListOptions, OpValInt64, OpValValue, OpValsInt64,
+	OpValsString, OpValsValue, SeaResult,
 };
-use sea_query::Nullable;
+use sea_query::{ColumnRef, ConditionExpression, Expr, Nullable};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 use sqlx::types::time::OffsetDateTime;
 use sqlx::FromRow;
+use std::collections::HashMap;
 
 // region:    --- Conv Types
 
@@ -74,19 +98,72 @@ pub enum ConvState {
 	Archived,
 }
 
+/// Note: The `derive(Bmc)` generates the `ConvBmc` with the default CRUD functions,
+///       and the `generate_conv_rpc_fns!()` for the rpc handlers
+///       (but the ones checking the conv access, written in the conv rpc module).
 #[serde_as]
-#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+#[derive(Debug, Clone, Fields, FromRow, Serialize, Bmc)]
+#[bmc(
+	table = "conv",
+	owner_id,
+	for_create = ConvForCreate,
+	for_update = ConvForUpdate,
+	filter = ConvFilter,
+	privileges(update_by_filter = Sys),
+	rpc(
+		suffix = "conv",
+		plural = "convs",
+		skip(
+			get_conv,
+			list_convs,
+			count_convs,
+			create_conv,
+			create_many_convs,
+			update_conv,
+			update_many_convs,
+			delete_conv,
+			delete_many_convs
+		)
+	)
+)]
 pub struct Conv {
 	pub id: i64,
 
 	// -- Relations
 	pub agent_id: i64,
+	/// The `AgentVersion` of the agent at the conv creation.
+	pub agent_version_id: i64,
 	pub owner_id: i64,
 
 	// -- Properties
 	pub title: Option<String>,
 	pub kind: ConvKind,
 	pub state: ConvState,
+	/// The leaf msg of the active branch (None if no msgs, see `ConvBmc::get_branch`).
+	pub head_msg_id: Option<i64>,
+	/// The owner folder of the conv (see `ConvBmc::move_to_folder`).
+	pub folder_id: Option<i64>,
+
+	/// The tags of the ctx user on the conv
+	/// (only set by `ConvBmc::load_tag_ids`, e.g., by `ConvBmc::list_page_with_unread`).
+	#[field(skip)]
+	#[sqlx(skip)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub tag_ids: Option<Vec<i64>>,
+
+	/// If the conv is pinned by the ctx user
+	/// (only set by `ConvBmc::list_page_pinned_first`, e.g., by `ConvBmc::list_page_with_unread`).
+	#[field(skip)]
+	#[sqlx(skip)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub pinned: Option<bool>,
+
+	/// The number of msgs from the other users after the ctx user last read msg
+	/// (only set by the read state functions, e.g., `ConvBmc::list_page_with_unread`).
+	#[field(skip)]
+	#[sqlx(skip)]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub unread_count: Option<i64>,
 
 	// -- Timestamps
 	// creator user_id and time
@@ -111,25 +188,66 @@ pub struct ConvForCreate {
 
 #[derive(Fields, Deserialize, Default)]
 pub struct ConvForUpdate {
-	pub owner_id: Option<i64>,
 	pub title: Option<String>,
 	pub closed: Option<bool>,
 	#[field(cast_as = "conv_state")]
 	pub state: Option<ConvState>,
 }
 
-#[derive(FilterNodes, Deserialize, Default, Debug)]
+/// The agent version of a forked conv (see `ConvBmc::fork_conv`).
+#[derive(Fields)]
+struct ConvForUpdateAgentVersion {
+	agent_version_id: i64,
+}
+
+/// The read mark of the ctx user in a conv.
+#[derive(Deserialize, Default)]
+pub struct ConvForMarkRead {
+	/// The last read msg (default: the last msg of the conv).
+	pub msg_id: Option<i64>,
+}
+
+/// The fork of a conv at one of its msgs (see `ConvBmc::fork_conv`).
+#[derive(Deserialize, Default)]
+pub struct ConvForFork {
+	pub conv_id: i64,
+	/// The last msg of the fork.
+	pub at_msg_id: i64,
+}
+
+/// The active branch of a conv, i.e., the msgs from the first msg
+/// to the conv head msg (see `ConvBmc::get_branch`).
+#[derive(Debug, Serialize)]
+pub struct ConvBranch {
+	pub conv: Conv,
+	pub msgs: Vec<ConvMsg>,
+}
+
+#[derive(FilterNodes, Deserialize, Default, Debug, Clone)]
 pub struct ConvFilter {
 	pub id: Option<OpValsInt64>,
 
 	pub owner_id: Option<OpValsInt64>,
 	pub agent_id: Option<OpValsInt64>,
+	pub agent_version_id: Option<OpValsInt64>,
 
 	#[modql(cast_as = "conv_kind")]
 	pub kind: Option<OpValsString>,
 
 	pub title: Option<OpValsString>,
 
+	pub folder_id: Option<OpValsInt64>,
+	/// The convs tagged (or not) by the tag of the ctx user, e.g., `{"tag_id": 12}`
+	/// or `{"tag_id": {"$not": 12}}` (see `conv_tag_id_to_sea_condition`).
+	#[modql(to_sea_condition_fn = "conv_tag_id_to_sea_condition")]
+	pub tag_id: Option<OpValsValue>,
+
+	/// The convs visible by the user id
+	/// (overwritten by `ConvBmc::visible_filters`, and not deserialized).
+	#[serde(skip)]
+	#[modql(to_sea_condition_fn = "conv_visible_to_sea_condition")]
+	pub visible_to: Option<OpValsValue>,
+
 	pub cid: Option<OpValsInt64>,
 	#[modql(to_sea_value_fn = "time_to_sea_value")]
 	pub ctime: Option<OpValsValue>,
@@ -142,164 +260,2434 @@ pub struct ConvFilter {
 
 // region:    --- ConvBmc
 
-pub struct ConvBmc;
+// Additional ConvBmc methods to manage the `ConvMsg` constructs.
+impl ConvBmc {
+	/// Create a conv with an agent the ctx user can use (see `AgentVisibility`).
+	///
+	/// Note: The default `ConvBmc::create` does not check the agent access.
+	pub async fn start(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_c: ConvForCreate,
+	) -> Result<i64> {
+		assert_agent_user(ctx, mm, conv_c.agent_id).await?;
+
+		Self::create(ctx, mm, conv_c).await
+	}
+
+	/// Same as `ConvBmc::start` for many convs (see `ConvBmc::create_many_partial`
+	/// for `partial`).
+	///
+	/// Note: All the agents are checked first (so no conv is created if one is not usable).
+	pub async fn start_many(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		convs_c: Vec<ConvForCreate>,
+		partial: bool,
+	) -> Result<Vec<Result<i64>>> {
+		let mut agent_ids: Vec<i64> =
+			convs_c.iter().map(|conv| conv.agent_id).collect();
+		agent_ids.sort_unstable();
+		agent_ids.dedup();
+		for agent_id in agent_ids {
+			assert_agent_user(ctx, mm, agent_id).await?;
+		}
+
+		if partial {
+			Self::create_many_partial(ctx, mm, convs_c).await
+		} else {
+			let ids = Self::create_many(ctx, mm, convs_c).await?;
+			Ok(ids.into_iter().map(Ok).collect())
+		}
+	}
 
-impl DbBmc for ConvBmc {
-	const TABLE: &'static str = "conv";
+	/// Returns the conv (conv users only).
+	pub async fn get_visible(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<Conv> {
+		let conv = Self::get(ctx, mm, id).await?;
+		assert_conv_user(ctx, mm, id).await?;
 
-	fn has_owner_id() -> bool {
-		true
+		Ok(conv)
+	}
+
+	/// Returns the number of convs matching the filters, and visible by the ctx user.
+	pub async fn count_visible(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: Option<Vec<ConvFilter>>,
+	) -> Result<i64> {
+		Self::count(ctx, mm, Self::visible_filters(ctx, filters)).await
 	}
-}
 
-// This will generate the `impl ConvBmc {...}` with the default CRUD functions.
-generate_common_bmc_fns!(
-	Bmc: ConvBmc,
-	Entity: Conv,
-	ForCreate: ConvForCreate,
-	ForUpdate: ConvForUpdate,
-	Filter: ConvFilter,
-);
+	/// Returns the filter groups (or a default one) restricted to the convs
+	/// visible by the ctx user, with their `tag_id` scoped to its tags (unchanged for root).
+	pub(in crate::model) fn visible_filters(
+		ctx: &Ctx,
+		filters: Option<Vec<ConvFilter>>,
+	) -> Option<Vec<ConvFilter>> {
+		let user_id = ctx.user_id();
+		if user_id == 0 {
+			return filters;
+		}
 
-// Additional ConvBmc methods to manage the `ConvMsg` constructs.
-impl ConvBmc {
-	/// Add a `ConvMsg` to a `Conv`
+		let mut filters = filters.unwrap_or_default();
+		if filters.is_empty() {
+			filters.push(ConvFilter::default());
+		}
+		for filter in filters.iter_mut() {
+			filter.visible_to =
+				Some(OpValsValue(vec![OpValValue::Eq(user_id.into())]));
+			if let Some(tag_id) = filter.tag_id.as_mut() {
+				scope_conv_tag_ids(tag_id, user_id);
+			}
+		}
+
+		Some(filters)
+	}
+
+	/// Update the conv (owner only).
+	pub async fn update_owned(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		conv_u: ConvForUpdate,
+	) -> Result<()> {
+		assert_conv_owner(ctx, mm, id).await?;
+
+		Self::update(ctx, mm, id, conv_u).await
+	}
+
+	/// Same as `ConvBmc::update_owned` for many convs.
+	///
+	/// Note: All the convs are checked first (so no conv is updated if one is not owned).
+	pub async fn update_many_owned(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		ids: Vec<i64>,
+		conv_u: ConvForUpdate,
+	) -> Result<u64> {
+		assert_convs_owner(ctx, mm, &ids).await?;
+
+		Self::update_many(ctx, mm, ids, conv_u).await
+	}
+
+	/// Delete the conv (owner only).
+	pub async fn delete_owned(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		assert_conv_owner(ctx, mm, id).await?;
+
+		Self::delete(ctx, mm, id).await
+	}
+
+	/// Same as `ConvBmc::delete_owned` for many convs (see `ConvBmc::delete_many_partial`
+	/// for `partial`).
 	///
-	// For access constrol, we will add:
-	// #[ctx_add(conv, space)]
-	// #[requires_privilege_any_of("og:FullAccess", "sp:FullAccess", "conv@owner_id" "conv:AddMsg")]
+	/// Note: All the convs are checked first (so no conv is deleted if one is not owned).
+	pub async fn delete_many_owned(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		ids: Vec<i64>,
+		partial: bool,
+	) -> Result<Vec<Result<()>>> {
+		assert_convs_owner(ctx, mm, &ids).await?;
+
+		if partial {
+			Self::delete_many_partial(ctx, mm, ids).await
+		} else {
+			let count = ids.len();
+			Self::delete_many(ctx, mm, ids).await?;
+			Ok((0..count).map(|_| Ok(())).collect())
+		}
+	}
+
+	/// Add a `ConvMsg` to a `Conv` (conv users only).
+	///
+	/// Note: The new msg becomes the conv head msg (see the `conv_msg_set_conv_head` trigger).
 	pub async fn add_msg(
 		ctx: &Ctx,
 		mm: &ModelManager,
 		msg_c: ConvMsgForCreate,
 	) -> Result<i64> {
+		msg_c.validate()?;
+		assert_conv_user(ctx, mm, msg_c.conv_id).await?;
+
 		let msg_i = ConvMsgForInsert::from_msg_for_create(ctx.user_id(), msg_c);
 		let conv_msg_id = base::create::<ConvMsgBmc, _>(ctx, mm, msg_i).await?;
 
 		Ok(conv_msg_id)
 	}
 
-	/// NOTE: The current strategy is to not require conv_id, but we will check
-	///       that user have `conv:ReadMsg` privilege on correponding conv (post base::get).
+	/// NOTE: The current strategy is to not require conv_id, but we check
+	///       that the ctx user is a user of the corresponding conv (post base::get).
 	pub async fn get_msg(
 		ctx: &Ctx,
 		mm: &ModelManager,
 		msg_id: i64,
 	) -> Result<ConvMsg> {
-		let conv_msg: ConvMsg = base::get::<ConvMsgBmc, _>(ctx, mm, msg_id).await?;
-
-		// TODO: Validate conv_msg is with ctx.conv_id
-		//       let _ctx = ctx.add_conv_id(conv_msg.conv_id());
-		//       assert_privileges(&ctx, &mm, &["conv@owner_id", "conv:ReadMsg"]);
+		let mut conv_msg = get_msg_for_conv_user(ctx, mm, msg_id).await?;
+		AttachmentBmc::load_into_msgs(ctx, mm, std::slice::from_mut(&mut conv_msg))
+			.await?;
 
 		Ok(conv_msg)
 	}
-}
 
-// endregion: --- ConvBmc
+	/// Add the user to the conv, or update its `ConvUser` properties if already added.
+	pub async fn upsert_user(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_user_c: ConvUserForCreate,
+	) -> Result<i64> {
+		base::upsert::<ConvUserBmc, _>(ctx, mm, conv_user_c).await
+	}
 
-// region:    --- Tests
+	pub async fn get_user(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_user_id: i64,
+	) -> Result<ConvUser> {
+		base::get::<ConvUserBmc, _>(ctx, mm, conv_user_id).await
+	}
 
-#[cfg(test)]
-mod tests {
-	type Error = Box<dyn std::error::Error>;
-	type Result<T> = core::result::Result<T, Error>; // For tests.
+	/// Full-text search of the `ConvMsg` contents and `Conv` titles of the convs
+	/// owned by the ctx user, or in which the ctx user is a `ConvUser`.
+	///
+	/// Returns one page of hits, ranked by relevance (see `ConvMsgHit`).
+	///
+	/// Notes:
+	///   - The `query` is in the web search syntax (e.g., `rust "web app" -java`).
+	///   - Only the `limit` and `offset` of the `list_options` are used (the `order_bys` are ignored).
+	pub async fn search_msgs(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		query: &str,
+		list_options: Option<ListOptions>,
+	) -> Result<ListPage<ConvMsgHit>> {
+		let query = query.trim();
+		if query.is_empty() {
+			return Err(Error::SearchQueryEmpty);
+		}
+		let list_options = base::compute_list_options(list_options)?;
+		let limit = list_options.limit.unwrap_or_default();
+		let offset = list_options.offset.unwrap_or_default();
 
-	use super::*;
-	use crate::_dev_utils::{self, seed_agent};
-	use crate::ctx::Ctx;
-	use crate::model::agent::AgentBmc;
-	use modql::filter::OpValString;
-	use serial_test::serial;
+		// -- Select the page hits and the total
+		let sql = format!("{SEARCH_HITS_CTE} {SEARCH_HITS_PAGE_SELECT}");
+		let sqlx_query = sqlx::query_as::<_, ConvMsgHitRow>(&sql)
+			.bind(query)
+			.bind(ctx.user_id())
+			.bind(limit)
+			.bind(offset);
+		let rows = mm.dbx().fetch_all(sqlx_query).await?;
 
-	#[serial]
-	#[tokio::test]
-	async fn test_create_ok() -> Result<()> {
-		// -- Setup & Fixtures
-		let mm = _dev_utils::init_test().await;
-		let ctx = Ctx::root_ctx();
-		let fx_title = "test_create_ok conv 01";
-		let fx_kind = ConvKind::MultiUsers;
-		let agent_id = seed_agent(&ctx, &mm, "test_create_ok conv agent 01").await?;
+		let sql = format!("{SEARCH_HITS_CTE} SELECT count(*) FROM hit");
+		let sqlx_query = sqlx::query_as::<_, (i64,)>(&sql)
+			.bind(query)
+			.bind(ctx.user_id());
+		let (total,) = mm.dbx().fetch_one(sqlx_query).await?;
 
-		// -- Exec
-		let conv_id = ConvBmc::create(
-			&ctx,
-			&mm,
-			ConvForCreate {
-				agent_id,
-				title: Some(fx_title.to_string()),
-				kind: Some(fx_kind.clone()),
-			},
+		// -- Load the hits convs and msgs
+		let conv_ids: Vec<i64> = rows.iter().map(|row| row.conv_id).collect();
+		let msg_ids: Vec<i64> = rows.iter().filter_map(|row| row.msg_id).collect();
+		let convs: HashMap<i64, Conv> = Self::list_by_ids(ctx, mm, conv_ids).await?;
+		let mut msgs: HashMap<i64, ConvMsg> =
+			list_msgs_by_ids(ctx, mm, msg_ids).await?;
+
+		// -- Build the hits (in rank order)
+		// Note: A conv or msg deleted in between is skipped.
+		let items = rows
+			.into_iter()
+			.filter_map(|row| {
+				let conv = convs.get(&row.conv_id)?.clone();
+				let msg = match row.msg_id {
+					Some(msg_id) => Some(msgs.remove(&msg_id)?),
+					None => None,
+				};
+				Some(ConvMsgHit {
+					conv,
+					msg,
+					rank: row.rank,
+					snippet: row.snippet.as_deref().map(to_html_snippet),
+					title_snippet: row.title_snippet.as_deref().map(to_html_snippet),
+				})
+			})
+			.collect();
+
+		Ok(ListPage {
+			items,
+			total,
+			limit,
+			offset,
+			next_cursor: None,
+		})
+	}
+
+	/// List one page of the `ConvMsg` of the conv (the `filter` is scoped to the conv).
+	///
+	/// Note: Use the returned `next_cursor` rather than deep offsets
+	///       to page through long conversations.
+	pub async fn list_msgs(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+		filter: Option<Vec<ConvMsgFilter>>,
+		list_options: Option<ListOptions>,
+		cursor: Option<String>,
+	) -> Result<ListPage<ConvMsg>> {
+		assert_conv_user(ctx, mm, conv_id).await?;
+
+		// -- Scope each filter (OR group) to the conv
+		let filter = filter
+			.unwrap_or_else(|| vec![ConvMsgFilter::default()])
+			.into_iter()
+			.map(|filter| ConvMsgFilter {
+				conv_id: Some(OpValInt64::Eq(conv_id).into()),
+				..filter
+			})
+			.collect::<Vec<_>>();
+
+		let mut msg_page = base::list_page::<ConvMsgBmc, _, _>(
+			ctx,
+			mm,
+			Some(filter),
+			list_options,
+			cursor,
 		)
 		.await?;
+		AttachmentBmc::load_into_msgs(ctx, mm, &mut msg_page.items).await?;
 
-		// -- Check
-		let conv: Conv = ConvBmc::get(&ctx, &mm, conv_id).await?;
-		assert_eq!(&conv.kind, &fx_kind);
-		assert_eq!(conv.title.ok_or("conv should have title")?, fx_title);
+		Ok(msg_page)
+	}
+}
 
-		// -- Clean
-		ConvBmc::delete(&ctx, &mm, conv_id).await?;
-		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+/// Assert that the ctx user owns all the convs of the `ids`.
+async fn assert_convs_owner(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	ids: &[i64],
+) -> Result<()> {
+	let mut ids = ids.to_vec();
+	ids.sort_unstable();
+	ids.dedup();
+	for id in ids {
+		assert_conv_owner(ctx, mm, id).await?;
+	}
 
-		Ok(())
+	Ok(())
+}
+
+// endregion: --- ConvBmc
+
+// region:    --- ConvFilter visible_to
+
+/// The `ConvFilter.visible_to` condition (see `acs::conv_visible_sql`).
+///
+/// Note: Only `Eq` with the user id (set by `ConvBmc::visible_filters`).
+fn conv_visible_to_sea_condition(
+	_col: &ColumnRef,
+	op_value: OpValValue,
+) -> SeaResult<ConditionExpression> {
+	let user_id = match op_value {
+		OpValValue::Eq(user_id) => user_id.as_i64(),
+		_ => None,
 	}
+	.ok_or_else(|| {
+		IntoSeaError::Custom("visible_to must be a user id $eq".to_string())
+	})?;
 
-	#[serial]
-	#[tokio::test]
-	async fn test_list_ok() -> Result<()> {
-		// -- Setup & Fixtures
-		let mm = _dev_utils::init_test().await;
-		let ctx = Ctx::root_ctx();
-		let fx_title_prefix = "test_list_ok conv - ";
-		let agent_id = seed_agent(&ctx, &mm, "test_create_ok conv agent 01").await?;
+	let cond = Expr::cust_with_values(conv_visible_sql("$1"), [user_id]);
 
-		for i in 1..=6 {
-			let kind = if i <= 3 {
-				ConvKind::OwnerOnly
-			} else {
-				ConvKind::MultiUsers
+	Ok(ConditionExpression::SimpleExpr(cond))
+}
+
+// endregion: --- ConvFilter visible_to
+
+// region:    --- ConvMsg Edit & Reactions
+
+// Additional ConvBmc methods to manage the `ConvMsg` edits (`ConvMsgRevision`)
+// and the `ConvMsgReaction`.
+// Note: Unlike the other msg functions (for now), they require the ctx user
+//       to be a conv user (owner or `ConvUser`) of the msg conv.
+impl ConvBmc {
+	/// Edit the content of a `ConvMsg`, keeping its prior content as a `ConvMsgRevision`.
+	///
+	/// Notes:
+	///   - Only the msg author (or root) can edit it.
+	///   - The `ConvMsg.edit_count` and `ConvMsg.etime` mark the msg as edited.
+	///   - Editing with the same content is a no-op.
+	pub async fn update_msg(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		msg_id: i64,
+		msg_u: ConvMsgForUpdate,
+	) -> Result<()> {
+		mm.in_txn(|mm| async move {
+			let msg = get_msg_for_conv_user(ctx, &mm, msg_id).await?;
+			if msg.conv_id != msg_u.conv_id {
+				return Err(Error::ConvMsgConvIdMismatch {
+					msg_id,
+					conv_id: msg_u.conv_id,
+				});
+			}
+			let user_id = ctx.user_id();
+			if user_id != 0 && user_id != msg.user_id {
+				return Err(Error::AccessDenied {
+					user_id,
+					required: "conv_msg_author",
+				});
+			}
+			validate_content(&msg_u.content, msg.content_type)?;
+
+			if msg_u.content == msg.content {
+				return Ok(());
+			}
+
+			// -- Keep the prior content
+			let revision = msg.edit_count + 1;
+			let revision_i = ConvMsgRevisionForInsert {
+				msg_id,
+				revision,
+				content: msg.content,
+				content_type: msg.content_type,
 			};
+			base::create::<ConvMsgRevisionBmc, _>(ctx, &mm, revision_i).await?;
 
-			let _conv_id = ConvBmc::create(
-				&ctx,
-				&mm,
-				ConvForCreate {
-					agent_id,
-					title: Some(format!("{fx_title_prefix}{:<02}", i)),
-					kind: Some(kind),
-				},
-			)
-			.await?;
+			// -- Update the msg
+			let msg_e = ConvMsgForEdit {
+				content: msg_u.content,
+				edit_count: revision,
+				etime: now_utc(),
+			};
+			base::update::<ConvMsgBmc, _>(ctx, &mm, msg_id, msg_e).await
+		})
+		.await
+	}
+
+	/// Returns the prior versions of the `ConvMsg` (ordered by revision).
+	pub async fn list_msg_revisions(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		msg_id: i64,
+	) -> Result<Vec<ConvMsgRevision>> {
+		get_msg_for_conv_user(ctx, mm, msg_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, ConvMsgRevision>(
+			"SELECT * FROM conv_msg_revision WHERE msg_id = $1 ORDER BY revision",
+		)
+		.bind(msg_id);
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+
+	/// Add the ctx user reaction to the `ConvMsg`.
+	///
+	/// Note: Adding the same emoji twice is a no-op (the existing reaction id is returned).
+	pub async fn add_msg_reaction(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		reaction_c: ConvMsgReactionForCreate,
+	) -> Result<i64> {
+		reaction_c.validate()?;
+		get_msg_for_conv_user(ctx, mm, reaction_c.msg_id).await?;
+
+		let reaction_i = ConvMsgReactionForInsert {
+			msg_id: reaction_c.msg_id,
+			user_id: ctx.user_id(),
+			emoji: reaction_c.emoji,
+		};
+
+		base::upsert::<ConvMsgReactionBmc, _>(ctx, mm, reaction_i).await
+	}
+
+	pub async fn get_msg_reaction(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		reaction_id: i64,
+	) -> Result<ConvMsgReaction> {
+		let reaction: ConvMsgReaction =
+			base::get::<ConvMsgReactionBmc, _>(ctx, mm, reaction_id).await?;
+		get_msg_for_conv_user(ctx, mm, reaction.msg_id).await?;
+
+		Ok(reaction)
+	}
+
+	/// Remove a reaction (only the reaction user, or root, can remove it).
+	pub async fn remove_msg_reaction(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		reaction_id: i64,
+	) -> Result<()> {
+		let reaction = Self::get_msg_reaction(ctx, mm, reaction_id).await?;
+
+		let user_id = ctx.user_id();
+		if user_id != 0 && user_id != reaction.user_id {
+			return Err(Error::AccessDenied {
+				user_id,
+				required: "conv_msg_reaction_user",
+			});
 		}
 
-		// -- Exec
-		let convs = ConvBmc::list(
-			&ctx,
-			&mm,
-			Some(vec![ConvFilter {
-				agent_id: Some(agent_id.into()),
+		base::delete::<ConvMsgReactionBmc>(ctx, mm, reaction_id).await
+	}
 
-				kind: Some(OpValString::In(vec!["MultiUsers".to_string()]).into()),
-				// or
-				// kind: Some(OpValString::Eq("MultiUsers".to_string()).into()),
-				..Default::default()
-			}]),
-			None,
+	/// Returns the reactions of the `ConvMsg` (ordered by id).
+	pub async fn list_msg_reactions(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		msg_id: i64,
+	) -> Result<Vec<ConvMsgReaction>> {
+		get_msg_for_conv_user(ctx, mm, msg_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, ConvMsgReaction>(
+			"SELECT * FROM conv_msg_reaction WHERE msg_id = $1 ORDER BY id",
 		)
-		.await?;
+		.bind(msg_id);
 
-		// -- Check
-		// extract the 04, 05, 06 parts of the tiles
-		let num_parts = convs
-			.iter()
-			.filter_map(|c| c.title.as_ref().and_then(|s| s.split("- ").nth(1)))
-			.collect::<Vec<&str>>();
-		assert_eq!(num_parts, &["04", "05", "06"]);
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+}
 
-		// -- Clean
-		// This should delete cascade
+/// Returns the `ConvMsg` (without its attachments) if the ctx user is a conv user of its conv.
+async fn get_msg_for_conv_user(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	msg_id: i64,
+) -> Result<ConvMsg> {
+	let msg: ConvMsg = base::get::<ConvMsgBmc, _>(ctx, mm, msg_id).await?;
+	assert_conv_user(ctx, mm, msg.conv_id).await?;
+
+	Ok(msg)
+}
+
+// endregion: --- ConvMsg Edit & Reactions
+
+// region:    --- Read State
+
+// Additional ConvBmc methods to manage the read state of the conv users
+// (`ConvUser.last_read_msg_id`) and their unread counts.
+impl ConvBmc {
+	/// Mark the conv msgs as read by the ctx user, up to `read_u.msg_id`
+	/// (default: the last msg of the conv).
+	///
+	/// Notes:
+	///   - Requires the ctx user to be a conv user (owner or `ConvUser`).
+	///     For the owner, the `ConvUser` is created if needed.
+	///   - No-op for root (which has no read state, and must not become a `ConvUser`).
+	///   - The read mark never moves backward.
+	pub async fn mark_read(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+		read_u: ConvForMarkRead,
+	) -> Result<()> {
+		assert_conv_user(ctx, mm, conv_id).await?;
+		let user_id = ctx.user_id();
+		if user_id == 0 {
+			return Ok(());
+		}
+
+		mm.in_txn(|mm| async move {
+			// -- Resolve the read msg_id
+			let msg_id = match read_u.msg_id {
+				Some(msg_id) => {
+					let msg: ConvMsg =
+						base::get::<ConvMsgBmc, _>(ctx, &mm, msg_id).await?;
+					if msg.conv_id != conv_id {
+						return Err(Error::ConvMsgConvIdMismatch {
+							msg_id,
+							conv_id,
+						});
+					}
+					msg_id
+				}
+				None => {
+					let sqlx_query = sqlx::query_as::<_, (Option<i64>,)>(
+						"SELECT max(id) FROM conv_msg WHERE conv_id = $1",
+					)
+					.bind(conv_id);
+					match mm.dbx().fetch_one(sqlx_query).await? {
+						(Some(msg_id),) => msg_id,
+						// no msgs, nothing to read
+						(None,) => return Ok(()),
+					}
+				}
+			};
+
+			// -- Skip if already read (never move backward)
+			let sqlx_query = sqlx::query_as::<_, (Option<i64>,)>(
+				"SELECT last_read_msg_id FROM conv_user
+				 WHERE conv_id = $1 AND user_id = $2",
+			)
+			.bind(conv_id)
+			.bind(user_id);
+			if let Some((Some(last_read_msg_id),)) =
+				mm.dbx().fetch_optional(sqlx_query).await?
+			{
+				if last_read_msg_id >= msg_id {
+					return Ok(());
+				}
+			}
+
+			let conv_user_r = ConvUserForMarkRead {
+				conv_id,
+				user_id,
+				last_read_msg_id: msg_id,
+			};
+			base::upsert::<ConvUserBmc, _>(ctx, &mm, conv_user_r).await?;
+
+			Ok(())
+		})
+		.await
+	}
+
+	/// Same as `ConvBmc::list_page_pinned_first` (the convs visible by the ctx user,
+	/// its pinned convs first), with the `unread_count` and `tag_ids` of the ctx user.
+	pub async fn list_page_with_unread(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filter: Option<Vec<ConvFilter>>,
+		list_options: Option<ListOptions>,
+		cursor: Option<String>,
+	) -> Result<ListPage<Conv>> {
+		let mut page =
+			Self::list_page_pinned_first(ctx, mm, filter, list_options, cursor)
+				.await?;
+		Self::load_unread_counts(ctx, mm, &mut page.items).await?;
+		Self::load_tag_ids(ctx, mm, &mut page.items).await?;
+
+		Ok(page)
+	}
+
+	/// Set the `unread_count` of the ctx user on the `convs`.
+	///
+	/// Note: Computed with one query for all the convs, each count being an index range scan
+	///       of the conv msgs after the `ConvUser.last_read_msg_id` (all msgs if none).
+	pub async fn load_unread_counts(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		convs: &mut [Conv],
+	) -> Result<()> {
+		if convs.is_empty() {
+			return Ok(());
+		}
+
+		let conv_ids: Vec<i64> = convs.iter().map(|conv| conv.id).collect();
+		let sqlx_query = sqlx::query_as::<_, (i64, i64)>(UNREAD_COUNTS_SELECT)
+			.bind(conv_ids)
+			.bind(ctx.user_id());
+		let unread_counts: HashMap<i64, i64> =
+			mm.dbx().fetch_all(sqlx_query).await?.into_iter().collect();
+
+		for conv in convs.iter_mut() {
+			conv.unread_count =
+				Some(unread_counts.get(&conv.id).copied().unwrap_or(0));
+		}
+
+		Ok(())
+	}
+}
+
+/// The unread counts (conv_id, count) of the user, for the convs with unread msgs.
+///
+/// Binds: `$1` the conv ids, `$2` the user_id.
+const UNREAD_COUNTS_SELECT: &str = r#"
+SELECT c.id, count(*)
+FROM unnest($1::bigint[]) AS c(id)
+	LEFT JOIN conv_user cu ON cu.conv_id = c.id AND cu.user_id = $2
+	JOIN conv_msg m ON m.conv_id = c.id
+		AND m.id > COALESCE(cu.last_read_msg_id, 0)
+		AND m.user_id <> $2
+GROUP BY c.id"#;
+
+// endregion: --- Read State
+
+// region:    --- Agent Reply
+
+/// The max number of AI calls of one reply (i.e., the tool call rounds + 1).
+const AGENT_REPLY_MAX_STEPS: usize = 8;
+
+impl ConvBmc {
+	/// Generate the agent reply to the conv msgs (e.g., after the user `add_msg`),
+	/// with the `AgentVersion` the conv was created with.
+	///
+	/// Returns the ids of the added msgs, in order: the eventual tool steps
+	/// (`ToolCall` and `ToolResult` msgs), then the `Assistant` reply msg.
+	///
+	/// Notes:
+	///   - The tool steps are persisted with the reply, so that the transcript is complete,
+	///     and the conv can be replayed from its msgs (see `model::conv_context`).
+	///   - The reply is added in one transaction, so on failure (e.g., provider error,
+	///     `Error::AgentReplyStepsOverMax`), none of its msgs and usage records are kept,
+	///     and the conv head msg is unchanged.
+	///   - The tools are called with the ctx user, and only the agent enabled tools
+	///     can be called (the others get an error `ToolResult`).
+	///   - The reply msgs are added with the ctx user as `user_id` (and their role).
+	///   - The usage of each AI call is recorded (see `UsageBmc`), and the daily quotas
+	///     are checked before the reply (`Error::UsageQuotaExceeded`).
+	pub async fn generate_reply(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+	) -> Result<Vec<i64>> {
+		assert_conv_user(ctx, mm, conv_id).await?;
+		let conv = Self::get(ctx, mm, conv_id).await?;
+		// Note: The conv users can use the conv agent version, even when the agent
+		//       is not visible to them (so not `AgentBmc::get_version`).
+		let agent_version: AgentVersion =
+			base::get::<AgentVersionBmc, _>(ctx, mm, conv.agent_version_id).await?;
+		UsageBmc::assert_quotas(ctx, mm, conv.agent_id).await?;
+
+		let registry = ToolRegistry::builtin();
+		let tools = agent_version
+			.tools
+			.names()
+			.iter()
+			.filter_map(|name| registry.get(name).ok())
+			.map(|tool| tool.spec())
+			.collect();
+
+		let mut chat_req =
+			conv_context::build_chat_request(ctx, mm, &conv, &agent_version, tools)
+				.await?;
+
+		// Note: The tools are called with the `mm` outside of the reply transaction,
+		//       so that their errors (e.g., db errors) cannot abort it.
+		let tool_mm = mm;
+		mm.in_txn(|mm| async move {
+			let mut msg_ids = Vec::new();
+
+			for _ in 0..AGENT_REPLY_MAX_STEPS {
+				let ChatResponse { reply, usage } = ai::exec_chat(
+					&agent_version.ai_provider,
+					&agent_version.ai_model,
+					&chat_req,
+				)
+				.await?;
+				let step_first_idx = msg_ids.len();
+
+				let tool_calls = match reply {
+					ChatReply::Content(content) => {
+						let msg_id = add_reply_msg(
+							ctx,
+							&mm,
+							conv_id,
+							ConvMsgRole::Assistant,
+							ConvMsgContentType::Text,
+							content,
+						)
+						.await?;
+						msg_ids.push(msg_id);
+						UsageBmc::record(
+							ctx,
+							&mm,
+							&conv,
+							&agent_version,
+							Some(msg_id),
+							usage,
+						)
+						.await?;
+						return Ok(msg_ids);
+					}
+					ChatReply::ToolCalls(tool_calls) => tool_calls,
+				};
+
+				for tool_call in tool_calls {
+					let msg_id = add_reply_msg(
+						ctx,
+						&mm,
+						conv_id,
+						ConvMsgRole::Assistant,
+						ConvMsgContentType::ToolCall,
+						to_json_content(&tool_call),
+					)
+					.await?;
+					msg_ids.push(msg_id);
+
+					let enabled = chat_req
+						.tools
+						.iter()
+						.any(|tool| tool.name == tool_call.name);
+					let tool_result = if enabled {
+						registry.exec_call(ctx, tool_mm, &tool_call).await
+					} else {
+						let err = ai::Error::ToolNotEnabled {
+							name: tool_call.name.clone(),
+						};
+						ToolResult::from_error(&tool_call, err)
+					};
+
+					let msg_id = add_reply_msg(
+						ctx,
+						&mm,
+						conv_id,
+						ConvMsgRole::Tool,
+						ConvMsgContentType::ToolResult,
+						to_json_content(&tool_result),
+					)
+					.await?;
+					msg_ids.push(msg_id);
+
+					chat_req.messages.push(ChatMessage::ToolCall(tool_call));
+					chat_req.messages.push(ChatMessage::ToolResult(tool_result));
+				}
+
+				let step_msg_id = msg_ids.get(step_first_idx).copied();
+				UsageBmc::record(
+					ctx,
+					&mm,
+					&conv,
+					&agent_version,
+					step_msg_id,
+					usage,
+				)
+				.await?;
+			}
+
+			Err(Error::AgentReplyStepsOverMax {
+				max: AGENT_REPLY_MAX_STEPS,
+			})
+		})
+		.await
+	}
+}
+
+async fn add_reply_msg(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	conv_id: i64,
+	role: ConvMsgRole,
+	content_type: ConvMsgContentType,
+	content: String,
+) -> Result<i64> {
+	validate_content(&content, content_type)?;
+
+	let msg_i = ConvMsgForInsert {
+		conv_id,
+		user_id: ctx.user_id(),
+		content,
+		content_type: Some(content_type),
+		role: Some(role),
+		metadata: None,
+	};
+
+	base::create::<ConvMsgBmc, _>(ctx, mm, msg_i).await
+}
+
+/// Note: The `ToolCall` / `ToolResult` are plain json types (cannot fail).
+fn to_json_content(value: &impl Serialize) -> String {
+	serde_json::to_string(value).unwrap_or_default()
+}
+
+// endregion: --- Agent Reply
+
+// region:    --- Branches
+
+/// The ancestors of a msg, up to the first msg of its conv (`ConvMsg.parent_msg_id` chain).
+const MSG_PATH_SELECT: &str = "
+WITH RECURSIVE path AS (
+	SELECT * FROM conv_msg WHERE id = $1
+	UNION ALL
+	SELECT m.* FROM conv_msg m JOIN path p ON m.id = p.parent_msg_id
+)
+SELECT * FROM path ORDER BY id";
+
+/// The most recent msg of the msg subtree (which is a leaf, as the children have greater ids).
+const MSG_SUBTREE_LAST_ID_SELECT: &str = "
+WITH RECURSIVE subtree AS (
+	SELECT id FROM conv_msg WHERE id = $1
+	UNION ALL
+	SELECT m.id FROM conv_msg m JOIN subtree s ON m.parent_msg_id = s.id
+)
+SELECT max(id) FROM subtree";
+
+/// Notes:
+///   - The conv msgs are a tree (`ConvMsg.parent_msg_id`), and the `Conv.head_msg_id`
+///     is the leaf of the active branch, to which the new msgs are appended (by the db trigger).
+///   - The `list_msgs` still lists the msgs of all the branches.
+impl ConvBmc {
+	/// Returns the active branch of the conv (with the msgs attachments).
+	pub async fn get_branch(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+	) -> Result<ConvBranch> {
+		assert_conv_user(ctx, mm, conv_id).await?;
+		let conv = Self::get(ctx, mm, conv_id).await?;
+
+		let mut msgs = match conv.head_msg_id {
+			Some(head_msg_id) => list_msg_path(mm, head_msg_id).await?,
+			None => Vec::new(),
+		};
+		AttachmentBmc::load_into_msgs(ctx, mm, &mut msgs).await?;
+
+		Ok(ConvBranch { conv, msgs })
+	}
+
+	/// Makes the branch of the msg the active one
+	/// (i.e., the most recent msg of its subtree becomes the conv head msg).
+	pub async fn switch_branch(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		msg_id: i64,
+	) -> Result<()> {
+		let msg = get_msg_for_conv_user(ctx, mm, msg_id).await?;
+
+		let sqlx_query =
+			sqlx::query_as::<_, (i64,)>(MSG_SUBTREE_LAST_ID_SELECT).bind(msg_id);
+		let (head_msg_id,) = mm.dbx().fetch_one(sqlx_query).await?;
+
+		set_head_msg_id(mm, msg.conv_id, Some(head_msg_id)).await
+	}
+
+	/// Generate a new agent reply in place of the reply of the msg
+	/// (an `Assistant` or `Tool` msg, e.g., the last msg of the reply).
+	///
+	/// Returns the ids of the added msgs (as `generate_reply`).
+	///
+	/// Note: The new reply is a new branch (sibling of the prior reply, which is kept),
+	///       from the last user (or system) msg before the reply, and becomes the active branch.
+	pub async fn regenerate_reply(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		msg_id: i64,
+	) -> Result<Vec<i64>> {
+		let msg = get_msg_for_conv_user(ctx, mm, msg_id).await?;
+		if !is_reply_role(msg.role) {
+			return Err(Error::ConvMsgNotReply { msg_id });
+		}
+
+		let path = list_msg_path(mm, msg_id).await?;
+		let branch_msg_id = path
+			.iter()
+			.rev()
+			.find(|msg| !is_reply_role(msg.role))
+			.map(|msg| msg.id);
+
+		let prev_head_msg_id = Self::get(ctx, mm, msg.conv_id).await?.head_msg_id;
+		set_head_msg_id(mm, msg.conv_id, branch_msg_id).await?;
+
+		// Note: On failure (e.g., quota exceeded), the previous head is restored,
+		//       so the conv keeps its active reply.
+		match Self::generate_reply(ctx, mm, msg.conv_id).await {
+			Ok(msg_ids) => Ok(msg_ids),
+			Err(err) => {
+				set_head_msg_id(mm, msg.conv_id, prev_head_msg_id).await?;
+				Err(err)
+			}
+		}
+	}
+
+	/// Create a new conv (owned by the ctx user) with a copy of the conv msgs,
+	/// from the first msg to the `at_msg_id` msg (of any branch).
+	///
+	/// Returns the id of the new conv.
+	///
+	/// Notes:
+	///   - The ctx user must be able to use the conv agent (as for `ConvBmc::start`).
+	///   - The new conv keeps the agent version, kind, and title of the conv.
+	///   - The msgs keep their author and content, but not their attachments,
+	///     reactions, and revisions.
+	pub async fn fork_conv(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+		at_msg_id: i64,
+	) -> Result<i64> {
+		let msg = get_msg_for_conv_user(ctx, mm, at_msg_id).await?;
+		if msg.conv_id != conv_id {
+			return Err(Error::ConvMsgConvIdMismatch {
+				msg_id: at_msg_id,
+				conv_id,
+			});
+		}
+		let conv = Self::get(ctx, mm, conv_id).await?;
+		let path = list_msg_path(mm, at_msg_id).await?;
+
+		mm.in_txn(|mm| async move {
+			let conv_c = ConvForCreate {
+				agent_id: conv.agent_id,
+				title: conv.title,
+				kind: Some(conv.kind),
+			};
+			let fork_id = Self::start(ctx, &mm, conv_c).await?;
+
+			// Note: The insert trigger sets the current agent version, so the
+			//       conv agent version is set after.
+			let conv_u = ConvForUpdateAgentVersion {
+				agent_version_id: conv.agent_version_id,
+			};
+			base::update::<Self, _>(ctx, &mm, fork_id, conv_u).await?;
+
+			for msg in path {
+				let msg_i = ConvMsgForInsert {
+					conv_id: fork_id,
+					user_id: msg.user_id,
+					content: msg.content,
+					content_type: Some(msg.content_type),
+					role: Some(msg.role),
+					metadata: msg.metadata,
+				};
+				base::create::<ConvMsgBmc, _>(ctx, &mm, msg_i).await?;
+			}
+
+			Ok(fork_id)
+		})
+		.await
+	}
+}
+
+/// Returns the msgs from the first msg of the conv to the `msg_id` msg
+/// (without their attachments).
+pub(in crate::model) async fn list_msg_path(
+	mm: &ModelManager,
+	msg_id: i64,
+) -> Result<Vec<ConvMsg>> {
+	let sqlx_query = sqlx::query_as::<_, ConvMsg>(MSG_PATH_SELECT).bind(msg_id);
+
+	Ok(mm.dbx().fetch_all(sqlx_query).await?)
+}
+
+pub(in crate::model) async fn set_head_msg_id(
+	mm: &ModelManager,
+	conv_id: i64,
+	head_msg_id: Option<i64>,
+) -> Result<()> {
+	let sqlx_query = sqlx::query("UPDATE conv SET head_msg_id = $2 WHERE id = $1")
+		.bind(conv_id)
+		.bind(head_msg_id);
+	mm.dbx().execute(sqlx_query).await?;
+
+	Ok(())
+}
+
+/// The msgs of an agent reply are the `Assistant` msgs and the `Tool` results.
+fn is_reply_role(role: ConvMsgRole) -> bool {
+	matches!(role, ConvMsgRole::Assistant | ConvMsgRole::Tool)
+}
+
+// endregion: --- Branches
+
+// region:    --- Search Support
+
+/// The search hits (`hit` cte) of the user convs.
+///
+/// Binds: `$1` the web search query, `$2` the ctx user_id.
+const SEARCH_HITS_CTE: &str = r#"
+WITH q AS (
+	SELECT websearch_to_tsquery('english', $1) AS q
+),
+user_conv AS (
+	SELECT c.id, c.title_tsv FROM conv c
+	WHERE c.owner_id = $2
+		OR EXISTS (SELECT 1 FROM conv_user cu WHERE cu.conv_id = c.id AND cu.user_id = $2)
+),
+hit AS (
+	SELECT m.id AS msg_id, c.id AS conv_id,
+		ts_rank(m.content_tsv, q.q) + ts_rank(c.title_tsv, q.q) AS rank
+	FROM conv_msg m JOIN user_conv c ON c.id = m.conv_id, q
+	WHERE m.content_tsv @@ q.q
+	UNION ALL
+	SELECT NULL, c.id, ts_rank(c.title_tsv, q.q)
+	FROM user_conv c, q
+	WHERE c.title_tsv @@ q.q
+)"#;
+
+/// The page of the `SEARCH_HITS_CTE` hits, with their highlighted snippets.
+///
+/// Note: The matched terms are wrapped with the `SNIPPET_START_SEL` and `SNIPPET_STOP_SEL`
+///       sentinels (stripped from the text first), see `to_html_snippet`.
+///
+/// Binds: `$3` the limit, `$4` the offset.
+const SEARCH_HITS_PAGE_SELECT: &str = r#"
+SELECT hit.msg_id, hit.conv_id, hit.rank,
+	CASE WHEN hit.msg_id IS NOT NULL THEN
+		ts_headline('english', translate(m.content, E'\uE000\uE001', ''), q.q,
+			E'StartSel=\uE000, StopSel=\uE001, MaxFragments=2, MaxWords=20, MinWords=5')
+	END AS snippet,
+	CASE WHEN c.title_tsv @@ q.q THEN
+		ts_headline('english', translate(c.title, E'\uE000\uE001', ''), q.q,
+			E'StartSel=\uE000, StopSel=\uE001, HighlightAll=true')
+	END AS title_snippet
+FROM hit
+	JOIN conv c ON c.id = hit.conv_id
+	LEFT JOIN conv_msg m ON m.id = hit.msg_id,
+	q
+ORDER BY hit.rank DESC, hit.conv_id DESC, hit.msg_id DESC NULLS FIRST
+LIMIT $3 OFFSET $4"#;
+
+/// The `ts_headline` selection sentinels of the `SEARCH_HITS_PAGE_SELECT` snippets
+/// (unicode private use chars).
+const SNIPPET_START_SEL: char = '\u{E000}';
+const SNIPPET_STOP_SEL: char = '\u{E001}';
+
+/// Returns the html escaped snippet, with its matched terms wrapped with `<mark>...</mark>`.
+fn to_html_snippet(snippet: &str) -> String {
+	let mut html = String::with_capacity(snippet.len());
+	for c in snippet.chars() {
+		match c {
+			SNIPPET_START_SEL => html.push_str("<mark>"),
+			SNIPPET_STOP_SEL => html.push_str("</mark>"),
+			'&' => html.push_str("&amp;"),
+			'<' => html.push_str("&lt;"),
+			'>' => html.push_str("&gt;"),
+			'"' => html.push_str("&quot;"),
+			'\'' => html.push_str("&#39;"),
+			c => html.push(c),
+		}
+	}
+
+	html
+}
+
+#[derive(FromRow)]
+struct ConvMsgHitRow {
+	msg_id: Option<i64>,
+	conv_id: i64,
+	rank: f32,
+	snippet: Option<String>,
+	title_snippet: Option<String>,
+}
+
+impl ConvBmc {
+	async fn list_by_ids(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		ids: Vec<i64>,
+	) -> Result<HashMap<i64, Conv>> {
+		let list_options = ListOptions {
+			limit: Some(ids.len() as i64),
+			..Default::default()
+		};
+		let filter = ConvFilter {
+			id: Some(OpValInt64::In(ids).into()),
+			..Default::default()
+		};
+		let convs =
+			Self::list(ctx, mm, Some(vec![filter]), Some(list_options)).await?;
+
+		Ok(convs.into_iter().map(|conv| (conv.id, conv)).collect())
+	}
+}
+
+async fn list_msgs_by_ids(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	ids: Vec<i64>,
+) -> Result<HashMap<i64, ConvMsg>> {
+	let list_options = ListOptions {
+		limit: Some(ids.len() as i64),
+		..Default::default()
+	};
+	let filter = ConvMsgFilter {
+		id: Some(OpValInt64::In(ids).into()),
+		..Default::default()
+	};
+	let mut msgs: Vec<ConvMsg> = base::list::<ConvMsgBmc, _, _>(
+		ctx,
+		mm,
+		Some(vec![filter]),
+		Some(list_options),
+	)
+	.await?;
+	AttachmentBmc::load_into_msgs(ctx, mm, &mut msgs).await?;
+
+	Ok(msgs.into_iter().map(|msg| (msg.id, msg)).collect())
+}
+
+// endregion: --- Search Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{
+		self, clean_users, seed_agent, seed_conv, seed_convs, seed_user,
+	};
+	use crate::ai::ToolCall;
+	use crate::ctx::Ctx;
+	use crate::model;
+	use crate::model::agent::{AgentBmc, AgentForCreate, AgentTools};
+	use crate::model::usage::{UsageQuotaBmc, UsageQuotaForCreate, UsageScope};
+	use modql::filter::OpValString;
+	use serde_json::json;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_title = "test_create_ok conv 01";
+		let fx_kind = ConvKind::MultiUsers;
+		let agent_id = seed_agent(&ctx, &mm, "test_create_ok conv agent 01").await?;
+
+		// -- Exec
+		let conv_id = ConvBmc::create(
+			&ctx,
+			&mm,
+			ConvForCreate {
+				agent_id,
+				title: Some(fx_title.to_string()),
+				kind: Some(fx_kind.clone()),
+			},
+		)
+		.await?;
+
+		// -- Check
+		let conv: Conv = ConvBmc::get(&ctx, &mm, conv_id).await?;
+		assert_eq!(&conv.kind, &fx_kind);
+		assert_eq!(conv.title.ok_or("conv should have title")?, fx_title);
+
+		// -- Clean
+		ConvBmc::delete(&ctx, &mm, conv_id).await?;
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_title_prefix = "test_list_ok conv - ";
+		let agent_id = seed_agent(&ctx, &mm, "test_create_ok conv agent 01").await?;
+
+		for i in 1..=6 {
+			let kind = if i <= 3 {
+				ConvKind::OwnerOnly
+			} else {
+				ConvKind::MultiUsers
+			};
+
+			let _conv_id = ConvBmc::create(
+				&ctx,
+				&mm,
+				ConvForCreate {
+					agent_id,
+					title: Some(format!("{fx_title_prefix}{:<02}", i)),
+					kind: Some(kind),
+				},
+			)
+			.await?;
+		}
+
+		// -- Exec
+		let convs = ConvBmc::list(
+			&ctx,
+			&mm,
+			Some(vec![ConvFilter {
+				agent_id: Some(agent_id.into()),
+
+				kind: Some(OpValString::In(vec!["MultiUsers".to_string()]).into()),
+				// or
+				// kind: Some(OpValString::Eq("MultiUsers".to_string()).into()),
+				..Default::default()
+			}]),
+			None,
+		)
+		.await?;
+
+		// -- Check
+		// extract the 04, 05, 06 parts of the tiles
+		let num_parts = convs
+			.iter()
+			.filter_map(|c| c.title.as_ref().and_then(|s| s.split("- ").nth(1)))
+			.collect::<Vec<&str>>();
+		assert_eq!(num_parts, &["04", "05", "06"]);
+
+		// -- Clean
+		// This should delete cascade
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_owned_err_not_owner() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let owner_id =
+			seed_user(&root_ctx, &mm, "test_owned_err_not_owner-user-owner").await?;
+		let other_id =
+			seed_user(&root_ctx, &mm, "test_owned_err_not_owner-user-other").await?;
+		let owner_ctx = Ctx::new(owner_id)?;
+		let other_ctx = Ctx::new(other_id)?;
+		let agent_id =
+			seed_agent(&owner_ctx, &mm, "test_owned_err_not_owner agent").await?;
+		let conv_ids = seed_convs(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			&[
+				"test_owned_err_not_owner conv 01",
+				"test_owned_err_not_owner conv 02",
+			],
+		)
+		.await?;
+		let other_conv_id = seed_conv(
+			&other_ctx,
+			&mm,
+			agent_id,
+			"test_owned_err_not_owner conv 03",
+		)
+		.await?;
+		let fx_conv_u = || ConvForUpdate {
+			title: Some("test_owned_err_not_owner conv updated".to_string()),
+			..Default::default()
+		};
+
+		// -- Exec & Check
+		let res = ConvBmc::get_visible(&other_ctx, &mm, conv_ids[0]).await;
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+		let res =
+			ConvBmc::update_owned(&other_ctx, &mm, conv_ids[0], fx_conv_u()).await;
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+		let res = ConvBmc::update_many_owned(
+			&other_ctx,
+			&mm,
+			vec![other_conv_id, conv_ids[0]],
+			fx_conv_u(),
+		)
+		.await;
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+		let res = ConvBmc::delete_owned(&other_ctx, &mm, conv_ids[0]).await;
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+		let res = ConvBmc::delete_many_owned(
+			&other_ctx,
+			&mm,
+			vec![other_conv_id, conv_ids[1]],
+			true,
+		)
+		.await;
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+		// nothing changed
+		let other_conv =
+			ConvBmc::get_visible(&other_ctx, &mm, other_conv_id).await?;
+		assert_eq!(
+			other_conv.title.as_deref(),
+			Some("test_owned_err_not_owner conv 03")
+		);
+		let conv = ConvBmc::get_visible(&owner_ctx, &mm, conv_ids[1]).await?;
+		assert_eq!(conv.owner_id, owner_id);
+		// the owner can update and delete
+		let count = ConvBmc::update_many_owned(
+			&owner_ctx,
+			&mm,
+			conv_ids.clone(),
+			fx_conv_u(),
+		)
+		.await?;
+		assert_eq!(count, 2);
+		let results =
+			ConvBmc::delete_many_owned(&owner_ctx, &mm, conv_ids.clone(), false)
+				.await?;
+		assert_eq!(results.len(), 2);
+		let res = ConvBmc::get(&root_ctx, &mm, conv_ids[0]).await;
+		assert!(
+			matches!(res, Err(model::Error::EntityNotFound { .. })),
+			"should be EntityNotFound, but was {res:?}"
+		);
+
+		// -- Clean
+		AgentBmc::delete(&root_ctx, &mm, agent_id).await?;
+		clean_users(&root_ctx, &mm, "test_owned_err_not_owner").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_visible_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let owner_id =
+			seed_user(&root_ctx, &mm, "test_list_visible_ok-user-owner").await?;
+		let member_id =
+			seed_user(&root_ctx, &mm, "test_list_visible_ok-user-member").await?;
+		let other_id =
+			seed_user(&root_ctx, &mm, "test_list_visible_ok-user-other").await?;
+		let owner_ctx = Ctx::new(owner_id)?;
+		let member_ctx = Ctx::new(member_id)?;
+		let other_ctx = Ctx::new(other_id)?;
+		let agent_id =
+			seed_agent(&owner_ctx, &mm, "test_list_visible_ok agent").await?;
+		let conv_ids = seed_convs(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			&[
+				"test_list_visible_ok conv 01",
+				"test_list_visible_ok conv 02",
+			],
+		)
+		.await?;
+		ConvBmc::upsert_user(
+			&root_ctx,
+			&mm,
+			ConvUserForCreate {
+				conv_id: conv_ids[1],
+				user_id: member_id,
+				auto_respond: None,
+			},
+		)
+		.await?;
+		let filters = || {
+			Some(vec![ConvFilter {
+				agent_id: Some(agent_id.into()),
+				..Default::default()
+			}])
+		};
+		let list_ids = |ctx: Ctx| {
+			let mm = mm.clone();
+			async move {
+				let page =
+					ConvBmc::list_page_with_unread(&ctx, &mm, filters(), None, None)
+						.await?;
+				let mut ids: Vec<i64> =
+					page.items.iter().map(|conv| conv.id).collect();
+				ids.sort_unstable();
+				Ok::<_, model::Error>((ids, page.total))
+			}
+		};
+
+		// -- Exec & Check
+		assert_eq!(list_ids(owner_ctx.clone()).await?, (conv_ids.clone(), 2));
+		assert_eq!(list_ids(member_ctx.clone()).await?, (vec![conv_ids[1]], 1));
+		assert_eq!(list_ids(other_ctx.clone()).await?, (vec![], 0));
+		assert_eq!(list_ids(root_ctx.clone()).await?, (conv_ids.clone(), 2));
+		assert_eq!(ConvBmc::count_visible(&owner_ctx, &mm, filters()).await?, 2);
+		assert_eq!(
+			ConvBmc::count_visible(&member_ctx, &mm, filters()).await?,
+			1
+		);
+		assert_eq!(ConvBmc::count_visible(&other_ctx, &mm, filters()).await?, 0);
+
+		// -- Clean
+		AgentBmc::delete(&root_ctx, &mm, agent_id).await?;
+		clean_users(&root_ctx, &mm, "test_list_visible_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_upsert_user_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id = seed_agent(&ctx, &mm, "test_upsert_user_ok agent 01").await?;
+		let conv_id =
+			seed_conv(&ctx, &mm, agent_id, "test_upsert_user_ok conv").await?;
+		let user_id = seed_user(&ctx, &mm, "test_upsert_user_ok-user-01").await?;
+		let conv_user_c = |auto_respond: Option<bool>| ConvUserForCreate {
+			conv_id,
+			user_id,
+			auto_respond,
+		};
+
+		// -- Exec
+		let inserted_id = ConvBmc::upsert_user(&ctx, &mm, conv_user_c(None)).await?;
+		let inserted = ConvBmc::get_user(&ctx, &mm, inserted_id).await?;
+		let updated_id =
+			ConvBmc::upsert_user(&ctx, &mm, conv_user_c(Some(true))).await?;
+		let updated = ConvBmc::get_user(&ctx, &mm, updated_id).await?;
+
+		// -- Check
+		assert_eq!(updated_id, inserted_id, "should have updated the same row");
+		assert!(!inserted.auto_respond);
+		assert!(updated.auto_respond);
+		assert_eq!(updated.ctime, inserted.ctime, "ctime should not change");
+		assert!(updated.mtime > inserted.mtime, "mtime should be updated");
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+		clean_users(&ctx, &mm, "test_upsert_user_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_add_msg_rich_content_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&ctx, &mm, "test_add_msg_rich_content_ok agent 01").await?;
+		let conv_id =
+			seed_conv(&ctx, &mm, agent_id, "test_add_msg_rich_content_ok conv")
+				.await?;
+		let fx_long_content = "long answer ".repeat(500);
+		let fx_json = r#"{"city": "Paris", "days": 3}"#;
+
+		// -- Exec
+		let text_msg_id = ConvBmc::add_msg(
+			&ctx,
+			&mm,
+			ConvMsgForCreate {
+				conv_id,
+				content: fx_long_content.clone(),
+				content_type: Some(ConvMsgContentType::Markdown),
+				..Default::default()
+			},
+		)
+		.await?;
+		let json_msg_id = ConvBmc::add_msg(
+			&ctx,
+			&mm,
+			ConvMsgForCreate {
+				conv_id,
+				content: fx_json.to_string(),
+				content_type: Some(ConvMsgContentType::Json),
+				metadata: Some(json!({"source": "form"})),
+			},
+		)
+		.await?;
+
+		// -- Check
+		let text_msg = ConvBmc::get_msg(&ctx, &mm, text_msg_id).await?;
+		assert_eq!(text_msg.content, fx_long_content);
+		assert_eq!(text_msg.content_type, ConvMsgContentType::Markdown);
+		assert_eq!(text_msg.role, ConvMsgRole::User);
+		assert!(text_msg.metadata.is_none());
+		let json_msg = ConvBmc::get_msg(&ctx, &mm, json_msg_id).await?;
+		assert_eq!(json_msg.content_type, ConvMsgContentType::Json);
+		assert_eq!(json_msg.role, ConvMsgRole::User);
+		assert_eq!(json_msg.metadata, Some(json!({"source": "form"})));
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_add_msg_err_invalid() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&ctx, &mm, "test_add_msg_err_invalid agent 01").await?;
+		let conv_id =
+			seed_conv(&ctx, &mm, agent_id, "test_add_msg_err_invalid conv").await?;
+		let msg_c =
+			|content: &str,
+			 content_type: Option<ConvMsgContentType>,
+			 metadata: Option<serde_json::Value>| ConvMsgForCreate {
+				conv_id,
+				content: content.to_string(),
+				content_type,
+				metadata,
+			};
+
+		// -- Exec
+		let res_empty = ConvBmc::add_msg(&ctx, &mm, msg_c(" ", None, None)).await;
+		let res_not_json = ConvBmc::add_msg(
+			&ctx,
+			&mm,
+			msg_c("not json", Some(ConvMsgContentType::Json), None),
+		)
+		.await;
+		let res_tool_call = ConvBmc::add_msg(
+			&ctx,
+			&mm,
+			msg_c(
+				r#"{"name": "calc"}"#,
+				Some(ConvMsgContentType::ToolCall),
+				None,
+			),
+		)
+		.await;
+		let res_tool_result = ConvBmc::add_msg(
+			&ctx,
+			&mm,
+			msg_c(
+				r#"{"ok": true}"#,
+				Some(ConvMsgContentType::ToolResult),
+				None,
+			),
+		)
+		.await;
+		let res_metadata =
+			ConvBmc::add_msg(&ctx, &mm, msg_c("hello", None, Some(json!([1, 2]))))
+				.await;
+
+		// -- Check
+		assert!(matches!(res_empty, Err(model::Error::ConvMsgContentEmpty)));
+		assert!(matches!(
+			res_not_json,
+			Err(model::Error::ConvMsgContentNotJson { .. })
+		));
+		assert!(matches!(
+			res_metadata,
+			Err(model::Error::ConvMsgMetadataNotObject)
+		));
+		for res in [res_tool_call, res_tool_result] {
+			assert!(
+				matches!(
+					res,
+					Err(model::Error::ConvMsgContentTypeNotAllowed { .. })
+				),
+				"should be ConvMsgContentTypeNotAllowed, but was {res:?}"
+			);
+		}
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_add_msg_err_not_conv_user() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let owner_ctx = Ctx::new(
+			seed_user(&root_ctx, &mm, "test_add_msg_err_not_conv_user-user-owner")
+				.await?,
+		)?;
+		let other_ctx = Ctx::new(
+			seed_user(&root_ctx, &mm, "test_add_msg_err_not_conv_user-user-other")
+				.await?,
+		)?;
+		let agent_id =
+			seed_agent(&root_ctx, &mm, "test_add_msg_err_not_conv_user agent")
+				.await?;
+		let conv_id = seed_conv(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			"test_add_msg_err_not_conv_user conv",
+		)
+		.await?;
+		let msg_c = |content: &str| ConvMsgForCreate {
+			conv_id,
+			content: content.to_string(),
+			..Default::default()
+		};
+		let fx_msg_id =
+			ConvBmc::add_msg(&owner_ctx, &mm, msg_c("owner msg")).await?;
+
+		// -- Exec
+		let res = ConvBmc::add_msg(&other_ctx, &mm, msg_c("other msg")).await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res:?}"
+		);
+		let conv = ConvBmc::get(&root_ctx, &mm, conv_id).await?;
+		assert_eq!(conv.head_msg_id, Some(fx_msg_id));
+
+		// -- Clean
+		AgentBmc::delete(&root_ctx, &mm, agent_id).await?;
+		clean_users(&root_ctx, &mm, "test_add_msg_err_not_conv_user").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_search_msgs_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id = seed_agent(&ctx, &mm, "test_search_msgs_ok agent 01").await?;
+		let owner_ctx =
+			Ctx::new(seed_user(&ctx, &mm, "test_search_msgs_ok-user-owner").await?)?;
+		let member_id =
+			seed_user(&ctx, &mm, "test_search_msgs_ok-user-member").await?;
+		let member_ctx = Ctx::new(member_id)?;
+		let other_ctx =
+			Ctx::new(seed_user(&ctx, &mm, "test_search_msgs_ok-user-other").await?)?;
+		// owner conv (title and one msg matching)
+		let conv_id = seed_conv(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			"zanzibar trip notes <script>alert(1)</script>",
+		)
+		.await?;
+		for content in [
+			"Booking the flights to Zanzibar",
+			"Nothing relevant",
+			"Zanzibar <img src=x onerror=alert(1)> & more",
+		] {
+			let msg_c = ConvMsgForCreate {
+				conv_id,
+				content: content.to_string(),
+				..Default::default()
+			};
+			ConvBmc::add_msg(&owner_ctx, &mm, msg_c).await?;
+		}
+		// other user conv (one msg matching)
+		let other_conv_id = seed_conv(&other_ctx, &mm, agent_id, "other").await?;
+		let msg_c = ConvMsgForCreate {
+			conv_id: other_conv_id,
+			content: "Zanzibar is far".to_string(),
+			..Default::default()
+		};
+		ConvBmc::add_msg(&other_ctx, &mm, msg_c).await?;
+
+		// -- Exec
+		let owner_page =
+			ConvBmc::search_msgs(&owner_ctx, &mm, "zanzibar", None).await?;
+		let member_page_before =
+			ConvBmc::search_msgs(&member_ctx, &mm, "zanzibar", None).await?;
+		let conv_user_c = ConvUserForCreate {
+			conv_id,
+			user_id: member_id,
+			auto_respond: None,
+		};
+		ConvBmc::upsert_user(&ctx, &mm, conv_user_c).await?;
+		let member_page =
+			ConvBmc::search_msgs(&member_ctx, &mm, "zanzibar", None).await?;
+		let res = ConvBmc::search_msgs(&owner_ctx, &mm, "  ", None).await;
+
+		// -- Check
+		assert_eq!(owner_page.total, 3);
+		assert!(owner_page.items.iter().all(|hit| hit.conv.id == conv_id));
+		let msg_snippets: Vec<&str> = owner_page
+			.items
+			.iter()
+			.filter(|hit| hit.msg.is_some())
+			.filter_map(|hit| hit.snippet.as_deref())
+			.collect();
+		assert!(
+			msg_snippets.contains(&"Booking the flights to <mark>Zanzibar</mark>"),
+			"snippets: {msg_snippets:?}"
+		);
+		// the msg content is html escaped (only the `<mark>` are markup)
+		assert!(
+			msg_snippets.contains(
+				&"<mark>Zanzibar</mark> &lt;img src=x onerror=alert(1)&gt; &amp; more"
+			),
+			"snippets: {msg_snippets:?}"
+		);
+		let msg_hit = owner_page
+			.items
+			.iter()
+			.find(|hit| hit.msg.is_some())
+			.ok_or("should have a msg hit")?;
+		assert_eq!(
+			msg_hit.title_snippet.as_deref(),
+			Some("<mark>zanzibar</mark> trip notes &lt;script&gt;alert(1)&lt;/script&gt;")
+		);
+		assert!(owner_page.items.iter().any(|hit| hit.msg.is_none()));
+		assert_eq!(member_page_before.total, 0, "not a member yet");
+		assert_eq!(member_page.total, 3);
+		assert!(matches!(res, Err(model::Error::SearchQueryEmpty)));
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+		clean_users(&ctx, &mm, "test_search_msgs_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_update_msg_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id = seed_agent(&ctx, &mm, "test_update_msg_ok agent 01").await?;
+		let conv_id =
+			seed_conv(&ctx, &mm, agent_id, "test_update_msg_ok conv").await?;
+		let msg_id = ConvBmc::add_msg(
+			&ctx,
+			&mm,
+			ConvMsgForCreate {
+				conv_id,
+				content: "content 01".to_string(),
+				..Default::default()
+			},
+		)
+		.await?;
+		let msg_u = |content: &str| ConvMsgForUpdate {
+			conv_id,
+			content: content.to_string(),
+		};
+
+		// -- Exec
+		ConvBmc::update_msg(&ctx, &mm, msg_id, msg_u("content 02")).await?;
+		ConvBmc::update_msg(&ctx, &mm, msg_id, msg_u("content 03")).await?;
+		// same content, no-op
+		ConvBmc::update_msg(&ctx, &mm, msg_id, msg_u("content 03")).await?;
+
+		// -- Check
+		let msg = ConvBmc::get_msg(&ctx, &mm, msg_id).await?;
+		assert_eq!(msg.content, "content 03");
+		assert_eq!(msg.edit_count, 2);
+		assert!(msg.etime.is_some(), "should be marked as edited");
+		let revisions = ConvBmc::list_msg_revisions(&ctx, &mm, msg_id).await?;
+		let revisions: Vec<(i32, &str)> = revisions
+			.iter()
+			.map(|r| (r.revision, r.content.as_str()))
+			.collect();
+		assert_eq!(revisions, &[(1, "content 01"), (2, "content 02")]);
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_update_msg_err_not_author() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&root_ctx, &mm, "test_update_msg_err_not_author agent 01")
+				.await?;
+		let conv_id = seed_conv(
+			&root_ctx,
+			&mm,
+			agent_id,
+			"test_update_msg_err_not_author conv",
+		)
+		.await?;
+		let user_id =
+			seed_user(&root_ctx, &mm, "test_update_msg_err_not_author-user-01")
+				.await?;
+		ConvBmc::upsert_user(
+			&root_ctx,
+			&mm,
+			ConvUserForCreate {
+				conv_id,
+				user_id,
+				auto_respond: None,
+			},
+		)
+		.await?;
+		let msg_id = ConvBmc::add_msg(
+			&root_ctx,
+			&mm,
+			ConvMsgForCreate {
+				conv_id,
+				content: "root content".to_string(),
+				..Default::default()
+			},
+		)
+		.await?;
+		let user_ctx = Ctx::new(user_id)?;
+
+		// -- Exec
+		let res = ConvBmc::update_msg(
+			&user_ctx,
+			&mm,
+			msg_id,
+			ConvMsgForUpdate {
+				conv_id,
+				content: "user content".to_string(),
+			},
+		)
+		.await;
+
+		// -- Check
+		assert!(
+			matches!(
+				res,
+				Err(model::Error::AccessDenied {
+					required: "conv_msg_author",
+					..
+				})
+			),
+			"should be denied, but was {res:?}"
+		);
+		// as a conv user, the revisions can be listed
+		let revisions = ConvBmc::list_msg_revisions(&user_ctx, &mm, msg_id).await?;
+		assert!(revisions.is_empty());
+
+		// -- Clean
+		AgentBmc::delete(&root_ctx, &mm, agent_id).await?;
+		clean_users(&root_ctx, &mm, "test_update_msg_err_not_author").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_msgs_err_not_conv_user() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&root_ctx, &mm, "test_list_msgs_err_not_conv_user agent")
+				.await?;
+		let conv_ids = seed_convs(
+			&root_ctx,
+			&mm,
+			agent_id,
+			&[
+				"test_list_msgs_err_not_conv_user conv 01",
+				"test_list_msgs_err_not_conv_user conv 02",
+			],
+		)
+		.await?;
+		for conv_id in conv_ids.iter() {
+			ConvBmc::add_msg(
+				&root_ctx,
+				&mm,
+				ConvMsgForCreate {
+					conv_id: *conv_id,
+					content: "root content".to_string(),
+					..Default::default()
+				},
+			)
+			.await?;
+		}
+		let user_id =
+			seed_user(&root_ctx, &mm, "test_list_msgs_err_not_conv_user-user-01")
+				.await?;
+		let user_ctx = Ctx::new(user_id)?;
+
+		// -- Exec
+		let res =
+			ConvBmc::list_msgs(&user_ctx, &mm, conv_ids[0], None, None, None).await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be denied, but was {res:?}"
+		);
+		// the filters are scoped to the conv (cannot list the msgs of another conv)
+		let filter: ConvMsgFilter =
+			serde_json::from_value(json!({"conv_id": conv_ids[1]}))?;
+		let page = ConvBmc::list_msgs(
+			&root_ctx,
+			&mm,
+			conv_ids[0],
+			Some(vec![filter]),
+			None,
+			None,
+		)
+		.await?;
+		assert_eq!(page.items.len(), 1);
+		assert_eq!(page.items[0].conv_id, conv_ids[0]);
+
+		// -- Clean
+		AgentBmc::delete(&root_ctx, &mm, agent_id).await?;
+		clean_users(&root_ctx, &mm, "test_list_msgs_err_not_conv_user").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_get_msg_err_not_conv_user() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&root_ctx, &mm, "test_get_msg_err_not_conv_user agent")
+				.await?;
+		let conv_id = seed_conv(
+			&root_ctx,
+			&mm,
+			agent_id,
+			"test_get_msg_err_not_conv_user conv",
+		)
+		.await?;
+		let msg_id = ConvBmc::add_msg(
+			&root_ctx,
+			&mm,
+			ConvMsgForCreate {
+				conv_id,
+				content: "root content".to_string(),
+				..Default::default()
+			},
+		)
+		.await?;
+		let user_id =
+			seed_user(&root_ctx, &mm, "test_get_msg_err_not_conv_user-user-01")
+				.await?;
+		let user_ctx = Ctx::new(user_id)?;
+
+		// -- Exec
+		let res = ConvBmc::get_msg(&user_ctx, &mm, msg_id).await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(model::Error::AccessDenied { .. })),
+			"should be denied, but was {res:?}"
+		);
+
+		// -- Clean
+		AgentBmc::delete(&root_ctx, &mm, agent_id).await?;
+		clean_users(&root_ctx, &mm, "test_get_msg_err_not_conv_user").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_msg_reactions_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&root_ctx, &mm, "test_msg_reactions_ok agent 01").await?;
+		let conv_id =
+			seed_conv(&root_ctx, &mm, agent_id, "test_msg_reactions_ok conv")
+				.await?;
+		let user_id =
+			seed_user(&root_ctx, &mm, "test_msg_reactions_ok-user-01").await?;
+		ConvBmc::upsert_user(
+			&root_ctx,
+			&mm,
+			ConvUserForCreate {
+				conv_id,
+				user_id,
+				auto_respond: None,
+			},
+		)
+		.await?;
+		let msg_id = ConvBmc::add_msg(
+			&root_ctx,
+			&mm,
+			ConvMsgForCreate {
+				conv_id,
+				content: "react to me".to_string(),
+				..Default::default()
+			},
+		)
+		.await?;
+		let user_ctx = Ctx::new(user_id)?;
+		let reaction_c = |emoji: &str| ConvMsgReactionForCreate {
+			msg_id,
+			emoji: emoji.to_string(),
+		};
+
+		// -- Exec
+		let root_id =
+			ConvBmc::add_msg_reaction(&root_ctx, &mm, reaction_c("👍")).await?;
+		let user_id_01 =
+			ConvBmc::add_msg_reaction(&user_ctx, &mm, reaction_c("👍")).await?;
+		let user_id_02 =
+			ConvBmc::add_msg_reaction(&user_ctx, &mm, reaction_c("👍")).await?;
+		ConvBmc::add_msg_reaction(&user_ctx, &mm, reaction_c("🎉")).await?;
+		let res_invalid =
+			ConvBmc::add_msg_reaction(&user_ctx, &mm, reaction_c("+1")).await;
+		let res_remove_other =
+			ConvBmc::remove_msg_reaction(&user_ctx, &mm, root_id).await;
+		ConvBmc::remove_msg_reaction(&user_ctx, &mm, user_id_01).await?;
+
+		// -- Check
+		assert_eq!(user_id_02, user_id_01, "same user and emoji is a no-op");
+		assert!(matches!(
+			res_invalid,
+			Err(model::Error::ConvMsgReactionEmojiInvalid { .. })
+		));
+		assert!(matches!(
+			res_remove_other,
+			Err(model::Error::AccessDenied { .. })
+		));
+		let reactions = ConvBmc::list_msg_reactions(&user_ctx, &mm, msg_id).await?;
+		let reactions: Vec<(i64, &str)> = reactions
+			.iter()
+			.map(|r| (r.user_id, r.emoji.as_str()))
+			.collect();
+		assert_eq!(reactions, &[(0, "👍"), (user_id, "🎉")]);
+
+		// -- Clean
+		AgentBmc::delete(&root_ctx, &mm, agent_id).await?;
+		clean_users(&root_ctx, &mm, "test_msg_reactions_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_mark_read_unread_counts_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&root_ctx, &mm, "test_mark_read_unread_counts_ok agent 01")
+				.await?;
+		let conv_ids = seed_convs(
+			&root_ctx,
+			&mm,
+			agent_id,
+			&[
+				"test_mark_read_unread_counts_ok conv 01",
+				"test_mark_read_unread_counts_ok conv 02",
+			],
+		)
+		.await?;
+		let (conv_id_01, conv_id_02) = (conv_ids[0], conv_ids[1]);
+		let user_id =
+			seed_user(&root_ctx, &mm, "test_mark_read_unread_counts_ok-user-01")
+				.await?;
+		let user_ctx = Ctx::new(user_id)?;
+		for conv_id in [conv_id_01, conv_id_02] {
+			ConvBmc::upsert_user(
+				&root_ctx,
+				&mm,
+				ConvUserForCreate {
+					conv_id,
+					user_id,
+					auto_respond: None,
+				},
+			)
+			.await?;
+		}
+		let add_msg = |ctx: Ctx, conv_id: i64, content: &'static str| {
+			let mm = mm.clone();
+			async move {
+				let msg_c = ConvMsgForCreate {
+					conv_id,
+					content: content.to_string(),
+					..Default::default()
+				};
+				ConvBmc::add_msg(&ctx, &mm, msg_c).await
+			}
+		};
+		let msg_id_01 = add_msg(root_ctx.clone(), conv_id_01, "msg 01").await?;
+		let msg_id_02 = add_msg(root_ctx.clone(), conv_id_01, "msg 02").await?;
+		add_msg(root_ctx.clone(), conv_id_01, "msg 03").await?;
+		// own msgs are never unread
+		add_msg(user_ctx.clone(), conv_id_01, "user msg").await?;
+		add_msg(root_ctx.clone(), conv_id_02, "msg 01").await?;
+		let unread_counts = || async {
+			let filter = ConvFilter {
+				id: Some(OpValInt64::In(conv_ids.clone()).into()),
+				..Default::default()
+			};
+			let page = ConvBmc::list_page_with_unread(
+				&user_ctx,
+				&mm,
+				Some(vec![filter]),
+				Some(ListOptions {
+					order_bys: Some("id".into()),
+					..Default::default()
+				}),
+				None,
+			)
+			.await?;
+			let counts: Vec<Option<i64>> =
+				page.items.iter().map(|conv| conv.unread_count).collect();
+			Ok::<_, model::Error>(counts)
+		};
+
+		// -- Exec & Check
+		assert_eq!(unread_counts().await?, &[Some(3), Some(1)]);
+
+		let read_u = |msg_id: Option<i64>| ConvForMarkRead { msg_id };
+		ConvBmc::mark_read(&user_ctx, &mm, conv_id_01, read_u(Some(msg_id_02)))
+			.await?;
+		assert_eq!(unread_counts().await?, &[Some(1), Some(1)]);
+
+		// never move backward
+		ConvBmc::mark_read(&user_ctx, &mm, conv_id_01, read_u(Some(msg_id_01)))
+			.await?;
+		ConvBmc::mark_read(&user_ctx, &mm, conv_id_02, read_u(None)).await?;
+		assert_eq!(unread_counts().await?, &[Some(1), Some(0)]);
+
+		let res =
+			ConvBmc::mark_read(&user_ctx, &mm, conv_id_02, read_u(Some(msg_id_01)))
+				.await;
+		assert!(matches!(
+			res,
+			Err(model::Error::ConvMsgConvIdMismatch { .. })
+		));
+
+		// root does not become a conv user
+		ConvBmc::mark_read(&root_ctx, &mm, conv_id_01, read_u(None)).await?;
+		let sqlx_query = sqlx::query_as::<_, (i64,)>(
+			"SELECT count(*) FROM conv_user WHERE conv_id = $1 AND user_id = 0",
+		)
+		.bind(conv_id_01);
+		let (root_conv_user_count,) = mm.dbx().fetch_one(sqlx_query).await?;
+		assert_eq!(root_conv_user_count, 0);
+
+		// -- Clean
+		AgentBmc::delete(&root_ctx, &mm, agent_id).await?;
+		clean_users(&root_ctx, &mm, "test_mark_read_unread_counts_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_generate_reply_tool_call_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id = AgentBmc::create(
+			&ctx,
+			&mm,
+			AgentForCreate {
+				name: "test_generate_reply_tool_call_ok agent 01".to_string(),
+				tools: Some(AgentTools::try_from(vec!["calculator".to_string()])?),
+				..Default::default()
+			},
+		)
+		.await?;
+		let conv_id =
+			seed_conv(&ctx, &mm, agent_id, "test_generate_reply_tool_call_ok conv")
+				.await?;
+		let add_msg = |content: &'static str| {
+			let mm = mm.clone();
+			async move {
+				let msg_c = ConvMsgForCreate {
+					conv_id,
+					content: content.to_string(),
+					..Default::default()
+				};
+				ConvBmc::add_msg(&Ctx::root_ctx(), &mm, msg_c).await
+			}
+		};
+
+		// -- Exec
+		add_msg(r#"/tool calculator {"expr": "1 + 2 * 3"}"#).await?;
+		let tool_msg_ids = ConvBmc::generate_reply(&ctx, &mm, conv_id).await?;
+		// not enabled tool, so parrot reply
+		add_msg("/tool get_current_time").await?;
+		let text_msg_ids = ConvBmc::generate_reply(&ctx, &mm, conv_id).await?;
+
+		// -- Check
+		assert_eq!(tool_msg_ids.len(), 3, "tool call, tool result, reply");
+		let call_msg = ConvBmc::get_msg(&ctx, &mm, tool_msg_ids[0]).await?;
+		assert_eq!(call_msg.role, ConvMsgRole::Assistant);
+		assert_eq!(call_msg.content_type, ConvMsgContentType::ToolCall);
+		let tool_call: ToolCall = serde_json::from_str(&call_msg.content)?;
+		assert_eq!(tool_call.name, "calculator");
+		assert_eq!(tool_call.args, json!({"expr": "1 + 2 * 3"}));
+
+		let result_msg = ConvBmc::get_msg(&ctx, &mm, tool_msg_ids[1]).await?;
+		assert_eq!(result_msg.role, ConvMsgRole::Tool);
+		assert_eq!(result_msg.content_type, ConvMsgContentType::ToolResult);
+		let tool_result: ToolResult = serde_json::from_str(&result_msg.content)?;
+		assert_eq!(tool_result.call_id, tool_call.call_id);
+		assert_eq!(
+			tool_result.result,
+			Some(json!({"expr": "1 + 2 * 3", "result": 7.0}))
+		);
+
+		let reply_msg = ConvBmc::get_msg(&ctx, &mm, tool_msg_ids[2]).await?;
+		assert_eq!(reply_msg.role, ConvMsgRole::Assistant);
+		assert_eq!(
+			reply_msg.content,
+			r#"calculator: {"expr":"1 + 2 * 3","result":7.0}"#
+		);
+
+		assert_eq!(text_msg_ids.len(), 1, "only the reply");
+		let reply_msg = ConvBmc::get_msg(&ctx, &mm, text_msg_ids[0]).await?;
+		assert_eq!(reply_msg.content, "/tool get_current_time");
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_generate_reply_err_tool_fail() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id = AgentBmc::create(
+			&ctx,
+			&mm,
+			AgentForCreate {
+				name: "test_generate_reply_err_tool_fail agent 01".to_string(),
+				tools: Some(AgentTools::try_from(vec!["calculator".to_string()])?),
+				..Default::default()
+			},
+		)
+		.await?;
+		let conv_id = seed_conv(
+			&ctx,
+			&mm,
+			agent_id,
+			"test_generate_reply_err_tool_fail conv",
+		)
+		.await?;
+		let msg_c = ConvMsgForCreate {
+			conv_id,
+			content: r#"/tool calculator {"expr": "1 +"}"#.to_string(),
+			..Default::default()
+		};
+		let fx_msg_id = ConvBmc::add_msg(&ctx, &mm, msg_c).await?;
+		let usage_count = || async {
+			let sqlx_query = sqlx::query_as::<_, (i64,)>(
+				"SELECT count(*) FROM usage_record WHERE conv_id = $1",
+			)
+			.bind(conv_id);
+			mm.dbx().fetch_one(sqlx_query).await.map(|(count,)| count)
+		};
+
+		// -- Exec
+		// The calculator always fails, so the parrot calls it until the steps max.
+		let res = ConvBmc::generate_reply(&ctx, &mm, conv_id).await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(model::Error::AgentReplyStepsOverMax { .. })),
+			"should be AgentReplyStepsOverMax, but was {res:?}"
+		);
+		let branch = ConvBmc::get_branch(&ctx, &mm, conv_id).await?;
+		assert_eq!(branch.conv.head_msg_id, Some(fx_msg_id));
+		assert_eq!(branch.msgs.len(), 1, "no tool msgs should be kept");
+		assert_eq!(usage_count().await?, 0, "no usage should be kept");
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_branches_regenerate_fork_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&ctx, &mm, "test_branches_regenerate_fork_ok agent 01")
+				.await?;
+		let conv_id =
+			seed_conv(&ctx, &mm, agent_id, "test_branches_regenerate_fork_ok conv")
+				.await?;
+		let mut fx_msg_ids = Vec::new();
+		for content in ["msg 01", "msg 02"] {
+			let msg_c = ConvMsgForCreate {
+				conv_id,
+				content: content.to_string(),
+				..Default::default()
+			};
+			fx_msg_ids.push(ConvBmc::add_msg(&ctx, &mm, msg_c).await?);
+			fx_msg_ids.extend(ConvBmc::generate_reply(&ctx, &mm, conv_id).await?);
+		}
+		let fx_reply_02_id = fx_msg_ids[3];
+
+		// -- Exec
+		let regen_ids = ConvBmc::regenerate_reply(&ctx, &mm, fx_reply_02_id).await?;
+		let branch_regen = ConvBmc::get_branch(&ctx, &mm, conv_id).await?;
+		ConvBmc::switch_branch(&ctx, &mm, fx_reply_02_id).await?;
+		let branch_switched = ConvBmc::get_branch(&ctx, &mm, conv_id).await?;
+		let fork_id = ConvBmc::fork_conv(&ctx, &mm, conv_id, fx_msg_ids[1]).await?;
+		let branch_fork = ConvBmc::get_branch(&ctx, &mm, fork_id).await?;
+		let res_not_reply =
+			ConvBmc::regenerate_reply(&ctx, &mm, fx_msg_ids[0]).await;
+
+		// -- Check
+		// regenerate, the new reply is a sibling of the prior reply
+		assert_eq!(regen_ids.len(), 1);
+		let branch_ids: Vec<i64> = branch_regen.msgs.iter().map(|m| m.id).collect();
+		assert_eq!(branch_ids, [&fx_msg_ids[..3], &regen_ids[..]].concat());
+		let regen_msg = &branch_regen.msgs[3];
+		assert_eq!(regen_msg.parent_msg_id, Some(fx_msg_ids[2]));
+		assert_eq!(regen_msg.content, "msg 02");
+		assert_eq!(branch_regen.conv.head_msg_id, Some(regen_ids[0]));
+
+		// switch back to the prior reply branch
+		let branch_ids: Vec<i64> =
+			branch_switched.msgs.iter().map(|m| m.id).collect();
+		assert_eq!(branch_ids, fx_msg_ids);
+
+		// fork at the first reply
+		let contents: Vec<&str> = branch_fork
+			.msgs
+			.iter()
+			.map(|m| m.content.as_str())
+			.collect();
+		assert_eq!(contents, ["msg 01", "msg 01"]);
+		assert_eq!(
+			branch_fork.msgs[1].parent_msg_id,
+			Some(branch_fork.msgs[0].id)
+		);
+		assert_eq!(
+			branch_fork.conv.agent_version_id,
+			branch_regen.conv.agent_version_id
+		);
+
+		assert!(
+			matches!(res_not_reply, Err(model::Error::ConvMsgNotReply { msg_id }) if msg_id == fx_msg_ids[0]),
+			"should be ConvMsgNotReply, but was {res_not_reply:?}"
+		);
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_regenerate_reply_err_quota() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&ctx, &mm, "test_regenerate_reply_err_quota agent").await?;
+		let conv_id =
+			seed_conv(&ctx, &mm, agent_id, "test_regenerate_reply_err_quota conv")
+				.await?;
+		let msg_c = ConvMsgForCreate {
+			conv_id,
+			content: "msg 01".to_string(),
+			..Default::default()
+		};
+		ConvBmc::add_msg(&ctx, &mm, msg_c).await?;
+		let fx_reply_ids = ConvBmc::generate_reply(&ctx, &mm, conv_id).await?;
+		// quota reached by the reply above
+		let quota_id = UsageQuotaBmc::create(
+			&ctx,
+			&mm,
+			UsageQuotaForCreate {
+				scope: UsageScope::Agent,
+				scope_id: agent_id,
+				daily_tokens_max: Some(1),
+				daily_cost_micros_max: None,
+			},
+		)
+		.await?;
+
+		// -- Exec
+		let res = ConvBmc::regenerate_reply(&ctx, &mm, fx_reply_ids[0]).await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(model::Error::UsageQuotaExceeded { .. })),
+			"should be quota exceeded, but was {res:?}"
+		);
+		let branch = ConvBmc::get_branch(&ctx, &mm, conv_id).await?;
+		assert_eq!(branch.conv.head_msg_id, Some(fx_reply_ids[0]));
+		assert_eq!(branch.msgs.len(), 2);
+
+		// -- Clean
+		UsageQuotaBmc::delete(&ctx, &mm, quota_id).await?;
 		AgentBmc::delete(&ctx, &mm, agent_id).await?;
 
 		Ok(())
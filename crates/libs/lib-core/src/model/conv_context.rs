@@ -0,0 +1,402 @@
+//! The conv context builder, which selects the `ConvMsg` history given to the AI
+//! within the agent context window (see `AgentContext`).
+//!
+//! Notes:
+//!   - Only the msgs of the active branch are given (see `ConvBmc::get_branch`).
+//!   - The tokens are counted with `ai::count_tokens` (approximate).
+//!   - The msgs budget is the `AgentContext.max_tokens` (default: the model context window),
+//!     minus the system prompt, the tool specs, and the `AgentParams.max_tokens` (completion).
+//!   - When the msgs do not fit, the oldest ones are dropped (`ContextStrategy::Truncate`),
+//!     or replaced by the rolling `ConvSummary` (`ContextStrategy::Summarize`), which is only
+//!     regenerated when more msgs are dropped (with the previous summary and the newly dropped msgs).
+//!   - The most recent msg is always kept, and the kept msgs never start with a `ToolResult`
+//!     (without its `ToolCall`).
+
+use crate::ai::tool::ToolSpec;
+use crate::ai::{self, count_tokens, ChatMessage, ChatRequest, SummaryRequest};
+use crate::ctx::Ctx;
+use crate::model::acs::assert_conv_user;
+use crate::model::agent::{AgentVersion, ContextStrategy};
+use crate::model::base;
+use crate::model::conv::{list_msg_path, Conv, ConvBmc};
+use crate::model::conv_msg::{ConvMsg, ConvMsgContentType, ConvMsgRole};
+use crate::model::conv_summary::{
+	ConvSummary, ConvSummaryBmc, ConvSummaryForInsert,
+};
+use crate::model::usage::UsageBmc;
+use crate::model::{ModelManager, Result};
+
+/// The default summary max tokens is the context max tokens / 4.
+const SUMMARY_MAX_TOKENS_DIVISOR: i64 = 4;
+
+/// A conv msg with its chat message and tokens.
+struct ContextMsg {
+	msg_id: i64,
+	chat_msg: ChatMessage,
+	tokens: i64,
+}
+
+impl ConvBmc {
+	/// Returns the rolling summaries of the conv (oldest first).
+	pub async fn list_summaries(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+	) -> Result<Vec<ConvSummary>> {
+		assert_conv_user(ctx, mm, conv_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, ConvSummary>(
+			"SELECT * FROM conv_summary WHERE conv_id = $1 ORDER BY up_to_msg_id, id",
+		)
+		.bind(conv_id);
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+}
+
+/// Returns the `ChatRequest` of the conv msgs, for the `agent_version`
+/// (with the msgs within its context window).
+pub(in crate::model) async fn build_chat_request(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	conv: &Conv,
+	agent_version: &AgentVersion,
+	tools: Vec<ToolSpec>,
+) -> Result<ChatRequest> {
+	let context = agent_version.context.clone().unwrap_or_default();
+	let max_tokens = context
+		.max_tokens
+		.map(i64::from)
+		.or_else(|| {
+			ai::model_context_window(
+				&agent_version.ai_provider,
+				&agent_version.ai_model,
+			)
+		})
+		.unwrap_or(i64::MAX);
+
+	// -- Compute the msgs budget
+	let mut reserved_tokens = agent_version
+		.system_prompt
+		.as_deref()
+		.map(count_tokens)
+		.unwrap_or(0);
+	if !tools.is_empty() {
+		reserved_tokens +=
+			count_tokens(&serde_json::to_string(&tools).unwrap_or_default());
+	}
+	if let Some(completion_tokens) = agent_version
+		.params
+		.as_ref()
+		.and_then(|params| params.max_tokens)
+	{
+		reserved_tokens += i64::from(completion_tokens);
+	}
+	let budget = max_tokens.saturating_sub(reserved_tokens);
+
+	// -- Load the msgs of the active branch
+	let branch_msgs = match conv.head_msg_id {
+		Some(head_msg_id) => list_msg_path(mm, head_msg_id).await?,
+		None => Vec::new(),
+	};
+	let msgs: Vec<ContextMsg> = branch_msgs
+		.into_iter()
+		.map(|msg| {
+			let msg_id = msg.id;
+			let chat_msg = chat_message_from_msg(msg);
+			let tokens = count_tokens(&chat_msg.text());
+			ContextMsg {
+				msg_id,
+				chat_msg,
+				tokens,
+			}
+		})
+		.collect();
+
+	// -- Select the msgs
+	let total_tokens: i64 = msgs.iter().map(|msg| msg.tokens).sum();
+	let mut messages = Vec::new();
+	let mut start = 0;
+	let mut summarized_up_to = 0;
+	if total_tokens > budget {
+		match context.strategy {
+			ContextStrategy::Truncate => {
+				start = window_start(&msgs, budget);
+			}
+			ContextStrategy::Summarize => {
+				let summary_max_tokens = context
+					.summary_max_tokens
+					.map(i64::from)
+					.unwrap_or(max_tokens / SUMMARY_MAX_TOKENS_DIVISOR);
+				start =
+					window_start(&msgs, budget.saturating_sub(summary_max_tokens));
+				if start > 0 {
+					let summary = rolling_summary(
+						ctx,
+						mm,
+						conv,
+						agent_version,
+						&msgs,
+						start,
+						summary_max_tokens,
+					)
+					.await?;
+					summarized_up_to = summary.up_to_msg_id;
+					messages.push(ChatMessage::System(format!(
+						"Summary: {}",
+						summary.content
+					)));
+				}
+			}
+		}
+	}
+	messages.extend(
+		msgs.into_iter()
+			.skip(start)
+			.filter(|msg| msg.msg_id > summarized_up_to)
+			.map(|msg| msg.chat_msg),
+	);
+
+	Ok(ChatRequest {
+		system: agent_version.system_prompt.clone(),
+		messages,
+		tools,
+		params: agent_version.params.clone(),
+	})
+}
+
+/// Returns the rolling summary of the branch `msgs` which covers (at least) the dropped msgs
+/// (i.e., before `start`), creating it if needed.
+///
+/// Note: Only the summaries up to a msg of the branch are reused (not the other branches ones).
+async fn rolling_summary(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	conv: &Conv,
+	agent_version: &AgentVersion,
+	msgs: &[ContextMsg],
+	start: usize,
+	summary_max_tokens: i64,
+) -> Result<ConvSummary> {
+	let dropped = &msgs[..start];
+	let last_dropped_id = dropped.last().map(|msg| msg.msg_id).unwrap_or(0);
+	let branch_msg_ids: Vec<i64> = msgs.iter().map(|msg| msg.msg_id).collect();
+
+	let sqlx_query = sqlx::query_as::<_, ConvSummary>(
+		"SELECT * FROM conv_summary WHERE conv_id = $1 AND up_to_msg_id = ANY($2)
+		ORDER BY up_to_msg_id DESC, id DESC LIMIT 1",
+	)
+	.bind(conv.id)
+	.bind(branch_msg_ids);
+	let latest = mm.dbx().fetch_optional(sqlx_query).await?;
+
+	// -- Reuse the latest summary if it covers the dropped msgs
+	if let Some(latest) = latest.as_ref() {
+		if latest.up_to_msg_id >= last_dropped_id {
+			return Ok(latest.clone());
+		}
+	}
+
+	// -- Otherwise, summarize the newly dropped msgs after the latest summary
+	let from_msg_id = latest.as_ref().map(|s| s.up_to_msg_id).unwrap_or(0);
+	let summary_req = SummaryRequest {
+		previous: latest.map(|summary| summary.content),
+		messages: dropped
+			.iter()
+			.filter(|msg| msg.msg_id > from_msg_id)
+			.map(|msg| msg.chat_msg.clone())
+			.collect(),
+		max_tokens: summary_max_tokens,
+	};
+	let summary_res = ai::exec_summary(
+		&agent_version.ai_provider,
+		&agent_version.ai_model,
+		&summary_req,
+	)
+	.await?;
+	UsageBmc::record(ctx, mm, conv, agent_version, None, summary_res.usage).await?;
+
+	let summary_i = ConvSummaryForInsert {
+		conv_id: conv.id,
+		up_to_msg_id: last_dropped_id,
+		tokens: count_tokens(&summary_res.content),
+		content: summary_res.content,
+	};
+	let summary_id = base::create::<ConvSummaryBmc, _>(ctx, mm, summary_i).await?;
+
+	base::get::<ConvSummaryBmc, _>(ctx, mm, summary_id).await
+}
+
+// region:    --- Support
+
+/// Returns the index of the first msg to keep, so that the kept msgs fit in the `budget`
+/// (but the last msg is always kept).
+fn window_start(msgs: &[ContextMsg], budget: i64) -> usize {
+	let mut start = msgs.len();
+	let mut tokens = 0;
+	while start > 0 {
+		let next_tokens = tokens + msgs[start - 1].tokens;
+		if next_tokens > budget && start < msgs.len() {
+			break;
+		}
+		tokens = next_tokens;
+		start -= 1;
+	}
+
+	// Note: A tool result cannot be the first kept msg (without its tool call).
+	while start + 1 < msgs.len()
+		&& matches!(msgs[start].chat_msg, ChatMessage::ToolResult(_))
+	{
+		start += 1;
+	}
+
+	start
+}
+
+/// Returns the `ChatMessage` of the persisted `ConvMsg`.
+///
+/// Note: The tool msgs which are not a valid `ToolCall` / `ToolResult` json
+///       (e.g., added by `add_msg`) are given as text.
+fn chat_message_from_msg(msg: ConvMsg) -> ChatMessage {
+	let tool_message = match msg.content_type {
+		ConvMsgContentType::ToolCall => serde_json::from_str(&msg.content)
+			.ok()
+			.map(ChatMessage::ToolCall),
+		ConvMsgContentType::ToolResult => serde_json::from_str(&msg.content)
+			.ok()
+			.map(ChatMessage::ToolResult),
+		_ => None,
+	};
+
+	tool_message.unwrap_or(match msg.role {
+		ConvMsgRole::User => ChatMessage::User(msg.content),
+		ConvMsgRole::Assistant => ChatMessage::Assistant(msg.content),
+		ConvMsgRole::System | ConvMsgRole::Tool => ChatMessage::System(msg.content),
+	})
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, seed_conv};
+	use crate::ai::{ToolCall, ToolResult};
+	use crate::model::agent::{AgentBmc, AgentContext, AgentForCreate};
+	use crate::model::conv_msg::ConvMsgForCreate;
+	use serial_test::serial;
+
+	#[test]
+	fn test_window_start_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_tool_call = ToolCall {
+			call_id: "call_01".to_string(),
+			name: "calculator".to_string(),
+			args: Default::default(),
+		};
+		let fx_tool_result = ToolResult::from_result(&fx_tool_call, 1.into());
+		let fx_msgs: Vec<ContextMsg> = [
+			(ChatMessage::User("01".to_string()), 10),
+			(ChatMessage::ToolCall(fx_tool_call), 10),
+			(ChatMessage::ToolResult(fx_tool_result), 10),
+			(ChatMessage::Assistant("04".to_string()), 10),
+			(ChatMessage::User("05".to_string()), 30),
+		]
+		.into_iter()
+		.enumerate()
+		.map(|(idx, (chat_msg, tokens))| ContextMsg {
+			msg_id: idx as i64,
+			chat_msg,
+			tokens,
+		})
+		.collect();
+
+		// -- Exec & Check
+		assert_eq!(window_start(&fx_msgs, 100), 0, "all fit");
+		assert_eq!(window_start(&fx_msgs, 55), 3, "tool result not first");
+		assert_eq!(window_start(&fx_msgs, 49), 3);
+		assert_eq!(window_start(&fx_msgs, 39), 4);
+		assert_eq!(window_start(&fx_msgs, 5), 4, "last msg always kept");
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_generate_reply_summarize_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_context = AgentContext {
+			max_tokens: Some(64),
+			strategy: ContextStrategy::Summarize,
+			summary_max_tokens: Some(16),
+		};
+		let agent_id = AgentBmc::create(
+			&ctx,
+			&mm,
+			AgentForCreate {
+				name: "test_generate_reply_summarize_ok agent 01".to_string(),
+				context: Some(fx_context),
+				..Default::default()
+			},
+		)
+		.await?;
+		let conv_id =
+			seed_conv(&ctx, &mm, agent_id, "test_generate_reply_summarize_ok conv")
+				.await?;
+		// 10 msgs of 40 chars (10 tokens)
+		let mut msg_ids = Vec::new();
+		for idx in 0..10 {
+			let msg_c = ConvMsgForCreate {
+				conv_id,
+				content: format!("{idx:02} {}", "x".repeat(37)),
+				..Default::default()
+			};
+			msg_ids.push(ConvBmc::add_msg(&ctx, &mm, msg_c).await?);
+		}
+
+		// -- Exec
+		let reply_ids_01 = ConvBmc::generate_reply(&ctx, &mm, conv_id).await?;
+		let summaries_01 = ConvBmc::list_summaries(&ctx, &mm, conv_id).await?;
+		ConvBmc::generate_reply(&ctx, &mm, conv_id).await?;
+		let summaries_02 = ConvBmc::list_summaries(&ctx, &mm, conv_id).await?;
+
+		// -- Check
+		// 48 tokens for the msgs (64 - 16 for the summary), so 4 msgs kept
+		assert_eq!(summaries_01.len(), 1);
+		assert_eq!(summaries_01[0].up_to_msg_id, msg_ids[5]);
+		assert!(summaries_01[0].tokens <= 16);
+		assert!(summaries_01[0]
+			.content
+			.ends_with("user: 05 xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"));
+		let reply = ConvBmc::get_msg(&ctx, &mm, reply_ids_01[0]).await?;
+		assert_eq!(reply.content, format!("09 {}", "x".repeat(37)));
+
+		// the 2nd reply drops the 7th msg, so a new rolling summary
+		assert_eq!(summaries_02.len(), 2);
+		assert_eq!(summaries_02[1].up_to_msg_id, msg_ids[6]);
+
+		let records = UsageBmc::list_for_conv(&ctx, &mm, conv_id).await?;
+		let reply_records: Vec<_> = records
+			.iter()
+			.filter(|record| record.msg_id.is_some())
+			.collect();
+		assert_eq!(records.len(), 4, "2 summaries and 2 replies");
+		for record in reply_records {
+			assert!(record.prompt_tokens <= 64, "prompt within the context");
+		}
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,559 @@
+//! The conv export and import, as a `ConvExport` document.
+//!
+//! Notes:
+//!   - The `Json` export is the full `ConvExport` (all the branches msgs tree),
+//!     while the `Markdown` transcript and the `Jsonl` (one `ConvExportMsg` per line,
+//!     e.g., for fine-tuning datasets) are the active branch msgs.
+//!   - The `Json` and `Jsonl` exports can be imported (not the `Markdown`),
+//!     as a new conv owned by the ctx user (see `ConvBmc::import_conv`).
+//!   - The attachments, reactions, revisions, and summaries are not exported.
+
+use crate::ctx::Ctx;
+use crate::model::acs::assert_conv_user;
+use crate::model::base;
+use crate::model::conv::{set_head_msg_id, ConvBmc, ConvForCreate, ConvKind};
+use crate::model::conv_msg::{
+	ConvMsg, ConvMsgBmc, ConvMsgContentType, ConvMsgForInsert, ConvMsgRole,
+};
+use crate::model::{Error, ModelManager, Result};
+use lib_utils::time::Rfc3339;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_with::serde_as;
+use sqlx::types::time::OffsetDateTime;
+use sqlx::FromRow;
+use std::collections::HashMap;
+
+/// The current `ConvExport.version` (the only one supported by the import).
+pub const CONV_EXPORT_VERSION: u32 = 1;
+
+/// The maximum size, in bytes, of an imported content (10 MiB).
+pub const CONV_IMPORT_MAX_SIZE: usize = 10 * 1024 * 1024;
+
+// region:    --- Types
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConvExportFormat {
+	#[default]
+	Json,
+	Markdown,
+	Jsonl,
+}
+
+impl ConvExportFormat {
+	pub fn content_type(&self) -> &'static str {
+		match self {
+			Self::Json => "application/json",
+			Self::Markdown => "text/markdown; charset=utf-8",
+			Self::Jsonl => "application/x-ndjson",
+		}
+	}
+
+	pub fn extension(&self) -> &'static str {
+		match self {
+			Self::Json => "json",
+			Self::Markdown => "md",
+			Self::Jsonl => "jsonl",
+		}
+	}
+}
+
+/// The exported conv, with its msgs, participants, and agent info.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ConvExport {
+	pub version: u32,
+	pub conv: ConvExportConv,
+	/// Informational only (the import takes the agent as a parameter).
+	#[serde(default)]
+	pub agent: Option<ConvExportAgent>,
+	/// Informational only (the imported conv has no participants).
+	#[serde(default)]
+	pub users: Vec<ConvExportUser>,
+	/// All the msgs (of all the branches), ordered by id.
+	pub msgs: Vec<ConvExportMsg>,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ConvExportConv {
+	pub id: i64,
+	pub title: Option<String>,
+	pub kind: ConvKind,
+	/// The leaf msg of the active branch.
+	pub head_msg_id: Option<i64>,
+	#[serde_as(as = "Option<Rfc3339>")]
+	#[serde(default)]
+	pub ctime: Option<OffsetDateTime>,
+}
+
+/// The agent version of the conv.
+#[derive(Debug, Deserialize, Serialize, FromRow)]
+pub struct ConvExportAgent {
+	pub id: i64,
+	pub name: String,
+	pub version: i32,
+	pub ai_provider: String,
+	pub ai_model: String,
+	pub system_prompt: Option<String>,
+}
+
+/// The owner and the `ConvUser` participants of the conv.
+#[derive(Debug, Deserialize, Serialize, FromRow)]
+pub struct ConvExportUser {
+	pub id: i64,
+	pub username: String,
+	pub owner: bool,
+}
+
+#[serde_as]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ConvExportMsg {
+	pub id: i64,
+	pub parent_msg_id: Option<i64>,
+	#[serde(default)]
+	pub role: ConvMsgRole,
+	#[serde(default)]
+	pub content_type: ConvMsgContentType,
+	pub content: String,
+	#[serde(default)]
+	pub metadata: Option<Value>,
+	/// The author username (informational only).
+	#[serde(default)]
+	pub author: Option<String>,
+	#[serde_as(as = "Option<Rfc3339>")]
+	#[serde(default)]
+	pub ctime: Option<OffsetDateTime>,
+}
+
+impl ConvExport {
+	/// Returns the msgs of the active branch (from the first msg to the conv head msg).
+	pub fn branch_msgs(&self) -> Vec<&ConvExportMsg> {
+		let msgs_by_id: HashMap<i64, &ConvExportMsg> =
+			self.msgs.iter().map(|msg| (msg.id, msg)).collect();
+
+		let mut branch_msgs = Vec::new();
+		let mut msg_id = self.conv.head_msg_id;
+		while let Some(msg) = msg_id.and_then(|id| msgs_by_id.get(&id)) {
+			branch_msgs.push(*msg);
+			msg_id = msg.parent_msg_id;
+		}
+		branch_msgs.reverse();
+
+		branch_msgs
+	}
+}
+
+/// The import of a conv content (the body of the import request).
+#[derive(Debug, Deserialize)]
+pub struct ConvForImport {
+	pub agent_id: i64,
+	/// default: `Json`
+	#[serde(default)]
+	pub format: ConvExportFormat,
+	/// default: the exported conv title (None for `Jsonl`)
+	pub title: Option<String>,
+}
+
+// endregion: --- Types
+
+// region:    --- ConvBmc Export & Import
+
+impl ConvBmc {
+	/// Returns the `ConvExport` of the conv.
+	pub async fn get_export(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+	) -> Result<ConvExport> {
+		assert_conv_user(ctx, mm, conv_id).await?;
+		let conv = Self::get(ctx, mm, conv_id).await?;
+
+		// -- Agent
+		let sqlx_query = sqlx::query_as::<_, ConvExportAgent>(
+			"SELECT a.id, a.name, v.version, v.ai_provider, v.ai_model, v.system_prompt
+			FROM agent_version v JOIN agent a ON a.id = v.agent_id
+			WHERE v.id = $1",
+		)
+		.bind(conv.agent_version_id);
+		let agent = mm.dbx().fetch_optional(sqlx_query).await?;
+
+		// -- Users
+		let sqlx_query = sqlx::query_as::<_, ConvExportUser>(
+			r#"SELECT u.id, u.username, u.id = c.owner_id AS owner
+			FROM conv c JOIN "user" u ON u.id = c.owner_id
+				OR u.id IN (SELECT user_id FROM conv_user WHERE conv_id = c.id)
+			WHERE c.id = $1
+			ORDER BY u.id"#,
+		)
+		.bind(conv_id);
+		let users = mm.dbx().fetch_all(sqlx_query).await?;
+
+		// -- Msgs (with their author username)
+		let sqlx_query = sqlx::query_as::<_, ConvMsg>(
+			"SELECT * FROM conv_msg WHERE conv_id = $1 ORDER BY id",
+		)
+		.bind(conv_id);
+		let msgs = mm.dbx().fetch_all(sqlx_query).await?;
+
+		let author_ids: Vec<i64> = msgs.iter().map(|msg| msg.user_id).collect();
+		let sqlx_query = sqlx::query_as::<_, (i64, String)>(
+			r#"SELECT id, username FROM "user" WHERE id = ANY($1)"#,
+		)
+		.bind(author_ids);
+		let usernames: HashMap<i64, String> =
+			mm.dbx().fetch_all(sqlx_query).await?.into_iter().collect();
+
+		let msgs = msgs
+			.into_iter()
+			.map(|msg| ConvExportMsg {
+				id: msg.id,
+				parent_msg_id: msg.parent_msg_id,
+				role: msg.role,
+				content_type: msg.content_type,
+				content: msg.content,
+				metadata: msg.metadata,
+				author: usernames.get(&msg.user_id).cloned(),
+				ctime: Some(msg.ctime),
+			})
+			.collect();
+
+		Ok(ConvExport {
+			version: CONV_EXPORT_VERSION,
+			conv: ConvExportConv {
+				id: conv.id,
+				title: conv.title,
+				kind: conv.kind,
+				head_msg_id: conv.head_msg_id,
+				ctime: Some(conv.ctime),
+			},
+			agent,
+			users,
+			msgs,
+		})
+	}
+
+	/// Returns the export of the conv in the `format`.
+	pub async fn export_conv(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+		format: ConvExportFormat,
+	) -> Result<String> {
+		let export = Self::get_export(ctx, mm, conv_id).await?;
+
+		// Note: The export types are plain json types (cannot fail).
+		let content = match format {
+			ConvExportFormat::Json => {
+				serde_json::to_string_pretty(&export).unwrap_or_default()
+			}
+			ConvExportFormat::Jsonl => export
+				.branch_msgs()
+				.into_iter()
+				.map(|msg| serde_json::to_string(msg).unwrap_or_default() + "\n")
+				.collect(),
+			ConvExportFormat::Markdown => to_markdown(&export),
+		};
+
+		Ok(content)
+	}
+
+	/// Create a new conv (owned by the ctx user) from a `Json` or `Jsonl` export content.
+	///
+	/// Returns the id of the new conv.
+	///
+	/// Notes:
+	///   - The msgs tree is kept (the `parent_msg_id` must be a prior msg of the content,
+	///     or None for a first msg), as well as the active branch.
+	///   - The msgs are added with the ctx user as `user_id` (and their role).
+	pub async fn import_conv(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_i: ConvForImport,
+		content: &str,
+	) -> Result<i64> {
+		if content.len() > CONV_IMPORT_MAX_SIZE {
+			return Err(Error::ConvImportInvalid {
+				cause: format!("content over {CONV_IMPORT_MAX_SIZE} bytes"),
+			});
+		}
+
+		// -- Parse the content
+		let (title, kind, msgs, head_msg_id) = match conv_i.format {
+			ConvExportFormat::Json => {
+				let export: ConvExport =
+					serde_json::from_str(content).map_err(import_invalid)?;
+				if export.version != CONV_EXPORT_VERSION {
+					return Err(Error::ConvImportInvalid {
+						cause: format!("version {} not supported", export.version),
+					});
+				}
+				let conv = export.conv;
+				(conv.title, Some(conv.kind), export.msgs, conv.head_msg_id)
+			}
+			ConvExportFormat::Jsonl => {
+				let msgs = content
+					.lines()
+					.filter(|line| !line.trim().is_empty())
+					.map(serde_json::from_str::<ConvExportMsg>)
+					.collect::<core::result::Result<Vec<_>, _>>()
+					.map_err(import_invalid)?;
+				let head_msg_id = msgs.last().map(|msg| msg.id);
+				(None, None, msgs, head_msg_id)
+			}
+			ConvExportFormat::Markdown => {
+				return Err(Error::ConvImportFormatNotSupported {
+					format: conv_i.format,
+				});
+			}
+		};
+
+		mm.in_txn(|mm| async move {
+			let conv_c = ConvForCreate {
+				agent_id: conv_i.agent_id,
+				title: conv_i.title.or(title),
+				kind,
+			};
+			let conv_id = Self::start(ctx, &mm, conv_c).await?;
+
+			// -- Add the msgs (with their parent as the conv head)
+			let mut msg_ids: HashMap<i64, i64> = HashMap::new();
+			let mut head: Option<i64> = None;
+			for msg in msgs {
+				let parent_id = match msg.parent_msg_id {
+					Some(parent_msg_id) => {
+						Some(*msg_ids.get(&parent_msg_id).ok_or_else(|| {
+							Error::ConvImportInvalid {
+								cause: format!(
+									"msg {} parent {parent_msg_id} not found",
+									msg.id
+								),
+							}
+						})?)
+					}
+					None => None,
+				};
+				if parent_id != head {
+					set_head_msg_id(&mm, conv_id, parent_id).await?;
+				}
+
+				let msg_i = ConvMsgForInsert {
+					conv_id,
+					user_id: ctx.user_id(),
+					content: msg.content,
+					content_type: Some(msg.content_type),
+					role: Some(msg.role),
+					metadata: msg.metadata,
+				};
+				msg_i.validate()?;
+				let new_msg_id =
+					base::create::<ConvMsgBmc, _>(ctx, &mm, msg_i).await?;
+
+				if msg_ids.insert(msg.id, new_msg_id).is_some() {
+					return Err(Error::ConvImportInvalid {
+						cause: format!("msg {} duplicated", msg.id),
+					});
+				}
+				head = Some(new_msg_id);
+			}
+
+			// -- Restore the active branch
+			let head_msg_id = head_msg_id
+				.and_then(|head_msg_id| msg_ids.get(&head_msg_id).copied())
+				.or(head);
+			if head_msg_id != head {
+				set_head_msg_id(&mm, conv_id, head_msg_id).await?;
+			}
+
+			Ok(conv_id)
+		})
+		.await
+	}
+}
+
+// endregion: --- ConvBmc Export & Import
+
+// region:    --- Support
+
+fn import_invalid(ex: serde_json::Error) -> Error {
+	Error::ConvImportInvalid {
+		cause: ex.to_string(),
+	}
+}
+
+/// Returns the markdown transcript of the active branch.
+fn to_markdown(export: &ConvExport) -> String {
+	let conv = &export.conv;
+	let title = conv.title.as_deref().unwrap_or("Untitled");
+	let mut md = format!("# {title}\n\n");
+
+	if let Some(agent) = export.agent.as_ref() {
+		md.push_str(&format!(
+			"- Agent: {} (v{}, {}/{})\n",
+			agent.name, agent.version, agent.ai_provider, agent.ai_model
+		));
+	}
+	let usernames: Vec<&str> = export
+		.users
+		.iter()
+		.map(|user| user.username.as_str())
+		.collect();
+	if !usernames.is_empty() {
+		md.push_str(&format!("- Participants: {}\n", usernames.join(", ")));
+	}
+	if let Some(ctime) = conv.ctime.and_then(|ctime| ctime.format(&Rfc3339).ok()) {
+		md.push_str(&format!("- Created: {ctime}\n"));
+	}
+
+	for msg in export.branch_msgs() {
+		md.push_str(&format!("\n---\n\n**{}**", msg.role));
+		if let Some(author) = msg.author.as_deref() {
+			md.push_str(&format!(" ({author})"));
+		}
+		md.push_str("\n\n");
+
+		if msg.content_type.is_json() {
+			md.push_str(&format!("```json\n{}\n```\n", msg.content));
+		} else {
+			md.push_str(&format!("{}\n", msg.content));
+		}
+	}
+
+	md
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, seed_agent, seed_conv};
+	use crate::model;
+	use crate::model::agent::AgentBmc;
+	use crate::model::conv_msg::ConvMsgForCreate;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_export_import_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&ctx, &mm, "test_export_import_ok agent 01").await?;
+		let conv_id =
+			seed_conv(&ctx, &mm, agent_id, "test_export_import_ok conv").await?;
+		for content in ["msg 01", "msg 02"] {
+			let msg_c = ConvMsgForCreate {
+				conv_id,
+				content: content.to_string(),
+				..Default::default()
+			};
+			ConvBmc::add_msg(&ctx, &mm, msg_c).await?;
+			ConvBmc::generate_reply(&ctx, &mm, conv_id).await?;
+		}
+		// a second branch (regenerated last reply)
+		let branch = ConvBmc::get_branch(&ctx, &mm, conv_id).await?;
+		ConvBmc::regenerate_reply(&ctx, &mm, branch.msgs[3].id).await?;
+
+		// -- Exec
+		let json =
+			ConvBmc::export_conv(&ctx, &mm, conv_id, ConvExportFormat::Json).await?;
+		let jsonl =
+			ConvBmc::export_conv(&ctx, &mm, conv_id, ConvExportFormat::Jsonl)
+				.await?;
+		let md =
+			ConvBmc::export_conv(&ctx, &mm, conv_id, ConvExportFormat::Markdown)
+				.await?;
+		let import_json_id = ConvBmc::import_conv(
+			&ctx,
+			&mm,
+			ConvForImport {
+				agent_id,
+				format: ConvExportFormat::Json,
+				title: None,
+			},
+			&json,
+		)
+		.await?;
+		let import_jsonl_id = ConvBmc::import_conv(
+			&ctx,
+			&mm,
+			ConvForImport {
+				agent_id,
+				format: ConvExportFormat::Jsonl,
+				title: Some("imported jsonl".to_string()),
+			},
+			&jsonl,
+		)
+		.await?;
+
+		// -- Check
+		let export: ConvExport = serde_json::from_str(&json)?;
+		assert_eq!(export.msgs.len(), 5, "all the branches msgs");
+		assert_eq!(export.users.len(), 1);
+		assert_eq!(export.agent.as_ref().map(|agent| agent.id), Some(agent_id));
+		assert_eq!(jsonl.lines().count(), 4, "the active branch msgs");
+		assert!(md.starts_with("# test_export_import_ok conv\n"));
+		assert_eq!(md.matches("**Assistant**").count(), 2);
+
+		let import_json = ConvBmc::get_export(&ctx, &mm, import_json_id).await?;
+		assert_eq!(import_json.conv.title, export.conv.title);
+		assert_eq!(import_json.msgs.len(), 5);
+		let contents = |export: &ConvExport| -> Vec<String> {
+			export
+				.branch_msgs()
+				.into_iter()
+				.map(|msg| msg.content.clone())
+				.collect()
+		};
+		assert_eq!(contents(&import_json), contents(&export));
+		let import_jsonl = ConvBmc::get_export(&ctx, &mm, import_jsonl_id).await?;
+		assert_eq!(import_jsonl.conv.title.as_deref(), Some("imported jsonl"));
+		assert_eq!(import_jsonl.msgs.len(), 4);
+		assert_eq!(contents(&import_jsonl), contents(&export));
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_import_err_invalid() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&ctx, &mm, "test_import_err_invalid agent 01").await?;
+		let fx_jsonl = r#"{"id": 1, "parent_msg_id": null, "content": "msg 01"}
+{"id": 2, "parent_msg_id": 3, "content": "msg 02"}"#;
+		let fx_conv_i = ConvForImport {
+			agent_id,
+			format: ConvExportFormat::Jsonl,
+			title: None,
+		};
+
+		// -- Exec
+		let res = ConvBmc::import_conv(&ctx, &mm, fx_conv_i, fx_jsonl).await;
+
+		// -- Check
+		assert!(
+			matches!(res, Err(model::Error::ConvImportInvalid { .. })),
+			"should be ConvImportInvalid, but was {res:?}"
+		);
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
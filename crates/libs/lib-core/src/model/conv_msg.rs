@@ -1,16 +1,73 @@
+use crate::model::attachment::Attachment;
 use crate::model::base::DbBmc;
-use crate::model::conv::ConvScoped;
+use crate::model::conv::{Conv, ConvScoped};
 use crate::model::modql_utils::time_to_sea_value;
+use crate::model::{Error, Result};
 use lib_utils::time::Rfc3339;
-use modql::field::Fields;
+use modql::field::{Fields, SeaFieldValue};
 use modql::filter::{FilterNodes, OpValsInt64, OpValsString, OpValsValue};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use serde_with::serde_as;
 use sqlx::FromRow;
 use time::OffsetDateTime;
 
 // region:    --- Types
 
+/// The format of the `ConvMsg` content.
+/// Note: The `Json`, `ToolCall`, and `ToolResult` contents must be valid json.
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	Default,
+	PartialEq,
+	sqlx::Type,
+	SeaFieldValue,
+	derive_more::Display,
+	Deserialize,
+	Serialize,
+)]
+#[sqlx(type_name = "conv_msg_content_type")]
+pub enum ConvMsgContentType {
+	#[default]
+	Text,
+	Markdown,
+	Json,
+	ToolCall,
+	ToolResult,
+}
+
+impl ConvMsgContentType {
+	pub fn is_json(&self) -> bool {
+		matches!(self, Self::Json | Self::ToolCall | Self::ToolResult)
+	}
+}
+
+/// The author role of the `ConvMsg`
+/// (distinct from the `user_id`, e.g., an agent answer posted by a service user).
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	Default,
+	PartialEq,
+	sqlx::Type,
+	SeaFieldValue,
+	derive_more::Display,
+	Deserialize,
+	Serialize,
+)]
+#[sqlx(type_name = "conv_msg_role")]
+pub enum ConvMsgRole {
+	#[default]
+	User,
+	Assistant,
+	System,
+	/// The result of a tool called by the agent (see `ConvBmc::generate_reply`).
+	Tool,
+}
+
 #[serde_as]
 #[derive(Debug, Clone, Fields, FromRow, Serialize)]
 pub struct ConvMsg {
@@ -19,9 +76,27 @@ pub struct ConvMsg {
 	// -- FK
 	pub conv_id: i64,
 	pub user_id: i64,
+	/// The previous msg in the branch (None for the first msg, see `ConvBmc::get_branch`).
+	pub parent_msg_id: Option<i64>,
 
 	// -- Properties
+	pub role: ConvMsgRole,
 	pub content: String,
+	pub content_type: ConvMsgContentType,
+	/// Free form json object (e.g., model name, tool call id).
+	pub metadata: Option<Value>,
+
+	// -- Edit marker
+	/// The number of edits (the prior versions are the `ConvMsgRevision`).
+	pub edit_count: i32,
+	/// The last edit time (None if never edited).
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub etime: Option<OffsetDateTime>,
+
+	/// The attachments metadata (loaded by the `ConvBmc` msg getters).
+	#[field(skip)]
+	#[sqlx(skip)]
+	pub attachments: Vec<Attachment>,
 
 	// -- Timestamps
 	// creator user_id and time
@@ -40,10 +115,40 @@ impl ConvScoped for ConvMsg {
 	}
 }
 
-#[derive(Fields, Deserialize)]
+/// The msg of a conv user (always with the `User` role).
+///
+/// Note: The other roles and the `ToolCall` / `ToolResult` contents are only added by
+///       the model layer (e.g., the agent replies, the imports), see `ConvMsgForInsert`.
+#[derive(Fields, Deserialize, Default)]
 pub struct ConvMsgForCreate {
 	pub conv_id: i64,
 	pub content: String,
+
+	/// default: `Text` (`ToolCall` and `ToolResult` not allowed)
+	#[field(cast_as = "conv_msg_content_type")]
+	pub content_type: Option<ConvMsgContentType>,
+	pub metadata: Option<Value>,
+}
+
+impl ConvMsgForCreate {
+	/// Validate the content type (`Text`, `Markdown`, or `Json`), the content against it,
+	/// and the metadata.
+	pub fn validate(&self) -> Result<()> {
+		let content_type = self.content_type.unwrap_or_default();
+		if !matches!(
+			content_type,
+			ConvMsgContentType::Text
+				| ConvMsgContentType::Markdown
+				| ConvMsgContentType::Json
+		) {
+			return Err(Error::ConvMsgContentTypeNotAllowed {
+				content_type: content_type.to_string(),
+			});
+		}
+
+		validate_content(&self.content, content_type)?;
+		validate_metadata(self.metadata.as_ref())
+	}
 }
 
 impl ConvScoped for ConvMsgForCreate {
@@ -63,11 +168,18 @@ impl ConvScoped for ConvMsgForCreate {
 ///     which can be set directly through the base:: functions or some utilities. There's not
 ///     significant value in introducing `...ForInsert` types for all entities just for these
 ///     common, low-level database properties.
+///   - Also the msgs with any role and content type (e.g., the agent replies, the imported
+///     and template msgs), which are not allowed in the `ConvMsgForCreate`.
 #[derive(Fields, Deserialize)]
 pub(in crate::model) struct ConvMsgForInsert {
 	pub conv_id: i64,
 	pub user_id: i64,
 	pub content: String,
+	#[field(cast_as = "conv_msg_content_type")]
+	pub content_type: Option<ConvMsgContentType>,
+	#[field(cast_as = "conv_msg_role")]
+	pub role: Option<ConvMsgRole>,
+	pub metadata: Option<Value>,
 }
 
 impl ConvMsgForInsert {
@@ -76,14 +188,24 @@ impl ConvMsgForInsert {
 			conv_id: msg_c.conv_id,
 			user_id,
 			content: msg_c.content,
+			content_type: msg_c.content_type,
+			role: Some(ConvMsgRole::User),
+			metadata: msg_c.metadata,
 		}
 	}
+
+	/// Validate the content against its content type (any), and the metadata.
+	pub fn validate(&self) -> Result<()> {
+		validate_content(&self.content, self.content_type.unwrap_or_default())?;
+		validate_metadata(self.metadata.as_ref())
+	}
 }
 
-#[derive(Fields, Deserialize, Default)]
+/// The edit of a `ConvMsg` content (the content type is kept).
+#[derive(Deserialize, Default)]
 pub struct ConvMsgForUpdate {
 	pub conv_id: i64,
-	pub content: Option<String>,
+	pub content: String,
 }
 
 impl ConvScoped for ConvMsgForUpdate {
@@ -92,23 +214,88 @@ impl ConvScoped for ConvMsgForUpdate {
 	}
 }
 
+/// ConvMsg for the edit update, which is derived from the public `ConvMsgForUpdate`.
+#[derive(Fields)]
+pub(in crate::model) struct ConvMsgForEdit {
+	pub content: String,
+	pub edit_count: i32,
+	pub etime: OffsetDateTime,
+}
+
 #[derive(FilterNodes, Deserialize, Default, Debug)]
 pub struct ConvMsgFilter {
-	id: Option<OpValsInt64>,
+	pub id: Option<OpValsInt64>,
 
-	conv_id: Option<OpValsInt64>,
-	content: Option<OpValsString>,
+	pub conv_id: Option<OpValsInt64>,
+	pub parent_msg_id: Option<OpValsInt64>,
+	#[modql(cast_as = "conv_msg_role")]
+	pub role: Option<OpValsString>,
+	pub content: Option<OpValsString>,
+	#[modql(cast_as = "conv_msg_content_type")]
+	pub content_type: Option<OpValsString>,
 
-	cid: Option<OpValsInt64>,
+	pub cid: Option<OpValsInt64>,
 	#[modql(to_sea_value_fn = "time_to_sea_value")]
-	ctime: Option<OpValsValue>,
-	mid: Option<OpValsInt64>,
+	pub ctime: Option<OpValsValue>,
+	pub mid: Option<OpValsInt64>,
 	#[modql(to_sea_value_fn = "time_to_sea_value")]
-	mtime: Option<OpValsValue>,
+	pub mtime: Option<OpValsValue>,
+}
+
+/// One full-text search hit of `ConvBmc::search_msgs`, with its parent `Conv`.
+///
+/// Notes:
+///   - A hit is either a `ConvMsg` (content match, boosted if the conv title matches too),
+///     or a `Conv` (title match, with `msg: None`).
+///   - The snippets are html escaped, with their matched terms wrapped with `<mark>...</mark>`.
+#[derive(Debug, Serialize)]
+pub struct ConvMsgHit {
+	pub conv: Conv,
+	pub msg: Option<ConvMsg>,
+	pub rank: f32,
+	/// The highlighted fragments of the `msg` content (None for title hits).
+	pub snippet: Option<String>,
+	/// The highlighted conv title (if it matches).
+	pub title_snippet: Option<String>,
 }
 
 // endregion: --- Types
 
+// region:    --- Support
+
+/// Validate the `content` against its `content_type`.
+pub(in crate::model) fn validate_content(
+	content: &str,
+	content_type: ConvMsgContentType,
+) -> Result<()> {
+	if content.trim().is_empty() {
+		return Err(Error::ConvMsgContentEmpty);
+	}
+
+	if content_type.is_json() {
+		serde_json::from_str::<Value>(content).map_err(|ex| {
+			Error::ConvMsgContentNotJson {
+				content_type: content_type.to_string(),
+				cause: ex.to_string(),
+			}
+		})?;
+	}
+
+	Ok(())
+}
+
+/// Validate that the eventual `metadata` is a json object.
+fn validate_metadata(metadata: Option<&Value>) -> Result<()> {
+	match metadata {
+		Some(metadata) if !metadata.is_object() => {
+			Err(Error::ConvMsgMetadataNotObject)
+		}
+		_ => Ok(()),
+	}
+}
+
+// endregion: --- Support
+
 // region:    --- ConvMsgBmc
 
 pub struct ConvMsgBmc;
@@ -0,0 +1,89 @@
+use crate::model::base::DbBmc;
+use crate::model::{Error, Result};
+use lib_utils::time::Rfc3339;
+use modql::field::Fields;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+const EMOJI_MAX_CHARS: usize = 16;
+
+// region:    --- Types
+
+/// The emoji reaction of a user on a `ConvMsg` (one per user and emoji).
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct ConvMsgReaction {
+	pub id: i64,
+
+	// -- FK
+	pub msg_id: i64,
+	pub user_id: i64,
+
+	// -- Properties
+	pub emoji: String,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConvMsgReactionForCreate {
+	pub msg_id: i64,
+	/// e.g., "👍", "🎉"
+	pub emoji: String,
+}
+
+impl ConvMsgReactionForCreate {
+	/// Validate that the emoji is one short non-ascii symbol sequence
+	/// (e.g., "👍", "👍🏽", "1️⃣", but not "ok" or "+1").
+	pub fn validate(&self) -> Result<()> {
+		let emoji = &self.emoji;
+		let valid = !emoji.is_empty()
+			&& emoji.chars().count() <= EMOJI_MAX_CHARS
+			&& !emoji.is_ascii()
+			&& !emoji.chars().any(|c| c.is_whitespace() || c.is_control());
+
+		if valid {
+			Ok(())
+		} else {
+			Err(Error::ConvMsgReactionEmojiInvalid {
+				emoji: emoji.clone(),
+			})
+		}
+	}
+}
+
+#[derive(Fields)]
+pub(in crate::model) struct ConvMsgReactionForInsert {
+	pub msg_id: i64,
+	pub user_id: i64,
+	pub emoji: String,
+}
+
+// endregion: --- Types
+
+// region:    --- ConvMsgReactionBmc
+
+pub struct ConvMsgReactionBmc;
+
+impl DbBmc for ConvMsgReactionBmc {
+	const TABLE: &'static str = "conv_msg_reaction";
+
+	fn unique_key() -> Option<&'static [&'static str]> {
+		Some(&["msg_id", "user_id", "emoji"])
+	}
+}
+
+// Note: Like `ConvMsg`, the reactions are managed by the `ConvBmc` container
+//       (e.g., `ConvBmc::add_msg_reaction`, `ConvBmc::remove_msg_reaction`).
+
+// endregion: --- ConvMsgReactionBmc
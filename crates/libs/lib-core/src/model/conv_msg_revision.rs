@@ -0,0 +1,68 @@
+use crate::model::base::DbBmc;
+use crate::model::conv_msg::ConvMsgContentType;
+use lib_utils::time::Rfc3339;
+use modql::field::Fields;
+use serde::Serialize;
+use serde_with::serde_as;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+// region:    --- Types
+
+/// A prior version of an edited `ConvMsg`.
+///
+/// Note: The `cid`/`ctime` are the editor and the time of the edit
+///       which replaced this version.
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct ConvMsgRevision {
+	pub id: i64,
+
+	// -- FK
+	pub msg_id: i64,
+
+	// -- Properties
+	/// 1 for the original content, 2 for the content of the first edit, ...
+	pub revision: i32,
+	pub content: String,
+	pub content_type: ConvMsgContentType,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+#[derive(Fields)]
+pub(in crate::model) struct ConvMsgRevisionForInsert {
+	pub msg_id: i64,
+	pub revision: i32,
+	pub content: String,
+	#[field(cast_as = "conv_msg_content_type")]
+	pub content_type: ConvMsgContentType,
+}
+
+// endregion: --- Types
+
+// region:    --- ConvMsgRevisionBmc
+
+pub struct ConvMsgRevisionBmc;
+
+impl DbBmc for ConvMsgRevisionBmc {
+	const TABLE: &'static str = "conv_msg_revision";
+
+	// Note: The revisions are the history themselves.
+	fn has_audit_log() -> bool {
+		false
+	}
+}
+
+// Note: Like `ConvMsg`, the revisions are managed by the `ConvBmc` container
+//       (e.g., `ConvBmc::update_msg`, `ConvBmc::list_msg_revisions`).
+
+// endregion: --- ConvMsgRevisionBmc
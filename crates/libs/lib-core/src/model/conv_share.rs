@@ -0,0 +1,333 @@
+use crate::ctx::Ctx;
+use crate::model::acs::assert_conv_owner;
+use crate::model::base::{self, DbBmc};
+use crate::model::conv::ConvBmc;
+use crate::model::conv_msg::{ConvMsgContentType, ConvMsgRole};
+use crate::model::{Error, ModelManager, Result};
+use lib_utils::time::{now_utc, Rfc3339};
+use modql::field::Fields;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_with::serde_as;
+use sqlx::types::Json;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+// region:    --- Types
+
+/// A public read-only link (by `token`) to a `ConvShareSnapshot` of the conv.
+///
+/// Note: The `snapshot` is not loaded (see `ConvBmc::get_shared_snapshot`).
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct ConvShare {
+	pub id: i64,
+
+	// -- FK
+	pub conv_id: i64,
+
+	// -- Properties
+	/// The link secret (only visible to the conv owner).
+	pub token: String,
+	/// Never expires if None.
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub expires_at: Option<OffsetDateTime>,
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub revoked_at: Option<OffsetDateTime>,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+#[serde_as]
+#[derive(Deserialize, Default)]
+pub struct ConvShareForCreate {
+	pub conv_id: i64,
+	/// Must be in the future (default: never expires).
+	#[serde_as(as = "Option<Rfc3339>")]
+	#[serde(default)]
+	pub expires_at: Option<OffsetDateTime>,
+}
+
+#[derive(Fields)]
+pub(in crate::model) struct ConvShareForInsert {
+	pub conv_id: i64,
+	pub token: String,
+	pub snapshot: Value,
+	pub expires_at: Option<OffsetDateTime>,
+}
+
+#[derive(Fields)]
+pub(in crate::model) struct ConvShareForRevoke {
+	pub revoked_at: OffsetDateTime,
+}
+
+/// The public view of the conv active branch at the share creation
+/// (without the users, metadata, and attachments).
+#[serde_as]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConvShareSnapshot {
+	pub title: Option<String>,
+	pub msgs: Vec<ConvShareMsg>,
+	#[serde_as(as = "Rfc3339")]
+	pub shared_at: OffsetDateTime,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ConvShareMsg {
+	pub role: ConvMsgRole,
+	pub content_type: ConvMsgContentType,
+	pub content: String,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+}
+
+// endregion: --- Types
+
+// region:    --- ConvShareBmc
+
+pub struct ConvShareBmc;
+
+impl DbBmc for ConvShareBmc {
+	const TABLE: &'static str = "conv_share";
+}
+
+// Note: Like `ConvMsg`, the shares are managed by the `ConvBmc` container.
+
+// endregion: --- ConvShareBmc
+
+// region:    --- ConvBmc Shares
+
+/// Notes:
+///   - Only the conv owner (or root) can create, list, and revoke the conv shares.
+///   - A revoked or expired share is reported as `Error::ConvShareNotFound`
+///     (as an unknown token) by `get_shared_snapshot`.
+impl ConvBmc {
+	/// Create a share of the current conv active branch.
+	pub async fn create_share(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		share_c: ConvShareForCreate,
+	) -> Result<i64> {
+		let ConvShareForCreate {
+			conv_id,
+			expires_at,
+		} = share_c;
+		assert_conv_owner(ctx, mm, conv_id).await?;
+
+		let now = now_utc();
+		if expires_at.is_some_and(|expires_at| expires_at <= now) {
+			return Err(Error::ConvShareExpiresAtNotFuture);
+		}
+
+		let branch = Self::get_branch(ctx, mm, conv_id).await?;
+		let snapshot = ConvShareSnapshot {
+			title: branch.conv.title,
+			msgs: branch
+				.msgs
+				.into_iter()
+				.map(|msg| ConvShareMsg {
+					role: msg.role,
+					content_type: msg.content_type,
+					content: msg.content,
+					ctime: msg.ctime,
+				})
+				.collect(),
+			shared_at: now,
+		};
+
+		let share_i = ConvShareForInsert {
+			conv_id,
+			token: Uuid::new_v4().simple().to_string(),
+			// Note: A plain json type (cannot fail).
+			snapshot: serde_json::to_value(snapshot).unwrap_or_default(),
+			expires_at,
+		};
+
+		base::create::<ConvShareBmc, _>(ctx, mm, share_i).await
+	}
+
+	pub async fn get_share(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		share_id: i64,
+	) -> Result<ConvShare> {
+		let share: ConvShare =
+			base::get::<ConvShareBmc, _>(ctx, mm, share_id).await?;
+		assert_conv_owner(ctx, mm, share.conv_id).await?;
+
+		Ok(share)
+	}
+
+	/// Returns the shares of the conv (including the revoked and expired ones).
+	pub async fn list_shares(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+	) -> Result<Vec<ConvShare>> {
+		assert_conv_owner(ctx, mm, conv_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, ConvShare>(
+			"SELECT * FROM conv_share WHERE conv_id = $1 ORDER BY id",
+		)
+		.bind(conv_id);
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+
+	/// Revoke the share (no-op if already revoked).
+	pub async fn revoke_share(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		share_id: i64,
+	) -> Result<()> {
+		let share = Self::get_share(ctx, mm, share_id).await?;
+		if share.revoked_at.is_some() {
+			return Ok(());
+		}
+
+		let share_r = ConvShareForRevoke {
+			revoked_at: now_utc(),
+		};
+		base::update::<ConvShareBmc, _>(ctx, mm, share_id, share_r).await
+	}
+
+	/// Returns the snapshot of the share of `token`, if not revoked nor expired.
+	///
+	/// Note: No ctx user access check, as the token is the access
+	///       (e.g., called with the root ctx by the public route).
+	pub async fn get_shared_snapshot(
+		_ctx: &Ctx,
+		mm: &ModelManager,
+		token: &str,
+	) -> Result<ConvShareSnapshot> {
+		let sqlx_query = sqlx::query_as::<_, (Json<ConvShareSnapshot>,)>(
+			"SELECT snapshot FROM conv_share
+			WHERE token = $1 AND revoked_at IS NULL
+				AND (expires_at IS NULL OR expires_at > now())",
+		)
+		.bind(token);
+		let (Json(snapshot),) = mm
+			.dbx()
+			.fetch_optional(sqlx_query)
+			.await?
+			.ok_or(Error::ConvShareNotFound)?;
+
+		Ok(snapshot)
+	}
+}
+
+// endregion: --- ConvBmc Shares
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, clean_users, seed_agent, seed_conv, seed_user};
+	use crate::model;
+	use crate::model::agent::AgentBmc;
+	use crate::model::conv_msg::ConvMsgForCreate;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_share_snapshot_revoke_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&ctx, &mm, "test_share_snapshot_revoke_ok agent 01").await?;
+		let owner_id =
+			seed_user(&ctx, &mm, "test_share_snapshot_revoke_ok-user-owner").await?;
+		let other_id =
+			seed_user(&ctx, &mm, "test_share_snapshot_revoke_ok-user-other").await?;
+		let owner_ctx = Ctx::new(owner_id)?;
+		let other_ctx = Ctx::new(other_id)?;
+		let conv_id = seed_conv(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			"test_share_snapshot_revoke_ok conv",
+		)
+		.await?;
+		let add_msg = |content: &str| ConvMsgForCreate {
+			conv_id,
+			content: content.to_string(),
+			..Default::default()
+		};
+		ConvBmc::add_msg(&owner_ctx, &mm, add_msg("msg 01")).await?;
+
+		// -- Exec
+		let share_id = ConvBmc::create_share(
+			&owner_ctx,
+			&mm,
+			ConvShareForCreate {
+				conv_id,
+				..Default::default()
+			},
+		)
+		.await?;
+		ConvBmc::add_msg(&owner_ctx, &mm, add_msg("msg 02 (after share)")).await?;
+		let share = ConvBmc::get_share(&owner_ctx, &mm, share_id).await?;
+		let snapshot = ConvBmc::get_shared_snapshot(&ctx, &mm, &share.token).await?;
+		let res_other = ConvBmc::list_shares(&other_ctx, &mm, conv_id).await;
+		ConvBmc::revoke_share(&owner_ctx, &mm, share_id).await?;
+		let res_revoked =
+			ConvBmc::get_shared_snapshot(&ctx, &mm, &share.token).await;
+		let res_expired = ConvBmc::create_share(
+			&owner_ctx,
+			&mm,
+			ConvShareForCreate {
+				conv_id,
+				expires_at: Some(now_utc() - time::Duration::minutes(1)),
+			},
+		)
+		.await;
+
+		// -- Check
+		assert_eq!(share.token.len(), 32);
+		assert_eq!(
+			snapshot.title.as_deref(),
+			Some("test_share_snapshot_revoke_ok conv")
+		);
+		let contents: Vec<&str> = snapshot
+			.msgs
+			.iter()
+			.map(|msg| msg.content.as_str())
+			.collect();
+		assert_eq!(contents, ["msg 01"], "snapshot at the share creation");
+		assert!(
+			matches!(res_other, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res_other:?}"
+		);
+		assert!(
+			matches!(res_revoked, Err(model::Error::ConvShareNotFound)),
+			"should be ConvShareNotFound, but was {res_revoked:?}"
+		);
+		assert!(
+			matches!(res_expired, Err(model::Error::ConvShareExpiresAtNotFuture)),
+			"should be ConvShareExpiresAtNotFuture, but was {res_expired:?}"
+		);
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+		clean_users(&ctx, &mm, "test_share_snapshot_revoke_ok").await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,64 @@
+use crate::model::base::DbBmc;
+use lib_utils::time::Rfc3339;
+use modql::field::Fields;
+use serde::Serialize;
+use serde_with::serde_as;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+// region:    --- Types
+
+/// A rolling summary of the conv msgs up to `up_to_msg_id` (inclusive),
+/// which replaces them in the agent context (see `model::conv_context`).
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct ConvSummary {
+	pub id: i64,
+
+	// -- FK
+	pub conv_id: i64,
+
+	// -- Properties
+	pub up_to_msg_id: i64,
+	pub content: String,
+	/// The tokens of the `content` (see `ai::count_tokens`).
+	pub tokens: i64,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+#[derive(Fields)]
+pub(in crate::model) struct ConvSummaryForInsert {
+	pub conv_id: i64,
+	pub up_to_msg_id: i64,
+	pub content: String,
+	pub tokens: i64,
+}
+
+// endregion: --- Types
+
+// region:    --- ConvSummaryBmc
+
+pub struct ConvSummaryBmc;
+
+impl DbBmc for ConvSummaryBmc {
+	const TABLE: &'static str = "conv_summary";
+
+	// Note: Derived data (regenerated from the msgs).
+	fn has_audit_log() -> bool {
+		false
+	}
+}
+
+// Note: Like `ConvMsg`, the summaries are managed by the `ConvBmc` container
+//       (see `model::conv_context`).
+
+// endregion: --- ConvSummaryBmc
@@ -0,0 +1,556 @@
+//! The conv templates of an agent, to start a conv with a title and initial msgs
+//! (e.g., a "Code review" template with its system instructions and first user msg).
+//!
+//! Notes:
+//!   - The `title_pattern` and the msgs `content` can have `{{var}}` placeholders,
+//!     replaced by the `ConvFromTemplate.vars` (plus the built-in `{{date}}`, as `YYYY-MM-DD`)
+//!     when the conv is created (see `ConvBmc::create_from_template`).
+//!   - The simpler `Agent.starter_prompts` are only suggestions of first user msgs
+//!     (nothing is created from them).
+
+use crate::ctx::Ctx;
+use crate::model::acs::{assert_agent_owner, assert_agent_user};
+use crate::model::agent::AgentBmc;
+use crate::model::base::{self, DbBmc};
+use crate::model::conv::{ConvBmc, ConvForCreate};
+use crate::model::conv_msg::{ConvMsgBmc, ConvMsgForInsert, ConvMsgRole};
+use crate::model::{Error, ModelManager, Result};
+use lib_utils::time::{now_utc, Rfc3339};
+use modql::field::Fields;
+use sea_query::Nullable;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::FromRow;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+const CONV_TEMPLATE_NAME_MAX_CHARS: usize = 128;
+const CONV_TEMPLATE_TITLE_PATTERN_MAX_CHARS: usize = 256;
+const CONV_TEMPLATE_MSGS_MAX: usize = 16;
+
+// region:    --- Types
+
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct ConvTemplate {
+	pub id: i64,
+
+	// -- FK
+	pub agent_id: i64,
+
+	// -- Properties
+	pub name: String,
+	/// The conv title, with `{{var}}` placeholders (no title if None).
+	pub title_pattern: Option<String>,
+	/// The initial msgs of the conv (in order).
+	#[sqlx(json)]
+	pub msgs: ConvTemplateMsgs,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+#[derive(Fields, Deserialize, Default)]
+pub struct ConvTemplateForCreate {
+	pub agent_id: i64,
+	pub name: String,
+	pub title_pattern: Option<String>,
+	pub msgs: Option<ConvTemplateMsgs>,
+}
+
+/// Note: The agent of the template cannot be changed.
+#[derive(Fields, Deserialize, Default)]
+pub struct ConvTemplateForUpdate {
+	pub name: Option<String>,
+	pub title_pattern: Option<String>,
+	pub msgs: Option<ConvTemplateMsgs>,
+}
+
+/// The creation of a conv from a template (see `ConvBmc::create_from_template`).
+#[derive(Deserialize, Default)]
+pub struct ConvFromTemplate {
+	pub template_id: i64,
+	/// The values of the `{{var}}` placeholders.
+	#[serde(default)]
+	pub vars: HashMap<String, String>,
+}
+
+/// An initial msg of a conv template.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConvTemplateMsg {
+	/// `System` or `User` only.
+	pub role: ConvMsgRole,
+	pub content: String,
+}
+
+/// The initial msgs of a conv template (stored as a json array).
+///
+/// Note: Validated on deserialization (e.g., rpc params), at most 16 `System`
+///       or `User` msgs, with a non-empty content.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Vec<ConvTemplateMsg>")]
+pub struct ConvTemplateMsgs(Vec<ConvTemplateMsg>);
+
+impl ConvTemplateMsgs {
+	pub fn msgs(&self) -> &[ConvTemplateMsg] {
+		&self.0
+	}
+}
+
+impl TryFrom<Vec<ConvTemplateMsg>> for ConvTemplateMsgs {
+	type Error = String;
+
+	fn try_from(
+		msgs: Vec<ConvTemplateMsg>,
+	) -> core::result::Result<Self, Self::Error> {
+		if msgs.len() > CONV_TEMPLATE_MSGS_MAX {
+			return Err(format!(
+				"invalid conv template msgs - more than {CONV_TEMPLATE_MSGS_MAX}"
+			));
+		}
+		for msg in msgs.iter() {
+			if !matches!(msg.role, ConvMsgRole::System | ConvMsgRole::User) {
+				return Err(format!(
+					"invalid conv template msgs - role '{}' not System or User",
+					msg.role
+				));
+			}
+			if msg.content.trim().is_empty() {
+				return Err("invalid conv template msgs - empty content".to_string());
+			}
+		}
+
+		Ok(ConvTemplateMsgs(msgs))
+	}
+}
+
+/// Note: Manual implementation (json), required for a modql::field::Fields.
+impl From<ConvTemplateMsgs> for sea_query::Value {
+	fn from(val: ConvTemplateMsgs) -> Self {
+		// Note: Cannot fail, as all the ConvTemplateMsg types serialize to json.
+		let json = serde_json::to_value(val).unwrap_or_default();
+		sea_query::Value::Json(Some(Box::new(json)))
+	}
+}
+
+/// Note: Manual implementation (see `ConvKind`).
+impl Nullable for ConvTemplateMsgs {
+	fn null() -> sea_query::Value {
+		sea_query::Value::Json(None)
+	}
+}
+
+// endregion: --- Types
+
+// region:    --- ConvTemplateBmc
+
+pub struct ConvTemplateBmc;
+
+impl DbBmc for ConvTemplateBmc {
+	const TABLE: &'static str = "conv_template";
+}
+
+// Note: Like the agent shares, the templates are managed by the `AgentBmc` container.
+
+// endregion: --- ConvTemplateBmc
+
+// region:    --- AgentBmc Templates
+
+/// Notes:
+///   - Only the agent owner (or root) can create, update, and delete its templates.
+///   - The users who can use the agent (see `AgentVisibility`) can get and list them.
+///   - The names are unique per agent (`Error::ConvTemplateAlreadyExists`).
+impl AgentBmc {
+	pub async fn create_template(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		template_c: ConvTemplateForCreate,
+	) -> Result<i64> {
+		assert_agent_owner(ctx, mm, template_c.agent_id).await?;
+		let ConvTemplateForCreate {
+			agent_id,
+			name,
+			title_pattern,
+			msgs,
+		} = template_c;
+		let template_c = ConvTemplateForCreate {
+			agent_id,
+			name: validated_name(name)?,
+			title_pattern: validated_title_pattern(title_pattern)?,
+			msgs,
+		};
+		let name = template_c.name.clone();
+
+		base::create::<ConvTemplateBmc, _>(ctx, mm, template_c)
+			.await
+			.map_err(|model_error| resolve_name_unique_violation(model_error, name))
+	}
+
+	pub async fn get_template(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		template_id: i64,
+	) -> Result<ConvTemplate> {
+		let template: ConvTemplate =
+			base::get::<ConvTemplateBmc, _>(ctx, mm, template_id).await?;
+		assert_agent_user(ctx, mm, template.agent_id).await?;
+
+		Ok(template)
+	}
+
+	/// Returns the templates of the agent (ordered by name).
+	pub async fn list_templates(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		agent_id: i64,
+	) -> Result<Vec<ConvTemplate>> {
+		assert_agent_user(ctx, mm, agent_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, ConvTemplate>(
+			"SELECT * FROM conv_template WHERE agent_id = $1 ORDER BY name, id",
+		)
+		.bind(agent_id);
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+
+	pub async fn update_template(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		template_id: i64,
+		template_u: ConvTemplateForUpdate,
+	) -> Result<()> {
+		let template = Self::get_template(ctx, mm, template_id).await?;
+		assert_agent_owner(ctx, mm, template.agent_id).await?;
+		let ConvTemplateForUpdate {
+			name,
+			title_pattern,
+			msgs,
+		} = template_u;
+		let template_u = ConvTemplateForUpdate {
+			name: name.map(validated_name).transpose()?,
+			title_pattern: validated_title_pattern(title_pattern)?,
+			msgs,
+		};
+		let name = template_u.name.clone().unwrap_or(template.name);
+
+		base::update::<ConvTemplateBmc, _>(ctx, mm, template_id, template_u)
+			.await
+			.map_err(|model_error| resolve_name_unique_violation(model_error, name))
+	}
+
+	pub async fn delete_template(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		template_id: i64,
+	) -> Result<()> {
+		let template = Self::get_template(ctx, mm, template_id).await?;
+		assert_agent_owner(ctx, mm, template.agent_id).await?;
+
+		base::delete::<ConvTemplateBmc>(ctx, mm, template_id).await
+	}
+}
+
+/// Trim the name, and validate it is not empty and not too long.
+fn validated_name(name: String) -> Result<String> {
+	let trimmed = name.trim();
+	if trimmed.is_empty() || trimmed.chars().count() > CONV_TEMPLATE_NAME_MAX_CHARS {
+		return Err(Error::ConvTemplateInvalid {
+			cause: format!(
+				"name '{name}' must be non-empty and at most {CONV_TEMPLATE_NAME_MAX_CHARS} chars"
+			),
+		});
+	}
+
+	Ok(trimmed.to_string())
+}
+
+fn validated_title_pattern(title_pattern: Option<String>) -> Result<Option<String>> {
+	if let Some(title_pattern) = title_pattern.as_ref() {
+		if title_pattern.chars().count() > CONV_TEMPLATE_TITLE_PATTERN_MAX_CHARS {
+			return Err(Error::ConvTemplateInvalid {
+				cause: format!(
+					"title_pattern longer than {CONV_TEMPLATE_TITLE_PATTERN_MAX_CHARS} chars"
+				),
+			});
+		}
+	}
+
+	Ok(title_pattern)
+}
+
+fn resolve_name_unique_violation(model_error: Error, name: String) -> Error {
+	Error::resolve_unique_violation(
+		model_error,
+		Some(|table: &str, constraint: &str| {
+			if table == "conv_template" && constraint.contains("name") {
+				Some(Error::ConvTemplateAlreadyExists { name })
+			} else {
+				None
+			}
+		}),
+	)
+}
+
+// endregion: --- AgentBmc Templates
+
+// region:    --- ConvBmc Template
+
+impl ConvBmc {
+	/// Create a conv of the ctx user from the template, with its rendered title,
+	/// and its rendered initial msgs.
+	///
+	/// Notes:
+	///   - The conv and its msgs are created in a single transaction
+	///     (see `ModelManager::in_txn`), so nothing is created on error.
+	///   - A placeholder without a value is an `Error::ConvTemplateVarMissing`.
+	pub async fn create_from_template(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_t: ConvFromTemplate,
+	) -> Result<i64> {
+		let ConvFromTemplate { template_id, vars } = conv_t;
+		let template = AgentBmc::get_template(ctx, mm, template_id).await?;
+
+		// -- Render (before the txn, as it may fail)
+		let date = now_utc().date().to_string();
+		let var = |name: &str| match name {
+			"date" => vars.get(name).cloned().or_else(|| Some(date.clone())),
+			_ => vars.get(name).cloned(),
+		};
+		let title = template
+			.title_pattern
+			.as_deref()
+			.map(|title_pattern| render(title_pattern, var))
+			.transpose()?;
+		let msgs = template
+			.msgs
+			.0
+			.into_iter()
+			.map(|msg| Ok((msg.role, render(&msg.content, var)?)))
+			.collect::<Result<Vec<_>>>()?;
+
+		mm.in_txn(|mm| async move {
+			let conv_c = ConvForCreate {
+				agent_id: template.agent_id,
+				title,
+				..Default::default()
+			};
+			let conv_id = Self::start(ctx, &mm, conv_c).await?;
+
+			for (role, content) in msgs {
+				// Note: The template `System` msgs are not allowed in `ConvBmc::add_msg`.
+				let msg_i = ConvMsgForInsert {
+					conv_id,
+					user_id: ctx.user_id(),
+					content,
+					content_type: None,
+					role: Some(role),
+					metadata: None,
+				};
+				msg_i.validate()?;
+				base::create::<ConvMsgBmc, _>(ctx, &mm, msg_i).await?;
+			}
+
+			Ok(conv_id)
+		})
+		.await
+	}
+}
+
+/// Replace the `{{var}}` placeholders of the pattern by their value
+/// (an unclosed `{{` is kept as is).
+fn render(pattern: &str, var: impl Fn(&str) -> Option<String>) -> Result<String> {
+	let mut rendered = String::with_capacity(pattern.len());
+	let mut rest = pattern;
+
+	while let Some(start) = rest.find("{{") {
+		let Some(len) = rest[start + 2..].find("}}") else {
+			break;
+		};
+		let name = rest[start + 2..start + 2 + len].trim();
+		let value = var(name).ok_or_else(|| Error::ConvTemplateVarMissing {
+			name: name.to_string(),
+		})?;
+
+		rendered.push_str(&rest[..start]);
+		rendered.push_str(&value);
+		rest = &rest[start + 2 + len + 2..];
+	}
+	rendered.push_str(rest);
+
+	Ok(rendered)
+}
+
+// endregion: --- ConvBmc Template
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, clean_users, seed_agent, seed_user};
+	use crate::model;
+	use crate::model::agent::{AgentForUpdate, AgentVisibility};
+	use serde_json::json;
+	use serial_test::serial;
+
+	#[test]
+	fn test_render_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let var = |name: &str| (name == "topic").then(|| "rust".to_string());
+
+		// -- Exec & Check
+		assert_eq!(render("About {{ topic }}!", var)?, "About rust!");
+		assert_eq!(render("{{topic}}{{topic}}", var)?, "rustrust");
+		assert_eq!(render("No {{ var", var)?, "No {{ var");
+		let res = render("About {{other}}", var);
+		assert!(
+			matches!(&res, Err(model::Error::ConvTemplateVarMissing { name }) if name == "other"),
+			"should be ConvTemplateVarMissing, but was {res:?}"
+		);
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_from_template_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let owner_id =
+			seed_user(&ctx, &mm, "test_create_from_template_ok-user-owner").await?;
+		let other_id =
+			seed_user(&ctx, &mm, "test_create_from_template_ok-user-other").await?;
+		let owner_ctx = Ctx::new(owner_id)?;
+		let other_ctx = Ctx::new(other_id)?;
+		let agent_id =
+			seed_agent(&owner_ctx, &mm, "test_create_from_template_ok agent")
+				.await?;
+		let fx_msgs: ConvTemplateMsgs = serde_json::from_value(json!([
+			{"role": "System", "content": "You review {{lang}} code."},
+			{"role": "User", "content": "Review my {{lang}} code please."},
+		]))?;
+		let template_id = AgentBmc::create_template(
+			&owner_ctx,
+			&mm,
+			ConvTemplateForCreate {
+				agent_id,
+				name: " Code review ".to_string(),
+				title_pattern: Some("{{lang}} review".to_string()),
+				msgs: Some(fx_msgs),
+			},
+		)
+		.await?;
+		let conv_t = |vars: &[(&str, &str)]| ConvFromTemplate {
+			template_id,
+			vars: vars
+				.iter()
+				.map(|(name, value)| (name.to_string(), value.to_string()))
+				.collect(),
+		};
+
+		// -- Exec
+		let res_other = AgentBmc::list_templates(&other_ctx, &mm, agent_id).await;
+		let res_missing =
+			ConvBmc::create_from_template(&owner_ctx, &mm, conv_t(&[])).await;
+		let res_duplicate = AgentBmc::create_template(
+			&owner_ctx,
+			&mm,
+			ConvTemplateForCreate {
+				agent_id,
+				name: "Code review".to_string(),
+				..Default::default()
+			},
+		)
+		.await;
+		AgentBmc::update_owned(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			AgentForUpdate {
+				visibility: Some(AgentVisibility::Public),
+				..Default::default()
+			},
+		)
+		.await?;
+		let templates = AgentBmc::list_templates(&other_ctx, &mm, agent_id).await?;
+		let conv_id = ConvBmc::create_from_template(
+			&other_ctx,
+			&mm,
+			conv_t(&[("lang", "Rust")]),
+		)
+		.await?;
+		let res_update = AgentBmc::update_template(
+			&other_ctx,
+			&mm,
+			template_id,
+			ConvTemplateForUpdate {
+				name: Some("Hacked".to_string()),
+				..Default::default()
+			},
+		)
+		.await;
+
+		// -- Check
+		assert!(
+			matches!(res_other, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res_other:?}"
+		);
+		assert!(
+			matches!(
+				res_missing,
+				Err(model::Error::ConvTemplateVarMissing { .. })
+			),
+			"should be ConvTemplateVarMissing, but was {res_missing:?}"
+		);
+		assert!(
+			matches!(
+				res_duplicate,
+				Err(model::Error::ConvTemplateAlreadyExists { .. })
+			),
+			"should be ConvTemplateAlreadyExists, but was {res_duplicate:?}"
+		);
+		assert!(
+			matches!(res_update, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res_update:?}"
+		);
+		assert_eq!(templates.len(), 1);
+		assert_eq!(templates[0].name, "Code review");
+		let conv = ConvBmc::get(&ctx, &mm, conv_id).await?;
+		assert_eq!(conv.owner_id, other_id);
+		assert_eq!(conv.title.as_deref(), Some("Rust review"));
+		let branch = ConvBmc::get_branch(&other_ctx, &mm, conv_id).await?;
+		let msgs: Vec<(ConvMsgRole, &str)> = branch
+			.msgs
+			.iter()
+			.map(|msg| (msg.role, msg.content.as_str()))
+			.collect();
+		assert_eq!(
+			msgs,
+			[
+				(ConvMsgRole::System, "You review Rust code."),
+				(ConvMsgRole::User, "Review my Rust code please."),
+			]
+		);
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+		clean_users(&ctx, &mm, "test_create_from_template_ok").await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
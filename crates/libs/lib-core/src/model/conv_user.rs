@@ -17,6 +17,11 @@ pub struct ConvUser {
 	pub conv_id: i64,
 	pub user_id: i64,
 
+	// -- Properties
+	pub auto_respond: bool,
+	/// The last msg read by the user (see `ConvBmc::mark_read`).
+	pub last_read_msg_id: Option<i64>,
+
 	// -- Timestamps
 	// creator user_id and time
 	pub cid: i64,
@@ -32,6 +37,16 @@ pub struct ConvUser {
 pub struct ConvUserForCreate {
 	pub conv_id: i64,
 	pub user_id: i64,
+
+	pub auto_respond: Option<bool>,
+}
+
+/// ConvUser read state upsert (see `ConvBmc::mark_read`).
+#[derive(Fields)]
+pub(in crate::model) struct ConvUserForMarkRead {
+	pub conv_id: i64,
+	pub user_id: i64,
+	pub last_read_msg_id: i64,
 }
 
 // endregion: --- Types
@@ -42,6 +57,10 @@ pub struct ConvUserBmc;
 
 impl DbBmc for ConvUserBmc {
 	const TABLE: &'static str = "conv_user";
+
+	fn unique_key() -> Option<&'static [&'static str]> {
+		Some(&["conv_id", "user_id"])
+	}
 }
 
 // Note: This is not implemented yet. It will likely be similar to `ConvMsg`, meaning it will be
@@ -1,4 +1,7 @@
-use crate::model::store::dbx;
+use crate::ai;
+use crate::model::conv_export::ConvExportFormat;
+use crate::model::store::{blob, dbx};
+use crate::model::usage::UsageScope;
 use derive_more::From;
 use lib_auth::pwd;
 use serde::Serialize;
@@ -15,12 +18,125 @@ pub enum Error {
 		entity: &'static str,
 		id: i64,
 	},
+	EntitiesNotFound {
+		entity: &'static str,
+		ids: Vec<i64>,
+	},
 	ListLimitOverMax {
 		max: i64,
 		actual: i64,
 	},
+	ListOrderByUnknown {
+		order_by: String,
+	},
+	ListCursorOrderByNotSupported {
+		order_by: String,
+	},
+	ListCursorInvalid,
+
+	SearchQueryEmpty,
+
+	ConvMsgContentEmpty,
+	ConvMsgContentTypeNotAllowed {
+		content_type: String,
+	},
+	ConvMsgContentNotJson {
+		content_type: String,
+		cause: String,
+	},
+	ConvMsgMetadataNotObject,
+	ConvMsgConvIdMismatch {
+		msg_id: i64,
+		conv_id: i64,
+	},
+	ConvMsgReactionEmojiInvalid {
+		emoji: String,
+	},
+	ConvMsgNotReply {
+		msg_id: i64,
+	},
+	ConvImportInvalid {
+		cause: String,
+	},
+	ConvImportFormatNotSupported {
+		format: ConvExportFormat,
+	},
+	ConvShareExpiresAtNotFuture,
+	ConvShareNotFound,
+	ConvTemplateInvalid {
+		cause: String,
+	},
+	ConvTemplateAlreadyExists {
+		name: String,
+	},
+	ConvTemplateVarMissing {
+		name: String,
+	},
 
-	CountFail,
+	FolderNameInvalid {
+		name: String,
+	},
+	FolderAlreadyExists {
+		name: String,
+	},
+	TagNameInvalid {
+		name: String,
+	},
+	TagAlreadyExists {
+		name: String,
+	},
+	UserFavoritesOverMax {
+		max: usize,
+	},
+
+	ScheduleInvalid {
+		cause: String,
+	},
+	ScheduleCronInvalid {
+		cron: String,
+		cause: String,
+	},
+	SchedulesOverMax {
+		max: i64,
+	},
+
+	AgentHasOtherUsersConvs {
+		agent_id: i64,
+		count: i64,
+	},
+	AgentReplyStepsOverMax {
+		max: usize,
+	},
+	UsageQuotaExceeded {
+		scope: UsageScope,
+		scope_id: i64,
+	},
+
+	AttachmentEmpty,
+	AttachmentTooLarge {
+		max: usize,
+		actual: usize,
+	},
+	AttachmentNameInvalid {
+		name: String,
+	},
+	AttachmentContentTypeInvalid {
+		content_type: String,
+	},
+
+	UpsertNoUniqueKey {
+		entity: &'static str,
+	},
+	UpsertMissingKeyField {
+		entity: &'static str,
+		field: &'static str,
+	},
+
+	// -- Access
+	AccessDenied {
+		user_id: i64,
+		required: &'static str,
+	},
 
 	// -- DB
 	UserAlreadyExists {
@@ -39,6 +155,10 @@ pub enum Error {
 	Pwd(pwd::Error),
 	#[from]
 	Dbx(dbx::Error),
+	#[from]
+	Blob(blob::Error),
+	#[from]
+	Ai(ai::Error),
 
 	// -- Externals
 	#[from]
@@ -0,0 +1,187 @@
+use crate::ctx::Ctx;
+use crate::model::acs::{assert_conv_owner, assert_owner};
+use crate::model::base::{self, DbBmc};
+use crate::model::conv::ConvBmc;
+use crate::model::{Error, ModelManager, Result};
+use lib_utils::time::Rfc3339;
+use modql::field::Fields;
+use sea_query::Value;
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+const FOLDER_NAME_MAX_CHARS: usize = 64;
+
+// region:    --- Types
+
+/// A user folder of owned convs (flat, one folder per conv, see `Conv.folder_id`).
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct Folder {
+	pub id: i64,
+
+	// -- FK
+	pub owner_id: i64,
+
+	// -- Properties
+	pub name: String,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+/// Note: Also used for the rename (see `FolderBmc::update`).
+#[derive(Fields, Deserialize, Default)]
+pub struct FolderForCreate {
+	pub name: String,
+}
+
+impl FolderForCreate {
+	/// Trim the name, and validate it is not empty and not too long.
+	fn validated(self) -> Result<Self> {
+		let name = self.name.trim();
+		if name.is_empty() || name.chars().count() > FOLDER_NAME_MAX_CHARS {
+			return Err(Error::FolderNameInvalid { name: self.name });
+		}
+
+		Ok(Self {
+			name: name.to_string(),
+		})
+	}
+}
+
+/// The move of a conv to a folder (see `ConvBmc::move_to_folder`).
+#[derive(Deserialize, Default)]
+pub struct ConvForMoveToFolder {
+	pub conv_id: i64,
+	/// None to remove the conv from its folder.
+	pub folder_id: Option<i64>,
+}
+
+/// Note: The `folder_id` is a sea `Value` (e.g., `Value::BigInt(None)`), rather than an `Option`,
+///       so that `base::update` sets it even when NULL (moving the conv out of its folder).
+#[derive(Fields)]
+pub(in crate::model) struct ConvForFolder {
+	pub folder_id: Value,
+}
+
+// endregion: --- Types
+
+// region:    --- FolderBmc
+
+/// Notes:
+///   - The folders are only visible and managed by their owner (or root).
+///   - The names are unique per owner (`Error::FolderAlreadyExists`).
+///   - Deleting a folder does not delete its convs (their `folder_id` is set to None).
+pub struct FolderBmc;
+
+impl DbBmc for FolderBmc {
+	const TABLE: &'static str = "folder";
+
+	fn has_owner_id() -> bool {
+		true
+	}
+}
+
+impl FolderBmc {
+	pub async fn create(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		folder_c: FolderForCreate,
+	) -> Result<i64> {
+		let folder_c = folder_c.validated()?;
+		let name = folder_c.name.clone();
+
+		base::create::<Self, _>(ctx, mm, folder_c)
+			.await
+			.map_err(|model_error| resolve_name_unique_violation(model_error, name))
+	}
+
+	pub async fn get(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<Folder> {
+		let folder: Folder = base::get::<Self, _>(ctx, mm, id).await?;
+		assert_owner(ctx, folder.owner_id, "folder_owner")?;
+
+		Ok(folder)
+	}
+
+	/// Returns the folders of the ctx user (ordered by name).
+	pub async fn list(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Folder>> {
+		let sqlx_query = sqlx::query_as::<_, Folder>(
+			"SELECT * FROM folder WHERE owner_id = $1 ORDER BY name, id",
+		)
+		.bind(ctx.user_id());
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+
+	/// Rename the folder.
+	pub async fn update(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		folder_u: FolderForCreate,
+	) -> Result<()> {
+		Self::get(ctx, mm, id).await?;
+		let folder_u = folder_u.validated()?;
+		let name = folder_u.name.clone();
+
+		base::update::<Self, _>(ctx, mm, id, folder_u)
+			.await
+			.map_err(|model_error| resolve_name_unique_violation(model_error, name))
+	}
+
+	pub async fn delete(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		Self::get(ctx, mm, id).await?;
+
+		base::delete::<Self>(ctx, mm, id).await
+	}
+}
+
+fn resolve_name_unique_violation(model_error: Error, name: String) -> Error {
+	Error::resolve_unique_violation(
+		model_error,
+		Some(|table: &str, constraint: &str| {
+			if table == "folder" && constraint.contains("name") {
+				Some(Error::FolderAlreadyExists { name })
+			} else {
+				None
+			}
+		}),
+	)
+}
+
+// endregion: --- FolderBmc
+
+// region:    --- ConvBmc Folder
+
+impl ConvBmc {
+	/// Move the conv to one of the ctx user folders (or out of its folder).
+	///
+	/// Note: Only the conv owner can file the conv.
+	pub async fn move_to_folder(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_move: ConvForMoveToFolder,
+	) -> Result<()> {
+		let ConvForMoveToFolder { conv_id, folder_id } = conv_move;
+		assert_conv_owner(ctx, mm, conv_id).await?;
+		if let Some(folder_id) = folder_id {
+			FolderBmc::get(ctx, mm, folder_id).await?;
+		}
+
+		let conv_u = ConvForFolder {
+			folder_id: Value::BigInt(folder_id),
+		};
+		base::update::<Self, _>(ctx, mm, conv_id, conv_u).await
+	}
+}
+
+// endregion: --- ConvBmc Folder
@@ -7,7 +7,7 @@
 //! - All application code data access must go through the Model layer.
 //! - The `ModelManager` holds the internal states/resources
 //!   needed by ModelControllers to access data.
-//!   (e.g., db_pool, S3 client, redis client).
+//!   (e.g., db_pool, blob store, S3 client, redis client).
 //! - Model Controllers (e.g., `ConvBmc`, `AgentBmc`) implement
 //!   CRUD and other data access methods on a given "entity"
 //!   (e.g., `Conv`, `Agent`).
@@ -21,20 +21,41 @@
 
 mod acs;
 mod base;
+mod conv_context;
 mod error;
 mod store;
 
 pub mod agent;
+pub mod agent_share;
+pub mod attachment;
+pub mod audit_log;
 pub mod conv;
+pub mod conv_export;
 pub mod conv_msg;
+pub mod conv_msg_reaction;
+pub mod conv_msg_revision;
+pub mod conv_share;
+pub mod conv_summary;
+pub mod conv_template;
 pub mod conv_user;
+pub mod folder;
 pub mod modql_utils;
+pub mod schedule;
+pub mod tag;
+pub mod usage;
 pub mod user;
+pub mod user_favorite;
 
+pub use self::base::ListPage;
 pub use self::error::{Error, Result};
+pub use self::store::dbx::TxnScope;
 
+use crate::core_config;
+use crate::model::store::blob::BlobStore;
 use crate::model::store::dbx::Dbx;
 use crate::model::store::new_db_pool;
+use std::future::Future;
+use tracing::warn;
 
 // endregion: --- Modules
 
@@ -44,6 +65,7 @@ use crate::model::store::new_db_pool;
 #[derive(Clone)]
 pub struct ModelManager {
 	dbx: Dbx,
+	blob: BlobStore,
 }
 
 impl ModelManager {
@@ -53,17 +75,63 @@ impl ModelManager {
 			.await
 			.map_err(|ex| Error::CantCreateModelManagerProvider(ex.to_string()))?;
 		let dbx = Dbx::new(db_pool, false)?;
-		Ok(ModelManager { dbx })
+		let blob = BlobStore::new_fs(&core_config().BLOB_DIR);
+		Ok(ModelManager { dbx, blob })
 	}
 
+	/// Returns a transactional ModelManager.
+	///
+	/// Note: If this ModelManager is already transactional, a clone is returned so that
+	///       the nested `begin_txn`/`commit_txn` join the eventual ongoing transaction.
 	pub fn new_with_txn(&self) -> Result<ModelManager> {
+		if self.dbx.with_txn() {
+			return Ok(self.clone());
+		}
+
 		let dbx = Dbx::new(self.dbx.db().clone(), true)?;
-		Ok(ModelManager { dbx })
+		Ok(ModelManager {
+			dbx,
+			blob: self.blob.clone(),
+		})
+	}
+
+	/// Executes `f` with a transactional ModelManager, and commits if it returns `Ok`,
+	/// or rolls back if it returns `Err`.
+	///
+	/// Notes:
+	///   - When called within an ongoing transaction, `f` runs in a `SAVEPOINT`,
+	///     so its error only rolls back its own changes.
+	///   - If the returned future is dropped before completion, the transaction
+	///     (or savepoint) is rolled back.
+	pub async fn in_txn<F, Fut, T>(&self, f: F) -> Result<T>
+	where
+		F: FnOnce(ModelManager) -> Fut,
+		Fut: Future<Output = Result<T>>,
+	{
+		let mm = self.new_with_txn()?;
+		let txn_scope = mm.dbx().begin_txn_scope().await?;
+
+		match f(mm).await {
+			Ok(val) => {
+				txn_scope.commit().await?;
+				Ok(val)
+			}
+			Err(err) => {
+				if let Err(rollback_err) = txn_scope.rollback().await {
+					warn!("ModelManager in_txn rollback fail: {rollback_err:?}");
+				}
+				Err(err)
+			}
+		}
 	}
 
 	pub fn dbx(&self) -> &Dbx {
 		&self.dbx
 	}
+
+	pub(in crate::model) fn blob(&self) -> &BlobStore {
+		&self.blob
+	}
 }
 
 // endregion: --- ModelManager
@@ -0,0 +1,790 @@
+//! The schedules of recurring agent runs in a conv (e.g., a daily standup summary).
+//!
+//! Notes:
+//!   - At each occurrence of the schedule `cron` (5 fields, UTC), its `prompt` is added
+//!     as a user msg of the schedule owner, followed by the agent reply
+//!     (see `ScheduleBmc::run_due`), and recorded as a `ScheduleRun`.
+//!   - The missed occurrences (e.g., server down) are not caught up, a late schedule
+//!     runs once, and then at its next occurrence from now.
+//!   - The due schedules are run by the leader of the scheduler instances only
+//!     (see `SchedulerLeaderLock`), at most once per occurrence (the `next_run_at` is
+//!     advanced before the run, so a run interrupted by a crash stays `Running`).
+
+use crate::ctx::Ctx;
+use crate::model::acs::{assert_conv_owner, assert_owner, assert_sys_user};
+use crate::model::base::{self, DbBmc};
+use crate::model::conv::ConvBmc;
+use crate::model::conv_msg::ConvMsgForCreate;
+use crate::model::store::dbx;
+use crate::model::{Error, ModelManager, Result};
+use chrono::{DateTime, Utc};
+use croner::Cron;
+use lib_utils::time::{now_utc, Rfc3339};
+use modql::field::{Fields, SeaFieldValue};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::{Connection, FromRow, PgConnection};
+use time::OffsetDateTime;
+
+/// The maximum number of schedules per user.
+pub const SCHEDULES_MAX: i64 = 20;
+
+const SCHEDULE_NAME_MAX_CHARS: usize = 128;
+const SCHEDULE_RUNS_LIST_LIMIT: i64 = 100;
+const SCHEDULE_RUN_DUE_BATCH: i64 = 20;
+
+/// The key of the Postgres advisory lock of the scheduler leader.
+const SCHEDULER_LEADER_LOCK_KEY: i64 = 0x5343_4845_4455_4c45; // "SCHEDULE"
+
+// region:    --- Types
+
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct Schedule {
+	pub id: i64,
+
+	// -- FK
+	pub owner_id: i64,
+	pub conv_id: i64,
+
+	// -- Properties
+	pub name: String,
+	/// The 5 fields cron expression (UTC), e.g., `"0 9 * * MON-FRI"`.
+	pub cron: String,
+	/// The user msg posted at each run.
+	pub prompt: String,
+	pub enabled: bool,
+	/// None when disabled.
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub next_run_at: Option<OffsetDateTime>,
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub last_run_at: Option<OffsetDateTime>,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+#[derive(Fields, Deserialize, Default)]
+pub struct ScheduleForCreate {
+	pub conv_id: i64,
+	pub name: String,
+	pub cron: String,
+	pub prompt: String,
+	/// Default: true
+	pub enabled: Option<bool>,
+}
+
+/// Note: The conv of the schedule cannot be changed.
+#[derive(Fields, Deserialize, Default)]
+pub struct ScheduleForUpdate {
+	pub name: Option<String>,
+	pub cron: Option<String>,
+	pub prompt: Option<String>,
+	pub enabled: Option<bool>,
+}
+
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	PartialEq,
+	sqlx::Type,
+	SeaFieldValue,
+	derive_more::Display,
+	Deserialize,
+	Serialize,
+)]
+#[sqlx(type_name = "schedule_run_status")]
+pub enum ScheduleRunStatus {
+	Running,
+	Succeeded,
+	Failed,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct ScheduleRun {
+	pub id: i64,
+
+	// -- FK
+	pub schedule_id: i64,
+
+	// -- Properties
+	pub status: ScheduleRunStatus,
+	/// The cron occurrence of the run.
+	#[serde_as(as = "Rfc3339")]
+	pub scheduled_at: OffsetDateTime,
+	#[serde_as(as = "Option<Rfc3339>")]
+	pub finished_at: Option<OffsetDateTime>,
+	/// The agent reply msg (if `Succeeded`).
+	pub reply_msg_id: Option<i64>,
+	/// The error description (if `Failed`).
+	pub error: Option<String>,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+#[derive(Fields)]
+pub(in crate::model) struct ScheduleRunForCreate {
+	pub schedule_id: i64,
+	pub scheduled_at: OffsetDateTime,
+}
+
+#[derive(Fields)]
+pub(in crate::model) struct ScheduleRunForFinish {
+	#[field(cast_as = "schedule_run_status")]
+	pub status: ScheduleRunStatus,
+	pub finished_at: OffsetDateTime,
+	pub reply_msg_id: Option<i64>,
+	pub error: Option<String>,
+}
+
+// endregion: --- Types
+
+// region:    --- ScheduleBmc
+
+/// Notes:
+///   - The schedules are only visible and managed by their owner (or root).
+///   - The owner must own the conv when creating the schedule, and be a user of the conv
+///     at each run (otherwise the run is `Failed`).
+///   - Root cannot own a schedule (the runs are done with the ctx of the schedule owner).
+pub struct ScheduleBmc;
+
+impl DbBmc for ScheduleBmc {
+	const TABLE: &'static str = "schedule";
+
+	fn has_owner_id() -> bool {
+		true
+	}
+}
+
+pub struct ScheduleRunBmc;
+
+impl DbBmc for ScheduleRunBmc {
+	const TABLE: &'static str = "schedule_run";
+}
+
+impl ScheduleBmc {
+	/// Create a schedule on a conv owned by the ctx user (not root).
+	///
+	/// Note: The `SCHEDULES_MAX` count and the insert are in one transaction, serialized
+	///       per owner by the lock of its user row (so concurrent creates cannot exceed it).
+	pub async fn create(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		schedule_c: ScheduleForCreate,
+	) -> Result<i64> {
+		let user_id = ctx.user_id();
+		if user_id == 0 {
+			return Err(Error::AccessDenied {
+				user_id,
+				required: "non_root_user",
+			});
+		}
+		assert_conv_owner(ctx, mm, schedule_c.conv_id).await?;
+		let ScheduleForCreate {
+			conv_id,
+			name,
+			cron,
+			prompt,
+			enabled,
+		} = schedule_c;
+		let schedule_c = ScheduleForCreate {
+			conv_id,
+			name: validated_name(name)?,
+			prompt: validated_prompt(prompt)?,
+			enabled: Some(enabled.unwrap_or(true)),
+			cron,
+		};
+		let next_run_at = next_run_at(&schedule_c.cron, schedule_c.enabled)?;
+
+		mm.in_txn(|mm| async move {
+			let sqlx_query = sqlx::query(
+				r#"SELECT id FROM "user" WHERE id = $1 FOR NO KEY UPDATE"#,
+			)
+			.bind(user_id);
+			mm.dbx().execute(sqlx_query).await?;
+
+			let sqlx_query = sqlx::query_as::<_, (i64,)>(
+				"SELECT count(*) FROM schedule WHERE owner_id = $1",
+			)
+			.bind(user_id);
+			let (count,) = mm.dbx().fetch_one(sqlx_query).await?;
+			if count >= SCHEDULES_MAX {
+				return Err(Error::SchedulesOverMax { max: SCHEDULES_MAX });
+			}
+
+			let id = base::create::<Self, _>(ctx, &mm, schedule_c).await?;
+			set_next_run_at(&mm, id, next_run_at).await?;
+
+			Ok(id)
+		})
+		.await
+	}
+
+	pub async fn get(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<Schedule> {
+		let schedule: Schedule = base::get::<Self, _>(ctx, mm, id).await?;
+		assert_owner(ctx, schedule.owner_id, "schedule_owner")?;
+
+		Ok(schedule)
+	}
+
+	/// Returns the schedules of the ctx user (ordered by id).
+	pub async fn list(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Schedule>> {
+		let sqlx_query = sqlx::query_as::<_, Schedule>(
+			"SELECT * FROM schedule WHERE owner_id = $1 ORDER BY id",
+		)
+		.bind(ctx.user_id());
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+
+	/// Update the schedule (its `next_run_at` is recomputed from now).
+	pub async fn update(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		schedule_u: ScheduleForUpdate,
+	) -> Result<()> {
+		let schedule = Self::get(ctx, mm, id).await?;
+		let ScheduleForUpdate {
+			name,
+			cron,
+			prompt,
+			enabled,
+		} = schedule_u;
+		let schedule_u = ScheduleForUpdate {
+			name: name.map(validated_name).transpose()?,
+			prompt: prompt.map(validated_prompt).transpose()?,
+			cron,
+			enabled,
+		};
+		let next_run_at = next_run_at(
+			schedule_u.cron.as_deref().unwrap_or(&schedule.cron),
+			schedule_u.enabled.or(Some(schedule.enabled)),
+		)?;
+
+		mm.in_txn(|mm| async move {
+			base::update::<Self, _>(ctx, &mm, id, schedule_u).await?;
+			set_next_run_at(&mm, id, next_run_at).await
+		})
+		.await
+	}
+
+	/// Delete the schedule (and its run history).
+	pub async fn delete(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		Self::get(ctx, mm, id).await?;
+
+		base::delete::<Self>(ctx, mm, id).await
+	}
+
+	/// Returns the last 100 runs of the schedule (latest first).
+	pub async fn list_runs(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		schedule_id: i64,
+	) -> Result<Vec<ScheduleRun>> {
+		Self::get(ctx, mm, schedule_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, ScheduleRun>(
+			"SELECT * FROM schedule_run WHERE schedule_id = $1 ORDER BY id DESC LIMIT $2",
+		)
+		.bind(schedule_id)
+		.bind(SCHEDULE_RUNS_LIST_LIMIT);
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+}
+
+// endregion: --- ScheduleBmc
+
+// region:    --- ScheduleBmc Runs
+
+impl ScheduleBmc {
+	/// Run the due schedules (`next_run_at` passed), and returns the number of runs.
+	///
+	/// Notes:
+	///   - For the scheduler leader only (see `SchedulerLeaderLock`), with a sys ctx.
+	///   - The runs are sequential, with the ctx of the schedule owner, and their errors
+	///     are recorded in their `ScheduleRun` (not returned).
+	pub async fn run_due(ctx: &Ctx, mm: &ModelManager) -> Result<usize> {
+		assert_sys_user(ctx, mm).await?;
+
+		let mut run_count = 0;
+		loop {
+			let sqlx_query = sqlx::query_as::<_, Schedule>(
+				"SELECT * FROM schedule WHERE next_run_at <= now()
+				ORDER BY next_run_at, id LIMIT $1",
+			)
+			.bind(SCHEDULE_RUN_DUE_BATCH);
+			let schedules = mm.dbx().fetch_all(sqlx_query).await?;
+			if schedules.is_empty() {
+				return Ok(run_count);
+			}
+
+			for schedule in schedules {
+				run_schedule(ctx, mm, schedule).await?;
+				run_count += 1;
+			}
+		}
+	}
+}
+
+/// Run the (due) schedule, after advancing its `next_run_at`.
+async fn run_schedule(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	schedule: Schedule,
+) -> Result<()> {
+	let now = now_utc();
+	let scheduled_at = schedule.next_run_at.unwrap_or(now);
+
+	// -- Advance the schedule (disabled if its cron has no next occurrence)
+	let next_run_at = next_cron_occurrence(&schedule.cron, now).ok();
+	let sqlx_query = sqlx::query(
+		"UPDATE schedule SET next_run_at = $2, last_run_at = $3 WHERE id = $1",
+	)
+	.bind(schedule.id)
+	.bind(next_run_at)
+	.bind(now);
+	mm.dbx().execute(sqlx_query).await?;
+
+	// -- Run (with the owner ctx)
+	let run_c = ScheduleRunForCreate {
+		schedule_id: schedule.id,
+		scheduled_at,
+	};
+	let run_id = base::create::<ScheduleRunBmc, _>(ctx, mm, run_c).await?;
+
+	// Note: `Ctx::new` only fails for root, which cannot own a schedule
+	//       (so never run with the root ctx).
+	let res = match Ctx::new(schedule.owner_id) {
+		Ok(owner_ctx) => post_prompt(&owner_ctx, mm, &schedule).await,
+		Err(_) => Err(Error::AccessDenied {
+			user_id: schedule.owner_id,
+			required: "non_root_user",
+		}),
+	};
+
+	let run_u = match res {
+		Ok(reply_msg_id) => ScheduleRunForFinish {
+			status: ScheduleRunStatus::Succeeded,
+			finished_at: now_utc(),
+			reply_msg_id,
+			error: None,
+		},
+		Err(err) => ScheduleRunForFinish {
+			status: ScheduleRunStatus::Failed,
+			finished_at: now_utc(),
+			reply_msg_id: None,
+			error: Some(err.to_string()),
+		},
+	};
+	base::update::<ScheduleRunBmc, _>(ctx, mm, run_id, run_u).await
+}
+
+/// Add the schedule prompt to its conv, and generate the agent reply.
+///
+/// Returns the agent reply msg id.
+async fn post_prompt(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	schedule: &Schedule,
+) -> Result<Option<i64>> {
+	let msg_c = ConvMsgForCreate {
+		conv_id: schedule.conv_id,
+		content: schedule.prompt.clone(),
+		..Default::default()
+	};
+	ConvBmc::add_msg(ctx, mm, msg_c).await?;
+	let msg_ids = ConvBmc::generate_reply(ctx, mm, schedule.conv_id).await?;
+
+	Ok(msg_ids.last().copied())
+}
+
+// endregion: --- ScheduleBmc Runs
+
+// region:    --- SchedulerLeaderLock
+
+/// The lock of the scheduler leader, among the scheduler instances
+/// (e.g., one per web-server instance).
+///
+/// Note: A Postgres session advisory lock, held by its own connection
+///       (detached from the pool), so it is released when the connection is closed
+///       (e.g., on `release`, drop, or when the instance dies).
+pub struct SchedulerLeaderLock {
+	conn: PgConnection,
+}
+
+impl ScheduleBmc {
+	/// Try to become the scheduler leader (with a sys ctx), and returns its lock
+	/// if acquired (None if another instance is the leader).
+	pub async fn try_lock_leader(
+		ctx: &Ctx,
+		mm: &ModelManager,
+	) -> Result<Option<SchedulerLeaderLock>> {
+		assert_sys_user(ctx, mm).await?;
+
+		let mut conn = mm
+			.dbx()
+			.db()
+			.acquire()
+			.await
+			.map_err(dbx::Error::from)?
+			.detach();
+
+		let (locked,) =
+			sqlx::query_as::<_, (bool,)>("SELECT pg_try_advisory_lock($1)")
+				.bind(SCHEDULER_LEADER_LOCK_KEY)
+				.fetch_one(&mut conn)
+				.await
+				.map_err(dbx::Error::from)?;
+
+		Ok(locked.then_some(SchedulerLeaderLock { conn }))
+	}
+}
+
+impl SchedulerLeaderLock {
+	/// Returns true if the lock connection is still alive (so the lock still held).
+	pub async fn is_held(&mut self) -> bool {
+		self.conn.ping().await.is_ok()
+	}
+
+	/// Release the lock (by closing its connection).
+	pub async fn release(self) -> Result<()> {
+		self.conn.close().await.map_err(dbx::Error::from)?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- SchedulerLeaderLock
+
+// region:    --- Support
+
+/// Returns the next occurrence of the cron expression (UTC), strictly after `after`.
+fn next_cron_occurrence(
+	cron: &str,
+	after: OffsetDateTime,
+) -> Result<OffsetDateTime> {
+	let cron_invalid = |cause: String| Error::ScheduleCronInvalid {
+		cron: cron.to_string(),
+		cause,
+	};
+
+	let after = DateTime::<Utc>::from_timestamp(after.unix_timestamp(), 0)
+		.ok_or_else(|| cron_invalid(format!("time {after} out of range")))?;
+	let next = Cron::new(cron)
+		.parse()
+		.and_then(|cron| cron.find_next_occurrence(&after, false))
+		.map_err(|ex| cron_invalid(ex.to_string()))?;
+
+	OffsetDateTime::from_unix_timestamp(next.timestamp())
+		.map_err(|ex| cron_invalid(ex.to_string()))
+}
+
+/// Returns the `next_run_at` of a schedule (None if disabled).
+fn next_run_at(cron: &str, enabled: Option<bool>) -> Result<Option<OffsetDateTime>> {
+	// Note: Validate the cron even if disabled.
+	let next_run_at = next_cron_occurrence(cron, now_utc())?;
+
+	Ok(enabled.unwrap_or(true).then_some(next_run_at))
+}
+
+async fn set_next_run_at(
+	mm: &ModelManager,
+	id: i64,
+	next_run_at: Option<OffsetDateTime>,
+) -> Result<()> {
+	let sqlx_query =
+		sqlx::query("UPDATE schedule SET next_run_at = $2 WHERE id = $1")
+			.bind(id)
+			.bind(next_run_at);
+	mm.dbx().execute(sqlx_query).await?;
+
+	Ok(())
+}
+
+/// Trim the name, and validate it is not empty and not too long.
+fn validated_name(name: String) -> Result<String> {
+	let trimmed = name.trim();
+	if trimmed.is_empty() || trimmed.chars().count() > SCHEDULE_NAME_MAX_CHARS {
+		return Err(Error::ScheduleInvalid {
+			cause: format!(
+				"name '{name}' must be non-empty and at most {SCHEDULE_NAME_MAX_CHARS} chars"
+			),
+		});
+	}
+
+	Ok(trimmed.to_string())
+}
+
+fn validated_prompt(prompt: String) -> Result<String> {
+	if prompt.trim().is_empty() {
+		return Err(Error::ScheduleInvalid {
+			cause: "prompt must be non-empty".to_string(),
+		});
+	}
+
+	Ok(prompt)
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, clean_users, seed_agent, seed_conv, seed_user};
+	use crate::model;
+	use crate::model::agent::AgentBmc;
+	use crate::model::conv_msg::ConvMsgRole;
+	use crate::model::conv_user::ConvUserForCreate;
+	use lib_utils::time::parse_utc;
+	use serial_test::serial;
+
+	#[test]
+	fn test_next_cron_occurrence_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_after = parse_utc("2026-01-02T10:30:00Z")?; // a Friday
+
+		// -- Exec & Check
+		assert_eq!(
+			next_cron_occurrence("0 9 * * *", fx_after)?,
+			parse_utc("2026-01-03T09:00:00Z")?
+		);
+		assert_eq!(
+			next_cron_occurrence("0 9 * * MON-FRI", fx_after)?,
+			parse_utc("2026-01-05T09:00:00Z")?
+		);
+		assert_eq!(
+			next_cron_occurrence("*/15 * * * *", fx_after)?,
+			parse_utc("2026-01-02T10:45:00Z")?
+		);
+		let res = next_cron_occurrence("0 25 * * *", fx_after);
+		assert!(
+			matches!(res, Err(model::Error::ScheduleCronInvalid { .. })),
+			"should be ScheduleCronInvalid, but was {res:?}"
+		);
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_create_err_access_and_max() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&ctx, &mm, "test_create_err_access_and_max agent").await?;
+		let owner_id =
+			seed_user(&ctx, &mm, "test_create_err_access_and_max-user-owner")
+				.await?;
+		let member_id =
+			seed_user(&ctx, &mm, "test_create_err_access_and_max-user-member")
+				.await?;
+		let owner_ctx = Ctx::new(owner_id)?;
+		let member_ctx = Ctx::new(member_id)?;
+		let conv_id = seed_conv(
+			&owner_ctx,
+			&mm,
+			agent_id,
+			"test_create_err_access_and_max conv",
+		)
+		.await?;
+		ConvBmc::upsert_user(
+			&ctx,
+			&mm,
+			ConvUserForCreate {
+				conv_id,
+				user_id: member_id,
+				auto_respond: None,
+			},
+		)
+		.await?;
+		let fx_schedule_c = |name: String| ScheduleForCreate {
+			conv_id,
+			name,
+			cron: "0 18 * * *".to_string(),
+			prompt: "test_create_err_access_and_max prompt".to_string(),
+			..Default::default()
+		};
+
+		// -- Exec
+		let res_root =
+			ScheduleBmc::create(&ctx, &mm, fx_schedule_c("Root".to_string())).await;
+		let res_member = ScheduleBmc::create(
+			&member_ctx,
+			&mm,
+			fx_schedule_c("Member".to_string()),
+		)
+		.await;
+		for i in 0..SCHEDULES_MAX {
+			ScheduleBmc::create(
+				&owner_ctx,
+				&mm,
+				fx_schedule_c(format!("Owner {i}")),
+			)
+			.await?;
+		}
+		let res_over =
+			ScheduleBmc::create(&owner_ctx, &mm, fx_schedule_c("Over".to_string()))
+				.await;
+
+		// -- Check
+		assert!(
+			matches!(res_root, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res_root:?}"
+		);
+		assert!(
+			matches!(res_member, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res_member:?}"
+		);
+		assert!(
+			matches!(res_over, Err(model::Error::SchedulesOverMax { .. })),
+			"should be SchedulesOverMax, but was {res_over:?}"
+		);
+		let (count,) = mm
+			.dbx()
+			.fetch_one(
+				sqlx::query_as::<_, (i64,)>(
+					"SELECT count(*) FROM schedule WHERE conv_id = $1",
+				)
+				.bind(conv_id),
+			)
+			.await?;
+		assert_eq!(count, SCHEDULES_MAX);
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+		clean_users(&ctx, &mm, "test_create_err_access_and_max").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_run_due_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id = seed_agent(&ctx, &mm, "test_run_due_ok agent").await?;
+		let user_id = seed_user(&ctx, &mm, "test_run_due_ok-user-01").await?;
+		let user_ctx = Ctx::new(user_id)?;
+		let conv_id =
+			seed_conv(&user_ctx, &mm, agent_id, "test_run_due_ok conv").await?;
+		let fx_prompt = "test_run_due_ok - summarize the day";
+		let schedule_id = ScheduleBmc::create(
+			&user_ctx,
+			&mm,
+			ScheduleForCreate {
+				conv_id,
+				name: "Daily".to_string(),
+				cron: "0 18 * * *".to_string(),
+				prompt: fx_prompt.to_string(),
+				..Default::default()
+			},
+		)
+		.await?;
+		let disabled_id = ScheduleBmc::create(
+			&user_ctx,
+			&mm,
+			ScheduleForCreate {
+				conv_id,
+				name: "Disabled".to_string(),
+				cron: "* * * * *".to_string(),
+				prompt: fx_prompt.to_string(),
+				enabled: Some(false),
+			},
+		)
+		.await?;
+		// Make the schedule due.
+		let fx_scheduled_at = now_utc() - time::Duration::minutes(1);
+		set_next_run_at(&mm, schedule_id, Some(fx_scheduled_at)).await?;
+
+		// -- Exec
+		let res_user = ScheduleBmc::run_due(&user_ctx, &mm).await;
+		let run_count = ScheduleBmc::run_due(&ctx, &mm).await?;
+		let run_count_again = ScheduleBmc::run_due(&ctx, &mm).await?;
+
+		// -- Check
+		assert!(
+			matches!(res_user, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res_user:?}"
+		);
+		assert_eq!(run_count, 1);
+		assert_eq!(run_count_again, 0, "should not run twice");
+		let schedule = ScheduleBmc::get(&user_ctx, &mm, schedule_id).await?;
+		let next_run_at = schedule.next_run_at.ok_or("should have a next_run_at")?;
+		assert!(next_run_at > now_utc());
+		assert!(schedule.last_run_at.is_some());
+		let disabled = ScheduleBmc::get(&user_ctx, &mm, disabled_id).await?;
+		assert!(disabled.next_run_at.is_none());
+		let runs = ScheduleBmc::list_runs(&user_ctx, &mm, schedule_id).await?;
+		assert_eq!(runs.len(), 1);
+		let run = &runs[0];
+		assert_eq!(run.status, ScheduleRunStatus::Succeeded, "{:?}", run.error);
+		let reply_msg_id = run.reply_msg_id.ok_or("should have a reply_msg_id")?;
+		let branch = ConvBmc::get_branch(&user_ctx, &mm, conv_id).await?;
+		let msgs: Vec<(ConvMsgRole, i64)> =
+			branch.msgs.iter().map(|msg| (msg.role, msg.id)).collect();
+		assert_eq!(msgs.len(), 2);
+		assert_eq!(branch.msgs[0].content, fx_prompt);
+		assert_eq!(msgs[1], (ConvMsgRole::Assistant, reply_msg_id));
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+		clean_users(&ctx, &mm, "test_run_due_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_scheduler_leader_lock_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+
+		// -- Exec
+		let mut leader = ScheduleBmc::try_lock_leader(&ctx, &mm)
+			.await?
+			.ok_or("should be the leader")?;
+		let other = ScheduleBmc::try_lock_leader(&ctx, &mm).await?;
+		let is_held = leader.is_held().await;
+		leader.release().await?;
+		let next_leader = ScheduleBmc::try_lock_leader(&ctx, &mm).await?;
+
+		// -- Check
+		assert!(other.is_none(), "should not be a second leader");
+		assert!(is_held);
+		let next_leader = next_leader.ok_or("should be the next leader")?;
+
+		// -- Clean
+		next_leader.release().await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
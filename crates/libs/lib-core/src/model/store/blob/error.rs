@@ -0,0 +1,41 @@
+use serde::Serialize;
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[derive(Debug, Serialize)]
+pub enum Error {
+	BlobKeyInvalid { key: String },
+	BlobNotFound { key: String },
+
+	// -- Externals
+	Io { key: String, cause: String },
+}
+
+impl Error {
+	pub(super) fn from_io(key: &str, io_error: std::io::Error) -> Self {
+		match io_error.kind() {
+			std::io::ErrorKind::NotFound => Error::BlobNotFound {
+				key: key.to_string(),
+			},
+			_ => Error::Io {
+				key: key.to_string(),
+				cause: io_error.to_string(),
+			},
+		}
+	}
+}
+
+// region:    --- Error Boilerplate
+
+impl core::fmt::Display for Error {
+	fn fmt(
+		&self,
+		fmt: &mut core::fmt::Formatter,
+	) -> core::result::Result<(), core::fmt::Error> {
+		write!(fmt, "{self:?}")
+	}
+}
+
+impl std::error::Error for Error {}
+
+// endregion: --- Error Boilerplate
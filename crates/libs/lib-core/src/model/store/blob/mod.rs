@@ -0,0 +1,170 @@
+//! The blob store of the binary contents (e.g., the `Attachment` contents).
+//!
+//! Notes:
+//!   - For now, only the local filesystem backend is implemented (`BlobStore::Fs`).
+//!     Other backends (e.g., S3) will be new variants, so that the Model Controllers
+//!     stay backend agnostic.
+//!   - The keys are `/` separated relative paths (e.g., `attachments/<uuid>`),
+//!     always generated by the model layer (never from user input).
+//!   - The blob store is not transactional. The Model Controllers are responsible
+//!     for the eventual cleanup when the database changes fail.
+
+// region:    --- Modules
+
+mod error;
+
+pub use error::{Error, Result};
+
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use uuid::Uuid;
+
+// endregion: --- Modules
+
+#[derive(Debug, Clone)]
+pub enum BlobStore {
+	/// The blobs are stored as files under `root_dir` (the key being the relative path).
+	Fs { root_dir: PathBuf },
+}
+
+impl BlobStore {
+	pub fn new_fs(root_dir: impl Into<PathBuf>) -> Self {
+		BlobStore::Fs {
+			root_dir: root_dir.into(),
+		}
+	}
+
+	/// Store the `content` at `key`, replacing the eventual existing blob.
+	pub async fn put(&self, key: &str, content: &[u8]) -> Result<()> {
+		match self {
+			BlobStore::Fs { root_dir } => {
+				let path = fs_path(root_dir, key)?;
+				if let Some(dir) = path.parent() {
+					fs::create_dir_all(dir)
+						.await
+						.map_err(|ex| Error::from_io(key, ex))?;
+				}
+
+				// Write to a temporary file first, so that a blob is never partially written.
+				let tmp_path =
+					path.with_extension(format!("tmp-{}", Uuid::new_v4()));
+				if let Err(ex) = fs::write(&tmp_path, content).await {
+					let _ = fs::remove_file(&tmp_path).await;
+					return Err(Error::from_io(key, ex));
+				}
+				fs::rename(&tmp_path, &path)
+					.await
+					.map_err(|ex| Error::from_io(key, ex))
+			}
+		}
+	}
+
+	pub async fn get(&self, key: &str) -> Result<Vec<u8>> {
+		match self {
+			BlobStore::Fs { root_dir } => {
+				let path = fs_path(root_dir, key)?;
+				fs::read(&path).await.map_err(|ex| Error::from_io(key, ex))
+			}
+		}
+	}
+
+	/// Delete the blob at `key`.
+	///
+	/// Note: Deleting a missing blob is not an error.
+	pub async fn delete(&self, key: &str) -> Result<()> {
+		match self {
+			BlobStore::Fs { root_dir } => {
+				let path = fs_path(root_dir, key)?;
+				match fs::remove_file(&path).await {
+					Ok(()) => Ok(()),
+					Err(ex) if ex.kind() == std::io::ErrorKind::NotFound => Ok(()),
+					Err(ex) => Err(Error::from_io(key, ex)),
+				}
+			}
+		}
+	}
+}
+
+// region:    --- Support
+
+/// Returns the file path of the `key`, which must be made of
+/// non-empty `[A-Za-z0-9_-]` segments separated by `/`.
+fn fs_path(root_dir: &Path, key: &str) -> Result<PathBuf> {
+	let valid = key.split('/').all(|segment| {
+		!segment.is_empty()
+			&& segment
+				.chars()
+				.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+	});
+
+	if !valid {
+		return Err(Error::BlobKeyInvalid {
+			key: key.to_string(),
+		});
+	}
+
+	Ok(root_dir.join(key))
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+
+	#[tokio::test]
+	async fn test_fs_put_get_delete_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_root_dir =
+			std::env::temp_dir().join(format!("test-blob-store-{}", Uuid::new_v4()));
+		let fx_key = "test/test_fs_put_get_delete_ok";
+		let fx_content = b"Hello blob store";
+		let store = BlobStore::new_fs(&fx_root_dir);
+
+		// -- Exec
+		store.put(fx_key, b"to be replaced").await?;
+		store.put(fx_key, fx_content).await?;
+		let content = store.get(fx_key).await?;
+		store.delete(fx_key).await?;
+		let res = store.get(fx_key).await;
+
+		// -- Check
+		assert_eq!(content, fx_content);
+		assert!(
+			matches!(res, Err(super::Error::BlobNotFound { ref key }) if key == fx_key),
+			"the blob should have been deleted"
+		);
+		// delete of a missing blob is ok
+		store.delete(fx_key).await?;
+
+		// -- Clean
+		fs::remove_dir_all(&fx_root_dir).await?;
+
+		Ok(())
+	}
+
+	#[tokio::test]
+	async fn test_fs_err_key_invalid() -> Result<()> {
+		// -- Setup & Fixtures
+		let store = BlobStore::new_fs(std::env::temp_dir());
+		let fx_keys = ["", "../escape", "a//b", "/abs", "a/./b", "a/b.txt"];
+
+		// -- Exec & Check
+		for fx_key in fx_keys {
+			let res = store.put(fx_key, b"content").await;
+			assert!(
+				matches!(res, Err(super::Error::BlobKeyInvalid { .. })),
+				"key '{fx_key}' should be invalid"
+			);
+		}
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -11,6 +11,9 @@ pub enum Error {
 	CannotBeginTxnWithTxnFalse,
 	CannotCommitTxnWithTxnFalse,
 	NoTxn,
+	TxnLevelNotOpen {
+		depth: usize,
+	},
 
 	// -- Externals
 	#[from]
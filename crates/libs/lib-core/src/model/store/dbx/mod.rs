@@ -7,9 +7,10 @@ pub use error::{Error, Result};
 use crate::model::store::Db;
 use sqlx::query::{Query, QueryAs};
 use sqlx::{FromRow, IntoArguments, Pool, Postgres, Transaction};
-use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::Mutex;
+use tracing::warn;
 
 // endregion: --- Modules
 
@@ -30,108 +31,271 @@ impl Dbx {
 	}
 }
 
+/// The transaction shared by the clones of a transactional `Dbx`.
+///
+/// Notes:
+///   - The root `begin_txn` starts the database transaction (depth 0), and each
+///     nested `begin_txn` creates a `SAVEPOINT` (depth 1, 2, ...).
+///   - Each level has a unique id, so that a level is never mistaken for a later one
+///     at the same depth (e.g., by the deferred rollback of a dropped `TxnScope`).
+///   - If dropped without commit (e.g., all `Dbx` clones dropped, request cancelled),
+///     the transaction is rolled back.
 #[derive(Debug)]
 struct TxnHolder {
-	txn: Transaction<'static, Postgres>,
-	counter: i32,
+	txn: Option<Transaction<'static, Postgres>>,
+	/// The ids of the open levels, by depth (only the root id when no savepoint).
+	level_ids: Vec<u64>,
+}
+
+/// One open transaction level (see `TxnHolder.level_ids`).
+#[derive(Debug, Clone, Copy)]
+struct TxnLevel {
+	depth: usize,
+	id: u64,
+}
+
+impl TxnLevel {
+	fn new(depth: usize) -> Self {
+		static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+		TxnLevel {
+			depth,
+			id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+		}
+	}
+
+	fn savepoint_name(&self) -> String {
+		format!("dbx_sp_{}_{}", self.depth, self.id)
+	}
 }
 
 impl TxnHolder {
-	fn new(txn: Transaction<'static, Postgres>) -> Self {
-		TxnHolder { txn, counter: 1 }
+	fn new(txn: Transaction<'static, Postgres>, root: TxnLevel) -> Self {
+		TxnHolder {
+			txn: Some(txn),
+			level_ids: vec![root.id],
+		}
 	}
 
-	fn inc(&mut self) {
-		self.counter += 1;
+	fn txn_mut(&mut self) -> Result<&mut Transaction<'static, Postgres>> {
+		self.txn.as_mut().ok_or(Error::NoTxn)
 	}
 
-	fn dec(&mut self) -> i32 {
-		self.counter -= 1;
-		self.counter
+	/// Returns the innermost open level.
+	fn innermost(&self) -> Option<TxnLevel> {
+		let id = *self.level_ids.last()?;
+		Some(TxnLevel {
+			depth: self.level_ids.len() - 1,
+			id,
+		})
 	}
-}
 
-impl Deref for TxnHolder {
-	type Target = Transaction<'static, Postgres>;
+	/// Returns true if the level is still open (same depth and id).
+	fn is_open(&self, level: TxnLevel) -> bool {
+		self.level_ids.get(level.depth) == Some(&level.id)
+	}
 
-	fn deref(&self) -> &Self::Target {
-		&self.txn
+	async fn savepoint(&mut self, stmt: &str, level: TxnLevel) -> Result<()> {
+		let sql = format!("{stmt} {}", level.savepoint_name());
+		sqlx::query(&sql).execute(self.txn_mut()?.as_mut()).await?;
+		Ok(())
 	}
 }
 
-impl DerefMut for TxnHolder {
-	fn deref_mut(&mut self) -> &mut Self::Target {
-		&mut self.txn
+impl Drop for TxnHolder {
+	fn drop(&mut self) {
+		let Some(txn) = self.txn.take() else {
+			return;
+		};
+
+		warn!("Dbx - transaction dropped without commit, rolling back");
+		// Note: If no runtime is available, the sqlx `Transaction` drop will still
+		//       queue the rollback on its connection before it returns to the pool.
+		if let Ok(rt) = tokio::runtime::Handle::try_current() {
+			rt.spawn(async move {
+				if let Err(err) = txn.rollback().await {
+					warn!("Dbx - dropped transaction rollback fail: {err:?}");
+				}
+			});
+		}
 	}
 }
 
 impl Dbx {
+	/// Begin the root transaction, or a `SAVEPOINT` if a transaction is already open.
 	pub async fn begin_txn(&self) -> Result<()> {
+		self.begin_txn_level().await.map(|_| ())
+	}
+
+	/// Commit the innermost transaction level
+	/// (i.e., `RELEASE SAVEPOINT` if nested, `COMMIT` otherwise).
+	pub async fn commit_txn(&self) -> Result<()> {
+		if !self.with_txn {
+			return Err(Error::CannotCommitTxnWithTxnFalse);
+		}
+
+		let mut txh_g = self.txn_holder.lock().await;
+		let level = txh_g
+			.as_ref()
+			.and_then(TxnHolder::innermost)
+			.ok_or(Error::TxnCantCommitNoOpenTxn)?;
+
+		Self::commit_txn_level(&mut txh_g, level).await
+	}
+
+	/// Rollback the innermost transaction level
+	/// (i.e., `ROLLBACK TO SAVEPOINT` if nested, `ROLLBACK` otherwise).
+	pub async fn rollback_txn(&self) -> Result<()> {
+		let mut txh_g = self.txn_holder.lock().await;
+		let level = txh_g
+			.as_ref()
+			.and_then(TxnHolder::innermost)
+			.ok_or(Error::NoTxn)?;
+
+		Self::rollback_txn_level(&mut txh_g, level).await
+	}
+
+	/// Begin a transaction level and returns its `TxnScope`, which will
+	/// roll back this level if dropped before `commit` or `rollback`.
+	pub async fn begin_txn_scope(&self) -> Result<TxnScope> {
+		let level = self.begin_txn_level().await?;
+
+		Ok(TxnScope {
+			dbx: self.clone(),
+			level,
+			done: false,
+		})
+	}
+
+	/// Returns the new transaction level.
+	async fn begin_txn_level(&self) -> Result<TxnLevel> {
 		if !self.with_txn {
 			return Err(Error::CannotBeginTxnWithTxnFalse);
 		}
 
 		let mut txh_g = self.txn_holder.lock().await;
-		// If we already have a tx holder, then, we increment
+		// If we already have a txn holder, then, we create a savepoint
 		if let Some(txh) = txh_g.as_mut() {
-			txh.inc();
+			let level = TxnLevel::new(txh.level_ids.len());
+			txh.savepoint("SAVEPOINT", level).await?;
+			txh.level_ids.push(level.id);
+			Ok(level)
 		}
 		// If not, we create one with a new transaction
 		else {
 			let transaction = self.db_pool.begin().await?;
-			let _ = txh_g.insert(TxnHolder::new(transaction));
+			let level = TxnLevel::new(0);
+			let _ = txh_g.insert(TxnHolder::new(transaction, level));
+			Ok(level)
+		}
+	}
+
+	/// Commit the transaction `level`.
+	/// Note: Releasing a savepoint also releases the savepoints created after it.
+	async fn commit_txn_level(
+		txh_g: &mut Option<TxnHolder>,
+		level: TxnLevel,
+	) -> Result<()> {
+		let txh = txh_g.as_mut().ok_or(Error::TxnCantCommitNoOpenTxn)?;
+		if !txh.is_open(level) {
+			return Err(Error::TxnLevelNotOpen { depth: level.depth });
+		}
+
+		if level.depth > 0 {
+			txh.savepoint("RELEASE SAVEPOINT", level).await?;
+			txh.level_ids.truncate(level.depth);
+		} else if let Some(txn) = txh_g.take().and_then(|mut txh| txh.txn.take()) {
+			txn.commit().await?;
 		}
 
 		Ok(())
 	}
 
-	pub async fn rollback_txn(&self) -> Result<()> {
-		let mut txh_g = self.txn_holder.lock().await;
-		if let Some(mut txn_holder) = txh_g.take() {
-			// Take the TxnHolder out of the Option
-			if txn_holder.counter > 1 {
-				txn_holder.counter -= 1;
-				let _ = txh_g.replace(txn_holder); // Put it back if not the last reference
-			} else {
-				// Perform the actual rollback
-				txn_holder.txn.rollback().await?;
-				// No need to replace, as we want to leave it as None
-			}
-			Ok(())
-		} else {
-			Err(Error::NoTxn)
+	/// Rollback the transaction `level`.
+	/// Note: Rolling back to a savepoint also discards the savepoints created after it.
+	async fn rollback_txn_level(
+		txh_g: &mut Option<TxnHolder>,
+		level: TxnLevel,
+	) -> Result<()> {
+		let txh = txh_g.as_mut().ok_or(Error::NoTxn)?;
+		if !txh.is_open(level) {
+			return Err(Error::TxnLevelNotOpen { depth: level.depth });
 		}
-	}
 
-	pub async fn commit_txn(&self) -> Result<()> {
-		if !self.with_txn {
-			return Err(Error::CannotCommitTxnWithTxnFalse);
+		if level.depth > 0 {
+			txh.savepoint("ROLLBACK TO SAVEPOINT", level).await?;
+			txh.savepoint("RELEASE SAVEPOINT", level).await?;
+			txh.level_ids.truncate(level.depth);
+		} else if let Some(txn) = txh_g.take().and_then(|mut txh| txh.txn.take()) {
+			txn.rollback().await?;
 		}
 
-		let mut txh_g = self.txn_holder.lock().await;
-		if let Some(txh) = txh_g.as_mut() {
-			let counter = txh.dec();
-			// If 0, then, it should be matching commit for the first first begin_txn
-			// so we can commit.
-			if counter == 0 {
-				// here we take the txh out of the option
-				if let Some(txn) = txh_g.take() {
-					txn.txn.commit().await?;
-				} // TODO: Might want to add a warning on the else.
-			} // TODO: Might want to add a warning on the else.
-
-			Ok(())
+		Ok(())
+	}
+}
+
+// region:    --- TxnScope
+
+/// Guard of one transaction level (root transaction or savepoint).
+///
+/// Must be ended with `commit` or `rollback`. When dropped before (e.g., future cancelled),
+/// the level is rolled back in a spawned task.
+#[derive(Debug)]
+pub struct TxnScope {
+	dbx: Dbx,
+	level: TxnLevel,
+	done: bool,
+}
+
+impl TxnScope {
+	pub async fn commit(mut self) -> Result<()> {
+		self.done = true;
+		let mut txh_g = self.dbx.txn_holder.lock().await;
+		Dbx::commit_txn_level(&mut txh_g, self.level).await
+	}
+
+	pub async fn rollback(mut self) -> Result<()> {
+		self.done = true;
+		let mut txh_g = self.dbx.txn_holder.lock().await;
+		Dbx::rollback_txn_level(&mut txh_g, self.level).await
+	}
+}
+
+impl Drop for TxnScope {
+	fn drop(&mut self) {
+		if self.done {
+			return;
 		}
-		// Ohterwise, we have an error
-		else {
-			Err(Error::TxnCantCommitNoOpenTxn)
+
+		let (dbx, level) = (self.dbx.clone(), self.level);
+		if let Ok(rt) = tokio::runtime::Handle::try_current() {
+			rt.spawn(async move {
+				let mut txh_g = dbx.txn_holder.lock().await;
+				// Note: The level might already be gone (e.g., outer level rolled back first),
+				//       and another level might since be open at the same depth (other id).
+				if txh_g.as_ref().is_some_and(|txh| txh.is_open(level)) {
+					if let Err(err) =
+						Dbx::rollback_txn_level(&mut txh_g, level).await
+					{
+						warn!("Dbx - dropped txn scope rollback fail: {err:?}");
+					}
+				}
+			});
 		}
 	}
+}
+
+// endregion: --- TxnScope
 
+impl Dbx {
 	pub fn db(&self) -> &Pool<Postgres> {
 		&self.db_pool
 	}
 
+	pub fn with_txn(&self) -> bool {
+		self.with_txn
+	}
+
 	pub async fn fetch_one<'q, O, A>(
 		&self,
 		query: QueryAs<'q, Postgres, O, A>,
@@ -142,7 +306,7 @@ impl Dbx {
 	{
 		let data = if self.with_txn {
 			let mut txh_g = self.txn_holder.lock().await;
-			if let Some(txn) = txh_g.as_deref_mut() {
+			if let Some(txn) = txh_g.as_mut().and_then(|txh| txh.txn.as_mut()) {
 				query.fetch_one(txn.as_mut()).await?
 			} else {
 				query.fetch_one(self.db()).await?
@@ -164,7 +328,7 @@ impl Dbx {
 	{
 		let data = if self.with_txn {
 			let mut txh_g = self.txn_holder.lock().await;
-			if let Some(txn) = txh_g.as_deref_mut() {
+			if let Some(txn) = txh_g.as_mut().and_then(|txh| txh.txn.as_mut()) {
 				query.fetch_optional(txn.as_mut()).await?
 			} else {
 				query.fetch_optional(self.db()).await?
@@ -186,7 +350,7 @@ impl Dbx {
 	{
 		let data = if self.with_txn {
 			let mut txh_g = self.txn_holder.lock().await;
-			if let Some(txn) = txh_g.as_deref_mut() {
+			if let Some(txn) = txh_g.as_mut().and_then(|txh| txh.txn.as_mut()) {
 				query.fetch_all(txn.as_mut()).await?
 			} else {
 				query.fetch_all(self.db()).await?
@@ -204,7 +368,7 @@ impl Dbx {
 	{
 		let row_affected = if self.with_txn {
 			let mut txh_g = self.txn_holder.lock().await;
-			if let Some(txn) = txh_g.as_deref_mut() {
+			if let Some(txn) = txh_g.as_mut().and_then(|txh| txh.txn.as_mut()) {
 				query.execute(txn.as_mut()).await?.rows_affected()
 			} else {
 				query.execute(self.db()).await?.rows_affected()
@@ -216,3 +380,171 @@ impl Dbx {
 		Ok(row_affected)
 	}
 }
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use crate::_dev_utils::{
+		self, clean_agents, clean_users, seed_agent, seed_user,
+	};
+	use crate::ctx::Ctx;
+	use crate::model::agent::{Agent, AgentBmc};
+	use crate::model::{self, ModelManager};
+	use serial_test::serial;
+	use std::time::Duration;
+	use tokio::time;
+
+	async fn agent_exists(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<bool> {
+		match AgentBmc::get(ctx, mm, id).await {
+			Ok(_agent) => Ok(true),
+			Err(model::Error::EntityNotFound { .. }) => Ok(false),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_in_txn_commit_and_rollback_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = &Ctx::root_ctx();
+		let fx_name = "test_in_txn_commit_and_rollback_ok agent";
+
+		// -- Exec
+		let committed_id = mm
+			.in_txn(|mm| async move { seed_agent(ctx, &mm, fx_name).await })
+			.await?;
+		let mut rolled_back_id = None;
+		let rolled_back_id_ref = &mut rolled_back_id;
+		let res: model::Result<()> = mm
+			.in_txn(|mm| async move {
+				*rolled_back_id_ref = Some(seed_agent(ctx, &mm, fx_name).await?);
+				Err(model::Error::ListLimitOverMax { max: 0, actual: 1 })
+			})
+			.await;
+
+		// -- Check
+		assert!(matches!(
+			res,
+			Err(model::Error::ListLimitOverMax { max: 0, actual: 1 })
+		));
+		assert!(agent_exists(ctx, &mm, committed_id).await?);
+		let rolled_back_id = rolled_back_id.ok_or("should have rolled_back_id")?;
+		assert!(!agent_exists(ctx, &mm, rolled_back_id).await?);
+
+		// -- Clean
+		clean_agents(ctx, &mm, "test_in_txn_commit_and_rollback_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_in_txn_nested_savepoint_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = &Ctx::root_ctx();
+		let fx_name = "test_in_txn_nested_savepoint_ok agent";
+
+		// -- Exec
+		let (outer_id, inner_id) = mm
+			.in_txn(|mm| async move {
+				let outer_id = seed_agent(ctx, &mm, fx_name).await?;
+				let mut inner_id = None;
+				let inner_id_ref = &mut inner_id;
+				let res: model::Result<()> = mm
+					.in_txn(|mm| async move {
+						*inner_id_ref = Some(seed_agent(ctx, &mm, fx_name).await?);
+						Err(model::Error::ListLimitOverMax { max: 0, actual: 1 })
+					})
+					.await;
+				assert!(res.is_err(), "inner in_txn should fail");
+
+				// the outer transaction is still usable after the inner rollback
+				let _agent: Agent = AgentBmc::get(ctx, &mm, outer_id).await?;
+
+				Ok((outer_id, inner_id))
+			})
+			.await?;
+
+		// -- Check
+		assert!(agent_exists(ctx, &mm, outer_id).await?);
+		let inner_id = inner_id.ok_or("should have inner_id")?;
+		assert!(!agent_exists(ctx, &mm, inner_id).await?);
+
+		// -- Clean
+		clean_agents(ctx, &mm, "test_in_txn_nested_savepoint_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_dbx_nested_rollback_txn_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = &Ctx::root_ctx();
+		let fx_name = "test_dbx_nested_rollback_txn_ok agent";
+
+		// -- Exec
+		let mm_txn = mm.new_with_txn()?;
+		mm_txn.dbx().begin_txn().await?;
+		let outer_id = seed_agent(ctx, &mm_txn, fx_name).await?;
+		mm_txn.dbx().begin_txn().await?;
+		let inner_id = seed_agent(ctx, &mm_txn, fx_name).await?;
+		mm_txn.dbx().rollback_txn().await?;
+		mm_txn.dbx().commit_txn().await?;
+
+		// -- Check
+		assert!(agent_exists(ctx, &mm, outer_id).await?);
+		assert!(!agent_exists(ctx, &mm, inner_id).await?);
+		assert!(matches!(
+			mm_txn.dbx().commit_txn().await,
+			Err(super::Error::TxnCantCommitNoOpenTxn)
+		));
+
+		// -- Clean
+		clean_agents(ctx, &mm, "test_dbx_nested_rollback_txn_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_dbx_drop_without_commit_rollback_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = &Ctx::root_ctx();
+		let fx_username = "test_dbx_drop_without_commit_rollback_ok-user-01";
+
+		// -- Exec
+		let mm_txn = mm.new_with_txn()?;
+		mm_txn.dbx().begin_txn().await?;
+		seed_user(ctx, &mm_txn, fx_username).await?;
+		drop(mm_txn);
+
+		// -- Check
+		// Note: The same username (unique) is created from the pool, which would block
+		//       on the uncommitted row if the dropped transaction were still open
+		//       (i.e., not rolled back, and its connection not released), and fail
+		//       if it were committed.
+		let res =
+			time::timeout(Duration::from_secs(5), seed_user(ctx, &mm, fx_username))
+				.await;
+		assert!(
+			matches!(res, Ok(Ok(_))),
+			"should create the user after the rollback, but was {res:?}"
+		);
+
+		// -- Clean
+		clean_users(ctx, &mm, "test_dbx_drop_without_commit_rollback_ok").await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
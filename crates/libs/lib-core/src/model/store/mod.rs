@@ -1,5 +1,6 @@
 // region:    --- Modules
 
+pub(in crate::model) mod blob;
 pub(in crate::model) mod dbx;
 
 use crate::core_config;
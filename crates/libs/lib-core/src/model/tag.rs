@@ -0,0 +1,565 @@
+use crate::ctx::Ctx;
+use crate::model::acs::{assert_conv_user, assert_owner};
+use crate::model::base::{self, DbBmc};
+use crate::model::conv::{Conv, ConvBmc};
+use crate::model::{Error, ModelManager, Result};
+use lib_utils::time::Rfc3339;
+use modql::field::Fields;
+use modql::filter::{IntoSeaError, OpValValue, OpValsValue, SeaResult};
+use sea_query::{Alias, ColumnRef, ConditionExpression, Expr, Query};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use serde_with::serde_as;
+use sqlx::FromRow;
+use std::collections::HashMap;
+use time::OffsetDateTime;
+
+const TAG_NAME_MAX_CHARS: usize = 64;
+
+// region:    --- Types
+
+/// A user label, attached to many convs (see `ConvFilter.tag_id`).
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct Tag {
+	pub id: i64,
+
+	// -- FK
+	pub owner_id: i64,
+
+	// -- Properties
+	pub name: String,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+/// Note: Also used for the rename (see `TagBmc::update`).
+#[derive(Fields, Deserialize, Default)]
+pub struct TagForCreate {
+	pub name: String,
+}
+
+impl TagForCreate {
+	/// Trim the name, and validate it is not empty and not too long.
+	fn validated(self) -> Result<Self> {
+		let name = self.name.trim();
+		if name.is_empty() || name.chars().count() > TAG_NAME_MAX_CHARS {
+			return Err(Error::TagNameInvalid { name: self.name });
+		}
+
+		Ok(Self {
+			name: name.to_string(),
+		})
+	}
+}
+
+#[derive(Fields, Deserialize, Default)]
+pub struct ConvTagForCreate {
+	pub conv_id: i64,
+	pub tag_id: i64,
+}
+
+// endregion: --- Types
+
+// region:    --- TagBmc
+
+/// Notes:
+///   - Like the folders, the tags are private to their owner (or root).
+///   - The names are unique per owner (`Error::TagAlreadyExists`).
+///   - Deleting a tag removes it from all its convs.
+pub struct TagBmc;
+
+impl DbBmc for TagBmc {
+	const TABLE: &'static str = "tag";
+
+	fn has_owner_id() -> bool {
+		true
+	}
+}
+
+impl TagBmc {
+	pub async fn create(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		tag_c: TagForCreate,
+	) -> Result<i64> {
+		let tag_c = tag_c.validated()?;
+		let name = tag_c.name.clone();
+
+		base::create::<Self, _>(ctx, mm, tag_c)
+			.await
+			.map_err(|model_error| resolve_name_unique_violation(model_error, name))
+	}
+
+	pub async fn get(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<Tag> {
+		let tag: Tag = base::get::<Self, _>(ctx, mm, id).await?;
+		assert_owner(ctx, tag.owner_id, "tag_owner")?;
+
+		Ok(tag)
+	}
+
+	/// Returns the tags of the ctx user (ordered by name).
+	pub async fn list(ctx: &Ctx, mm: &ModelManager) -> Result<Vec<Tag>> {
+		let sqlx_query = sqlx::query_as::<_, Tag>(
+			"SELECT * FROM tag WHERE owner_id = $1 ORDER BY name, id",
+		)
+		.bind(ctx.user_id());
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+
+	/// Rename the tag.
+	pub async fn update(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		id: i64,
+		tag_u: TagForCreate,
+	) -> Result<()> {
+		Self::get(ctx, mm, id).await?;
+		let tag_u = tag_u.validated()?;
+		let name = tag_u.name.clone();
+
+		base::update::<Self, _>(ctx, mm, id, tag_u)
+			.await
+			.map_err(|model_error| resolve_name_unique_violation(model_error, name))
+	}
+
+	pub async fn delete(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<()> {
+		Self::get(ctx, mm, id).await?;
+
+		base::delete::<Self>(ctx, mm, id).await
+	}
+}
+
+fn resolve_name_unique_violation(model_error: Error, name: String) -> Error {
+	Error::resolve_unique_violation(
+		model_error,
+		Some(|table: &str, constraint: &str| {
+			if table == "tag" && constraint.contains("name") {
+				Some(Error::TagAlreadyExists { name })
+			} else {
+				None
+			}
+		}),
+	)
+}
+
+// endregion: --- TagBmc
+
+// region:    --- ConvTagBmc
+
+pub struct ConvTagBmc;
+
+impl DbBmc for ConvTagBmc {
+	const TABLE: &'static str = "conv_tag";
+
+	fn unique_key() -> Option<&'static [&'static str]> {
+		Some(&["conv_id", "tag_id"])
+	}
+}
+
+// Note: The conv tags are managed by the `ConvBmc` container.
+
+// endregion: --- ConvTagBmc
+
+// region:    --- ConvBmc Tags
+
+/// Note: Any conv user can tag the conv, but only with its own tags,
+///       and only sees its own tags on the conv (`Conv.tag_ids`).
+impl ConvBmc {
+	/// Add the tag to the conv (no-op if already tagged).
+	pub async fn add_tag(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_tag_c: ConvTagForCreate,
+	) -> Result<()> {
+		assert_conv_user(ctx, mm, conv_tag_c.conv_id).await?;
+		TagBmc::get(ctx, mm, conv_tag_c.tag_id).await?;
+
+		base::upsert::<ConvTagBmc, _>(ctx, mm, conv_tag_c).await?;
+
+		Ok(())
+	}
+
+	/// Remove the tag from the conv (no-op if not tagged).
+	pub async fn remove_tag(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_tag_c: ConvTagForCreate,
+	) -> Result<()> {
+		let ConvTagForCreate { conv_id, tag_id } = conv_tag_c;
+		assert_conv_user(ctx, mm, conv_id).await?;
+		TagBmc::get(ctx, mm, tag_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, (i64,)>(
+			"SELECT id FROM conv_tag WHERE conv_id = $1 AND tag_id = $2",
+		)
+		.bind(conv_id)
+		.bind(tag_id);
+		if let Some((conv_tag_id,)) = mm.dbx().fetch_optional(sqlx_query).await? {
+			base::delete::<ConvTagBmc>(ctx, mm, conv_tag_id).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Returns the ctx user tags of the conv (ordered by name).
+	pub async fn list_tags(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+	) -> Result<Vec<Tag>> {
+		assert_conv_user(ctx, mm, conv_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, Tag>(
+			"SELECT tag.* FROM tag
+			JOIN conv_tag ON conv_tag.tag_id = tag.id
+			WHERE conv_tag.conv_id = $1 AND tag.owner_id = $2
+			ORDER BY tag.name, tag.id",
+		)
+		.bind(conv_id)
+		.bind(ctx.user_id());
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+
+	/// Set the `tag_ids` of the ctx user on the `convs` (one query for all the convs).
+	pub async fn load_tag_ids(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		convs: &mut [Conv],
+	) -> Result<()> {
+		if convs.is_empty() {
+			return Ok(());
+		}
+
+		let conv_ids: Vec<i64> = convs.iter().map(|conv| conv.id).collect();
+		let sqlx_query = sqlx::query_as::<_, (i64, i64)>(
+			"SELECT conv_tag.conv_id, conv_tag.tag_id FROM conv_tag
+			JOIN tag ON tag.id = conv_tag.tag_id
+			WHERE conv_tag.conv_id = ANY($1) AND tag.owner_id = $2
+			ORDER BY conv_tag.conv_id, conv_tag.tag_id",
+		)
+		.bind(conv_ids)
+		.bind(ctx.user_id());
+
+		let mut tag_ids_by_conv: HashMap<i64, Vec<i64>> = HashMap::new();
+		for (conv_id, tag_id) in mm.dbx().fetch_all(sqlx_query).await? {
+			tag_ids_by_conv.entry(conv_id).or_default().push(tag_id);
+		}
+
+		for conv in convs.iter_mut() {
+			conv.tag_ids =
+				Some(tag_ids_by_conv.remove(&conv.id).unwrap_or_default());
+		}
+
+		Ok(())
+	}
+}
+
+// endregion: --- ConvBmc Tags
+
+// region:    --- ConvFilter tag_id
+
+/// The `ConvFilter.tag_id` condition, as a `conv.id` sub select on `conv_tag`
+/// (e.g., `{"tag_id": 1}` for the convs with the tag, `{"tag_id": {"$not": 1}}` without).
+///
+/// Notes:
+///   - Only `Eq` and `Not` are supported. The list operators are rejected, as modql 0.4
+///     parses both the json `$in` and `$notIn` of a `OpValsValue` as `NotIn`
+///     (use one filter group per tag id, or-ed, for the convs with any of the tags).
+///   - The values scoped by `scope_conv_tag_ids` only match the tags of their user.
+pub(in crate::model) fn conv_tag_id_to_sea_condition(
+	_col: &ColumnRef,
+	op_value: OpValValue,
+) -> SeaResult<ConditionExpression> {
+	let (value, is_in) = match op_value {
+		OpValValue::Eq(value) => (value, true),
+		OpValValue::Not(value) => (value, false),
+		OpValValue::In(_) | OpValValue::NotIn(_) => {
+			return Err(IntoSeaError::Custom(
+				"tag_id list operators ($in, $notIn) not supported, \
+				use one filter group per tag_id"
+					.to_string(),
+			))
+		}
+		other => {
+			return Err(IntoSeaError::Custom(format!(
+				"tag_id operator not supported: {other:?}"
+			)))
+		}
+	};
+
+	// -- The tag id, and the eventual tag owner (see `scope_conv_tag_ids`)
+	let (tag_id, owner_id) = match &value {
+		Value::Object(scoped) => (
+			scoped.get(SCOPED_TAG_ID).and_then(Value::as_i64),
+			scoped.get(SCOPED_OWNER_ID).and_then(Value::as_i64),
+		),
+		value => (value.as_i64(), None),
+	};
+	let tag_id = tag_id.ok_or_else(|| {
+		IntoSeaError::Custom(format!("tag_id must be an integer: {value}"))
+	})?;
+
+	let mut sub_select = Query::select();
+	sub_select
+		.column((Alias::new("conv_tag"), Alias::new("conv_id")))
+		.from(Alias::new("conv_tag"))
+		.inner_join(
+			Alias::new("tag"),
+			Expr::col((Alias::new("tag"), Alias::new("id")))
+				.equals((Alias::new("conv_tag"), Alias::new("tag_id"))),
+		)
+		.and_where(Expr::col((Alias::new("tag"), Alias::new("id"))).eq(tag_id));
+	if let Some(owner_id) = owner_id {
+		sub_select.and_where(
+			Expr::col((Alias::new("tag"), Alias::new("owner_id"))).eq(owner_id),
+		);
+	}
+	let sub_select = sub_select.to_owned();
+	let conv_id = Expr::col((Alias::new("conv"), Alias::new("id")));
+	let cond = if is_in {
+		conv_id.in_subquery(sub_select)
+	} else {
+		conv_id.not_in_subquery(sub_select)
+	};
+
+	Ok(ConditionExpression::SimpleExpr(cond))
+}
+
+const SCOPED_TAG_ID: &str = "tag_id";
+const SCOPED_OWNER_ID: &str = "owner_id";
+
+/// Scope the `ConvFilter.tag_id` values to the tags of the user
+/// (set by `ConvBmc::visible_filters`, so that the other users tags never match).
+///
+/// Note: A value already scoped (e.g., sent as such) is nested, and so rejected
+///       by `conv_tag_id_to_sea_condition`.
+pub(in crate::model) fn scope_conv_tag_ids(tag_id: &mut OpValsValue, user_id: i64) {
+	for op_value in tag_id.0.iter_mut() {
+		if let OpValValue::Eq(value) | OpValValue::Not(value) = op_value {
+			*value = json!({
+				SCOPED_TAG_ID: value.take(),
+				SCOPED_OWNER_ID: user_id,
+			});
+		}
+	}
+}
+
+// endregion: --- ConvFilter tag_id
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, clean_users, seed_agent, seed_conv, seed_user};
+	use crate::model;
+	use crate::model::agent::AgentBmc;
+	use crate::model::conv::ConvFilter;
+	use crate::model::conv_user::ConvUserForCreate;
+	use crate::model::folder::{ConvForMoveToFolder, FolderBmc, FolderForCreate};
+	use modql::filter::ListOptions;
+	use serde_json::json;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_convs_by_tag_and_folder_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_prefix = "test_list_convs_by_tag_and_folder_ok";
+		let agent_id = seed_agent(&ctx, &mm, &format!("{fx_prefix} agent")).await?;
+		let user_id = seed_user(&ctx, &mm, &format!("{fx_prefix}-user-01")).await?;
+		let other_id = seed_user(&ctx, &mm, &format!("{fx_prefix}-user-02")).await?;
+		let user_ctx = Ctx::new(user_id)?;
+		let other_ctx = Ctx::new(other_id)?;
+		let mut conv_ids = Vec::new();
+		for title in ["conv 01 alpha", "conv 02 alpha", "conv 03 beta"] {
+			conv_ids.push(
+				seed_conv(&user_ctx, &mm, agent_id, &format!("{fx_prefix} {title}"))
+					.await?,
+			);
+		}
+		let tag_c = |name: &str| TagForCreate {
+			name: name.to_string(),
+		};
+		let tag_work_id = TagBmc::create(&user_ctx, &mm, tag_c(" work ")).await?;
+		let tag_later_id = TagBmc::create(&user_ctx, &mm, tag_c("later")).await?;
+		let other_tag_id = TagBmc::create(&other_ctx, &mm, tag_c("work")).await?;
+		let folder_id = FolderBmc::create(
+			&user_ctx,
+			&mm,
+			FolderForCreate {
+				name: "projects".to_string(),
+			},
+		)
+		.await?;
+		let conv_tag =
+			|conv_id: i64, tag_id: i64| ConvTagForCreate { conv_id, tag_id };
+
+		// -- Exec
+		for conv_id in &conv_ids {
+			ConvBmc::add_tag(&user_ctx, &mm, conv_tag(*conv_id, tag_work_id))
+				.await?;
+		}
+		// twice is a no-op
+		ConvBmc::add_tag(&user_ctx, &mm, conv_tag(conv_ids[0], tag_work_id)).await?;
+		ConvBmc::add_tag(&user_ctx, &mm, conv_tag(conv_ids[0], tag_later_id))
+			.await?;
+		ConvBmc::move_to_folder(
+			&user_ctx,
+			&mm,
+			ConvForMoveToFolder {
+				conv_id: conv_ids[1],
+				folder_id: Some(folder_id),
+			},
+		)
+		.await?;
+		let res_other_tag =
+			ConvBmc::add_tag(&user_ctx, &mm, conv_tag(conv_ids[0], other_tag_id))
+				.await;
+		let res_dup = TagBmc::create(&user_ctx, &mm, tag_c("work")).await;
+		let res_empty = TagBmc::create(&user_ctx, &mm, tag_c("  ")).await;
+
+		let list_titles = |filters: serde_json::Value| {
+			let user_ctx = user_ctx.clone();
+			let mm = mm.clone();
+			async move {
+				let filters: Vec<ConvFilter> = serde_json::from_value(filters)?;
+				let page = ConvBmc::list_page_with_unread(
+					&user_ctx,
+					&mm,
+					Some(filters),
+					Some(ListOptions {
+						order_bys: Some("id".into()),
+						..Default::default()
+					}),
+					None,
+				)
+				.await?;
+				let titles: Vec<String> = page
+					.items
+					.into_iter()
+					.filter_map(|conv| conv.title)
+					.map(|title| {
+						title.trim_start_matches(fx_prefix).trim().to_string()
+					})
+					.collect();
+				Ok::<_, Error>(titles)
+			}
+		};
+		let work_alpha = list_titles(json!([{
+			"tag_id": tag_work_id,
+			"kind": "OwnerOnly",
+			"title": {"$contains": "alpha"},
+		}]))
+		.await?;
+		let later_or_beta = list_titles(json!([
+			{"tag_id": tag_later_id},
+			{"tag_id": tag_work_id, "title": {"$endsWith": "beta"}},
+		]))
+		.await?;
+		let not_later = list_titles(json!([{
+			"tag_id": {"$not": tag_later_id},
+			"id": {"$in": conv_ids.clone()},
+		}]))
+		.await?;
+		let in_folder = list_titles(json!([{
+			"tag_id": tag_work_id,
+			"folder_id": folder_id,
+		}]))
+		.await?;
+		let res_op_is_err =
+			list_titles(json!([{"tag_id": {"$gt": 1}}])).await.is_err();
+		let res_in_is_err = list_titles(json!([{"tag_id": {"$in": [tag_work_id]}}]))
+			.await
+			.is_err();
+		// the other user tags of a shared conv never match
+		ConvBmc::upsert_user(
+			&ctx,
+			&mm,
+			ConvUserForCreate {
+				conv_id: conv_ids[2],
+				user_id: other_id,
+				auto_respond: None,
+			},
+		)
+		.await?;
+		ConvBmc::add_tag(&other_ctx, &mm, conv_tag(conv_ids[2], other_tag_id))
+			.await?;
+		let other_tagged = list_titles(json!([{"tag_id": other_tag_id}])).await?;
+		let not_other_tagged = list_titles(json!([{
+			"tag_id": {"$not": other_tag_id},
+			"id": {"$in": conv_ids.clone()},
+		}]))
+		.await?;
+
+		let move_conv_01 = |folder_id: Option<i64>| ConvForMoveToFolder {
+			conv_id: conv_ids[0],
+			folder_id,
+		};
+		ConvBmc::move_to_folder(&user_ctx, &mm, move_conv_01(Some(folder_id)))
+			.await?;
+		ConvBmc::move_to_folder(&user_ctx, &mm, move_conv_01(None)).await?;
+
+		let mut convs = vec![ConvBmc::get(&user_ctx, &mm, conv_ids[0]).await?];
+		ConvBmc::load_tag_ids(&user_ctx, &mm, &mut convs).await?;
+		FolderBmc::delete(&user_ctx, &mm, folder_id).await?;
+		let conv_02 = ConvBmc::get(&user_ctx, &mm, conv_ids[1]).await?;
+		ConvBmc::remove_tag(&user_ctx, &mm, conv_tag(conv_ids[0], tag_later_id))
+			.await?;
+		// twice is a no-op
+		ConvBmc::remove_tag(&user_ctx, &mm, conv_tag(conv_ids[0], tag_later_id))
+			.await?;
+		let conv_01_tags = ConvBmc::list_tags(&user_ctx, &mm, conv_ids[0]).await?;
+
+		// -- Check
+		assert_eq!(work_alpha, ["conv 01 alpha", "conv 02 alpha"]);
+		assert_eq!(later_or_beta, ["conv 01 alpha", "conv 03 beta"]);
+		assert_eq!(not_later, ["conv 02 alpha", "conv 03 beta"]);
+		assert_eq!(in_folder, ["conv 02 alpha"]);
+		assert!(res_op_is_err, "tag_id $gt should fail");
+		assert!(res_in_is_err, "tag_id $in should fail");
+		assert!(other_tagged.is_empty(), "other user tag should not match");
+		assert_eq!(not_other_tagged.len(), 3);
+		assert_eq!(convs[0].tag_ids, Some(vec![tag_work_id, tag_later_id]));
+		assert_eq!(convs[0].folder_id, None, "move out of folder should unset");
+		assert_eq!(conv_02.folder_id, None, "folder delete should unset");
+		let conv_01_tag_ids: Vec<i64> = conv_01_tags.iter().map(|t| t.id).collect();
+		assert_eq!(conv_01_tag_ids, [tag_work_id]);
+		assert!(
+			matches!(res_other_tag, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res_other_tag:?}"
+		);
+		assert!(
+			matches!(res_dup, Err(model::Error::TagAlreadyExists { .. })),
+			"should be TagAlreadyExists, but was {res_dup:?}"
+		);
+		assert!(
+			matches!(res_empty, Err(model::Error::TagNameInvalid { .. })),
+			"should be TagNameInvalid, but was {res_empty:?}"
+		);
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+		clean_users(&ctx, &mm, fx_prefix).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -0,0 +1,549 @@
+//! The AI usage accounting (tokens and cost) of the agent replies, and its daily quotas.
+//!
+//! Notes:
+//!   - One append-only `UsageRecord` per AI call of `ConvBmc::generate_reply`.
+//!   - The `UsageQuota` are the daily (UTC) limits per user, agent, or org. They are
+//!     checked before a reply is generated (so the last reply of a day can exceed them).
+//!   - The costs are in micros (millionths of USD), see `ai::ModelPricing`.
+
+use crate::ai::{self, ChatUsage};
+use crate::ctx::Ctx;
+use crate::model::acs;
+use crate::model::agent::AgentVersion;
+use crate::model::base::DbBmc;
+use crate::model::conv::Conv;
+use crate::model::modql_utils::time_to_sea_value;
+use crate::model::{Error, ModelManager, Result};
+use lib_macros::Bmc;
+use lib_utils::time::{now_utc, Rfc3339};
+use modql::field::{Fields, SeaFieldValue};
+use modql::filter::{FilterNodes, OpValsInt64, OpValsString, OpValsValue};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+const USAGE_REPORT_DAYS_DEFAULT: i32 = 30;
+const USAGE_REPORT_DAYS_MAX: i32 = 366;
+
+// region:    --- Usage Types
+
+#[derive(
+	Debug,
+	Clone,
+	Copy,
+	PartialEq,
+	sqlx::Type,
+	SeaFieldValue,
+	derive_more::Display,
+	Deserialize,
+	Serialize,
+)]
+#[sqlx(type_name = "usage_scope")]
+pub enum UsageScope {
+	User,
+	Agent,
+	Org,
+}
+
+/// The usage of one AI call of an agent reply.
+#[serde_as]
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct UsageRecord {
+	pub id: i64,
+
+	// -- Relations
+	pub user_id: i64,
+	pub org_id: Option<i64>,
+	pub agent_id: i64,
+	pub conv_id: i64,
+	/// The first `ConvMsg` of the reply step of the AI call.
+	pub msg_id: Option<i64>,
+
+	// -- Properties
+	pub ai_provider: String,
+	pub ai_model: String,
+	pub prompt_tokens: i64,
+	pub completion_tokens: i64,
+	pub cost_micros: i64,
+
+	// -- Timestamp
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+}
+
+/// The usage of one day (UTC) and scope id (e.g., the `agent_id` of the `Agent` scope).
+#[derive(Debug, Clone, FromRow, Serialize)]
+pub struct UsageDaily {
+	/// `YYYY-MM-DD`
+	pub day: String,
+	/// None for the usage without org (`Org` scope).
+	pub scope_id: Option<i64>,
+	pub call_count: i64,
+	pub prompt_tokens: i64,
+	pub completion_tokens: i64,
+	pub cost_micros: i64,
+}
+
+#[derive(Deserialize)]
+pub struct UsageForReport {
+	pub group_by: UsageScope,
+	/// The number of days, up to today (default 30, max 366).
+	pub days: Option<i32>,
+}
+
+/// Note: The `derive(Bmc)` generates the `UsageQuotaBmc` with the CRUD functions
+///       (restricted to root and `Sys` users), and the `generate_usage_quota_rpc_fns!()`.
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize, Bmc)]
+#[bmc(
+	table = "usage_quota",
+	for_create = UsageQuotaForCreate,
+	for_update = UsageQuotaForUpdate,
+	filter = UsageQuotaFilter,
+	ops(create, list, update, delete),
+	privileges(
+		create = Sys,
+		create_many = Sys,
+		get = Sys,
+		first = Sys,
+		list = Sys,
+		list_page = Sys,
+		count = Sys,
+		update = Sys,
+		update_many = Sys,
+		update_by_filter = Sys,
+		delete = Sys,
+		delete_many = Sys,
+	),
+	rpc(suffix = "usage_quota", plural = "usage_quotas")
+)]
+pub struct UsageQuota {
+	pub id: i64,
+
+	// -- Properties
+	pub scope: UsageScope,
+	/// The user, agent, or org id (per `scope`).
+	pub scope_id: i64,
+	/// The max daily prompt + completion tokens (None for no limit).
+	pub daily_tokens_max: Option<i64>,
+	/// The max daily cost in micros (None for no limit).
+	pub daily_cost_micros_max: Option<i64>,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+#[derive(Fields, Deserialize)]
+pub struct UsageQuotaForCreate {
+	#[field(cast_as = "usage_scope")]
+	pub scope: UsageScope,
+	pub scope_id: i64,
+	pub daily_tokens_max: Option<i64>,
+	pub daily_cost_micros_max: Option<i64>,
+}
+
+#[derive(Fields, Deserialize, Default)]
+pub struct UsageQuotaForUpdate {
+	pub daily_tokens_max: Option<i64>,
+	pub daily_cost_micros_max: Option<i64>,
+}
+
+#[derive(FilterNodes, Deserialize, Default, Debug)]
+pub struct UsageQuotaFilter {
+	pub id: Option<OpValsInt64>,
+
+	#[modql(cast_as = "usage_scope")]
+	pub scope: Option<OpValsString>,
+	pub scope_id: Option<OpValsInt64>,
+
+	pub cid: Option<OpValsInt64>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	pub ctime: Option<OpValsValue>,
+	pub mid: Option<OpValsInt64>,
+	#[modql(to_sea_value_fn = "time_to_sea_value")]
+	pub mtime: Option<OpValsValue>,
+}
+
+/// The quota of a scope with the usage of the current day.
+#[derive(FromRow)]
+struct UsageQuotaState {
+	scope: UsageScope,
+	scope_id: i64,
+	daily_tokens_max: Option<i64>,
+	daily_cost_micros_max: Option<i64>,
+	tokens: i64,
+	cost_micros: i64,
+}
+
+// endregion: --- Usage Types
+
+// region:    --- UsageBmc
+
+pub struct UsageBmc;
+
+impl DbBmc for UsageBmc {
+	const TABLE: &'static str = "usage_record";
+
+	fn has_timestamps() -> bool {
+		false
+	}
+
+	fn has_audit_log() -> bool {
+		false
+	}
+}
+
+impl UsageBmc {
+	/// Records the `usage` of one AI call of the agent reply in `conv`.
+	pub(in crate::model) async fn record(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv: &Conv,
+		agent_version: &AgentVersion,
+		msg_id: Option<i64>,
+		usage: ChatUsage,
+	) -> Result<i64> {
+		let cost_micros =
+			ai::model_pricing(&agent_version.ai_provider, &agent_version.ai_model)
+				.map(|pricing| pricing.cost_micros(&usage))
+				.unwrap_or(0);
+
+		let sqlx_query = sqlx::query_as::<_, (i64,)>(
+			r#"INSERT INTO usage_record
+				(user_id, org_id, agent_id, conv_id, msg_id, ai_provider, ai_model,
+				 prompt_tokens, completion_tokens, cost_micros, ctime)
+			VALUES
+				($1, (SELECT org_id FROM "user" WHERE id = $1), $2, $3, $4, $5, $6,
+				 $7, $8, $9, $10)
+			RETURNING id"#,
+		)
+		.bind(ctx.user_id())
+		.bind(conv.agent_id)
+		.bind(conv.id)
+		.bind(msg_id)
+		.bind(&agent_version.ai_provider)
+		.bind(&agent_version.ai_model)
+		.bind(usage.prompt_tokens)
+		.bind(usage.completion_tokens)
+		.bind(cost_micros)
+		.bind(now_utc());
+		let (id,) = mm.dbx().fetch_one(sqlx_query).await?;
+
+		Ok(id)
+	}
+
+	/// Assert that the daily quotas of the ctx user, its org, and the `agent_id`
+	/// are not reached.
+	pub(in crate::model) async fn assert_quotas(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		agent_id: i64,
+	) -> Result<()> {
+		let sqlx_query =
+			sqlx::query_as::<_, UsageQuotaState>(USAGE_QUOTA_STATES_SELECT)
+				.bind(ctx.user_id())
+				.bind(agent_id);
+		let states = mm.dbx().fetch_all(sqlx_query).await?;
+
+		for state in states {
+			let tokens_over = state
+				.daily_tokens_max
+				.is_some_and(|max| state.tokens >= max);
+			let cost_over = state
+				.daily_cost_micros_max
+				.is_some_and(|max| state.cost_micros >= max);
+
+			if tokens_over || cost_over {
+				return Err(Error::UsageQuotaExceeded {
+					scope: state.scope,
+					scope_id: state.scope_id,
+				});
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Returns the usage records of the conv (ordered by id).
+	pub async fn list_for_conv(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		conv_id: i64,
+	) -> Result<Vec<UsageRecord>> {
+		acs::assert_conv_user(ctx, mm, conv_id).await?;
+
+		let sqlx_query = sqlx::query_as::<_, UsageRecord>(
+			"SELECT * FROM usage_record WHERE conv_id = $1 ORDER BY id",
+		)
+		.bind(conv_id);
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+
+	/// Returns the daily usage of the last `days`, grouped by the `group_by` scope
+	/// (ordered by day and scope id).
+	///
+	/// Note: Only the usage of the ctx user for the non `Sys` users.
+	pub async fn report(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		report_q: UsageForReport,
+	) -> Result<Vec<UsageDaily>> {
+		let days = report_q
+			.days
+			.unwrap_or(USAGE_REPORT_DAYS_DEFAULT)
+			.clamp(1, USAGE_REPORT_DAYS_MAX);
+		let user_id = match acs::assert_sys_user(ctx, mm).await {
+			Ok(()) => None,
+			Err(Error::AccessDenied { .. }) => Some(ctx.user_id()),
+			Err(err) => return Err(err),
+		};
+
+		let sqlx_query = sqlx::query_as::<_, UsageDaily>(USAGE_DAILY_SELECT)
+			.bind(report_q.group_by)
+			.bind(days)
+			.bind(user_id);
+
+		Ok(mm.dbx().fetch_all(sqlx_query).await?)
+	}
+}
+
+/// The quotas of the user ($1), of its org, and of the agent ($2),
+/// with their usage of the current day (UTC).
+const USAGE_QUOTA_STATES_SELECT: &str = r#"
+SELECT q.scope, q.scope_id, q.daily_tokens_max, q.daily_cost_micros_max,
+	COALESCE(SUM(r.prompt_tokens + r.completion_tokens), 0)::bigint AS tokens,
+	COALESCE(SUM(r.cost_micros), 0)::bigint AS cost_micros
+FROM usage_quota q
+LEFT JOIN usage_record r
+	ON r.ctime >= date_trunc('day', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'
+	AND q.scope_id = CASE q.scope
+		WHEN 'User' THEN r.user_id
+		WHEN 'Agent' THEN r.agent_id
+		ELSE r.org_id
+	END
+WHERE (q.scope = 'User' AND q.scope_id = $1)
+	OR (q.scope = 'Agent' AND q.scope_id = $2)
+	OR (q.scope = 'Org' AND q.scope_id = (SELECT org_id FROM "user" WHERE id = $1))
+GROUP BY q.id
+ORDER BY q.scope, q.id
+"#;
+
+/// The daily usage by scope ($1) of the last days ($2), of one user ($3) or all (NULL).
+const USAGE_DAILY_SELECT: &str = r#"
+SELECT
+	to_char(date_trunc('day', r.ctime AT TIME ZONE 'UTC'), 'YYYY-MM-DD') AS day,
+	CASE $1::usage_scope
+		WHEN 'User' THEN r.user_id
+		WHEN 'Agent' THEN r.agent_id
+		ELSE r.org_id
+	END AS scope_id,
+	count(*) AS call_count,
+	SUM(r.prompt_tokens)::bigint AS prompt_tokens,
+	SUM(r.completion_tokens)::bigint AS completion_tokens,
+	SUM(r.cost_micros)::bigint AS cost_micros
+FROM usage_record r
+WHERE r.ctime >= date_trunc('day', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'
+		- make_interval(days => $2 - 1)
+	AND ($3::bigint IS NULL OR r.user_id = $3)
+GROUP BY 1, 2
+ORDER BY 1, 2
+"#;
+
+// endregion: --- UsageBmc
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, clean_users, seed_agent, seed_conv, seed_user};
+	use crate::model;
+	use crate::model::agent::AgentBmc;
+	use crate::model::conv::ConvBmc;
+	use crate::model::conv_msg::ConvMsgForCreate;
+	use crate::model::conv_user::ConvUserForCreate;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_generate_reply_usage_and_quota_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let root_ctx = Ctx::root_ctx();
+		let fx_content = "test_generate_reply_usage_and_quota_ok hello";
+		let agent_id = seed_agent(
+			&root_ctx,
+			&mm,
+			"test_generate_reply_usage_and_quota_ok agent",
+		)
+		.await?;
+		let conv_id = seed_conv(
+			&root_ctx,
+			&mm,
+			agent_id,
+			"test_generate_reply_usage_and_quota_ok conv",
+		)
+		.await?;
+		let user_id = seed_user(
+			&root_ctx,
+			&mm,
+			"test_generate_reply_usage_and_quota_ok-user",
+		)
+		.await?;
+		let user_ctx = Ctx::new(user_id)?;
+		ConvBmc::upsert_user(
+			&root_ctx,
+			&mm,
+			ConvUserForCreate {
+				conv_id,
+				user_id,
+				auto_respond: None,
+			},
+		)
+		.await?;
+		let msg_c = ConvMsgForCreate {
+			conv_id,
+			content: fx_content.to_string(),
+			..Default::default()
+		};
+		ConvBmc::add_msg(&user_ctx, &mm, msg_c).await?;
+
+		// -- Exec
+		let msg_ids = ConvBmc::generate_reply(&user_ctx, &mm, conv_id).await?;
+		let records = UsageBmc::list_for_conv(&user_ctx, &mm, conv_id).await?;
+		let report = UsageBmc::report(
+			&user_ctx,
+			&mm,
+			UsageForReport {
+				group_by: UsageScope::Agent,
+				days: None,
+			},
+		)
+		.await?;
+		// quota reached by the reply above
+		let quota_id = UsageQuotaBmc::create(
+			&root_ctx,
+			&mm,
+			UsageQuotaForCreate {
+				scope: UsageScope::User,
+				scope_id: user_id,
+				daily_tokens_max: Some(1),
+				daily_cost_micros_max: None,
+			},
+		)
+		.await?;
+		let res = ConvBmc::generate_reply(&user_ctx, &mm, conv_id).await;
+		let res_quota_create = UsageQuotaBmc::create(
+			&user_ctx,
+			&mm,
+			UsageQuotaForCreate {
+				scope: UsageScope::User,
+				scope_id: user_id,
+				daily_tokens_max: None,
+				daily_cost_micros_max: None,
+			},
+		)
+		.await;
+
+		// -- Check
+		// (parrot: 11 tokens of the user msg, 11 tokens of the reply, 1 + 2 micros per token)
+		assert_eq!(records.len(), 1);
+		let record = &records[0];
+		assert_eq!(record.msg_id, Some(msg_ids[0]));
+		assert_eq!(record.user_id, user_id);
+		assert_eq!(record.agent_id, agent_id);
+		assert_eq!(record.prompt_tokens, 11);
+		assert_eq!(record.completion_tokens, 11);
+		assert_eq!(record.cost_micros, 33);
+
+		assert_eq!(report.len(), 1, "only the user usage");
+		assert_eq!(report[0].scope_id, Some(agent_id));
+		assert_eq!(report[0].call_count, 1);
+		assert_eq!(report[0].cost_micros, 33);
+
+		assert!(
+			matches!(
+				res,
+				Err(model::Error::UsageQuotaExceeded { scope: UsageScope::User, scope_id })
+					if scope_id == user_id
+			),
+			"should be quota exceeded, but was {res:?}"
+		);
+		assert!(
+			matches!(res_quota_create, Err(model::Error::AccessDenied { .. })),
+			"quotas should be sys only"
+		);
+
+		// -- Clean
+		UsageQuotaBmc::delete(&root_ctx, &mm, quota_id).await?;
+		AgentBmc::delete(&root_ctx, &mm, agent_id).await?;
+		clean_users(&root_ctx, &mm, "test_generate_reply_usage_and_quota_ok")
+			.await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_usage_record_err_append_only() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let agent_id =
+			seed_agent(&ctx, &mm, "test_usage_record_err_append_only agent").await?;
+		let conv_id = seed_conv(
+			&ctx,
+			&mm,
+			agent_id,
+			"test_usage_record_err_append_only conv",
+		)
+		.await?;
+		let msg_c = ConvMsgForCreate {
+			conv_id,
+			content: "hello".to_string(),
+			..Default::default()
+		};
+		ConvBmc::add_msg(&ctx, &mm, msg_c).await?;
+		ConvBmc::generate_reply(&ctx, &mm, conv_id).await?;
+		let fx_records = UsageBmc::list_for_conv(&ctx, &mm, conv_id).await?;
+
+		// -- Exec
+		let res_update = sqlx::query(
+			"UPDATE usage_record SET cost_micros = 0 WHERE conv_id = $1",
+		)
+		.bind(conv_id)
+		.execute(mm.dbx().db())
+		.await;
+		let res_delete = sqlx::query("DELETE FROM usage_record WHERE conv_id = $1")
+			.bind(conv_id)
+			.execute(mm.dbx().db())
+			.await;
+
+		// -- Check
+		assert!(res_update.is_err(), "update should fail");
+		assert!(res_delete.is_err(), "delete should fail");
+		let records = UsageBmc::list_for_conv(&ctx, &mm, conv_id).await?;
+		assert_eq!(records.len(), 1);
+		assert_eq!(records[0].cost_micros, fx_records[0].cost_micros);
+		assert_ne!(records[0].cost_micros, 0);
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
@@ -1,10 +1,10 @@
 use crate::ctx::Ctx;
-use crate::model::base::{self, prep_fields_for_update, DbBmc};
+use crate::model::base::{self, DbBmc};
 use crate::model::modql_utils::time_to_sea_value;
 use crate::model::ModelManager;
 use crate::model::{Error, Result};
 use lib_auth::pwd::{self, ContentToHash};
-use modql::field::{Fields, HasSeaFields, SeaField, SeaFields};
+use modql::field::{Fields, HasSeaFields};
 use modql::filter::{
 	FilterNodes, ListOptions, OpValsInt64, OpValsString, OpValsValue,
 };
@@ -46,6 +46,11 @@ pub struct UserForInsert {
 	pub username: String,
 }
 
+#[derive(Fields)]
+struct UserForUpdatePwd {
+	pwd: String,
+}
+
 #[derive(Clone, FromRow, Fields, Debug)]
 pub struct UserForLogin {
 	pub id: i64,
@@ -78,9 +83,7 @@ impl UserBy for UserForAuth {}
 //       we use in our specific code.
 #[derive(Iden)]
 enum UserIden {
-	Id,
 	Username,
-	Pwd,
 }
 
 #[derive(FilterNodes, Deserialize, Default, Debug)]
@@ -105,6 +108,10 @@ pub struct UserBmc;
 
 impl DbBmc for UserBmc {
 	const TABLE: &'static str = "user";
+
+	fn audit_redacted_fields() -> &'static [&'static str] {
+		&["pwd", "pwd_salt", "token_salt"]
+	}
 }
 
 impl UserBmc {
@@ -123,33 +130,29 @@ impl UserBmc {
 			username: username.to_string(),
 		};
 
-		// Start the transaction
-		let mm = mm.new_with_txn()?;
-
-		mm.dbx().begin_txn().await?;
-
-		let user_id = base::create::<Self, _>(ctx, &mm, user_fi).await.map_err(
-			|model_error| {
-				Error::resolve_unique_violation(
-					model_error,
-					Some(|table: &str, constraint: &str| {
-						if table == "user" && constraint.contains("username") {
-							Some(Error::UserAlreadyExists { username })
-						} else {
-							None // Error::UniqueViolation will be created by resolve_unique_violation
-						}
-					}),
-				)
-			},
-		)?;
-
-		// -- Update the database
-		Self::update_pwd(ctx, &mm, user_id, &pwd_clear).await?;
-
-		// Commit the transaction
-		mm.dbx().commit_txn().await?;
-
-		Ok(user_id)
+		// -- Create the user row and set its pwd in a transaction
+		mm.in_txn(|mm| async move {
+			let user_id = base::create::<Self, _>(ctx, &mm, user_fi).await.map_err(
+				|model_error| {
+					Error::resolve_unique_violation(
+						model_error,
+						Some(|table: &str, constraint: &str| {
+							if table == "user" && constraint.contains("username") {
+								Some(Error::UserAlreadyExists { username })
+							} else {
+								None // Error::UniqueViolation will be created by resolve_unique_violation
+							}
+						}),
+					)
+				},
+			)?;
+
+			// -- Update the database
+			Self::update_pwd(ctx, &mm, user_id, &pwd_clear).await?;
+
+			Ok(user_id)
+		})
+		.await
 	}
 
 	pub async fn get<E>(ctx: &Ctx, mm: &ModelManager, id: i64) -> Result<E>
@@ -206,24 +209,8 @@ impl UserBmc {
 		})
 		.await?;
 
-		// -- Prep the data
-		let mut fields = SeaFields::new(vec![SeaField::new(UserIden::Pwd, pwd)]);
-		prep_fields_for_update::<Self>(&mut fields, ctx.user_id());
-
-		// -- Build query
-		let fields = fields.for_sea_update();
-		let mut query = Query::update();
-		query
-			.table(Self::table_ref())
-			.values(fields)
-			.and_where(Expr::col(UserIden::Id).eq(id));
-
-		// -- Exec query
-		let (sql, values) = query.build_sqlx(PostgresQueryBuilder);
-		let sqlx_query = sqlx::query_with(&sql, values);
-		let _count = mm.dbx().execute(sqlx_query).await?;
-
-		Ok(())
+		// -- Update (audited, with the pwd redacted, see `audit_redacted_fields`)
+		base::update::<Self, _>(ctx, mm, id, UserForUpdatePwd { pwd }).await
 	}
 
 	/// TODO: For User, deletion will require a soft-delete approach:
@@ -0,0 +1,492 @@
+use crate::ctx::Ctx;
+use crate::model::acs::assert_conv_user;
+use crate::model::agent::{Agent, AgentBmc, AgentFilter};
+use crate::model::base::{self, DbBmc};
+use crate::model::conv::{Conv, ConvBmc, ConvFilter};
+use crate::model::{Error, ListPage, ModelManager, Result};
+use lib_utils::time::Rfc3339;
+use modql::field::{Fields, HasFields, HasSeaFields};
+use modql::filter::{FilterGroups, ListOptions, OpValInt64, OpValsInt64};
+use serde::Serialize;
+use serde_with::serde_as;
+use sqlx::postgres::PgRow;
+use sqlx::FromRow;
+use time::OffsetDateTime;
+
+/// The max number of pinned convs (and of pinned agents) per user
+/// (as they are all listed on the first page).
+pub const USER_FAVORITE_MAX: usize = 50;
+
+// region:    --- Types
+
+/// A conv or agent pinned by a user (exactly one of `conv_id` or `agent_id`).
+#[serde_as]
+#[derive(Debug, Clone, Fields, FromRow, Serialize)]
+pub struct UserFavorite {
+	pub id: i64,
+
+	// -- FK
+	pub user_id: i64,
+	pub conv_id: Option<i64>,
+	pub agent_id: Option<i64>,
+
+	// -- Timestamps
+	// creator user_id and time
+	pub cid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub ctime: OffsetDateTime,
+	// last modifier user_id and time
+	pub mid: i64,
+	#[serde_as(as = "Rfc3339")]
+	pub mtime: OffsetDateTime,
+}
+
+#[derive(Fields)]
+pub(in crate::model) struct UserFavoriteForInsert {
+	pub user_id: i64,
+	pub conv_id: Option<i64>,
+	pub agent_id: Option<i64>,
+}
+
+/// The entity kind of a `UserFavorite`.
+#[derive(Debug, Clone, Copy)]
+enum FavoriteKind {
+	Conv,
+	Agent,
+}
+
+impl FavoriteKind {
+	fn column(&self) -> &'static str {
+		match self {
+			FavoriteKind::Conv => "conv_id",
+			FavoriteKind::Agent => "agent_id",
+		}
+	}
+}
+
+/// The entity filters which can be restricted by id (see `list_page_pinned_first`).
+trait IdFilter: Default + Clone {
+	fn id_mut(&mut self) -> &mut Option<OpValsInt64>;
+}
+
+impl IdFilter for ConvFilter {
+	fn id_mut(&mut self) -> &mut Option<OpValsInt64> {
+		&mut self.id
+	}
+}
+
+impl IdFilter for AgentFilter {
+	fn id_mut(&mut self) -> &mut Option<OpValsInt64> {
+		&mut self.id
+	}
+}
+
+// endregion: --- Types
+
+// region:    --- UserFavoriteBmc
+
+pub struct UserFavoriteBmc;
+
+impl DbBmc for UserFavoriteBmc {
+	const TABLE: &'static str = "user_favorite";
+}
+
+impl UserFavoriteBmc {
+	/// Pin the entity for the ctx user (no-op if already pinned).
+	async fn pin(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		kind: FavoriteKind,
+		id: i64,
+	) -> Result<()> {
+		let pinned_ids = Self::pinned_ids(ctx, mm, kind).await?;
+		if pinned_ids.contains(&id) {
+			return Ok(());
+		}
+		if pinned_ids.len() >= USER_FAVORITE_MAX {
+			return Err(Error::UserFavoritesOverMax {
+				max: USER_FAVORITE_MAX,
+			});
+		}
+
+		let (conv_id, agent_id) = match kind {
+			FavoriteKind::Conv => (Some(id), None),
+			FavoriteKind::Agent => (None, Some(id)),
+		};
+		let favorite_i = UserFavoriteForInsert {
+			user_id: ctx.user_id(),
+			conv_id,
+			agent_id,
+		};
+		base::create::<Self, _>(ctx, mm, favorite_i).await?;
+
+		Ok(())
+	}
+
+	/// Unpin the entity for the ctx user (no-op if not pinned).
+	async fn unpin(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		kind: FavoriteKind,
+		id: i64,
+	) -> Result<()> {
+		let sql = format!(
+			"SELECT id FROM user_favorite WHERE user_id = $1 AND {} = $2",
+			kind.column()
+		);
+		let sqlx_query = sqlx::query_as::<_, (i64,)>(&sql)
+			.bind(ctx.user_id())
+			.bind(id);
+		if let Some((favorite_id,)) = mm.dbx().fetch_optional(sqlx_query).await? {
+			base::delete::<Self>(ctx, mm, favorite_id).await?;
+		}
+
+		Ok(())
+	}
+
+	/// Returns the ids of the entities of the `kind` pinned by the ctx user.
+	async fn pinned_ids(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		kind: FavoriteKind,
+	) -> Result<Vec<i64>> {
+		let column = kind.column();
+		let sql = format!(
+			"SELECT {column} FROM user_favorite
+			WHERE user_id = $1 AND {column} IS NOT NULL ORDER BY id"
+		);
+		let sqlx_query = sqlx::query_as::<_, (i64,)>(&sql).bind(ctx.user_id());
+		let ids = mm.dbx().fetch_all(sqlx_query).await?;
+
+		Ok(ids.into_iter().map(|(id,)| id).collect())
+	}
+}
+
+// endregion: --- UserFavoriteBmc
+
+// region:    --- ConvBmc & AgentBmc Pins
+
+impl ConvBmc {
+	/// Pin the conv for the ctx user (must be a conv user).
+	pub async fn pin(ctx: &Ctx, mm: &ModelManager, conv_id: i64) -> Result<()> {
+		assert_conv_user(ctx, mm, conv_id).await?;
+
+		UserFavoriteBmc::pin(ctx, mm, FavoriteKind::Conv, conv_id).await
+	}
+
+	pub async fn unpin(ctx: &Ctx, mm: &ModelManager, conv_id: i64) -> Result<()> {
+		UserFavoriteBmc::unpin(ctx, mm, FavoriteKind::Conv, conv_id).await
+	}
+
+	/// Same as `ConvBmc::list_page`, but only the convs visible by the ctx user,
+	/// with its pinned ones first (see `list_page_pinned_first`), and their `pinned` set.
+	pub async fn list_page_pinned_first(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: Option<Vec<ConvFilter>>,
+		list_options: Option<ListOptions>,
+		cursor: Option<String>,
+	) -> Result<ListPage<Conv>> {
+		let pinned_ids =
+			UserFavoriteBmc::pinned_ids(ctx, mm, FavoriteKind::Conv).await?;
+		let filters = Self::visible_filters(ctx, filters);
+		let mut page: ListPage<Conv> = list_page_pinned_first::<Self, _, _>(
+			ctx,
+			mm,
+			&pinned_ids,
+			filters,
+			list_options,
+			cursor,
+		)
+		.await?;
+		for conv in page.items.iter_mut() {
+			conv.pinned = Some(pinned_ids.contains(&conv.id));
+		}
+
+		Ok(page)
+	}
+}
+
+impl AgentBmc {
+	pub async fn pin(ctx: &Ctx, mm: &ModelManager, agent_id: i64) -> Result<()> {
+		Self::get_visible(ctx, mm, agent_id).await?;
+
+		UserFavoriteBmc::pin(ctx, mm, FavoriteKind::Agent, agent_id).await
+	}
+
+	pub async fn unpin(ctx: &Ctx, mm: &ModelManager, agent_id: i64) -> Result<()> {
+		UserFavoriteBmc::unpin(ctx, mm, FavoriteKind::Agent, agent_id).await
+	}
+
+	/// Same as `AgentBmc::list_page`, but only the agents visible by the ctx user,
+	/// with its pinned ones first (see `list_page_pinned_first`), and their `pinned` set.
+	pub async fn list_page_pinned_first(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		filters: Option<Vec<AgentFilter>>,
+		list_options: Option<ListOptions>,
+		cursor: Option<String>,
+	) -> Result<ListPage<Agent>> {
+		let pinned_ids =
+			UserFavoriteBmc::pinned_ids(ctx, mm, FavoriteKind::Agent).await?;
+		let filters = Self::visible_filters(ctx, filters);
+		let mut page: ListPage<Agent> = list_page_pinned_first::<Self, _, _>(
+			ctx,
+			mm,
+			&pinned_ids,
+			filters,
+			list_options,
+			cursor,
+		)
+		.await?;
+		for agent in page.items.iter_mut() {
+			agent.pinned = Some(pinned_ids.contains(&agent.id));
+		}
+
+		Ok(page)
+	}
+}
+
+// endregion: --- ConvBmc & AgentBmc Pins
+
+// region:    --- Support
+
+/// Returns the `base::list_page` of the not pinned entities, with the pinned ones
+/// (matching the filters, in the same order) before the items of the first page.
+///
+/// Notes:
+///   - The pinned items are in addition to the `limit` (at most `USER_FAVORITE_MAX`),
+///     and counted in the `total`.
+///   - The cursor and offset only apply to the not pinned entities.
+async fn list_page_pinned_first<MC, E, F>(
+	ctx: &Ctx,
+	mm: &ModelManager,
+	pinned_ids: &[i64],
+	filters: Option<Vec<F>>,
+	list_options: Option<ListOptions>,
+	cursor: Option<String>,
+) -> Result<ListPage<E>>
+where
+	MC: DbBmc,
+	F: IdFilter,
+	Vec<F>: Into<FilterGroups>,
+	E: for<'r> FromRow<'r, PgRow> + Unpin + Send,
+	E: HasFields + HasSeaFields,
+{
+	if pinned_ids.is_empty() {
+		return base::list_page::<MC, _, _>(ctx, mm, filters, list_options, cursor)
+			.await;
+	}
+
+	let is_first_page = cursor.is_none()
+		&& list_options
+			.as_ref()
+			.and_then(|list_options| list_options.offset)
+			.unwrap_or(0)
+			== 0;
+	let order_bys = list_options
+		.as_ref()
+		.and_then(|list_options| list_options.order_bys.clone());
+
+	// -- The not pinned page
+	let not_pinned_filters =
+		with_id_op(filters.clone(), OpValInt64::NotIn(pinned_ids.to_vec()));
+	let mut page = base::list_page::<MC, _, _>(
+		ctx,
+		mm,
+		Some(not_pinned_filters),
+		list_options,
+		cursor,
+	)
+	.await?;
+
+	// -- The pinned items
+	let pinned_filters = with_id_op(filters, OpValInt64::In(pinned_ids.to_vec()));
+	if is_first_page {
+		let list_options = ListOptions {
+			order_bys,
+			..Default::default()
+		};
+		let pinned: Vec<E> = base::list::<MC, _, _>(
+			ctx,
+			mm,
+			Some(pinned_filters),
+			Some(list_options),
+		)
+		.await?;
+		page.total += pinned.len() as i64;
+		page.items.splice(0..0, pinned);
+	} else {
+		page.total += base::count::<MC, _>(ctx, mm, Some(pinned_filters)).await?;
+	}
+
+	Ok(page)
+}
+
+/// Returns the filter groups (or a default one) with the `op` added to their `id`
+/// (and-ed with their eventual id conditions).
+fn with_id_op<F: IdFilter>(filters: Option<Vec<F>>, op: OpValInt64) -> Vec<F> {
+	let mut filters = filters.unwrap_or_default();
+	if filters.is_empty() {
+		filters.push(F::default());
+	}
+
+	for filter in filters.iter_mut() {
+		match filter.id_mut() {
+			Some(OpValsInt64(ops)) => ops.push(op.clone()),
+			id @ None => *id = Some(op.clone().into()),
+		}
+	}
+
+	filters
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::_dev_utils::{self, clean_users, seed_agent, seed_conv, seed_user};
+	use crate::model;
+	use crate::model::conv_user::ConvUserForCreate;
+	use modql::filter::OpValString;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_list_page_pinned_first_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = _dev_utils::init_test().await;
+		let ctx = Ctx::root_ctx();
+		let fx_prefix = "test_list_page_pinned_first_ok";
+		let agent_id = seed_agent(&ctx, &mm, &format!("{fx_prefix} agent")).await?;
+		let owner_id =
+			seed_user(&ctx, &mm, &format!("{fx_prefix}-user-owner")).await?;
+		let member_id =
+			seed_user(&ctx, &mm, &format!("{fx_prefix}-user-member")).await?;
+		let owner_ctx = Ctx::new(owner_id)?;
+		let member_ctx = Ctx::new(member_id)?;
+		let mut conv_ids = Vec::new();
+		for num in 1..=5 {
+			let title = format!("{fx_prefix} conv {num:02}");
+			conv_ids.push(seed_conv(&owner_ctx, &mm, agent_id, &title).await?);
+		}
+		let title = format!("{fx_prefix} conv 06");
+		seed_conv(&member_ctx, &mm, agent_id, &title).await?;
+		ConvBmc::upsert_user(
+			&ctx,
+			&mm,
+			ConvUserForCreate {
+				conv_id: conv_ids[2],
+				user_id: member_id,
+				auto_respond: None,
+			},
+		)
+		.await?;
+		let filters = || {
+			Some(vec![ConvFilter {
+				title: Some(OpValString::StartsWith(fx_prefix.to_string()).into()),
+				..Default::default()
+			}])
+		};
+		let list_options = |limit: i64| ListOptions {
+			order_bys: Some("id".into()),
+			limit: Some(limit),
+			..Default::default()
+		};
+		let nums = |page: &ListPage<Conv>| -> Vec<(String, Option<bool>)> {
+			page.items
+				.iter()
+				.filter_map(|conv| {
+					let num = conv.title.as_ref()?.rsplit(' ').next()?.to_string();
+					Some((num, conv.pinned))
+				})
+				.collect()
+		};
+
+		// -- Exec
+		ConvBmc::pin(&owner_ctx, &mm, conv_ids[3]).await?;
+		ConvBmc::pin(&owner_ctx, &mm, conv_ids[1]).await?;
+		// twice is a no-op
+		ConvBmc::pin(&owner_ctx, &mm, conv_ids[1]).await?;
+		ConvBmc::pin(&member_ctx, &mm, conv_ids[2]).await?;
+		let res_member_pin = ConvBmc::pin(&member_ctx, &mm, conv_ids[0]).await;
+
+		let owner_page_01 = ConvBmc::list_page_pinned_first(
+			&owner_ctx,
+			&mm,
+			filters(),
+			Some(list_options(2)),
+			None,
+		)
+		.await?;
+		let owner_page_02 = ConvBmc::list_page_pinned_first(
+			&owner_ctx,
+			&mm,
+			filters(),
+			Some(list_options(2)),
+			owner_page_01.next_cursor.clone(),
+		)
+		.await?;
+		let member_page = ConvBmc::list_page_pinned_first(
+			&member_ctx,
+			&mm,
+			filters(),
+			Some(list_options(10)),
+			None,
+		)
+		.await?;
+		ConvBmc::unpin(&owner_ctx, &mm, conv_ids[1]).await?;
+		let owner_page_unpinned = ConvBmc::list_page_pinned_first(
+			&owner_ctx,
+			&mm,
+			filters(),
+			Some(list_options(10)),
+			None,
+		)
+		.await?;
+
+		// -- Check
+		let pinned = |num: &str| (num.to_string(), Some(true));
+		let not_pinned = |num: &str| (num.to_string(), Some(false));
+		assert_eq!(
+			nums(&owner_page_01),
+			[
+				pinned("02"),
+				pinned("04"),
+				not_pinned("01"),
+				not_pinned("03")
+			]
+		);
+		assert_eq!(owner_page_01.total, 5);
+		assert_eq!(nums(&owner_page_02), [not_pinned("05")]);
+		assert_eq!(owner_page_02.total, 5);
+		assert_eq!(
+			nums(&member_page),
+			[pinned("03"), not_pinned("06")],
+			"pins are per user, and only the visible convs are listed"
+		);
+		assert_eq!(member_page.total, 2);
+		assert_eq!(
+			nums(&owner_page_unpinned)[..2],
+			[pinned("04"), not_pinned("01")]
+		);
+		assert!(
+			matches!(res_member_pin, Err(model::Error::AccessDenied { .. })),
+			"should be AccessDenied, but was {res_member_pin:?}"
+		);
+
+		// -- Clean
+		AgentBmc::delete(&ctx, &mm, agent_id).await?;
+		clean_users(&ctx, &mm, fx_prefix).await?;
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
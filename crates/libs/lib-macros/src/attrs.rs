@@ -0,0 +1,416 @@
+//! Parsing of the `#[bmc(...)]` attribute of the `#[derive(Bmc)]`.
+
+use heck::ToSnakeCase;
+use syn::meta::ParseNestedMeta;
+use syn::punctuated::Punctuated;
+use syn::{
+	bracketed, token, Data, DeriveInput, Error, Ident, LitBool, LitStr, Result,
+	Token, Type, Visibility,
+};
+
+// region:    --- Op
+
+/// The groups of CRUD functions which can be generated
+/// (`get` is always generated).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+	Create,
+	List,
+	Update,
+	Upsert,
+	Delete,
+}
+
+impl Op {
+	fn from_ident(ident: &Ident) -> Result<Op> {
+		match ident.to_string().as_str() {
+			"create" => Ok(Op::Create),
+			"list" => Ok(Op::List),
+			"update" => Ok(Op::Update),
+			"upsert" => Ok(Op::Upsert),
+			"delete" => Ok(Op::Delete),
+			_ => Err(Error::new_spanned(
+				ident,
+				"unknown bmc op, expected one of: create, list, update, upsert, delete",
+			)),
+		}
+	}
+
+	/// Returns the op of a Bmc method name (`None` for `get`),
+	/// or an error if it is not a generated method.
+	fn from_method(method: &Ident) -> Result<Option<Op>> {
+		match method.to_string().as_str() {
+			"create" | "create_many" => Ok(Some(Op::Create)),
+			"get" => Ok(None),
+			"first" | "list" | "list_page" | "count" => Ok(Some(Op::List)),
+			"update" | "update_many" | "update_by_filter" => Ok(Some(Op::Update)),
+			"upsert" => Ok(Some(Op::Upsert)),
+			"delete" | "delete_many" => Ok(Some(Op::Delete)),
+			_ => Err(Error::new_spanned(
+				method,
+				"unknown bmc method for the privilege, expected one of: create, \
+				 create_many, get, first, list, list_page, count, update, update_many, \
+				 update_by_filter, upsert, delete, delete_many",
+			)),
+		}
+	}
+
+	fn name(&self) -> &'static str {
+		match self {
+			Op::Create => "create",
+			Op::List => "list",
+			Op::Update => "update",
+			Op::Upsert => "upsert",
+			Op::Delete => "delete",
+		}
+	}
+}
+
+// endregion: --- Op
+
+// region:    --- BmcAttrs
+
+pub struct RpcAttrs {
+	pub suffix: String,
+	pub plural: String,
+	/// The generated rpc fns to skip (e.g., to be written by hand).
+	pub skip: Vec<Ident>,
+}
+
+pub struct BmcAttrs {
+	pub vis: Visibility,
+	pub entity: Ident,
+	pub bmc: Ident,
+	pub table: LitStr,
+
+	pub owner_id: bool,
+	pub timestamps: bool,
+	pub audit_log: bool,
+	pub unique_key: Option<Vec<LitStr>>,
+
+	pub for_create: Option<Type>,
+	pub for_update: Option<Type>,
+	pub for_upsert: Option<Type>,
+	pub filter: Option<Type>,
+
+	pub ops: Vec<Op>,
+	/// The (method, privilege) pairs.
+	pub privileges: Vec<(Ident, Ident)>,
+
+	pub rpc: Option<RpcAttrs>,
+}
+
+impl BmcAttrs {
+	pub fn has_op(&self, op: Op) -> bool {
+		self.ops.contains(&op)
+	}
+
+	/// Returns the `acs::Privilege` variant required by the Bmc `method`.
+	pub fn privilege(&self, method: &str) -> Option<&Ident> {
+		self.privileges
+			.iter()
+			.find(|(m, _)| m == method)
+			.map(|(_, privilege)| privilege)
+	}
+}
+
+impl BmcAttrs {
+	pub fn from_derive_input(input: &DeriveInput) -> Result<Self> {
+		if !matches!(input.data, Data::Struct(_)) {
+			return Err(Error::new_spanned(
+				&input.ident,
+				"derive(Bmc) is only supported on structs",
+			));
+		}
+
+		let entity = input.ident.clone();
+
+		let mut bmc: Option<Ident> = None;
+		let mut table: Option<LitStr> = None;
+		let mut owner_id = false;
+		let mut timestamps = true;
+		let mut audit_log = true;
+		let mut unique_key: Option<Vec<LitStr>> = None;
+		let mut for_create: Option<Type> = None;
+		let mut for_update: Option<Type> = None;
+		let mut for_upsert: Option<Type> = None;
+		let mut filter: Option<Type> = None;
+		let mut ops: Option<Vec<(Op, Ident)>> = None;
+		let mut privileges: Vec<(Ident, Ident)> = Vec::new();
+		let mut rpc: Option<RpcAttrs> = None;
+
+		for attr in input.attrs.iter().filter(|a| a.path().is_ident("bmc")) {
+			attr.parse_nested_meta(|meta| {
+				if meta.path.is_ident("table") {
+					table = Some(meta.value()?.parse()?);
+				} else if meta.path.is_ident("name") {
+					bmc = Some(meta.value()?.parse()?);
+				} else if meta.path.is_ident("owner_id") {
+					owner_id = parse_flag(&meta)?;
+				} else if meta.path.is_ident("timestamps") {
+					timestamps = parse_flag(&meta)?;
+				} else if meta.path.is_ident("audit_log") {
+					audit_log = parse_flag(&meta)?;
+				} else if meta.path.is_ident("unique_key") {
+					let value = meta.value()?;
+					let content;
+					bracketed!(content in value);
+					let cols =
+						Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+					unique_key = Some(cols.into_iter().collect());
+				} else if meta.path.is_ident("for_create") {
+					for_create = Some(meta.value()?.parse()?);
+				} else if meta.path.is_ident("for_update") {
+					for_update = Some(meta.value()?.parse()?);
+				} else if meta.path.is_ident("for_upsert") {
+					for_upsert = Some(meta.value()?.parse()?);
+				} else if meta.path.is_ident("filter") {
+					filter = Some(meta.value()?.parse()?);
+				} else if meta.path.is_ident("ops") {
+					let ops = ops.get_or_insert_with(Vec::new);
+					meta.parse_nested_meta(|op_meta| {
+						let ident = op_meta.path.require_ident()?;
+						ops.push((Op::from_ident(ident)?, ident.clone()));
+						Ok(())
+					})?;
+				} else if meta.path.is_ident("privileges") {
+					meta.parse_nested_meta(|privilege_meta| {
+						let method = privilege_meta.path.require_ident()?.clone();
+						let privilege: Ident = privilege_meta.value()?.parse()?;
+						privileges.push((method, privilege));
+						Ok(())
+					})?;
+				} else if meta.path.is_ident("rpc") {
+					rpc = Some(parse_rpc(&meta, &entity)?);
+				} else {
+					return Err(meta.error("unsupported bmc property"));
+				}
+				Ok(())
+			})?;
+		}
+
+		let table = table.ok_or_else(|| {
+			Error::new_spanned(
+				&entity,
+				"derive(Bmc) requires #[bmc(table = \"...\")]",
+			)
+		})?;
+		let bmc = bmc.unwrap_or_else(|| quote::format_ident!("{entity}Bmc"));
+
+		// -- Resolve the ops (default: the ones with their types given)
+		let has_type = |op: Op| match op {
+			Op::Create => for_create.is_some(),
+			Op::List => filter.is_some(),
+			Op::Update => for_update.is_some(),
+			Op::Upsert => for_upsert.is_some(),
+			Op::Delete => true,
+		};
+		let ops: Vec<Op> = match ops {
+			Some(ops) => {
+				for (op, ident) in ops.iter() {
+					if !has_type(*op) {
+						return Err(Error::new_spanned(
+							ident,
+							format!(
+								"bmc op '{}' requires its type property",
+								op.name()
+							),
+						));
+					}
+				}
+				ops.into_iter().map(|(op, _)| op).collect()
+			}
+			None => [Op::Create, Op::List, Op::Update, Op::Upsert, Op::Delete]
+				.into_iter()
+				.filter(|op| has_type(*op))
+				.collect(),
+		};
+
+		if ops.contains(&Op::Upsert) && unique_key.is_none() {
+			return Err(Error::new_spanned(
+				&entity,
+				"bmc op 'upsert' requires #[bmc(unique_key = [...])]",
+			));
+		}
+
+		// -- Validate the privileges
+		for (method, _) in privileges.iter() {
+			if let Some(op) = Op::from_method(method)? {
+				if !ops.contains(&op) {
+					return Err(Error::new_spanned(
+						method,
+						format!("bmc method '{method}' is not generated"),
+					));
+				}
+			}
+		}
+
+		Ok(BmcAttrs {
+			vis: input.vis.clone(),
+			entity,
+			bmc,
+			table,
+			owner_id,
+			timestamps,
+			audit_log,
+			unique_key,
+			for_create,
+			for_update,
+			for_upsert,
+			filter,
+			ops,
+			privileges,
+			rpc,
+		})
+	}
+}
+
+// endregion: --- BmcAttrs
+
+// region:    --- Support
+
+/// Parses `name` (true) or `name = bool`.
+fn parse_flag(meta: &ParseNestedMeta) -> Result<bool> {
+	if meta.input.peek(Token![=]) {
+		let value: LitBool = meta.value()?.parse()?;
+		Ok(value.value)
+	} else {
+		Ok(true)
+	}
+}
+
+/// Parses `rpc` or `rpc(suffix = "...", plural = "...", skip(rpc_fn, ...))`.
+fn parse_rpc(meta: &ParseNestedMeta, entity: &Ident) -> Result<RpcAttrs> {
+	let mut suffix: Option<String> = None;
+	let mut plural: Option<String> = None;
+	let mut skip: Vec<Ident> = Vec::new();
+
+	if meta.input.peek(token::Paren) {
+		meta.parse_nested_meta(|rpc_meta| {
+			if rpc_meta.path.is_ident("suffix") {
+				suffix = Some(rpc_meta.value()?.parse::<LitStr>()?.value());
+			} else if rpc_meta.path.is_ident("plural") {
+				plural = Some(rpc_meta.value()?.parse::<LitStr>()?.value());
+			} else if rpc_meta.path.is_ident("skip") {
+				rpc_meta.parse_nested_meta(|skip_meta| {
+					skip.push(skip_meta.path.require_ident()?.clone());
+					Ok(())
+				})?;
+			} else {
+				return Err(rpc_meta.error("unsupported bmc rpc property"));
+			}
+			Ok(())
+		})?;
+	}
+
+	let suffix = suffix.unwrap_or_else(|| entity.to_string().to_snake_case());
+	let plural = plural.unwrap_or_else(|| format!("{suffix}s"));
+
+	Ok(RpcAttrs {
+		suffix,
+		plural,
+		skip,
+	})
+}
+
+// endregion: --- Support
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use syn::parse_quote;
+
+	#[test]
+	fn test_bmc_attrs_defaults_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_input: DeriveInput = parse_quote! {
+			#[bmc(table = "conv_msg", for_create = ConvMsgForCreate, rpc)]
+			pub struct ConvMsg {
+				pub id: i64,
+			}
+		};
+
+		// -- Exec
+		let attrs = BmcAttrs::from_derive_input(&fx_input)?;
+
+		// -- Check
+		assert_eq!(attrs.bmc, "ConvMsgBmc");
+		assert!(attrs.timestamps && attrs.audit_log && !attrs.owner_id);
+		assert_eq!(attrs.ops, &[Op::Create, Op::Delete]);
+		let rpc = attrs.rpc.ok_or("should have rpc")?;
+		assert_eq!(rpc.suffix, "conv_msg");
+		assert_eq!(rpc.plural, "conv_msgs");
+		assert!(rpc.skip.is_empty());
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_bmc_attrs_rpc_skip_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_input: DeriveInput = parse_quote! {
+			#[bmc(table = "conv", rpc(plural = "convs", skip(list_convs, get_conv)))]
+			pub struct Conv {
+				pub id: i64,
+			}
+		};
+
+		// -- Exec
+		let attrs = BmcAttrs::from_derive_input(&fx_input)?;
+
+		// -- Check
+		let rpc = attrs.rpc.ok_or("should have rpc")?;
+		assert_eq!(rpc.suffix, "conv");
+		let skip: Vec<String> = rpc.skip.iter().map(|i| i.to_string()).collect();
+		assert_eq!(skip, &["list_convs", "get_conv"]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_bmc_attrs_err_op_without_type() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_input: DeriveInput = parse_quote! {
+			#[bmc(table = "agent", ops(create, delete))]
+			pub struct Agent {
+				pub id: i64,
+			}
+		};
+
+		// -- Exec
+		let res = BmcAttrs::from_derive_input(&fx_input);
+
+		// -- Check
+		let err = res.err().ok_or("should be an error")?;
+		assert!(err.to_string().contains("'create' requires its type"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn test_bmc_attrs_err_privilege_method_not_generated() -> Result<()> {
+		// -- Setup & Fixtures
+		let fx_input: DeriveInput = parse_quote! {
+			#[bmc(table = "agent", privileges(update = Sys))]
+			pub struct Agent {
+				pub id: i64,
+			}
+		};
+
+		// -- Exec
+		let res = BmcAttrs::from_derive_input(&fx_input);
+
+		// -- Check
+		let err = res.err().ok_or("should be an error")?;
+		assert!(err.to_string().contains("'update' is not generated"));
+
+		Ok(())
+	}
+}
+
+// endregion: --- Tests
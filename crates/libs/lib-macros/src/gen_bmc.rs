@@ -0,0 +1,462 @@
+//! Generation of the Bmc struct, its `DbBmc` implementation, and its CRUD functions.
+
+use crate::attrs::{BmcAttrs, Op};
+use proc_macro2::TokenStream;
+use quote::quote;
+
+pub fn gen_bmc(attrs: &BmcAttrs) -> TokenStream {
+	let BmcAttrs {
+		vis,
+		entity,
+		bmc,
+		table,
+		owner_id,
+		timestamps,
+		audit_log,
+		unique_key,
+		..
+	} = attrs;
+
+	// -- Bmc struct & DbBmc
+	let struct_doc = format!(
+		" The backend model controller of the `{}` table (entity `{entity}`).",
+		table.value()
+	);
+	let unique_key_fn = unique_key.as_ref().map(|cols| {
+		quote! {
+			fn unique_key() -> Option<&'static [&'static str]> {
+				Some(&[#(#cols),*])
+			}
+		}
+	});
+
+	// -- Crud fns
+	let mut fns: Vec<TokenStream> = Vec::new();
+	if attrs.has_op(Op::Create) {
+		fns.extend(create_fns(attrs));
+	}
+	fns.push(get_fn(attrs));
+	if attrs.has_op(Op::List) {
+		fns.extend(list_fns(attrs));
+	}
+	if attrs.has_op(Op::Update) {
+		fns.extend(update_fns(attrs));
+	}
+	if attrs.has_op(Op::Upsert) {
+		fns.push(upsert_fn(attrs));
+	}
+	if attrs.has_op(Op::Delete) {
+		fns.extend(delete_fns(attrs));
+	}
+
+	quote! {
+		#[doc = #struct_doc]
+		#vis struct #bmc;
+
+		impl crate::model::base::DbBmc for #bmc {
+			const TABLE: &'static str = #table;
+
+			fn has_timestamps() -> bool {
+				#timestamps
+			}
+
+			fn has_owner_id() -> bool {
+				#owner_id
+			}
+
+			fn has_audit_log() -> bool {
+				#audit_log
+			}
+
+			#unique_key_fn
+		}
+
+		impl #bmc {
+			#(#fns)*
+		}
+	}
+}
+
+// region:    --- Crud Fns
+
+fn create_fns(attrs: &BmcAttrs) -> Vec<TokenStream> {
+	let for_create = &attrs.for_create;
+	let table = attrs.table.value();
+
+	let create_docs = docs(
+		attrs,
+		"create",
+		&[format!("Creates a `{table}` and returns its id.")],
+	);
+	let create_check = privilege_check(attrs, "create");
+
+	let create_many_docs = docs(
+		attrs,
+		"create_many",
+		&[format!(
+			"Creates the `{table}`s (atomically) and returns their ids (in order)."
+		)],
+	);
+	let create_many_partial_docs = docs(attrs, "create_many", &[
+		format!("Creates the `{table}`s and returns the result of each of them (in order)."),
+		String::new(),
+		"Note: Each failing item is rolled back alone, and does not fail the others."
+			.to_string(),
+	]);
+	let create_many_check = privilege_check(attrs, "create_many");
+
+	vec![
+		quote! {
+			#create_docs
+			pub async fn create(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				entity_c: #for_create,
+			) -> crate::model::Result<i64> {
+				#create_check
+				crate::model::base::create::<Self, _>(ctx, mm, entity_c).await
+			}
+		},
+		quote! {
+			#create_many_docs
+			pub async fn create_many(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				entity_c: Vec<#for_create>,
+			) -> crate::model::Result<Vec<i64>> {
+				#create_many_check
+				crate::model::base::create_many::<Self, _>(ctx, mm, entity_c).await
+			}
+		},
+		quote! {
+			#create_many_partial_docs
+			pub async fn create_many_partial(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				entity_c: Vec<#for_create>,
+			) -> crate::model::Result<Vec<crate::model::Result<i64>>> {
+				#create_many_check
+				crate::model::base::create_many_partial::<Self, _>(ctx, mm, entity_c).await
+			}
+		},
+	]
+}
+
+fn get_fn(attrs: &BmcAttrs) -> TokenStream {
+	let entity = &attrs.entity;
+	let table = attrs.table.value();
+
+	let get_docs = docs(
+		attrs,
+		"get",
+		&[format!("Returns the `{table}` of this id.")],
+	);
+	let get_check = privilege_check(attrs, "get");
+
+	quote! {
+		#get_docs
+		pub async fn get(
+			ctx: &crate::ctx::Ctx,
+			mm: &crate::model::ModelManager,
+			id: i64,
+		) -> crate::model::Result<#entity> {
+			#get_check
+			crate::model::base::get::<Self, _>(ctx, mm, id).await
+		}
+	}
+}
+
+fn list_fns(attrs: &BmcAttrs) -> Vec<TokenStream> {
+	let entity = &attrs.entity;
+	let filter = &attrs.filter;
+	let table = attrs.table.value();
+
+	let first_docs = docs(
+		attrs,
+		"first",
+		&[format!(
+			"Returns the first `{table}` matching the filter, if any."
+		)],
+	);
+	let first_check = privilege_check(attrs, "first");
+
+	let list_docs = docs(
+		attrs,
+		"list",
+		&[format!(
+			"Returns the `{table}`s matching the filter (and list options)."
+		)],
+	);
+	let list_check = privilege_check(attrs, "list");
+
+	let list_page_docs = docs(
+		attrs,
+		"list_page",
+		&[
+			format!("Returns one page of the `{table}`s matching the filter,"),
+			"with the total count and the eventual `next_cursor`.".to_string(),
+		],
+	);
+	let list_page_check = privilege_check(attrs, "list_page");
+
+	let count_docs = docs(
+		attrs,
+		"count",
+		&[format!(
+			"Returns the number of `{table}`s matching the filter."
+		)],
+	);
+	let count_check = privilege_check(attrs, "count");
+
+	vec![
+		quote! {
+			#first_docs
+			pub async fn first(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				filter: Option<Vec<#filter>>,
+				list_options: Option<modql::filter::ListOptions>,
+			) -> crate::model::Result<Option<#entity>> {
+				#first_check
+				crate::model::base::first::<Self, _, _>(ctx, mm, filter, list_options).await
+			}
+		},
+		quote! {
+			#list_docs
+			pub async fn list(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				filter: Option<Vec<#filter>>,
+				list_options: Option<modql::filter::ListOptions>,
+			) -> crate::model::Result<Vec<#entity>> {
+				#list_check
+				crate::model::base::list::<Self, _, _>(ctx, mm, filter, list_options).await
+			}
+		},
+		quote! {
+			#list_page_docs
+			pub async fn list_page(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				filter: Option<Vec<#filter>>,
+				list_options: Option<modql::filter::ListOptions>,
+				cursor: Option<String>,
+			) -> crate::model::Result<crate::model::ListPage<#entity>> {
+				#list_page_check
+				crate::model::base::list_page::<Self, _, _>(
+					ctx,
+					mm,
+					filter,
+					list_options,
+					cursor,
+				)
+				.await
+			}
+		},
+		quote! {
+			#count_docs
+			pub async fn count(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				filter: Option<Vec<#filter>>,
+			) -> crate::model::Result<i64> {
+				#count_check
+				crate::model::base::count::<Self, _>(ctx, mm, filter).await
+			}
+		},
+	]
+}
+
+fn update_fns(attrs: &BmcAttrs) -> Vec<TokenStream> {
+	let for_update = &attrs.for_update;
+	let table = attrs.table.value();
+
+	let update_docs = docs(
+		attrs,
+		"update",
+		&[format!("Updates the `{table}` of this id.")],
+	);
+	let update_check = privilege_check(attrs, "update");
+
+	let update_many_docs = docs(
+		attrs,
+		"update_many",
+		&[
+			format!("Updates the `{table}`s of these ids (atomically),"),
+			"and returns the number of updated rows.".to_string(),
+		],
+	);
+	let update_many_check = privilege_check(attrs, "update_many");
+
+	let update_by_filter_docs = docs(
+		attrs,
+		"update_by_filter",
+		&[
+			format!("Updates all the `{table}`s matching the filter,"),
+			"and returns the number of updated rows.".to_string(),
+		],
+	);
+	let update_by_filter_check = privilege_check(attrs, "update_by_filter");
+
+	vec![
+		quote! {
+			#update_docs
+			pub async fn update(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				id: i64,
+				entity_u: #for_update,
+			) -> crate::model::Result<()> {
+				#update_check
+				crate::model::base::update::<Self, _>(ctx, mm, id, entity_u).await
+			}
+		},
+		quote! {
+			#update_many_docs
+			pub async fn update_many(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				ids: Vec<i64>,
+				entity_u: #for_update,
+			) -> crate::model::Result<u64> {
+				#update_many_check
+				crate::model::base::update_many::<Self, _>(ctx, mm, ids, entity_u).await
+			}
+		},
+		quote! {
+			#update_by_filter_docs
+			pub async fn update_by_filter<F>(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				filter: F,
+				entity_u: #for_update,
+			) -> crate::model::Result<u64>
+			where
+				F: Into<modql::filter::FilterGroups>,
+			{
+				#update_by_filter_check
+				crate::model::base::update_by_filter::<Self, _, _>(ctx, mm, filter, entity_u)
+					.await
+			}
+		},
+	]
+}
+
+fn upsert_fn(attrs: &BmcAttrs) -> TokenStream {
+	let for_upsert = &attrs.for_upsert;
+	let table = attrs.table.value();
+
+	let upsert_docs = docs(attrs, "upsert", &[
+		format!("Creates the `{table}`, or updates the one with the same unique key,"),
+		"and returns its id.".to_string(),
+	]);
+	let upsert_check = privilege_check(attrs, "upsert");
+
+	quote! {
+		#upsert_docs
+		pub async fn upsert(
+			ctx: &crate::ctx::Ctx,
+			mm: &crate::model::ModelManager,
+			entity_u: #for_upsert,
+		) -> crate::model::Result<i64> {
+			#upsert_check
+			crate::model::base::upsert::<Self, _>(ctx, mm, entity_u).await
+		}
+	}
+}
+
+fn delete_fns(attrs: &BmcAttrs) -> Vec<TokenStream> {
+	let table = attrs.table.value();
+
+	let delete_docs = docs(
+		attrs,
+		"delete",
+		&[format!("Deletes the `{table}` of this id.")],
+	);
+	let delete_check = privilege_check(attrs, "delete");
+
+	let delete_many_docs = docs(
+		attrs,
+		"delete_many",
+		&[
+			format!("Deletes the `{table}`s of these ids (atomically),"),
+			"and returns the number of deleted rows.".to_string(),
+		],
+	);
+	let delete_many_partial_docs = docs(attrs, "delete_many", &[
+		format!("Deletes the `{table}`s of these ids, and returns the result of each id."),
+	]);
+	let delete_many_check = privilege_check(attrs, "delete_many");
+
+	vec![
+		quote! {
+			#delete_docs
+			pub async fn delete(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				id: i64,
+			) -> crate::model::Result<()> {
+				#delete_check
+				crate::model::base::delete::<Self>(ctx, mm, id).await
+			}
+		},
+		quote! {
+			#delete_many_docs
+			pub async fn delete_many(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				ids: Vec<i64>,
+			) -> crate::model::Result<u64> {
+				#delete_many_check
+				crate::model::base::delete_many::<Self>(ctx, mm, ids).await
+			}
+		},
+		quote! {
+			#delete_many_partial_docs
+			pub async fn delete_many_partial(
+				ctx: &crate::ctx::Ctx,
+				mm: &crate::model::ModelManager,
+				ids: Vec<i64>,
+			) -> crate::model::Result<Vec<crate::model::Result<()>>> {
+				#delete_many_check
+				crate::model::base::delete_many_partial::<Self>(ctx, mm, ids).await
+			}
+		},
+	]
+}
+
+// endregion: --- Crud Fns
+
+// region:    --- Support
+
+/// The `#[doc = ...]` attributes of a Bmc method,
+/// with the eventual required privilege.
+fn docs(attrs: &BmcAttrs, method: &str, lines: &[String]) -> TokenStream {
+	let mut lines: Vec<String> =
+		lines.iter().map(|line| format!(" {line}")).collect();
+	if let Some(privilege) = attrs.privilege(method) {
+		lines.push(String::new());
+		lines.push(format!(" Requires the `{privilege}` privilege."));
+	}
+
+	quote! {
+		#(#[doc = #lines])*
+	}
+}
+
+/// The `acs` assertion of the privilege required by the Bmc method, if any.
+fn privilege_check(attrs: &BmcAttrs, method: &str) -> TokenStream {
+	match attrs.privilege(method) {
+		Some(privilege) => quote! {
+			crate::model::acs::assert_privilege(
+				ctx,
+				mm,
+				crate::model::acs::Privilege::#privilege,
+			)
+			.await?;
+		},
+		None => quote! {},
+	}
+}
+
+// endregion: --- Support
@@ -0,0 +1,323 @@
+//! Generation of the exported `generate_<suffix>_rpc_fns!()` macro.
+//!
+//! Notes:
+//!   - lib-core cannot depend on lib-rpc-core (the other way around), so the rpc handlers
+//!     are generated as a `macro_rules!` to be called in the rpc module (e.g., in web-server).
+//!   - The rpc handlers call the Bmc functions, so the privileges are asserted there.
+
+use crate::attrs::{BmcAttrs, Op};
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::Ident;
+
+pub fn gen_rpc_macro(attrs: &BmcAttrs) -> TokenStream {
+	let Some(rpc) = attrs.rpc.as_ref() else {
+		return quote! {};
+	};
+	let BmcAttrs {
+		entity,
+		bmc,
+		for_create,
+		for_update,
+		for_upsert,
+		filter,
+		..
+	} = attrs;
+	let suffix = rpc.suffix.as_str();
+	let plural = rpc.plural.as_str();
+	let table = attrs.table.value();
+
+	let mut fns: Vec<(Ident, TokenStream)> = Vec::new();
+	let fn_name = |name: String| format_ident!("{name}");
+	let docs = |lines: &[String]| {
+		let lines = lines.iter().map(|line| format!(" {line}"));
+		quote! { #(#[doc = #lines])* }
+	};
+
+	// -- Create
+	if attrs.has_op(Op::Create) {
+		let name = fn_name(format!("create_{suffix}"));
+		let doc = docs(&[format!("Creates a `{table}` and returns it.")]);
+		fns.push((name.clone(), quote! {
+			#doc
+			pub async fn #name(
+				ctx: ::lib_rpc_core::prelude::Ctx,
+				mm: ::lib_rpc_core::prelude::ModelManager,
+				params: ::lib_rpc_core::prelude::ParamsForCreate<#for_create>,
+			) -> ::lib_rpc_core::prelude::Result<::lib_rpc_core::prelude::DataRpcResult<#entity>> {
+				let ::lib_rpc_core::prelude::ParamsForCreate { data } = params;
+				let id = #bmc::create(&ctx, &mm, data).await?;
+				let entity = #bmc::get(&ctx, &mm, id).await?;
+				Ok(entity.into())
+			}
+		}));
+
+		let name = fn_name(format!("create_many_{plural}"));
+		let doc = docs(&[
+			format!("Creates the `{table}`s and returns the result (id or error) of each of them."),
+			String::new(),
+			"Note: Atomic unless `partial` is true.".to_string(),
+		]);
+		fns.push((
+			name.clone(),
+			quote! {
+				#doc
+				pub async fn #name(
+					ctx: ::lib_rpc_core::prelude::Ctx,
+					mm: ::lib_rpc_core::prelude::ModelManager,
+					params: ::lib_rpc_core::prelude::ParamsForCreateMany<#for_create>,
+				) -> ::lib_rpc_core::prelude::Result<
+					::lib_rpc_core::prelude::DataRpcResult<
+						Vec<::lib_rpc_core::prelude::BulkItemRpcResult<i64>>,
+					>,
+				> {
+					let ::lib_rpc_core::prelude::ParamsForCreateMany { data, partial } = params;
+					let results = if partial {
+						#bmc::create_many_partial(&ctx, &mm, data).await?
+					} else {
+						#bmc::create_many(&ctx, &mm, data)
+							.await?
+							.into_iter()
+							.map(Ok)
+							.collect()
+					};
+					let results: Vec<_> = results
+						.into_iter()
+						.map(::lib_rpc_core::prelude::BulkItemRpcResult::from)
+						.collect();
+					Ok(results.into())
+				}
+			},
+		));
+	}
+
+	// -- Get
+	let name = fn_name(format!("get_{suffix}"));
+	let doc = docs(&[format!("Returns the `{table}` of this id.")]);
+	fns.push((name.clone(), quote! {
+		#doc
+		pub async fn #name(
+			ctx: ::lib_rpc_core::prelude::Ctx,
+			mm: ::lib_rpc_core::prelude::ModelManager,
+			params: ::lib_rpc_core::prelude::ParamsIded,
+		) -> ::lib_rpc_core::prelude::Result<::lib_rpc_core::prelude::DataRpcResult<#entity>> {
+			let entity = #bmc::get(&ctx, &mm, params.id).await?;
+			Ok(entity.into())
+		}
+	}));
+
+	// -- List
+	if attrs.has_op(Op::List) {
+		let name = fn_name(format!("list_{plural}"));
+		let doc = docs(&[
+			format!("Returns one page of the `{table}`s matching the filters,"),
+			"with the total and the eventual `next_cursor`.".to_string(),
+		]);
+		fns.push((name.clone(), quote! {
+			#doc
+			pub async fn #name(
+				ctx: ::lib_rpc_core::prelude::Ctx,
+				mm: ::lib_rpc_core::prelude::ModelManager,
+				params: ::lib_rpc_core::prelude::ParamsList<#filter>,
+			) -> ::lib_rpc_core::prelude::Result<::lib_rpc_core::prelude::ListRpcResult<#entity>> {
+				let ::lib_rpc_core::prelude::ParamsList {
+					filters,
+					list_options,
+					cursor,
+				} = params;
+				let page = #bmc::list_page(&ctx, &mm, filters, list_options, cursor).await?;
+				Ok(page.into())
+			}
+		}));
+
+		let name = fn_name(format!("count_{plural}"));
+		let doc = docs(&[format!(
+			"Returns the number of `{table}`s matching the filters (same as the list)."
+		)]);
+		fns.push((name.clone(), quote! {
+			#doc
+			pub async fn #name(
+				ctx: ::lib_rpc_core::prelude::Ctx,
+				mm: ::lib_rpc_core::prelude::ModelManager,
+				params: ::lib_rpc_core::prelude::ParamsFilters<#filter>,
+			) -> ::lib_rpc_core::prelude::Result<::lib_rpc_core::prelude::DataRpcResult<i64>> {
+				let count = #bmc::count(&ctx, &mm, params.filters).await?;
+				Ok(count.into())
+			}
+		}));
+	}
+
+	// -- Update
+	if attrs.has_op(Op::Update) {
+		let name = fn_name(format!("update_{suffix}"));
+		let doc =
+			docs(&[format!("Updates the `{table}` of this id and returns it.")]);
+		fns.push((name.clone(), quote! {
+			#doc
+			pub async fn #name(
+				ctx: ::lib_rpc_core::prelude::Ctx,
+				mm: ::lib_rpc_core::prelude::ModelManager,
+				params: ::lib_rpc_core::prelude::ParamsForUpdate<#for_update>,
+			) -> ::lib_rpc_core::prelude::Result<::lib_rpc_core::prelude::DataRpcResult<#entity>> {
+				let ::lib_rpc_core::prelude::ParamsForUpdate { id, data } = params;
+				#bmc::update(&ctx, &mm, id, data).await?;
+				let entity = #bmc::get(&ctx, &mm, id).await?;
+				Ok(entity.into())
+			}
+		}));
+
+		let name = fn_name(format!("update_many_{plural}"));
+		let doc = docs(&[format!(
+			"Updates the `{table}`s of these ids and returns the updated count."
+		)]);
+		fns.push((name.clone(), quote! {
+			#doc
+			pub async fn #name(
+				ctx: ::lib_rpc_core::prelude::Ctx,
+				mm: ::lib_rpc_core::prelude::ModelManager,
+				params: ::lib_rpc_core::prelude::ParamsForUpdateMany<#for_update>,
+			) -> ::lib_rpc_core::prelude::Result<::lib_rpc_core::prelude::DataRpcResult<u64>> {
+				let ::lib_rpc_core::prelude::ParamsForUpdateMany { ids, data } = params;
+				let count = #bmc::update_many(&ctx, &mm, ids, data).await?;
+				Ok(count.into())
+			}
+		}));
+
+		// Note: The by filter update requires the filter type.
+		if attrs.has_op(Op::List) {
+			let name = fn_name(format!("update_{plural}_by_filter"));
+			let doc = docs(&[format!(
+				"Updates the `{table}`s matching the filters and returns the updated count."
+			)]);
+			fns.push((name.clone(), quote! {
+				#doc
+				pub async fn #name(
+					ctx: ::lib_rpc_core::prelude::Ctx,
+					mm: ::lib_rpc_core::prelude::ModelManager,
+					params: ::lib_rpc_core::prelude::ParamsForUpdateByFilter<#filter, #for_update>,
+				) -> ::lib_rpc_core::prelude::Result<::lib_rpc_core::prelude::DataRpcResult<u64>> {
+					let ::lib_rpc_core::prelude::ParamsForUpdateByFilter { filters, data } = params;
+					let count = #bmc::update_by_filter(&ctx, &mm, filters, data).await?;
+					Ok(count.into())
+				}
+			}));
+		}
+	}
+
+	// -- Upsert
+	if attrs.has_op(Op::Upsert) {
+		let name = fn_name(format!("upsert_{suffix}"));
+		let doc = docs(&[format!(
+			"Creates the `{table}`, or updates the one with the same unique key, and returns it."
+		)]);
+		fns.push((name.clone(), quote! {
+			#doc
+			pub async fn #name(
+				ctx: ::lib_rpc_core::prelude::Ctx,
+				mm: ::lib_rpc_core::prelude::ModelManager,
+				params: ::lib_rpc_core::prelude::ParamsForCreate<#for_upsert>,
+			) -> ::lib_rpc_core::prelude::Result<::lib_rpc_core::prelude::DataRpcResult<#entity>> {
+				let ::lib_rpc_core::prelude::ParamsForCreate { data } = params;
+				let id = #bmc::upsert(&ctx, &mm, data).await?;
+				let entity = #bmc::get(&ctx, &mm, id).await?;
+				Ok(entity.into())
+			}
+		}));
+	}
+
+	// -- Delete
+	if attrs.has_op(Op::Delete) {
+		let name = fn_name(format!("delete_{suffix}"));
+		let doc =
+			docs(&[format!("Deletes the `{table}` of this id and returns it.")]);
+		fns.push((name.clone(), quote! {
+			#doc
+			pub async fn #name(
+				ctx: ::lib_rpc_core::prelude::Ctx,
+				mm: ::lib_rpc_core::prelude::ModelManager,
+				params: ::lib_rpc_core::prelude::ParamsIded,
+			) -> ::lib_rpc_core::prelude::Result<::lib_rpc_core::prelude::DataRpcResult<#entity>> {
+				let ::lib_rpc_core::prelude::ParamsIded { id } = params;
+				let entity = #bmc::get(&ctx, &mm, id).await?;
+				#bmc::delete(&ctx, &mm, id).await?;
+				Ok(entity.into())
+			}
+		}));
+
+		let name = fn_name(format!("delete_many_{plural}"));
+		let doc = docs(&[
+			format!("Deletes the `{table}`s of these ids and returns the result of each id."),
+			String::new(),
+			"Note: Atomic unless `partial` is true.".to_string(),
+		]);
+		fns.push((
+			name.clone(),
+			quote! {
+				#doc
+				pub async fn #name(
+					ctx: ::lib_rpc_core::prelude::Ctx,
+					mm: ::lib_rpc_core::prelude::ModelManager,
+					params: ::lib_rpc_core::prelude::ParamsIds,
+				) -> ::lib_rpc_core::prelude::Result<
+					::lib_rpc_core::prelude::DataRpcResult<
+						Vec<::lib_rpc_core::prelude::BulkItemRpcResult<()>>,
+					>,
+				> {
+					let ::lib_rpc_core::prelude::ParamsIds { ids, partial } = params;
+					let results = if partial {
+						#bmc::delete_many_partial(&ctx, &mm, ids).await?
+					} else {
+						let count = ids.len();
+						#bmc::delete_many(&ctx, &mm, ids).await?;
+						(0..count).map(|_| Ok(())).collect()
+					};
+					let results: Vec<_> = results
+						.into_iter()
+						.map(::lib_rpc_core::prelude::BulkItemRpcResult::from)
+						.collect();
+					Ok(results.into())
+				}
+			},
+		));
+	}
+
+	// -- The macro
+	let macro_name = format_ident!("generate_{suffix}_rpc_fns");
+	let builder_name = format_ident!("{suffix}_rpc_router_builder");
+	let macro_doc = format!(
+		" Generates the common rpc handlers of the `{entity}` entity (see `{bmc}`),\n \
+		 and the `{builder_name}()` with all of them.\n\n \
+		 Note: The `{entity}` and its Bmc/data types must be imported at the call site."
+	);
+	let builder_doc =
+		format!(" The rpc router builder with the common `{table}` handlers.");
+
+	// -- Skip the rpc fns written by hand
+	for skip_name in rpc.skip.iter() {
+		if !fns.iter().any(|(name, _)| name == skip_name) {
+			return syn::Error::new_spanned(
+				skip_name,
+				format!("rpc fn '{skip_name}' is not generated"),
+			)
+			.to_compile_error();
+		}
+	}
+	fns.retain(|(name, _)| !rpc.skip.contains(name));
+
+	let (names, fns): (Vec<Ident>, Vec<TokenStream>) = fns.into_iter().unzip();
+
+	quote! {
+		#[doc = #macro_doc]
+		#[macro_export]
+		macro_rules! #macro_name {
+			() => {
+				#(#fns)*
+
+				#[doc = #builder_doc]
+				pub fn #builder_name() -> ::rpc_router::RouterBuilder {
+					::rpc_router::router_builder!(#(#names),*)
+				}
+			};
+		}
+	}
+}
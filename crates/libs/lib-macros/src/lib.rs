@@ -0,0 +1,81 @@
+//! Proc macros of the application libraries.
+//!
+//! `#[derive(Bmc)]` on a lib-core model entity generates, from its `#[bmc(...)]` attribute:
+//!
+//! - The `pub struct <Entity>Bmc;` and its `DbBmc` implementation.
+//! - The common CRUD functions of the Bmc (e.g., `create`, `get`, `list_page`, `delete_many`),
+//!   with the eventual privilege assertions.
+//! - With `rpc(...)`, the exported `generate_<suffix>_rpc_fns!()` macro, which generates
+//!   the rpc handlers and the `<suffix>_rpc_router_builder()` in the rpc module calling it.
+//!
+//! Example:
+//!
+//! ```ignore
+//! #[derive(Debug, Clone, Fields, FromRow, Serialize, Bmc)]
+//! #[bmc(
+//!     table = "agent",
+//!     owner_id,
+//!     for_create = AgentForCreate,
+//!     for_update = AgentForUpdate,
+//!     filter = AgentFilter,
+//!     privileges(update_by_filter = Sys),
+//!     rpc(suffix = "agent", plural = "agents"),
+//! )]
+//! pub struct Agent { /* ... */ }
+//! ```
+//!
+//! The `#[bmc(...)]` properties:
+//!
+//! - `table = "..."` (required) - The table name.
+//! - `name = ...` - The Bmc struct name (default `<Entity>Bmc`).
+//! - `owner_id` - The table has an `owner_id` column (default false).
+//! - `timestamps = false` - The table does not have the cid/ctime/mid/mtime columns.
+//! - `audit_log = false` - The changes are not recorded in the `audit_log` table.
+//! - `unique_key = ["col_a", "col_b"]` - The unique key used by `upsert`.
+//! - `for_create = ...`, `for_update = ...`, `for_upsert = ...`, `filter = ...` - The
+//!   types of the corresponding functions.
+//! - `ops(create, list, update, upsert, delete)` - The CRUD function groups to generate
+//!   (default: all the groups with their types given). `get` is always generated.
+//! - `privileges(method = Privilege, ...)` - The `acs::Privilege` required per Bmc method
+//!   (the `_partial` variants share the privilege of their non-partial method).
+//! - `rpc` or `rpc(suffix = "...", plural = "...", skip(...))` - Generate the rpc macro
+//!   (default suffix is the snake case entity name, and plural is the suffix + `s`).
+//!   The `skip(list_convs, ...)` rpc fns are not generated (e.g., to be written by hand
+//!   in the rpc module, and added to its router builder).
+//!
+//! Notes:
+//!   - The Bmc functions use `crate::model::...` paths, so the entity must be in a
+//!     `lib_core::model` sub-module.
+//!   - The rpc macro expects the entity, Bmc, and data types to be imported at the call site.
+
+// region:    --- Modules
+
+mod attrs;
+mod gen_bmc;
+mod gen_rpc;
+
+use crate::attrs::BmcAttrs;
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput};
+
+// endregion: --- Modules
+
+#[proc_macro_derive(Bmc, attributes(bmc))]
+pub fn derive_bmc(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as DeriveInput);
+
+	let attrs = match BmcAttrs::from_derive_input(&input) {
+		Ok(attrs) => attrs,
+		Err(err) => return err.to_compile_error().into(),
+	};
+
+	let bmc_tokens = gen_bmc::gen_bmc(&attrs);
+	let rpc_tokens = gen_rpc::gen_rpc_macro(&attrs);
+
+	quote! {
+		#bmc_tokens
+		#rpc_tokens
+	}
+	.into()
+}
@@ -3,7 +3,6 @@
 mod error;
 mod rpc_params;
 mod rpc_result;
-mod utils;
 
 pub use self::error::{Error, Result};
 pub use rpc_params::*;
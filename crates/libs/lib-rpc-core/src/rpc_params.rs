@@ -21,6 +21,17 @@ pub struct ParamsForCreate<D> {
 
 impl<D> IntoParams for ParamsForCreate<D> where D: DeserializeOwned + Send {}
 
+/// Params structure for any RPC Bulk Create call.
+/// When `partial`, the valid items are created even if some others fail.
+#[derive(Deserialize)]
+pub struct ParamsForCreateMany<D> {
+	pub data: Vec<D>,
+	#[serde(default)]
+	pub partial: bool,
+}
+
+impl<D> IntoParams for ParamsForCreateMany<D> where D: DeserializeOwned + Send {}
+
 /// Params structure for any RPC Update call.
 #[derive(Deserialize)]
 pub struct ParamsForUpdate<D> {
@@ -30,6 +41,35 @@ pub struct ParamsForUpdate<D> {
 
 impl<D> IntoParams for ParamsForUpdate<D> where D: DeserializeOwned + Send {}
 
+/// Params structure for any RPC Update Many call (same data for all ids).
+#[derive(Deserialize)]
+pub struct ParamsForUpdateMany<D> {
+	pub ids: Vec<i64>,
+	pub data: D,
+}
+
+impl<D> IntoParams for ParamsForUpdateMany<D> where D: DeserializeOwned + Send {}
+
+/// Params structure for any RPC Update By Filter call.
+/// Note: The `filters` are required, to avoid updating all entities by mistake.
+#[serde_as]
+#[derive(Deserialize)]
+pub struct ParamsForUpdateByFilter<F, D>
+where
+	F: DeserializeOwned,
+{
+	#[serde_as(deserialize_as = "OneOrMany<_>")]
+	pub filters: Vec<F>,
+	pub data: D,
+}
+
+impl<F, D> IntoParams for ParamsForUpdateByFilter<F, D>
+where
+	F: DeserializeOwned + Send,
+	D: DeserializeOwned + Send,
+{
+}
+
 /// Params structure for any RPC Update call.
 #[derive(Deserialize)]
 pub struct ParamsIded {
@@ -37,6 +77,16 @@ pub struct ParamsIded {
 }
 impl IntoParams for ParamsIded {}
 
+/// Params structure for any RPC Bulk call by ids (e.g., bulk delete).
+/// When `partial`, the found ids are processed even if some others are missing.
+#[derive(Deserialize)]
+pub struct ParamsIds {
+	pub ids: Vec<i64>,
+	#[serde(default)]
+	pub partial: bool,
+}
+impl IntoParams for ParamsIds {}
+
 /// Params structure for any RPC List call.
 #[serde_as]
 #[derive(Deserialize, Default)]
@@ -47,9 +97,54 @@ where
 	#[serde_as(deserialize_as = "Option<OneOrMany<_>>")]
 	pub filters: Option<Vec<F>>,
 	pub list_options: Option<ListOptions>,
+	/// The `next_cursor` of the previous page (keyset pagination).
+	pub cursor: Option<String>,
 }
 
 impl<D> IntoDefaultRpcParams for ParamsList<D> where
 	D: DeserializeOwned + Send + Default
 {
 }
+
+/// Params structure for any RPC List call of the children of an entity
+/// (e.g., the msgs of a conv, with `id` being the conv id).
+#[serde_as]
+#[derive(Deserialize)]
+pub struct ParamsIdedList<F>
+where
+	F: DeserializeOwned,
+{
+	pub id: i64,
+	#[serde_as(deserialize_as = "Option<OneOrMany<_>>")]
+	pub filters: Option<Vec<F>>,
+	pub list_options: Option<ListOptions>,
+	/// The `next_cursor` of the previous page (keyset pagination).
+	pub cursor: Option<String>,
+}
+
+impl<F> IntoParams for ParamsIdedList<F> where F: DeserializeOwned + Send + Default {}
+
+/// Params structure for any RPC Search call (e.g., full-text search).
+#[derive(Deserialize)]
+pub struct ParamsForSearch {
+	pub query: String,
+	pub list_options: Option<ListOptions>,
+}
+
+impl IntoParams for ParamsForSearch {}
+
+/// Params structure for any RPC Count call.
+#[serde_as]
+#[derive(Deserialize, Default)]
+pub struct ParamsFilters<F>
+where
+	F: DeserializeOwned,
+{
+	#[serde_as(deserialize_as = "Option<OneOrMany<_>>")]
+	pub filters: Option<Vec<F>>,
+}
+
+impl<D> IntoDefaultRpcParams for ParamsFilters<D> where
+	D: DeserializeOwned + Send + Default
+{
+}
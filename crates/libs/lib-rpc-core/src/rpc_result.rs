@@ -2,15 +2,17 @@
 //! JSON-RPC APIs.
 //!
 //! The primary type is the simple DataRpcResult, which contains only a `data` property.
+//! For lists, the ListRpcResult adds the pagination information.
+//! For bulk calls, the data is a list of BulkItemRpcResult (one per item).
 //!
 //! Notes:
-//!   - In the future, we may introduce types like `DataRpcResult` that include metadata
-//!     about the returned list data (e.g., pagination information).
 //!   - Although the struct is named with `Result`, it is not a typical Rust result. Instead,
 //!     it represents the `.result` property of a JSON-RPC response.
 //!
 
+use lib_core::model::{self, ListPage};
 use serde::Serialize;
+use serde_json::{json, Value};
 
 #[derive(Serialize)]
 pub struct DataRpcResult<T>
@@ -28,3 +30,74 @@ where
 		Self { data: val }
 	}
 }
+
+/// The `.result` of the list rpc calls.
+///
+/// `next_cursor` is None when the page is the last one (or when the order
+/// does not support cursors), otherwise, it can be given as the `cursor` param
+/// to get the next page.
+#[derive(Serialize)]
+pub struct ListRpcResult<T>
+where
+	T: Serialize,
+{
+	data: Vec<T>,
+	total: i64,
+	limit: i64,
+	offset: i64,
+	next_cursor: Option<String>,
+}
+
+impl<T> From<ListPage<T>> for ListRpcResult<T>
+where
+	T: Serialize,
+{
+	fn from(page: ListPage<T>) -> Self {
+		Self {
+			data: page.items,
+			total: page.total,
+			limit: page.limit,
+			offset: page.offset,
+			next_cursor: page.next_cursor,
+		}
+	}
+}
+
+/// The result of one item of a bulk rpc call
+/// (i.e., `{"data": ...}` or `{"error": ...}`).
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum BulkItemRpcResult<T>
+where
+	T: Serialize,
+{
+	Data { data: T },
+	Error { error: Value },
+}
+
+impl<T> From<model::Result<T>> for BulkItemRpcResult<T>
+where
+	T: Serialize,
+{
+	fn from(res: model::Result<T>) -> Self {
+		match res {
+			Ok(data) => Self::Data { data },
+			Err(err) => Self::Error {
+				error: bulk_item_error(&err),
+			},
+		}
+	}
+}
+
+/// Note: Only the entity errors are given to the client as is.
+///       The other ones (e.g., database errors) might expose internal information.
+fn bulk_item_error(err: &model::Error) -> Value {
+	match err {
+		model::Error::EntityNotFound { .. }
+		| model::Error::UniqueViolation { .. }
+		| model::Error::UserAlreadyExists { .. } => {
+			serde_json::to_value(err).unwrap_or_else(|_| json!("SERVICE_ERROR"))
+		}
+		_ => json!("SERVICE_ERROR"),
+	}
+}
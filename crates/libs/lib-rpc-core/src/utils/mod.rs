@@ -1,5 +0,0 @@
-// region:    --- Modules
-
-mod macro_utils;
-
-// endregion: --- Modules
@@ -4,6 +4,7 @@ use axum::response::{IntoResponse, Response};
 use derive_more::From;
 use lib_auth::{pwd, token};
 use lib_core::model;
+use lib_core::model::usage::UsageScope;
 use serde::Serialize;
 use serde_json::Value;
 use serde_with::{serde_as, DisplayFromStr};
@@ -32,6 +33,9 @@ pub enum Error {
 	// -- Extractors
 	ReqStampNotInReqExt,
 
+	// -- RateLimit
+	RateLimited,
+
 	// -- Modules
 	#[from]
 	Model(model::Error),
@@ -46,6 +50,17 @@ pub enum Error {
 	// Simple mapping for the RpcRequestParsingError. It will have the eventual id, method context.
 	#[from]
 	RpcRequestParsing(rpc_router::RequestParsingError),
+	RpcBatchEmpty,
+	RpcBatchTooLarge {
+		max: usize,
+		actual: usize,
+	},
+	/// The first error of an atomic batch (all its calls rolled back), returned
+	/// to each request with an id of the batch (see `mw_reponse_map`).
+	RpcBatchAtomic {
+		ids: Vec<Value>,
+		error: Box<Error>,
+	},
 
 	// When encountering `rpc_router::Error::Handler`, we deconstruct it into the appropriate concrete application error types.
 	RpcLibRpc(lib_rpc_core::Error),
@@ -56,10 +71,11 @@ pub enum Error {
 	RpcHandlerErrorUnhandled(&'static str),
 	// When the `rpc_router::Error` is not a `Handler`, we can pass through the rpc_router::Error
 	// as all variants contain concrete types.
+	// Note: Boxed as `rpc_router::Error` is comparatively large.
 	RpcRouter {
 		id: Value,
 		method: String,
-		error: rpc_router::Error,
+		error: Box<rpc_router::Error>,
 	},
 
 	// -- External Modules
@@ -96,7 +112,11 @@ impl From<rpc_router::CallError> for Error {
 					Error::RpcHandlerErrorUnhandled(type_name)
 				}
 			}
-			error => Error::RpcRouter { id, method, error },
+			error => Error::RpcRouter {
+				id,
+				method,
+				error: Box::new(error),
+			},
 		}
 	}
 }
@@ -150,44 +170,141 @@ impl Error {
 			// -- Auth
 			CtxExt(_) => (StatusCode::FORBIDDEN, ClientError::NO_AUTH),
 
+			// -- RateLimit
+			RateLimited => {
+				(StatusCode::TOO_MANY_REQUESTS, ClientError::RATE_LIMITED)
+			}
+
 			// -- Model
-			Model(model::Error::EntityNotFound { entity, id }) => (
+			Model(model::Error::EntityNotFound { entity, id })
+			| RpcLibRpc(lib_rpc_core::Error::Model(
+				model::Error::EntityNotFound { entity, id },
+			)) => (
 				StatusCode::BAD_REQUEST,
 				ClientError::ENTITY_NOT_FOUND { entity, id: *id },
 			),
+			Model(model::Error::EntitiesNotFound { entity, ids })
+			| RpcLibRpc(lib_rpc_core::Error::Model(
+				model::Error::EntitiesNotFound { entity, ids },
+			)) => (
+				StatusCode::BAD_REQUEST,
+				ClientError::ENTITIES_NOT_FOUND {
+					entity,
+					ids: ids.clone(),
+				},
+			),
+			Model(model::Error::AccessDenied { .. })
+			| RpcLibRpc(lib_rpc_core::Error::Model(model::Error::AccessDenied {
+				..
+			})) => (StatusCode::FORBIDDEN, ClientError::ACCESS_DENIED),
+			Model(model_error)
+			| RpcLibRpc(lib_rpc_core::Error::Model(model_error))
+				if matches!(
+					model_error,
+					model::Error::ListLimitOverMax { .. }
+						| model::Error::ListOrderByUnknown { .. }
+						| model::Error::ListCursorOrderByNotSupported { .. }
+						| model::Error::ListCursorInvalid
+						| model::Error::SearchQueryEmpty
+						| model::Error::ConvMsgContentEmpty
+						| model::Error::ConvMsgContentTypeNotAllowed { .. }
+						| model::Error::ConvMsgContentNotJson { .. }
+						| model::Error::ConvMsgMetadataNotObject
+						| model::Error::ConvMsgConvIdMismatch { .. }
+						| model::Error::ConvMsgReactionEmojiInvalid { .. }
+						| model::Error::ConvMsgNotReply { .. }
+						| model::Error::ConvImportInvalid { .. }
+						| model::Error::ConvImportFormatNotSupported { .. }
+						| model::Error::ConvShareExpiresAtNotFuture
+						| model::Error::ConvTemplateInvalid { .. }
+						| model::Error::ConvTemplateAlreadyExists { .. }
+						| model::Error::ConvTemplateVarMissing { .. }
+						| model::Error::FolderNameInvalid { .. }
+						| model::Error::FolderAlreadyExists { .. }
+						| model::Error::TagNameInvalid { .. }
+						| model::Error::TagAlreadyExists { .. }
+						| model::Error::UserFavoritesOverMax { .. }
+						| model::Error::ScheduleInvalid { .. }
+						| model::Error::ScheduleCronInvalid { .. }
+						| model::Error::SchedulesOverMax { .. }
+						| model::Error::AgentHasOtherUsersConvs { .. }
+						| model::Error::ModqlIntoSea(_)
+				) =>
+			{
+				(
+					StatusCode::BAD_REQUEST,
+					ClientError::RPC_PARAMS_INVALID(model_error.to_string()),
+				)
+			}
+
+			Model(model::Error::AttachmentTooLarge { max, .. }) => (
+				StatusCode::PAYLOAD_TOO_LARGE,
+				ClientError::ATTACHMENT_INVALID(format!(
+					"attachment larger than {max} bytes"
+				)),
+			),
+			Model(
+				model_error @ (model::Error::AttachmentEmpty
+				| model::Error::AttachmentNameInvalid { .. }
+				| model::Error::AttachmentContentTypeInvalid { .. }),
+			) => (
+				StatusCode::BAD_REQUEST,
+				ClientError::ATTACHMENT_INVALID(model_error.to_string()),
+			),
+
+			Model(model::Error::ConvShareNotFound) => {
+				(StatusCode::NOT_FOUND, ClientError::SHARE_NOT_FOUND)
+			}
+
+			Model(model::Error::UsageQuotaExceeded { scope, scope_id })
+			| RpcLibRpc(lib_rpc_core::Error::Model(
+				model::Error::UsageQuotaExceeded { scope, scope_id },
+			)) => (
+				StatusCode::TOO_MANY_REQUESTS,
+				ClientError::USAGE_QUOTA_EXCEEDED {
+					scope: *scope,
+					scope_id: *scope_id,
+				},
+			),
 
 			// -- Rpc
 			RpcRequestParsing(req_parsing_err) => (
 				StatusCode::BAD_REQUEST,
 				ClientError::RPC_REQUEST_INVALID(req_parsing_err.to_string()),
 			),
-			RpcRouter {
-				error: rpc_router::Error::MethodUnknown,
-				method,
-				..
-			} => (
+			RpcBatchEmpty => (
 				StatusCode::BAD_REQUEST,
-				ClientError::RPC_REQUEST_METHOD_UNKNOWN(format!(
-					"rpc method '{method}' unknown"
-				)),
+				ClientError::RPC_REQUEST_INVALID("rpc batch is empty".to_string()),
 			),
-			RpcRouter {
-				error: rpc_router::Error::ParamsParsing(params_parsing_err),
-				..
-			} => (
+			RpcBatchTooLarge { max, actual } => (
 				StatusCode::BAD_REQUEST,
-				ClientError::RPC_PARAMS_INVALID(params_parsing_err.to_string()),
-			),
-			RpcRouter {
-				error: rpc_router::Error::ParamsMissingButRequested,
-				method,
-				..
-			} => (
-				StatusCode::BAD_REQUEST,
-				ClientError::RPC_PARAMS_INVALID(format!(
-					"Params missing. Method '{method}' requires params"
+				ClientError::RPC_REQUEST_INVALID(format!(
+					"rpc batch has {actual} requests (max {max})"
 				)),
 			),
+			RpcBatchAtomic { error, .. } => error.client_status_and_error(),
+			RpcRouter { error, method, .. } => match error.as_ref() {
+				rpc_router::Error::MethodUnknown => (
+					StatusCode::BAD_REQUEST,
+					ClientError::RPC_REQUEST_METHOD_UNKNOWN(format!(
+						"rpc method '{method}' unknown"
+					)),
+				),
+				rpc_router::Error::ParamsParsing(params_parsing_err) => (
+					StatusCode::BAD_REQUEST,
+					ClientError::RPC_PARAMS_INVALID(params_parsing_err.to_string()),
+				),
+				rpc_router::Error::ParamsMissingButRequested => (
+					StatusCode::BAD_REQUEST,
+					ClientError::RPC_PARAMS_INVALID(format!(
+						"Params missing. Method '{method}' requires params"
+					)),
+				),
+				_ => (
+					StatusCode::INTERNAL_SERVER_ERROR,
+					ClientError::SERVICE_ERROR,
+				),
+			},
 
 			// -- Fallback.
 			_ => (
@@ -204,12 +321,21 @@ impl Error {
 pub enum ClientError {
 	LOGIN_FAIL,
 	NO_AUTH,
+	ACCESS_DENIED,
 	ENTITY_NOT_FOUND { entity: &'static str, id: i64 },
+	ENTITIES_NOT_FOUND { entity: &'static str, ids: Vec<i64> },
 
 	RPC_REQUEST_INVALID(String),
 	RPC_REQUEST_METHOD_UNKNOWN(String),
 	RPC_PARAMS_INVALID(String),
 
+	ATTACHMENT_INVALID(String),
+
+	USAGE_QUOTA_EXCEEDED { scope: UsageScope, scope_id: i64 },
+
+	SHARE_NOT_FOUND,
+	RATE_LIMITED,
+
 	SERVICE_ERROR,
 }
 // endregion: --- Client Error
@@ -0,0 +1,139 @@
+use crate::error::Result;
+use crate::middleware::mw_auth::CtxW;
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderMap, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use lib_core::model::attachment::{AttachmentBmc, AttachmentForCreate};
+use lib_core::model::ModelManager;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+// region:    --- Upload
+
+/// Attach the raw request body to the `ConvMsg` of `msg_id`.
+///
+/// The attachment content type is the request `Content-Type` header.
+pub async fn upload_attachment_handler(
+	State(mm): State<ModelManager>,
+	ctx: CtxW,
+	Path(msg_id): Path<i64>,
+	Query(params): Query<UploadParams>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> Result<Json<Value>> {
+	debug!("{:<12} - upload_attachment_handler", "HANDLER");
+
+	let ctx = ctx.0;
+	let content_type = headers
+		.get(header::CONTENT_TYPE)
+		.map(|value| String::from_utf8_lossy(value.as_bytes()).to_string());
+
+	let att_c = AttachmentForCreate {
+		msg_id,
+		name: params.name,
+		content_type,
+	};
+	let id = AttachmentBmc::create(&ctx, &mm, att_c, &body).await?;
+	let attachment = AttachmentBmc::get(&ctx, &mm, id).await?;
+
+	let body = Json(json!({
+		"result": {
+			"data": attachment
+		}
+	}));
+
+	Ok(body)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadParams {
+	// Note: Defaulted so that a missing name is reported as an invalid name.
+	#[serde(default)]
+	name: String,
+}
+
+// endregion: --- Upload
+
+// region:    --- Download
+
+pub async fn download_attachment_handler(
+	State(mm): State<ModelManager>,
+	ctx: CtxW,
+	Path(id): Path<i64>,
+) -> Result<Response> {
+	debug!("{:<12} - download_attachment_handler", "HANDLER");
+
+	let (attachment, content) =
+		AttachmentBmc::get_with_content(&ctx.0, &mm, id).await?;
+
+	// Note: The name and content type are validated on create,
+	//       so the header values should never fail.
+	let content_type = HeaderValue::from_str(&attachment.content_type)
+		.unwrap_or(HeaderValue::from_static("application/octet-stream"));
+	let disposition = format!(
+		"attachment; filename*=UTF-8''{}",
+		percent_encode(&attachment.name)
+	);
+	let disposition = HeaderValue::from_str(&disposition)
+		.unwrap_or(HeaderValue::from_static("attachment"));
+	let etag = HeaderValue::from_str(&format!("\"{}\"", attachment.checksum))
+		.unwrap_or(HeaderValue::from_static("\"\""));
+
+	let headers = [
+		(header::CONTENT_TYPE, content_type),
+		(header::CONTENT_DISPOSITION, disposition),
+		(header::ETAG, etag),
+		(
+			header::X_CONTENT_TYPE_OPTIONS,
+			HeaderValue::from_static("nosniff"),
+		),
+	];
+
+	Ok((headers, content).into_response())
+}
+
+// endregion: --- Download
+
+// region:    --- Delete
+
+pub async fn delete_attachment_handler(
+	State(mm): State<ModelManager>,
+	ctx: CtxW,
+	Path(id): Path<i64>,
+) -> Result<Json<Value>> {
+	debug!("{:<12} - delete_attachment_handler", "HANDLER");
+
+	AttachmentBmc::delete(&ctx.0, &mm, id).await?;
+
+	let body = Json(json!({
+		"result": {
+			"success": true
+		}
+	}));
+
+	Ok(body)
+}
+
+// endregion: --- Delete
+
+// region:    --- Support
+
+/// Percent-encode all but the RFC 3986 unreserved characters
+/// (for the `filename*` of the `Content-Disposition`).
+fn percent_encode(value: &str) -> String {
+	let mut encoded = String::with_capacity(value.len());
+	for byte in value.bytes() {
+		match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+				encoded.push(byte as char)
+			}
+			_ => encoded.push_str(&format!("%{byte:02X}")),
+		}
+	}
+	encoded
+}
+
+// endregion: --- Support
@@ -0,0 +1,80 @@
+use crate::error::Result;
+use crate::middleware::mw_auth::CtxW;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use lib_core::model::conv::ConvBmc;
+use lib_core::model::conv_export::{ConvExportFormat, ConvForImport};
+use lib_core::model::ModelManager;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+// region:    --- Export
+
+/// Download the export of the conv of `id` (see `ConvBmc::export_conv`).
+pub async fn export_conv_handler(
+	State(mm): State<ModelManager>,
+	ctx: CtxW,
+	Path(id): Path<i64>,
+	Query(params): Query<ExportParams>,
+) -> Result<Response> {
+	debug!("{:<12} - export_conv_handler", "HANDLER");
+
+	let format = params.format.unwrap_or_default();
+	let content = ConvBmc::export_conv(&ctx.0, &mm, id, format).await?;
+
+	// Note: The format values are static ascii, so the header values should never fail.
+	let content_type = HeaderValue::from_static(format.content_type());
+	let disposition =
+		format!("attachment; filename=\"conv-{id}.{}\"", format.extension());
+	let disposition = HeaderValue::from_str(&disposition)
+		.unwrap_or(HeaderValue::from_static("attachment"));
+
+	let headers = [
+		(header::CONTENT_TYPE, content_type),
+		(header::CONTENT_DISPOSITION, disposition),
+		(
+			header::X_CONTENT_TYPE_OPTIONS,
+			HeaderValue::from_static("nosniff"),
+		),
+	];
+
+	Ok((headers, content).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+	/// default: `json`
+	format: Option<ConvExportFormat>,
+}
+
+// endregion: --- Export
+
+// region:    --- Import
+
+/// Create a new conv from the raw request body export content
+/// (with the `agent_id`, `format`, and `title` query params, see `ConvForImport`).
+pub async fn import_conv_handler(
+	State(mm): State<ModelManager>,
+	ctx: CtxW,
+	Query(conv_i): Query<ConvForImport>,
+	body: String,
+) -> Result<Json<Value>> {
+	debug!("{:<12} - import_conv_handler", "HANDLER");
+
+	let ctx = ctx.0;
+	let id = ConvBmc::import_conv(&ctx, &mm, conv_i, &body).await?;
+	let conv = ConvBmc::get(&ctx, &mm, id).await?;
+
+	let body = Json(json!({
+		"result": {
+			"data": conv
+		}
+	}));
+
+	Ok(body)
+}
+
+// endregion: --- Import
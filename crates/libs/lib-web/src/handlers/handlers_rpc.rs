@@ -1,10 +1,31 @@
+use crate::error::{Error, Result};
 use crate::middleware::mw_auth::CtxW;
-use axum::extract::State;
+use crate::middleware::mw_req_stamp::ReqStamp;
+use crate::middleware::mw_res_map::client_error_body;
+use axum::extract::{FromRef, Query, State};
+use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
-use rpc_router::resources_builder;
+use lib_core::model::{self, ModelManager, TxnScope};
+use rpc_router::{resources_builder, Resources};
+use serde::Deserialize;
 use serde_json::{json, Value};
 use std::sync::Arc;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// The maximum number of requests of a JSON-RPC batch
+/// (e.g., bounds the calls made in one atomic transaction).
+const RPC_BATCH_MAX: usize = 100;
+
+/// The Axum state for the `rpc_axum_handler`.
+/// Note: The `ModelManager` is needed to create the eventual
+///       transactional `ModelManager` of an atomic call.
+#[derive(Clone, FromRef)]
+pub struct RpcState {
+	pub rpc_router: rpc_router::Router,
+	pub mm: ModelManager,
+}
 
 /// RPC ID and Method Capture
 /// Note: This will be injected into the Axum Response extensions so that
@@ -16,60 +37,521 @@ pub struct RpcInfo {
 	pub method: String,
 }
 
+/// The url query options of the rpc call (e.g., `/api/rpc?atomic=true`)
+#[derive(Debug, Default, Deserialize)]
+pub struct RpcCallOptions {
+	/// When true, the rpc request (single or batch) is executed in one database
+	/// transaction, which is rolled back on the first error.
+	#[serde(default)]
+	pub atomic: bool,
+}
+
+/// Handles a single JSON-RPC request or a JSON-RPC 2.0 batch (array of requests).
+///
+/// - For a non-atomic batch, each request is executed independently, and the response
+///   array contains either the result or the error of each of them.
+/// - For an atomic batch, the first error rolls back the whole batch, and the response
+///   array contains this error for each request (`Error::RpcBatchAtomic`).
+/// - The batch notifications (requests without `id`) are executed, but have no response
+///   (no content if the batch has only notifications).
+/// - A batch has at most `RPC_BATCH_MAX` requests (`Error::RpcBatchTooLarge` otherwise).
 pub async fn rpc_axum_handler(
 	State(rpc_router): State<rpc_router::Router>,
+	State(mm): State<ModelManager>,
+	Query(call_options): Query<RpcCallOptions>,
+	req_stamp: ReqStamp,
 	ctx: CtxW,
 	Json(rpc_req): Json<Value>,
 ) -> Response {
 	let ctx = ctx.0;
 
+	// -- Begin the eventual transaction
+	// Note: The txn scope rolls back the transaction if this handler future is dropped.
+	let (mm_txn, txn_scope) = if call_options.atomic {
+		match begin_txn(&mm).await {
+			Ok((mm_txn, txn_scope)) => (Some(mm_txn), Some(txn_scope)),
+			Err(err) => return err.into_response(),
+		}
+	} else {
+		(None, None)
+	};
+
+	// -- Add the request specific resources
+	// Note: Since Ctx is per axum request, we construct additional RPC resources.
+	//       These additional resources will be "overlayed" on top of the base router services,
+	//       meaning they will take precedence over the base router ones, but won't replace them.
+	//       This is also how the eventual transactional ModelManager takes precedence.
+	let additional_resources = match mm_txn {
+		Some(mm_txn) => resources_builder![ctx, mm_txn].build(),
+		None => resources_builder![ctx].build(),
+	};
+
+	// -- Exec Rpc Route(s)
+	let (rpc_info, res) = match rpc_req {
+		Value::Array(rpc_reqs) => {
+			exec_rpc_batch(
+				&rpc_router,
+				rpc_reqs,
+				additional_resources,
+				txn_scope.is_some(),
+				req_stamp.uuid,
+			)
+			.await
+		}
+		rpc_req => exec_rpc(&rpc_router, rpc_req, additional_resources).await,
+	};
+
+	// -- Commit or Rollback the eventual transaction
+	let res = match (txn_scope, res) {
+		(Some(txn_scope), Ok(body)) => txn_scope
+			.commit()
+			.await
+			.map(|_| body)
+			.map_err(|err| model::Error::from(err).into()),
+		(Some(txn_scope), Err(err)) => {
+			if let Err(rollback_err) = txn_scope.rollback().await {
+				warn!("Rpc atomic call rollback fail: {rollback_err:?}");
+			}
+			Err(err)
+		}
+		(None, res) => res,
+	};
+
+	// -- Create and Update Axum Response
+	// Note: Error Json response will be generated in the mw_res_map as wil other error.
+	//       We store data in the Axum Response extensions so that
+	//       we can unpack it in the `mw_res_map` for client-side rendering.
+	//       This approach centralizes error handling for the client at the `mw_res_map` module
+	let mut res = match res {
+		Ok(Value::Array(items)) if items.is_empty() => {
+			StatusCode::NO_CONTENT.into_response()
+		}
+		res => res.map(Json).into_response(),
+	};
+	// Note: Here, add the capture RpcInfo (RPC ID and method) into the Axum response to be used
+	//       later in the `mw_res_map` for RequestLineLogging, and eventual JSON-RPC error serialization.
+	if let Some(rpc_info) = rpc_info {
+		res.extensions_mut().insert(Arc::new(rpc_info));
+	}
+
+	res
+}
+
+/// Parse and execute one rpc request.
+///
+/// Returns the eventual RpcInfo (None if the request could not be parsed)
+/// and the Json Rpc Success Response body.
+async fn exec_rpc(
+	rpc_router: &rpc_router::Router,
+	rpc_req: Value,
+	additional_resources: Resources,
+) -> (Option<RpcInfo>, Result<Value>) {
 	// -- Parse and RpcRequest validate the rpc_request
 	let rpc_req = match rpc_router::Request::try_from(rpc_req) {
 		Ok(rpc_req) => rpc_req,
 		Err(rpc_req_error) => {
-			let res = crate::Error::RpcRequestParsing(rpc_req_error).into_response();
-			return res;
+			return (None, Err(Error::RpcRequestParsing(rpc_req_error)));
 		}
 	};
 
 	// -- Create the RPC Info
-	//    (will be set to the response.extensions)
 	let rpc_info = RpcInfo {
 		id: Some(rpc_req.id.clone()),
 		method: rpc_req.method.clone(),
 	};
 
-	// -- Add the request specific resources
-	// Note: Since Ctx is per axum request, we construct additional RPC resources.
-	//       These additional resources will be "overlayed" on top of the base router services,
-	//       meaning they will take precedence over the base router ones, but won't replace them.
-	let additional_resources = resources_builder![ctx].build();
-
 	// -- Exec Rpc Route
 	let rpc_call_result = rpc_router
 		.call_with_resources(rpc_req, additional_resources)
 		.await;
 
 	// -- Build Json Rpc Success Response
-	// Note: Error Json response will be generated in the mw_res_map as wil other error.
-	let res = rpc_call_result.map(|rpc_call_response| {
-		let body_response = json!({
+	let res = rpc_call_result
+		.map(|rpc_call_response| {
+			json!({
+				"jsonrpc": "2.0",
+				"id": rpc_call_response.id,
+				"result": rpc_call_response.value
+			})
+		})
+		.map_err(Error::from);
+
+	(Some(rpc_info), res)
+}
+
+/// Execute each rpc request of a batch in order.
+///
+/// When `atomic`, returns the RpcInfo of the first failing request, and its error
+/// for each request with an id (`Error::RpcBatchAtomic`).
+/// Otherwise, the errors are serialized in the returned response array.
+///
+/// Note: The notifications (no `id`) are executed with a `null` id, but have no response.
+async fn exec_rpc_batch(
+	rpc_router: &rpc_router::Router,
+	rpc_reqs: Vec<Value>,
+	additional_resources: Resources,
+	atomic: bool,
+	req_uuid: Uuid,
+) -> (Option<RpcInfo>, Result<Value>) {
+	if rpc_reqs.is_empty() {
+		return (None, Err(Error::RpcBatchEmpty));
+	}
+	if rpc_reqs.len() > RPC_BATCH_MAX {
+		let err = Error::RpcBatchTooLarge {
+			max: RPC_BATCH_MAX,
+			actual: rpc_reqs.len(),
+		};
+		return (None, Err(err));
+	}
+
+	// The response ids (None for the notifications).
+	let ids: Vec<Option<Value>> = rpc_reqs.iter().map(rpc_res_id).collect();
+	let mut methods: Vec<String> = Vec::with_capacity(rpc_reqs.len());
+	let mut responses: Vec<Value> = Vec::with_capacity(rpc_reqs.len());
+
+	for (mut rpc_req, id) in rpc_reqs.into_iter().zip(ids.iter()) {
+		if let (None, Some(rpc_req)) = (id, rpc_req.as_object_mut()) {
+			rpc_req.insert("id".to_string(), Value::Null);
+		}
+		let (rpc_info, res) =
+			exec_rpc(rpc_router, rpc_req, additional_resources.clone()).await;
+
+		match (res, id) {
+			(Err(err), _) if atomic => {
+				let err = Error::RpcBatchAtomic {
+					ids: ids.into_iter().flatten().collect(),
+					error: Box::new(err),
+				};
+				return (rpc_info, Err(err));
+			}
+			(Ok(body), Some(_)) => responses.push(body),
+			(Err(err), Some(id)) => {
+				debug!("{:<12} - rpc batch item error: {err:?}", "HANDLER");
+				let (_, client_error) = err.client_status_and_error();
+				responses.push(client_error_body(
+					Some(id.clone()),
+					req_uuid,
+					&client_error,
+				));
+			}
+			(Ok(_), None) => (),
+			(Err(err), None) => {
+				debug!("{:<12} - rpc batch notification error: {err:?}", "HANDLER");
+			}
+		}
+
+		if let Some(rpc_info) = rpc_info {
+			methods.push(rpc_info.method);
+		}
+	}
+
+	let rpc_info = RpcInfo {
+		id: None,
+		method: format!("batch[{}]", methods.join(",")),
+	};
+
+	(Some(rpc_info), Ok(Value::Array(responses)))
+}
+
+/// Returns the response id of a batch request
+/// (None for a notification, i.e., a request object without `id`).
+fn rpc_res_id(rpc_req: &Value) -> Option<Value> {
+	match rpc_req.as_object() {
+		Some(rpc_req) => rpc_req.get("id").cloned(),
+		None => Some(Value::Null),
+	}
+}
+
+async fn begin_txn(mm: &ModelManager) -> Result<(ModelManager, TxnScope)> {
+	let mm_txn = mm.new_with_txn()?;
+	let txn_scope = mm_txn
+		.dbx()
+		.begin_txn_scope()
+		.await
+		.map_err(model::Error::from)?;
+
+	Ok((mm_txn, txn_scope))
+}
+
+// region:    --- Tests
+
+#[cfg(test)]
+mod tests {
+	type Error = Box<dyn std::error::Error>;
+	type Result<T> = core::result::Result<T, Error>; // For tests.
+
+	use super::*;
+	use crate::middleware::mw_res_map::mw_reponse_map;
+	use axum::body::to_bytes;
+	use axum::http::{Method, Uri};
+	use lib_core::_dev_utils::{self, clean_agents};
+	use lib_core::ctx::Ctx;
+	use lib_core::model::agent::{AgentBmc, AgentFilter, AgentForCreate};
+	use lib_rpc_core::ParamsForCreate;
+	use lib_utils::time::now_utc;
+	use rpc_router::router_builder;
+	use serial_test::serial;
+
+	#[serial]
+	#[tokio::test]
+	async fn test_rpc_batch_ok_with_errors() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = init_test_mm().await?;
+		let ctx = Ctx::root_ctx();
+		let fx_names = &[
+			"test_rpc_batch_ok_with_errors agent 01",
+			"test_rpc_batch_ok_with_errors agent 02",
+		];
+		let fx_rpc_reqs = json!([
+			create_agent_req(1, fx_names[0]),
+			{"jsonrpc": "2.0", "id": 2, "method": "unknown_method"},
+			create_agent_req(3, fx_names[1]),
+		]);
+
+		// -- Exec
+		let res = exec_handler(&mm, &ctx, false, fx_rpc_reqs).await;
+
+		// -- Check
+		let body = res_json(res).await?;
+		let items = body.as_array().ok_or("should be an array")?;
+		assert_eq!(items.len(), 3);
+		assert_eq!(items[0]["id"], 1);
+		assert!(items[0]["result"].is_i64(), "item 0 should be a result");
+		assert_eq!(items[1]["id"], 2);
+		assert_eq!(items[1]["error"]["message"], "RPC_REQUEST_METHOD_UNKNOWN");
+		assert_eq!(items[2]["id"], 3);
+		assert!(items[2]["result"].is_i64(), "item 2 should be a result");
+		assert_eq!(
+			list_agents(&ctx, &mm, "test_rpc_batch_ok_with_errors").await?,
+			2
+		);
+
+		// -- Clean
+		clean_agents(&ctx, &mm, "test_rpc_batch_ok_with_errors").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_rpc_batch_atomic_err_rollback() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = init_test_mm().await?;
+		let ctx = Ctx::root_ctx();
+		let fx_rpc_reqs = json!([
+			create_agent_req(1, "test_rpc_batch_atomic_err_rollback agent 01"),
+			{"jsonrpc": "2.0", "id": 2, "method": "unknown_method"},
+			{"jsonrpc": "2.0", "method": "create_agent", "params": {"data": {"name": "test_rpc_batch_atomic_err_rollback agent 02"}}},
+			create_agent_req(4, "test_rpc_batch_atomic_err_rollback agent 04"),
+		]);
+
+		// -- Exec
+		let res = exec_handler(&mm, &ctx, true, fx_rpc_reqs).await;
+		let rpc_info_id = res
+			.extensions()
+			.get::<Arc<RpcInfo>>()
+			.and_then(|rpc_info| rpc_info.id.clone());
+		let res = exec_res_map(&ctx, res).await;
+
+		// -- Check
+		assert_eq!(rpc_info_id, Some(json!(2)));
+		let body = res_json(res).await?;
+		let items = body.as_array().ok_or("should be an array")?;
+		let ids: Vec<&Value> = items.iter().map(|item| &item["id"]).collect();
+		assert_eq!(ids, [&json!(1), &json!(2), &json!(4)]);
+		for item in items {
+			assert_eq!(item["error"]["message"], "RPC_REQUEST_METHOD_UNKNOWN");
+		}
+		let count =
+			list_agents(&ctx, &mm, "test_rpc_batch_atomic_err_rollback").await?;
+		assert_eq!(count, 0, "the first insert should be rolled back");
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_rpc_batch_notifications_ok() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = init_test_mm().await?;
+		let ctx = Ctx::root_ctx();
+		let notification_req = |name: &str| {
+			json!({
+				"jsonrpc": "2.0",
+				"method": "create_agent",
+				"params": {"data": {"name": name}}
+			})
+		};
+		let fx_rpc_reqs = json!([
+			create_agent_req(1, "test_rpc_batch_notifications_ok agent 01"),
+			notification_req("test_rpc_batch_notifications_ok agent 02"),
+			{"jsonrpc": "2.0", "method": "unknown_method"},
+		]);
+		let fx_rpc_reqs_only_notifications =
+			json!([notification_req("test_rpc_batch_notifications_ok agent 03")]);
+
+		// -- Exec
+		let res = exec_handler(&mm, &ctx, false, fx_rpc_reqs).await;
+		let res_only_notifications =
+			exec_handler(&mm, &ctx, true, fx_rpc_reqs_only_notifications).await;
+
+		// -- Check
+		let body = res_json(res).await?;
+		let items = body.as_array().ok_or("should be an array")?;
+		assert_eq!(items.len(), 1, "only the request with an id");
+		assert_eq!(items[0]["id"], 1);
+		assert!(items[0]["result"].is_i64(), "item 0 should be a result");
+		assert_eq!(res_only_notifications.status(), StatusCode::NO_CONTENT);
+		assert_eq!(
+			list_agents(&ctx, &mm, "test_rpc_batch_notifications_ok").await?,
+			3
+		);
+
+		// -- Clean
+		clean_agents(&ctx, &mm, "test_rpc_batch_notifications_ok").await?;
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_rpc_batch_err_empty() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = init_test_mm().await?;
+		let ctx = Ctx::root_ctx();
+
+		// -- Exec
+		let res = exec_handler(&mm, &ctx, false, json!([])).await;
+
+		// -- Check
+		assert!(
+			matches!(
+				res.extensions()
+					.get::<Arc<crate::Error>>()
+					.map(|e| e.as_ref()),
+				Some(crate::Error::RpcBatchEmpty)
+			),
+			"should return a RpcBatchEmpty"
+		);
+
+		Ok(())
+	}
+
+	#[serial]
+	#[tokio::test]
+	async fn test_rpc_batch_err_too_large() -> Result<()> {
+		// -- Setup & Fixtures
+		let mm = init_test_mm().await?;
+		let ctx = Ctx::root_ctx();
+		let fx_rpc_reqs: Vec<Value> = (0..=RPC_BATCH_MAX as i64)
+			.map(|id| create_agent_req(id, "test_rpc_batch_err_too_large agent"))
+			.collect();
+
+		// -- Exec
+		let res = exec_handler(&mm, &ctx, true, Value::Array(fx_rpc_reqs)).await;
+
+		// -- Check
+		assert!(
+			matches!(
+				res.extensions()
+					.get::<Arc<crate::Error>>()
+					.map(|e| e.as_ref()),
+				Some(crate::Error::RpcBatchTooLarge { .. })
+			),
+			"should return a RpcBatchTooLarge"
+		);
+		let count = list_agents(&ctx, &mm, "test_rpc_batch_err_too_large").await?;
+		assert_eq!(count, 0, "no call should be executed");
+
+		Ok(())
+	}
+
+	// region:    --- Support
+
+	/// Returns a new `ModelManager` (with its own db pool) for the current test.
+	/// Note: Each `#[tokio::test]` has its own runtime, so the db pool of the shared
+	///       `_dev_utils::init_test()` cannot be used across the tests of this crate.
+	async fn init_test_mm() -> Result<ModelManager> {
+		_dev_utils::init_dev().await;
+		Ok(ModelManager::new().await?)
+	}
+
+	async fn create_agent(
+		ctx: Ctx,
+		mm: ModelManager,
+		params: ParamsForCreate<AgentForCreate>,
+	) -> lib_rpc_core::Result<i64> {
+		Ok(AgentBmc::create(&ctx, &mm, params.data).await?)
+	}
+
+	fn create_agent_req(id: i64, name: &str) -> Value {
+		json!({
 			"jsonrpc": "2.0",
-			"id": rpc_call_response.id,
-			"result": rpc_call_response.value
-		});
-		Json(body_response)
-	});
+			"id": id,
+			"method": "create_agent",
+			"params": {"data": {"name": name}}
+		})
+	}
 
-	// -- Create and Update Axum Response
-	// Note: We store data in the Axum Response extensions so that
-	//       we can unpack it in the `mw_res_map` for client-side rendering.
-	//       This approach centralizes error handling for the client at the `mw_res_map` module
-	let res: crate::error::Result<_> = res.map_err(crate::error::Error::from);
-	let mut res = res.into_response();
-	// Note: Here, add the capture RpcInfo (RPC ID and method) into the Axum response to be used
-	//       later in the `mw_res_map` for RequestLineLogging, and eventual JSON-RPC error serialization.
-	res.extensions_mut().insert(Arc::new(rpc_info));
+	async fn exec_handler(
+		mm: &ModelManager,
+		ctx: &Ctx,
+		atomic: bool,
+		rpc_req: Value,
+	) -> Response {
+		let rpc_router = router_builder!(create_agent)
+			.append_resource(mm.clone())
+			.build();
+		let req_stamp = ReqStamp {
+			uuid: Uuid::new_v4(),
+			time_in: now_utc(),
+		};
 
-	res
+		rpc_axum_handler(
+			State(rpc_router),
+			State(mm.clone()),
+			Query(RpcCallOptions { atomic }),
+			req_stamp,
+			CtxW(ctx.clone()),
+			Json(rpc_req),
+		)
+		.await
+	}
+
+	/// Map the handler response as the `mw_reponse_map` (e.g., for the error bodies).
+	async fn exec_res_map(ctx: &Ctx, res: Response) -> Response {
+		let req_stamp = ReqStamp {
+			uuid: Uuid::new_v4(),
+			time_in: now_utc(),
+		};
+
+		mw_reponse_map(
+			Ok(CtxW(ctx.clone())),
+			Uri::from_static("/api/rpc"),
+			Method::POST,
+			req_stamp,
+			res,
+		)
+		.await
+	}
+
+	async fn res_json(res: Response) -> Result<Value> {
+		let bytes = to_bytes(res.into_body(), usize::MAX).await?;
+		Ok(serde_json::from_slice(&bytes)?)
+	}
+
+	async fn list_agents(
+		ctx: &Ctx,
+		mm: &ModelManager,
+		contains_name: &str,
+	) -> Result<usize> {
+		let filter: AgentFilter =
+			serde_json::from_value(json!({"name": {"$contains": contains_name}}))?;
+		let agents = AgentBmc::list(ctx, mm, Some(vec![filter]), None).await?;
+		Ok(agents.len())
+	}
+
+	// endregion: --- Support
 }
+
+// endregion: --- Tests
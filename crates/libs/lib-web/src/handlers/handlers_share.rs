@@ -0,0 +1,32 @@
+use crate::error::Result;
+use axum::extract::{Path, State};
+use axum::http::{header, HeaderValue};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use lib_core::ctx::Ctx;
+use lib_core::model::conv::ConvBmc;
+use lib_core::model::ModelManager;
+use serde_json::json;
+use tracing::debug;
+
+/// Returns the conv snapshot of the share `token` (public, no ctx required).
+///
+/// Note: Not cached, as the share can be revoked at any time.
+pub async fn get_share_handler(
+	State(mm): State<ModelManager>,
+	Path(token): Path<String>,
+) -> Result<Response> {
+	debug!("{:<12} - get_share_handler", "HANDLER");
+
+	let root_ctx = Ctx::root_ctx();
+	let snapshot = ConvBmc::get_shared_snapshot(&root_ctx, &mm, &token).await?;
+
+	let body = Json(json!({
+		"result": {
+			"data": snapshot
+		}
+	}));
+	let headers = [(header::CACHE_CONTROL, HeaderValue::from_static("no-store"))];
+
+	Ok((headers, body).into_response())
+}
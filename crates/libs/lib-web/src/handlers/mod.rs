@@ -1,2 +1,5 @@
+pub mod handlers_attachment;
+pub mod handlers_conv;
 pub mod handlers_login;
-pub mod handlers_rpc;
\ No newline at end of file
+pub mod handlers_rpc;
+pub mod handlers_share;
\ No newline at end of file
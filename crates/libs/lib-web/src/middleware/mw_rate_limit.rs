@@ -0,0 +1,81 @@
+use crate::error::{Error, Result};
+use axum::body::Body;
+use axum::extract::{ConnectInfo, State};
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Above this number of tracked clients, the ended windows are pruned.
+const RATE_LIMIT_PRUNE_LEN: usize = 10_000;
+
+/// A fixed window rate limiter, by client ip, for the public routes
+/// (e.g., `layer(from_fn_with_state(RateLimit::new(60, ...), mw_rate_limit))`).
+///
+/// Notes:
+///   - The client ip is the `ConnectInfo<SocketAddr>` (requires the server to be served
+///     `with_connect_info`), otherwise all the requests share the same window.
+///   - The windows are in memory, so per server instance.
+#[derive(Clone)]
+pub struct RateLimit {
+	max_per_window: u32,
+	window: Duration,
+	windows: Arc<Mutex<HashMap<IpAddr, (Instant, u32)>>>,
+}
+
+impl RateLimit {
+	pub fn new(max_per_window: u32, window: Duration) -> Self {
+		Self {
+			max_per_window,
+			window,
+			windows: Arc::default(),
+		}
+	}
+
+	/// Count one request of the `ip`, and returns false if over the max of its window.
+	fn check(&self, ip: IpAddr) -> bool {
+		let now = Instant::now();
+		// Note: A poisoned lock only means a panic while holding it (the windows stay valid).
+		let mut windows = self
+			.windows
+			.lock()
+			.unwrap_or_else(|poisoned| poisoned.into_inner());
+
+		if windows.len() > RATE_LIMIT_PRUNE_LEN {
+			windows.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+		}
+
+		let (start, count) = windows.entry(ip).or_insert((now, 0));
+		if now.duration_since(*start) >= self.window {
+			*start = now;
+			*count = 0;
+		}
+		*count += 1;
+
+		*count <= self.max_per_window
+	}
+}
+
+pub async fn mw_rate_limit(
+	State(rate_limit): State<RateLimit>,
+	req: Request<Body>,
+	next: Next,
+) -> Result<Response> {
+	debug!("{:<12} - mw_rate_limit", "MIDDLEWARE");
+
+	let ip = req
+		.extensions()
+		.get::<ConnectInfo<SocketAddr>>()
+		.map(|ConnectInfo(addr)| addr.ip())
+		.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+	if !rate_limit.check(ip) {
+		return Err(Error::RateLimited);
+	}
+
+	Ok(next.run(req).await)
+}
@@ -1,13 +1,13 @@
-use crate::error::{Error, Result};
+use crate::error::{ClientError, Error, Result};
 use crate::handlers::handlers_rpc::RpcInfo;
 use crate::log::log_request;
 use crate::middleware::mw_auth::CtxW;
 use crate::middleware::mw_req_stamp::ReqStamp;
 
-use axum::http::{Method, Uri};
+use axum::http::{Method, StatusCode, Uri};
 use axum::response::{IntoResponse, Response};
 use axum::Json;
-use serde_json::{json, to_value};
+use serde_json::{json, to_value, Value};
 use std::sync::Arc;
 use tracing::debug;
 use uuid::Uuid;
@@ -35,20 +35,29 @@ pub async fn mw_reponse_map(
 		client_status_error
 			.as_ref()
 			.map(|(status_code, client_error)| {
-				let client_error = to_value(client_error).ok();
-				let message = client_error.as_ref().and_then(|v| v.get("message"));
-				let detail = client_error.as_ref().and_then(|v| v.get("detail"));
-
-				let client_error_body = json!({
-					"id": rpc_info.as_ref().map(|rpc| rpc.id.clone()),
-					"error": {
-						"message": message, // Variant name
-						"data": {
-							"req_uuid": uuid.to_string(),
-							"detail": detail
-						},
+				// The failed atomic batch response has the error for each request with an id
+				// (and no content if only notifications).
+				if let Some(Error::RpcBatchAtomic { ids, .. }) = web_error {
+					if ids.is_empty() {
+						return StatusCode::NO_CONTENT.into_response();
 					}
-				});
+					let items: Vec<Value> = ids
+						.iter()
+						.map(|id| {
+							client_error_body(Some(id.clone()), uuid, client_error)
+						})
+						.collect();
+
+					debug!("CLIENT ERROR BATCH BODY:\n{items:?}");
+
+					return Json(Value::Array(items)).into_response();
+				}
+
+				let client_error_body = client_error_body(
+					rpc_info.and_then(|rpc| rpc.id.clone()),
+					uuid,
+					client_error,
+				);
 
 				debug!("CLIENT ERROR BODY:\n{client_error_body}");
 
@@ -75,3 +84,26 @@ pub async fn mw_reponse_map(
 
 	error_response.unwrap_or(res)
 }
+
+/// Build the JSON-RPC error body for a client error.
+/// (also used for the error items of a JSON-RPC batch response)
+pub(crate) fn client_error_body(
+	id: Option<Value>,
+	req_uuid: Uuid,
+	client_error: &ClientError,
+) -> Value {
+	let client_error = to_value(client_error).ok();
+	let message = client_error.as_ref().and_then(|v| v.get("message"));
+	let detail = client_error.as_ref().and_then(|v| v.get("detail"));
+
+	json!({
+		"id": id,
+		"error": {
+			"message": message, // Variant name
+			"data": {
+				"req_uuid": req_uuid.to_string(),
+				"detail": detail
+			},
+		}
+	})
+}
@@ -2,6 +2,7 @@
 
 mod config;
 mod error;
+mod scheduler;
 mod web;
 
 pub use self::error::{Error, Result};
@@ -12,11 +13,12 @@ use lib_web::middleware::mw_req_stamp::mw_req_stamp_resolver;
 use lib_web::middleware::mw_res_map::mw_reponse_map;
 use lib_web::routes::routes_static;
 
-use crate::web::routes_login;
+use crate::web::{routes_login, routes_share};
 
 use axum::{middleware, Router};
 use lib_core::_dev_utils;
 use lib_core::model::ModelManager;
+use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use tower_cookies::CookieManagerLayer;
 use tracing::info;
@@ -37,13 +39,19 @@ async fn main() -> Result<()> {
 
 	let mm = ModelManager::new().await?;
 
+	// -- Start the background scheduler (of the schedule runs)
+	scheduler::spawn_scheduler(mm.clone());
+
 	// -- Define Routes
-	let routes_rpc = web::routes_rpc::routes(mm.clone())
+	let routes_api = web::routes_rpc::routes(mm.clone())
+		.merge(web::routes_attachment::routes(mm.clone()))
+		.merge(web::routes_conv::routes(mm.clone()))
 		.route_layer(middleware::from_fn(mw_ctx_require));
 
 	let routes_all = Router::new()
 		.merge(routes_login::routes(mm.clone()))
-		.nest("/api", routes_rpc)
+		.merge(routes_share::routes(mm.clone()))
+		.nest("/api", routes_api)
 		.layer(middleware::map_response(mw_reponse_map))
 		.layer(middleware::from_fn_with_state(mm.clone(), mw_ctx_resolver))
 		.layer(CookieManagerLayer::new())
@@ -54,9 +62,13 @@ async fn main() -> Result<()> {
 	// Note: For this block, ok to unwrap.
 	let listener = TcpListener::bind("127.0.0.1:8080").await.unwrap();
 	info!("{:<12} - {:?}\n", "LISTENING", listener.local_addr());
-	axum::serve(listener, routes_all.into_make_service())
-		.await
-		.unwrap();
+	// Note: With the connect info for the client ip (e.g., `mw_rate_limit`).
+	axum::serve(
+		listener,
+		routes_all.into_make_service_with_connect_info::<SocketAddr>(),
+	)
+	.await
+	.unwrap();
 	// endregion: --- Start Server
 
 	Ok(())
@@ -0,0 +1,65 @@
+//! The background scheduler of the `Schedule` runs.
+//!
+//! Each web-server instance runs a scheduler, but only the leader runs the due schedules.
+//! The leader is elected with a Postgres advisory lock (see `SchedulerLeaderLock`),
+//! so another instance takes over when the leader dies (and its lock connection closes).
+
+use lib_core::ctx::Ctx;
+use lib_core::model::schedule::{ScheduleBmc, SchedulerLeaderLock};
+use lib_core::model::{self, ModelManager};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{debug, info, warn};
+
+/// The delay between two checks of the due schedules (and leader elections).
+const SCHEDULER_TICK: Duration = Duration::from_secs(10);
+
+/// Spawn the scheduler task (runs until the process ends).
+pub fn spawn_scheduler(mm: ModelManager) -> JoinHandle<()> {
+	tokio::spawn(async move {
+		let mut ticker = interval(SCHEDULER_TICK);
+		// Note: The runs can be longer than a tick (AI calls).
+		ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+		let mut leader_lock: Option<SchedulerLeaderLock> = None;
+
+		loop {
+			ticker.tick().await;
+			if let Err(ex) = tick(&mm, &mut leader_lock).await {
+				warn!("{:<12} - tick fail: {ex:?}", "SCHEDULER");
+			}
+		}
+	})
+}
+
+/// Make sure this instance is the leader (or try to become it), and run the due schedules.
+async fn tick(
+	mm: &ModelManager,
+	leader_lock: &mut Option<SchedulerLeaderLock>,
+) -> model::Result<()> {
+	let ctx = Ctx::root_ctx();
+
+	// -- Check or elect the leader
+	let is_held = match leader_lock.as_mut() {
+		Some(lock) => lock.is_held().await,
+		None => false,
+	};
+	if !is_held {
+		if leader_lock.take().is_some() {
+			warn!("{:<12} - leader lock lost", "SCHEDULER");
+		}
+		*leader_lock = ScheduleBmc::try_lock_leader(&ctx, mm).await?;
+		if leader_lock.is_none() {
+			return Ok(());
+		}
+		info!("{:<12} - elected leader", "SCHEDULER");
+	}
+
+	// -- Run the due schedules
+	let run_count = ScheduleBmc::run_due(&ctx, mm).await?;
+	if run_count > 0 {
+		debug!("{:<12} - {run_count} schedule run(s)", "SCHEDULER");
+	}
+
+	Ok(())
+}
@@ -1,6 +1,9 @@
 // region:    --- Modules
+pub mod routes_attachment;
+pub mod routes_conv;
 pub mod routes_login;
 pub mod routes_rpc;
+pub mod routes_share;
 pub mod rpcs;
 
 // endregion: --- Modules
@@ -0,0 +1,23 @@
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{get, post};
+use axum::Router;
+use lib_core::model::attachment::ATTACHMENT_MAX_SIZE;
+use lib_core::model::ModelManager;
+use lib_web::handlers::handlers_attachment;
+
+/// Build the Axum router for the attachment upload/download
+/// (under '/api', so requiring the ctx).
+pub fn routes(mm: ModelManager) -> Router {
+	Router::new()
+		.route(
+			"/conv-msgs/{msg_id}/attachments",
+			post(handlers_attachment::upload_attachment_handler)
+				.layer(DefaultBodyLimit::max(ATTACHMENT_MAX_SIZE)),
+		)
+		.route(
+			"/attachments/{id}",
+			get(handlers_attachment::download_attachment_handler)
+				.delete(handlers_attachment::delete_attachment_handler),
+		)
+		.with_state(mm)
+}
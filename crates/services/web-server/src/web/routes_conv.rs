@@ -0,0 +1,22 @@
+use axum::extract::DefaultBodyLimit;
+use axum::routing::{get, post};
+use axum::Router;
+use lib_core::model::conv_export::CONV_IMPORT_MAX_SIZE;
+use lib_core::model::ModelManager;
+use lib_web::handlers::handlers_conv;
+
+/// Build the Axum router for the conv export/import
+/// (under '/api', so requiring the ctx).
+pub fn routes(mm: ModelManager) -> Router {
+	Router::new()
+		.route(
+			"/convs/{id}/export",
+			get(handlers_conv::export_conv_handler),
+		)
+		.route(
+			"/convs/import",
+			post(handlers_conv::import_conv_handler)
+				.layer(DefaultBodyLimit::max(CONV_IMPORT_MAX_SIZE)),
+		)
+		.with_state(mm)
+}
@@ -2,7 +2,7 @@ use crate::web::rpcs::all_rpc_router_builder;
 use axum::routing::post;
 use axum::Router;
 use lib_core::model::ModelManager;
-use lib_web::handlers::handlers_rpc;
+use lib_web::handlers::handlers_rpc::{self, RpcState};
 
 ///  Build the Axum router for '/api/rpc'
 /// Note: This will build the `rpc-router::Router` that will be used by the
@@ -11,11 +11,13 @@ pub fn routes(mm: ModelManager) -> Router {
 	// Build the combined Rpc Router (from `rpc-router` crate)
 	let rpc_router = all_rpc_router_builder()
 		// Add the common resources for all rpc calls
-		.append_resource(mm)
+		.append_resource(mm.clone())
 		.build();
 
 	// Build the Axum Router for '/rpc'
+	// Note: The ModelManager is also in the state for the atomic calls
+	//       (e.g., `/api/rpc?atomic=true`)
 	Router::new()
 		.route("/rpc", post(handlers_rpc::rpc_axum_handler))
-		.with_state(rpc_router)
+		.with_state(RpcState { rpc_router, mm })
 }
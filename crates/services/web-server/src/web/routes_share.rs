@@ -0,0 +1,22 @@
+use axum::middleware;
+use axum::routing::get;
+use axum::Router;
+use lib_core::model::ModelManager;
+use lib_web::handlers::handlers_share;
+use lib_web::middleware::mw_rate_limit::{mw_rate_limit, RateLimit};
+use std::time::Duration;
+
+/// The max requests per client ip and window of the public share route.
+const SHARE_RATE_LIMIT_MAX: u32 = 60;
+const SHARE_RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Build the Axum router for the public conv share links
+/// (NOT under '/api', so no ctx required, but rate limited).
+pub fn routes(mm: ModelManager) -> Router {
+	let rate_limit = RateLimit::new(SHARE_RATE_LIMIT_MAX, SHARE_RATE_LIMIT_WINDOW);
+
+	Router::new()
+		.route("/share/{token}", get(handlers_share::get_share_handler))
+		.route_layer(middleware::from_fn_with_state(rate_limit, mw_rate_limit))
+		.with_state(mm)
+}
@@ -1,24 +1,229 @@
-use lib_rpc_core::prelude::*;
+use lib_core::ai::tool::{ToolRegistry, ToolSpec};
+use lib_core::generate_agent_rpc_fns;
 use lib_core::model::agent::{
-	Agent, AgentBmc, AgentFilter, AgentForCreate, AgentForUpdate,
+	Agent, AgentBmc, AgentFilter, AgentForCreate, AgentForUpdate, AgentVersion,
 };
+use lib_core::model::agent_share::{AgentForClone, AgentShare, AgentShareForCreate};
+use lib_rpc_core::prelude::*;
 
 pub fn rpc_router_builder() -> RouterBuilder {
-	router_builder!(
+	agent_rpc_router_builder().extend(router_builder!(
 		// Same as RpcRouter::new().add...
-		create_agent,
 		get_agent,
 		list_agents,
+		count_agents,
 		update_agent,
 		delete_agent,
-	)
+		clone_agent,
+		share_agent,
+		unshare_agent,
+		list_agent_shares,
+		pin_agent,
+		unpin_agent,
+		list_agent_versions,
+		get_agent_version,
+		list_agent_tools,
+	))
+}
+
+// This will generate the common agent rpc handlers and the `agent_rpc_router_builder()`
+// (but the ones below, which check the agent visibility or ownership).
+//
+// Note: The `update_many_agents` and `delete_many_agents` are not exposed
+//       (they do not check the agent ownership).
+generate_agent_rpc_fns!();
+
+/// Returns the agent (if visible by the ctx user).
+pub async fn get_agent(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Agent>> {
+	let ParamsIded { id } = params;
+
+	let agent = AgentBmc::get_visible(&ctx, &mm, id).await?;
+
+	Ok(agent.into())
+}
+
+/// Returns one page of the agents visible by the ctx user, with its pinned agents first.
+pub async fn list_agents(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsList<AgentFilter>,
+) -> Result<ListRpcResult<Agent>> {
+	let ParamsList {
+		filters,
+		list_options,
+		cursor,
+	} = params;
+
+	let page =
+		AgentBmc::list_page_pinned_first(&ctx, &mm, filters, list_options, cursor)
+			.await?;
+
+	Ok(page.into())
+}
+
+/// Returns the number of agents matching the filters (and visible by the ctx user).
+pub async fn count_agents(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsFilters<AgentFilter>,
+) -> Result<DataRpcResult<i64>> {
+	let ParamsFilters { filters } = params;
+
+	let count = AgentBmc::count_visible(&ctx, &mm, filters).await?;
+
+	Ok(count.into())
+}
+
+/// Updates the agent (owner only), and returns it.
+pub async fn update_agent(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForUpdate<AgentForUpdate>,
+) -> Result<DataRpcResult<Agent>> {
+	let ParamsForUpdate { id, data } = params;
+
+	AgentBmc::update_owned(&ctx, &mm, id, data).await?;
+	let agent = AgentBmc::get(&ctx, &mm, id).await?;
+
+	Ok(agent.into())
+}
+
+/// Deletes the agent (owner only), and returns it.
+pub async fn delete_agent(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Agent>> {
+	let ParamsIded { id } = params;
+
+	let agent = AgentBmc::get_visible(&ctx, &mm, id).await?;
+	AgentBmc::delete_owned(&ctx, &mm, id).await?;
+
+	Ok(agent.into())
+}
+
+/// Clones the agent (visible by the ctx user) into a new private agent of the ctx user,
+/// with `"data": {"agent_id": 123, "name": "My copy"}`, and returns the clone.
+pub async fn clone_agent(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<AgentForClone>,
+) -> Result<DataRpcResult<Agent>> {
+	let ParamsForCreate { data } = params;
+
+	let id = AgentBmc::clone_agent(&ctx, &mm, data).await?;
+	let agent = AgentBmc::get(&ctx, &mm, id).await?;
+
+	Ok(agent.into())
+}
+
+/// Shares the agent (owner only) with `"data": {"agent_id": 123, "user_id": 456}`,
+/// and returns the agent shares.
+///
+/// Note: Effective for the `Shared` and `Org` visibilities only.
+pub async fn share_agent(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<AgentShareForCreate>,
+) -> Result<DataRpcResult<Vec<AgentShare>>> {
+	let ParamsForCreate { data } = params;
+	let agent_id = data.agent_id;
+
+	AgentBmc::share(&ctx, &mm, data).await?;
+	let shares = AgentBmc::list_shares(&ctx, &mm, agent_id).await?;
+
+	Ok(shares.into())
+}
+
+/// Unshares the agent (same params as `share_agent`), and returns the agent shares.
+pub async fn unshare_agent(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<AgentShareForCreate>,
+) -> Result<DataRpcResult<Vec<AgentShare>>> {
+	let ParamsForCreate { data } = params;
+	let agent_id = data.agent_id;
+
+	AgentBmc::unshare(&ctx, &mm, data).await?;
+	let shares = AgentBmc::list_shares(&ctx, &mm, agent_id).await?;
+
+	Ok(shares.into())
+}
+
+/// Returns the shares of the agent `id` (owner only).
+pub async fn list_agent_shares(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<AgentShare>>> {
+	let ParamsIded { id: agent_id } = params;
+
+	let shares = AgentBmc::list_shares(&ctx, &mm, agent_id).await?;
+
+	Ok(shares.into())
+}
+
+/// Pins the agent for the ctx user, and returns it.
+pub async fn pin_agent(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Agent>> {
+	let ParamsIded { id } = params;
+
+	AgentBmc::pin(&ctx, &mm, id).await?;
+	let mut agent = AgentBmc::get(&ctx, &mm, id).await?;
+	agent.pinned = Some(true);
+
+	Ok(agent.into())
+}
+
+/// Unpins the agent for the ctx user, and returns it.
+pub async fn unpin_agent(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Agent>> {
+	let ParamsIded { id } = params;
+
+	AgentBmc::unpin(&ctx, &mm, id).await?;
+	let mut agent = AgentBmc::get(&ctx, &mm, id).await?;
+	agent.pinned = Some(false);
+
+	Ok(agent.into())
+}
+
+/// Returns the agent versions (for the agent `id`), ordered by version.
+pub async fn list_agent_versions(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<AgentVersion>>> {
+	let ParamsIded { id: agent_id } = params;
+
+	let versions = AgentBmc::list_versions(&ctx, &mm, agent_id).await?;
+
+	Ok(versions.into())
 }
 
-generate_common_rpc_fns!(
-	Bmc: AgentBmc,
-	Entity: Agent,
-	ForCreate: AgentForCreate,
-	ForUpdate: AgentForUpdate,
-	Filter: AgentFilter,
-	Suffix: agent
-);
+/// Returns agent_version (e.g., the `Conv.agent_version_id`)
+pub async fn get_agent_version(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<AgentVersion>> {
+	let ParamsIded { id } = params;
+
+	let version = AgentBmc::get_version(&ctx, &mm, id).await?;
+
+	Ok(version.into())
+}
+
+/// Returns the tools which can be enabled in the `Agent.tools`.
+pub async fn list_agent_tools() -> Result<DataRpcResult<Vec<ToolSpec>>> {
+	Ok(ToolRegistry::builtin().specs().into())
+}
@@ -0,0 +1,22 @@
+use lib_core::model::audit_log::{AuditLog, AuditLogBmc, AuditLogFilter};
+use lib_rpc_core::prelude::*;
+
+pub fn rpc_router_builder() -> RouterBuilder {
+	router_builder!(
+		// Same as RpcRouter::new().add...
+		list_audit_logs,
+	)
+}
+
+/// Returns the audit log entries matching the filters.
+/// (root only)
+pub async fn list_audit_logs(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsList<AuditLogFilter>,
+) -> Result<DataRpcResult<Vec<AuditLog>>> {
+	let logs =
+		AuditLogBmc::list(&ctx, &mm, params.filters, params.list_options).await?;
+
+	Ok(logs.into())
+}
@@ -1,29 +1,207 @@
+use lib_core::generate_conv_rpc_fns;
 use lib_core::model::conv::{
-	Conv, ConvBmc, ConvFilter, ConvForCreate, ConvForUpdate,
+	Conv, ConvBmc, ConvBranch, ConvFilter, ConvForCreate, ConvForFork,
+	ConvForMarkRead, ConvForUpdate,
 };
-use lib_core::model::conv_msg::{ConvMsg, ConvMsgForCreate};
+use lib_core::model::conv_msg::{
+	ConvMsg, ConvMsgFilter, ConvMsgForCreate, ConvMsgForUpdate, ConvMsgHit,
+};
+use lib_core::model::conv_msg_reaction::{
+	ConvMsgReaction, ConvMsgReactionForCreate,
+};
+use lib_core::model::conv_msg_revision::ConvMsgRevision;
+use lib_core::model::conv_share::{ConvShare, ConvShareForCreate};
+use lib_core::model::conv_summary::ConvSummary;
 use lib_rpc_core::prelude::*;
 
 pub fn rpc_router_builder() -> RouterBuilder {
-	router_builder!(
+	conv_rpc_router_builder().extend(router_builder!(
 		// Same as RpcRouter::new().add...
 		create_conv,
+		create_many_convs,
 		get_conv,
 		list_convs,
+		count_convs,
 		update_conv,
+		update_many_convs,
 		delete_conv,
+		delete_many_convs,
+		mark_conv_read,
 		add_conv_msg,
-	)
+		generate_conv_reply,
+		list_conv_summaries,
+		get_conv_branch,
+		switch_conv_branch,
+		regenerate_conv_reply,
+		fork_conv,
+		pin_conv,
+		unpin_conv,
+		create_conv_share,
+		list_conv_shares,
+		revoke_conv_share,
+		update_conv_msg,
+		list_conv_msgs,
+		search_conv_msgs,
+		list_conv_msg_revisions,
+		add_conv_msg_reaction,
+		remove_conv_msg_reaction,
+		list_conv_msg_reactions,
+	))
 }
 
-generate_common_rpc_fns!(
-	Bmc: ConvBmc,
-	Entity: Conv,
-	ForCreate: ConvForCreate,
-	ForUpdate: ConvForUpdate,
-	Filter: ConvFilter,
-	Suffix: conv
-);
+// This will generate the common conv rpc handlers and the `conv_rpc_router_builder()`
+// (but the ones below, which check the agent usage, or the conv membership or ownership).
+generate_conv_rpc_fns!();
+
+/// Creates a conv (with an agent usable by the ctx user) and returns it.
+pub async fn create_conv(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ConvForCreate>,
+) -> Result<DataRpcResult<Conv>> {
+	let ParamsForCreate { data } = params;
+
+	let id = ConvBmc::start(&ctx, &mm, data).await?;
+	let conv = ConvBmc::get(&ctx, &mm, id).await?;
+
+	Ok(conv.into())
+}
+
+/// Creates the convs and returns the result (id or error) of each of them.
+///
+/// Note: Atomic unless `partial` is true.
+pub async fn create_many_convs(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreateMany<ConvForCreate>,
+) -> Result<DataRpcResult<Vec<BulkItemRpcResult<i64>>>> {
+	let ParamsForCreateMany { data, partial } = params;
+
+	let results = ConvBmc::start_many(&ctx, &mm, data, partial).await?;
+	let results: Vec<_> = results.into_iter().map(BulkItemRpcResult::from).collect();
+
+	Ok(results.into())
+}
+
+/// Returns one page of the convs visible by the ctx user, with its pinned convs first,
+/// and its `unread_count` and `tag_ids`.
+pub async fn list_convs(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsList<ConvFilter>,
+) -> Result<ListRpcResult<Conv>> {
+	let ParamsList {
+		filters,
+		list_options,
+		cursor,
+	} = params;
+
+	let page =
+		ConvBmc::list_page_with_unread(&ctx, &mm, filters, list_options, cursor)
+			.await?;
+
+	Ok(page.into())
+}
+
+/// Returns the number of convs matching the filters (and visible by the ctx user).
+pub async fn count_convs(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsFilters<ConvFilter>,
+) -> Result<DataRpcResult<i64>> {
+	let ParamsFilters { filters } = params;
+
+	let count = ConvBmc::count_visible(&ctx, &mm, filters).await?;
+
+	Ok(count.into())
+}
+
+/// Returns the conv (if the ctx user is a conv user).
+pub async fn get_conv(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Conv>> {
+	let ParamsIded { id } = params;
+
+	let conv = ConvBmc::get_visible(&ctx, &mm, id).await?;
+
+	Ok(conv.into())
+}
+
+/// Updates the conv (owner only), and returns it.
+pub async fn update_conv(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForUpdate<ConvForUpdate>,
+) -> Result<DataRpcResult<Conv>> {
+	let ParamsForUpdate { id, data } = params;
+
+	ConvBmc::update_owned(&ctx, &mm, id, data).await?;
+	let conv = ConvBmc::get(&ctx, &mm, id).await?;
+
+	Ok(conv.into())
+}
+
+/// Updates the convs (owner only) and returns the updated count.
+pub async fn update_many_convs(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForUpdateMany<ConvForUpdate>,
+) -> Result<DataRpcResult<u64>> {
+	let ParamsForUpdateMany { ids, data } = params;
+
+	let count = ConvBmc::update_many_owned(&ctx, &mm, ids, data).await?;
+
+	Ok(count.into())
+}
+
+/// Deletes the conv (owner only), and returns it.
+pub async fn delete_conv(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Conv>> {
+	let ParamsIded { id } = params;
+
+	let conv = ConvBmc::get_visible(&ctx, &mm, id).await?;
+	ConvBmc::delete_owned(&ctx, &mm, id).await?;
+
+	Ok(conv.into())
+}
+
+/// Deletes the convs (owner only) and returns the result of each id.
+///
+/// Note: Atomic unless `partial` is true.
+pub async fn delete_many_convs(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIds,
+) -> Result<DataRpcResult<Vec<BulkItemRpcResult<()>>>> {
+	let ParamsIds { ids, partial } = params;
+
+	let results = ConvBmc::delete_many_owned(&ctx, &mm, ids, partial).await?;
+	let results: Vec<_> = results.into_iter().map(BulkItemRpcResult::from).collect();
+
+	Ok(results.into())
+}
+
+/// Mark the conv msgs as read by the ctx user, up to `"data": {"msg_id": 123}`
+/// (default: the last msg).
+/// Returns conv (with the `unread_count` of the ctx user)
+pub async fn mark_conv_read(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForUpdate<ConvForMarkRead>,
+) -> Result<DataRpcResult<Conv>> {
+	let ParamsForUpdate { id: conv_id, data } = params;
+
+	ConvBmc::mark_read(&ctx, &mm, conv_id, data).await?;
+	let mut conv = ConvBmc::get(&ctx, &mm, conv_id).await?;
+	ConvBmc::load_unread_counts(&ctx, &mm, std::slice::from_mut(&mut conv)).await?;
+
+	Ok(conv.into())
+}
 
 /// Returns conv_msg
 pub async fn add_conv_msg(
@@ -39,6 +217,166 @@ pub async fn add_conv_msg(
 	Ok(msg.into())
 }
 
+/// Generate the agent reply of the conv `id` (with the eventual tool steps).
+/// Returns the added conv_msgs, in order.
+pub async fn generate_conv_reply(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<ConvMsg>>> {
+	let ParamsIded { id: conv_id } = params;
+
+	let msg_ids = ConvBmc::generate_reply(&ctx, &mm, conv_id).await?;
+	let mut msgs = Vec::with_capacity(msg_ids.len());
+	for msg_id in msg_ids {
+		msgs.push(ConvBmc::get_msg(&ctx, &mm, msg_id).await?);
+	}
+
+	Ok(msgs.into())
+}
+
+/// Returns the active branch of the conv.
+pub async fn get_conv_branch(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<ConvBranch>> {
+	let ParamsIded { id: conv_id } = params;
+
+	let branch = ConvBmc::get_branch(&ctx, &mm, conv_id).await?;
+
+	Ok(branch.into())
+}
+
+/// Makes the branch of the msg the active one, and returns it.
+pub async fn switch_conv_branch(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<ConvBranch>> {
+	let ParamsIded { id: msg_id } = params;
+
+	let msg = ConvBmc::get_msg(&ctx, &mm, msg_id).await?;
+	ConvBmc::switch_branch(&ctx, &mm, msg_id).await?;
+	let branch = ConvBmc::get_branch(&ctx, &mm, msg.conv_id).await?;
+
+	Ok(branch.into())
+}
+
+/// Regenerates the agent reply of the msg, and returns the new active branch.
+pub async fn regenerate_conv_reply(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<ConvBranch>> {
+	let ParamsIded { id: msg_id } = params;
+
+	let msg = ConvBmc::get_msg(&ctx, &mm, msg_id).await?;
+	ConvBmc::regenerate_reply(&ctx, &mm, msg_id).await?;
+	let branch = ConvBmc::get_branch(&ctx, &mm, msg.conv_id).await?;
+
+	Ok(branch.into())
+}
+
+/// Forks the conv at the msg, and returns the active branch of the new conv.
+pub async fn fork_conv(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ConvForFork>,
+) -> Result<DataRpcResult<ConvBranch>> {
+	let ParamsForCreate { data } = params;
+	let ConvForFork { conv_id, at_msg_id } = data;
+
+	let fork_id = ConvBmc::fork_conv(&ctx, &mm, conv_id, at_msg_id).await?;
+	let branch = ConvBmc::get_branch(&ctx, &mm, fork_id).await?;
+
+	Ok(branch.into())
+}
+
+/// Pins the conv for the ctx user (a conv user), and returns it.
+pub async fn pin_conv(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Conv>> {
+	let ParamsIded { id } = params;
+
+	ConvBmc::pin(&ctx, &mm, id).await?;
+	let mut conv = ConvBmc::get(&ctx, &mm, id).await?;
+	conv.pinned = Some(true);
+
+	Ok(conv.into())
+}
+
+/// Unpins the conv for the ctx user, and returns it.
+pub async fn unpin_conv(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Conv>> {
+	let ParamsIded { id } = params;
+
+	ConvBmc::unpin(&ctx, &mm, id).await?;
+	let mut conv = ConvBmc::get(&ctx, &mm, id).await?;
+	conv.pinned = Some(false);
+
+	Ok(conv.into())
+}
+
+/// Creates a public share link of the conv active branch (owner only).
+pub async fn create_conv_share(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ConvShareForCreate>,
+) -> Result<DataRpcResult<ConvShare>> {
+	let ParamsForCreate { data } = params;
+
+	let share_id = ConvBmc::create_share(&ctx, &mm, data).await?;
+	let share = ConvBmc::get_share(&ctx, &mm, share_id).await?;
+
+	Ok(share.into())
+}
+
+/// Returns the share links of the conv (owner only).
+pub async fn list_conv_shares(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<ConvShare>>> {
+	let ParamsIded { id: conv_id } = params;
+
+	let shares = ConvBmc::list_shares(&ctx, &mm, conv_id).await?;
+
+	Ok(shares.into())
+}
+
+/// Revokes the share link (owner only), and returns it.
+pub async fn revoke_conv_share(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<ConvShare>> {
+	let ParamsIded { id: share_id } = params;
+
+	ConvBmc::revoke_share(&ctx, &mm, share_id).await?;
+	let share = ConvBmc::get_share(&ctx, &mm, share_id).await?;
+
+	Ok(share.into())
+}
+
+/// Returns the rolling summaries of the conv (oldest first).
+pub async fn list_conv_summaries(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<ConvSummary>>> {
+	let ParamsIded { id: conv_id } = params;
+
+	let summaries = ConvBmc::list_summaries(&ctx, &mm, conv_id).await?;
+
+	Ok(summaries.into())
+}
+
 /// Returns conv_msg
 #[allow(unused)]
 pub async fn get_conv_msg(
@@ -52,3 +390,108 @@ pub async fn get_conv_msg(
 
 	Ok(msg.into())
 }
+
+/// Edit the conv_msg content (the prior content is kept as a revision).
+/// Returns conv_msg
+pub async fn update_conv_msg(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForUpdate<ConvMsgForUpdate>,
+) -> Result<DataRpcResult<ConvMsg>> {
+	let ParamsForUpdate { id: msg_id, data } = params;
+
+	ConvBmc::update_msg(&ctx, &mm, msg_id, data).await?;
+	let msg = ConvBmc::get_msg(&ctx, &mm, msg_id).await?;
+
+	Ok(msg.into())
+}
+
+/// Returns one page of the conv_msg of the conv (e.g., `"id": 123`)
+pub async fn list_conv_msgs(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIdedList<ConvMsgFilter>,
+) -> Result<ListRpcResult<ConvMsg>> {
+	let ParamsIdedList {
+		id: conv_id,
+		filters,
+		list_options,
+		cursor,
+	} = params;
+
+	let page = ConvBmc::list_msgs(&ctx, &mm, conv_id, filters, list_options, cursor)
+		.await?;
+
+	Ok(page.into())
+}
+
+/// Returns one page of the ranked conv_msg/conv hits of the user convs
+/// (e.g., `"query": "rust \"web app\""`)
+pub async fn search_conv_msgs(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForSearch,
+) -> Result<ListRpcResult<ConvMsgHit>> {
+	let ParamsForSearch {
+		query,
+		list_options,
+	} = params;
+
+	let page = ConvBmc::search_msgs(&ctx, &mm, &query, list_options).await?;
+
+	Ok(page.into())
+}
+
+/// Returns the prior versions of the conv_msg (`"id"` is the msg id)
+pub async fn list_conv_msg_revisions(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<ConvMsgRevision>>> {
+	let ParamsIded { id: msg_id } = params;
+
+	let revisions = ConvBmc::list_msg_revisions(&ctx, &mm, msg_id).await?;
+
+	Ok(revisions.into())
+}
+
+/// Returns conv_msg_reaction
+pub async fn add_conv_msg_reaction(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ConvMsgReactionForCreate>,
+) -> Result<DataRpcResult<ConvMsgReaction>> {
+	let ParamsForCreate { data: reaction_c } = params;
+
+	let reaction_id = ConvBmc::add_msg_reaction(&ctx, &mm, reaction_c).await?;
+	let reaction = ConvBmc::get_msg_reaction(&ctx, &mm, reaction_id).await?;
+
+	Ok(reaction.into())
+}
+
+/// Returns the removed conv_msg_reaction
+pub async fn remove_conv_msg_reaction(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<ConvMsgReaction>> {
+	let ParamsIded { id: reaction_id } = params;
+
+	let reaction = ConvBmc::get_msg_reaction(&ctx, &mm, reaction_id).await?;
+	ConvBmc::remove_msg_reaction(&ctx, &mm, reaction_id).await?;
+
+	Ok(reaction.into())
+}
+
+/// Returns the conv_msg reactions (`"id"` is the msg id)
+pub async fn list_conv_msg_reactions(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<ConvMsgReaction>>> {
+	let ParamsIded { id: msg_id } = params;
+
+	let reactions = ConvBmc::list_msg_reactions(&ctx, &mm, msg_id).await?;
+
+	Ok(reactions.into())
+}
@@ -0,0 +1,103 @@
+use lib_core::model::agent::AgentBmc;
+use lib_core::model::conv::{Conv, ConvBmc};
+use lib_core::model::conv_template::{
+	ConvFromTemplate, ConvTemplate, ConvTemplateForCreate, ConvTemplateForUpdate,
+};
+use lib_rpc_core::prelude::*;
+
+pub fn rpc_router_builder() -> RouterBuilder {
+	router_builder!(
+		// Same as RpcRouter::new().add...
+		create_conv_template,
+		get_conv_template,
+		list_conv_templates,
+		update_conv_template,
+		delete_conv_template,
+		create_conv_from_template,
+	)
+}
+
+/// Returns the created template (agent owner only).
+pub async fn create_conv_template(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ConvTemplateForCreate>,
+) -> Result<DataRpcResult<ConvTemplate>> {
+	let ParamsForCreate { data: template_c } = params;
+
+	let id = AgentBmc::create_template(&ctx, &mm, template_c).await?;
+	let template = AgentBmc::get_template(&ctx, &mm, id).await?;
+
+	Ok(template.into())
+}
+
+pub async fn get_conv_template(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<ConvTemplate>> {
+	let ParamsIded { id } = params;
+
+	let template = AgentBmc::get_template(&ctx, &mm, id).await?;
+
+	Ok(template.into())
+}
+
+/// Returns the templates of the agent `id` (by name).
+pub async fn list_conv_templates(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<ConvTemplate>>> {
+	let ParamsIded { id: agent_id } = params;
+
+	let templates = AgentBmc::list_templates(&ctx, &mm, agent_id).await?;
+
+	Ok(templates.into())
+}
+
+/// Updates the template (agent owner only), and returns it.
+pub async fn update_conv_template(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForUpdate<ConvTemplateForUpdate>,
+) -> Result<DataRpcResult<ConvTemplate>> {
+	let ParamsForUpdate {
+		id,
+		data: template_u,
+	} = params;
+
+	AgentBmc::update_template(&ctx, &mm, id, template_u).await?;
+	let template = AgentBmc::get_template(&ctx, &mm, id).await?;
+
+	Ok(template.into())
+}
+
+/// Returns the deleted template (agent owner only).
+pub async fn delete_conv_template(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<ConvTemplate>> {
+	let ParamsIded { id } = params;
+
+	let template = AgentBmc::get_template(&ctx, &mm, id).await?;
+	AgentBmc::delete_template(&ctx, &mm, id).await?;
+
+	Ok(template.into())
+}
+
+/// Creates the conv (with its initial msgs) from the template with
+/// `"data": {"template_id": 123, "vars": {"lang": "Rust"}}`, and returns the conv.
+pub async fn create_conv_from_template(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ConvFromTemplate>,
+) -> Result<DataRpcResult<Conv>> {
+	let ParamsForCreate { data: conv_t } = params;
+
+	let conv_id = ConvBmc::create_from_template(&ctx, &mm, conv_t).await?;
+	let conv = ConvBmc::get(&ctx, &mm, conv_id).await?;
+
+	Ok(conv.into())
+}
@@ -0,0 +1,84 @@
+use lib_core::model::conv::{Conv, ConvBmc};
+use lib_core::model::folder::{
+	ConvForMoveToFolder, Folder, FolderBmc, FolderForCreate,
+};
+use lib_rpc_core::prelude::*;
+
+pub fn rpc_router_builder() -> RouterBuilder {
+	router_builder!(
+		// Same as RpcRouter::new().add...
+		create_folder,
+		list_folders,
+		update_folder,
+		delete_folder,
+		move_conv_to_folder,
+	)
+}
+
+/// Returns the created folder (of the ctx user).
+pub async fn create_folder(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<FolderForCreate>,
+) -> Result<DataRpcResult<Folder>> {
+	let ParamsForCreate { data: folder_c } = params;
+
+	let id = FolderBmc::create(&ctx, &mm, folder_c).await?;
+	let folder = FolderBmc::get(&ctx, &mm, id).await?;
+
+	Ok(folder.into())
+}
+
+/// Returns the folders of the ctx user (by name).
+pub async fn list_folders(
+	ctx: Ctx,
+	mm: ModelManager,
+) -> Result<DataRpcResult<Vec<Folder>>> {
+	let folders = FolderBmc::list(&ctx, &mm).await?;
+
+	Ok(folders.into())
+}
+
+/// Renames the folder, and returns it.
+pub async fn update_folder(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForUpdate<FolderForCreate>,
+) -> Result<DataRpcResult<Folder>> {
+	let ParamsForUpdate { id, data: folder_u } = params;
+
+	FolderBmc::update(&ctx, &mm, id, folder_u).await?;
+	let folder = FolderBmc::get(&ctx, &mm, id).await?;
+
+	Ok(folder.into())
+}
+
+/// Returns the deleted folder (its convs are kept, without folder).
+pub async fn delete_folder(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Folder>> {
+	let ParamsIded { id } = params;
+
+	let folder = FolderBmc::get(&ctx, &mm, id).await?;
+	FolderBmc::delete(&ctx, &mm, id).await?;
+
+	Ok(folder.into())
+}
+
+/// Moves the conv with `"data": {"conv_id": 123, "folder_id": 456}`
+/// (`"folder_id": null` to remove it from its folder), and returns the conv.
+pub async fn move_conv_to_folder(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ConvForMoveToFolder>,
+) -> Result<DataRpcResult<Conv>> {
+	let ParamsForCreate { data: conv_move } = params;
+	let conv_id = conv_move.conv_id;
+
+	ConvBmc::move_to_folder(&ctx, &mm, conv_move).await?;
+	let conv = ConvBmc::get(&ctx, &mm, conv_id).await?;
+
+	Ok(conv.into())
+}
@@ -1,7 +1,13 @@
 // region:    --- Modules
 
 pub mod agent_rpc;
+pub mod audit_log_rpc;
 pub mod conv_rpc;
+pub mod conv_template_rpc;
+pub mod folder_rpc;
+pub mod schedule_rpc;
+pub mod tag_rpc;
+pub mod usage_rpc;
 
 use rpc_router::{Router, RouterBuilder};
 
@@ -10,5 +16,11 @@ use rpc_router::{Router, RouterBuilder};
 pub fn all_rpc_router_builder() -> RouterBuilder {
 	Router::builder()
 		.extend(agent_rpc::rpc_router_builder())
+		.extend(audit_log_rpc::rpc_router_builder())
 		.extend(conv_rpc::rpc_router_builder())
+		.extend(conv_template_rpc::rpc_router_builder())
+		.extend(folder_rpc::rpc_router_builder())
+		.extend(schedule_rpc::rpc_router_builder())
+		.extend(tag_rpc::rpc_router_builder())
+		.extend(usage_rpc::rpc_router_builder())
 }
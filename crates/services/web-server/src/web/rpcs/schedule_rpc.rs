@@ -0,0 +1,97 @@
+use lib_core::model::schedule::{
+	Schedule, ScheduleBmc, ScheduleForCreate, ScheduleForUpdate, ScheduleRun,
+};
+use lib_rpc_core::prelude::*;
+
+pub fn rpc_router_builder() -> RouterBuilder {
+	router_builder!(
+		// Same as RpcRouter::new().add...
+		create_schedule,
+		get_schedule,
+		list_schedules,
+		update_schedule,
+		delete_schedule,
+		list_schedule_runs,
+	)
+}
+
+/// Returns the created schedule (of the ctx user), with
+/// `"data": {"conv_id": 123, "name": "Standup", "cron": "0 9 * * MON-FRI", "prompt": "..."}`.
+pub async fn create_schedule(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ScheduleForCreate>,
+) -> Result<DataRpcResult<Schedule>> {
+	let ParamsForCreate { data: schedule_c } = params;
+
+	let id = ScheduleBmc::create(&ctx, &mm, schedule_c).await?;
+	let schedule = ScheduleBmc::get(&ctx, &mm, id).await?;
+
+	Ok(schedule.into())
+}
+
+pub async fn get_schedule(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Schedule>> {
+	let ParamsIded { id } = params;
+
+	let schedule = ScheduleBmc::get(&ctx, &mm, id).await?;
+
+	Ok(schedule.into())
+}
+
+/// Returns the schedules of the ctx user.
+pub async fn list_schedules(
+	ctx: Ctx,
+	mm: ModelManager,
+) -> Result<DataRpcResult<Vec<Schedule>>> {
+	let schedules = ScheduleBmc::list(&ctx, &mm).await?;
+
+	Ok(schedules.into())
+}
+
+/// Updates the schedule (e.g., `"enabled": false` to pause it), and returns it.
+pub async fn update_schedule(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForUpdate<ScheduleForUpdate>,
+) -> Result<DataRpcResult<Schedule>> {
+	let ParamsForUpdate {
+		id,
+		data: schedule_u,
+	} = params;
+
+	ScheduleBmc::update(&ctx, &mm, id, schedule_u).await?;
+	let schedule = ScheduleBmc::get(&ctx, &mm, id).await?;
+
+	Ok(schedule.into())
+}
+
+/// Returns the deleted schedule (its run history is deleted as well).
+pub async fn delete_schedule(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Schedule>> {
+	let ParamsIded { id } = params;
+
+	let schedule = ScheduleBmc::get(&ctx, &mm, id).await?;
+	ScheduleBmc::delete(&ctx, &mm, id).await?;
+
+	Ok(schedule.into())
+}
+
+/// Returns the last runs of the schedule `id` (latest first).
+pub async fn list_schedule_runs(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<ScheduleRun>>> {
+	let ParamsIded { id: schedule_id } = params;
+
+	let runs = ScheduleBmc::list_runs(&ctx, &mm, schedule_id).await?;
+
+	Ok(runs.into())
+}
@@ -0,0 +1,113 @@
+use lib_core::model::conv::ConvBmc;
+use lib_core::model::tag::{ConvTagForCreate, Tag, TagBmc, TagForCreate};
+use lib_rpc_core::prelude::*;
+
+pub fn rpc_router_builder() -> RouterBuilder {
+	router_builder!(
+		// Same as RpcRouter::new().add...
+		create_tag,
+		list_tags,
+		update_tag,
+		delete_tag,
+		add_conv_tag,
+		remove_conv_tag,
+		list_conv_tags,
+	)
+}
+
+/// Returns the created tag (of the ctx user).
+pub async fn create_tag(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<TagForCreate>,
+) -> Result<DataRpcResult<Tag>> {
+	let ParamsForCreate { data: tag_c } = params;
+
+	let id = TagBmc::create(&ctx, &mm, tag_c).await?;
+	let tag = TagBmc::get(&ctx, &mm, id).await?;
+
+	Ok(tag.into())
+}
+
+/// Returns the tags of the ctx user (by name).
+pub async fn list_tags(
+	ctx: Ctx,
+	mm: ModelManager,
+) -> Result<DataRpcResult<Vec<Tag>>> {
+	let tags = TagBmc::list(&ctx, &mm).await?;
+
+	Ok(tags.into())
+}
+
+/// Renames the tag, and returns it.
+pub async fn update_tag(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForUpdate<TagForCreate>,
+) -> Result<DataRpcResult<Tag>> {
+	let ParamsForUpdate { id, data: tag_u } = params;
+
+	TagBmc::update(&ctx, &mm, id, tag_u).await?;
+	let tag = TagBmc::get(&ctx, &mm, id).await?;
+
+	Ok(tag.into())
+}
+
+/// Returns the deleted tag (removed from all its convs).
+pub async fn delete_tag(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Tag>> {
+	let ParamsIded { id } = params;
+
+	let tag = TagBmc::get(&ctx, &mm, id).await?;
+	TagBmc::delete(&ctx, &mm, id).await?;
+
+	Ok(tag.into())
+}
+
+/// Tags the conv with `"data": {"conv_id": 123, "tag_id": 456}`,
+/// and returns the ctx user tags of the conv.
+pub async fn add_conv_tag(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ConvTagForCreate>,
+) -> Result<DataRpcResult<Vec<Tag>>> {
+	let ParamsForCreate { data: conv_tag_c } = params;
+	let conv_id = conv_tag_c.conv_id;
+
+	ConvBmc::add_tag(&ctx, &mm, conv_tag_c).await?;
+	let tags = ConvBmc::list_tags(&ctx, &mm, conv_id).await?;
+
+	Ok(tags.into())
+}
+
+/// Untags the conv (same params as `add_conv_tag`),
+/// and returns the ctx user tags of the conv.
+pub async fn remove_conv_tag(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<ConvTagForCreate>,
+) -> Result<DataRpcResult<Vec<Tag>>> {
+	let ParamsForCreate { data: conv_tag_c } = params;
+	let conv_id = conv_tag_c.conv_id;
+
+	ConvBmc::remove_tag(&ctx, &mm, conv_tag_c).await?;
+	let tags = ConvBmc::list_tags(&ctx, &mm, conv_id).await?;
+
+	Ok(tags.into())
+}
+
+/// Returns the ctx user tags of the conv `id`.
+pub async fn list_conv_tags(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<Tag>>> {
+	let ParamsIded { id: conv_id } = params;
+
+	let tags = ConvBmc::list_tags(&ctx, &mm, conv_id).await?;
+
+	Ok(tags.into())
+}
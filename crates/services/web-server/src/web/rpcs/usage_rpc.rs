@@ -0,0 +1,45 @@
+use lib_core::generate_usage_quota_rpc_fns;
+use lib_core::model::usage::{
+	UsageBmc, UsageDaily, UsageForReport, UsageQuota, UsageQuotaBmc,
+	UsageQuotaFilter, UsageQuotaForCreate, UsageQuotaForUpdate, UsageRecord,
+};
+use lib_rpc_core::prelude::*;
+
+pub fn rpc_router_builder() -> RouterBuilder {
+	usage_quota_rpc_router_builder().extend(router_builder!(
+		// Same as RpcRouter::new().add...
+		get_usage_report,
+		list_conv_usage,
+	))
+}
+
+// This will generate the usage_quota rpc handlers (root and `Sys` users only)
+// and the `usage_quota_rpc_router_builder()`.
+generate_usage_quota_rpc_fns!();
+
+/// Returns the daily usage, with `"data": {"group_by": "Agent", "days": 7}`
+/// (only the ctx user usage for the non `Sys` users).
+pub async fn get_usage_report(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsForCreate<UsageForReport>,
+) -> Result<DataRpcResult<Vec<UsageDaily>>> {
+	let ParamsForCreate { data: report_q } = params;
+
+	let report = UsageBmc::report(&ctx, &mm, report_q).await?;
+
+	Ok(report.into())
+}
+
+/// Returns the usage records (per AI call) of the conv `id`.
+pub async fn list_conv_usage(
+	ctx: Ctx,
+	mm: ModelManager,
+	params: ParamsIded,
+) -> Result<DataRpcResult<Vec<UsageRecord>>> {
+	let ParamsIded { id: conv_id } = params;
+
+	let records = UsageBmc::list_for_conv(&ctx, &mm, conv_id).await?;
+
+	Ok(records.into())
+}
@@ -1,5 +1,15 @@
 ---- Base app schema
 
+-- Rejects the changes to the append-only tables (see their `*_append_only` triggers)
+-- and immutable rows (e.g., `agent_version_no_update`),
+-- so that a tampering fails rather than silently affecting no rows.
+CREATE FUNCTION reject_append_only_change() RETURNS trigger AS $$
+BEGIN
+  RAISE EXCEPTION '% on append-only table %', TG_OP, TG_TABLE_NAME
+    USING ERRCODE = 'insufficient_privilege';
+END;
+$$ LANGUAGE plpgsql;
+
 -- Org
 CREATE TABLE "org" (
   id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
@@ -20,6 +30,7 @@ CREATE TABLE "user" (
 
   username varchar(128) NOT NULL UNIQUE,
   typ user_typ NOT NULL DEFAULT 'User',
+  org_id BIGINT REFERENCES "org"(id) ON DELETE SET NULL, -- e.g., for the org usage quotas
 
   -- Auth
   pwd varchar(256),
@@ -35,6 +46,10 @@ CREATE TABLE "user" (
 
 -- Agent
 
+-- Who can see, use (start convs), and clone the agent, besides its owner
+-- (see `AgentVisibility`).
+CREATE TYPE agent_visibility AS ENUM ('Private', 'Shared', 'Org', 'Public');
+
 CREATE TABLE agent (
   -- PK
   id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
@@ -44,8 +59,15 @@ CREATE TABLE agent (
 
   -- Properties
   name varchar(256) NOT NULL,
+  visibility agent_visibility NOT NULL default 'Private',
   ai_provider varchar(256) NOT NULL default 'dev', -- For now only support 'dev' provider
   ai_model varchar(256) NOT NULL default 'parrot', -- For now only support 'parrot' model
+  system_prompt text,
+  params jsonb, -- `AgentParams` (e.g., temperature, max_tokens)
+  tools jsonb NOT NULL default '[]', -- The enabled tool names (see `ai::tool::ToolRegistry`)
+  context jsonb, -- `AgentContext` (e.g., max_tokens, strategy)
+  starter_prompts jsonb NOT NULL default '[]', -- The suggested first user msgs (not versioned)
+  version int NOT NULL default 1, -- set by the agent_version triggers below
 
   -- Timestamps
   cid bigint NOT NULL,
@@ -54,6 +76,140 @@ CREATE TABLE agent (
   mtime timestamp with time zone NOT NULL  
 );
 
+-- Agent Versions (immutable snapshots of the agent configuration)
+--
+-- Notes:
+--   - Created by the triggers below on agent insert, and on the agent update
+--     of the configuration columns (which increments `agent.version`).
+--     So, all the agent creates/updates (e.g., base::update_many) are versioned.
+--   - The cid/ctime are the agent mid/mtime of the change.
+CREATE TABLE agent_version (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  agent_id BIGINT NOT NULL,
+
+  -- Properties
+  version int NOT NULL,
+  ai_provider varchar(256) NOT NULL,
+  ai_model varchar(256) NOT NULL,
+  system_prompt text,
+  params jsonb,
+  tools jsonb NOT NULL,
+  context jsonb,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE agent_version ADD CONSTRAINT fk_agent_version_agent
+  FOREIGN KEY (agent_id) REFERENCES "agent"(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE agent_version ADD CONSTRAINT uk_agent_version_agent_id_version
+  UNIQUE (agent_id, version);
+
+-- The versions are immutable (deleted only with their agent).
+CREATE TRIGGER agent_version_no_update BEFORE UPDATE ON agent_version
+  FOR EACH STATEMENT EXECUTE FUNCTION reject_append_only_change();
+
+CREATE FUNCTION agent_set_version() RETURNS trigger AS $$
+BEGIN
+  IF TG_OP = 'INSERT' THEN
+    NEW.version := 1;
+  ELSIF (NEW.ai_provider, NEW.ai_model, NEW.system_prompt, NEW.params, NEW.tools,
+         NEW.context)
+      IS DISTINCT FROM
+      (OLD.ai_provider, OLD.ai_model, OLD.system_prompt, OLD.params, OLD.tools,
+       OLD.context) THEN
+    NEW.version := OLD.version + 1;
+  ELSE
+    NEW.version := OLD.version;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER agent_set_version BEFORE INSERT OR UPDATE ON agent
+  FOR EACH ROW EXECUTE FUNCTION agent_set_version();
+
+CREATE FUNCTION agent_insert_version() RETURNS trigger AS $$
+BEGIN
+  IF TG_OP = 'INSERT' OR NEW.version <> OLD.version THEN
+    INSERT INTO agent_version
+      (agent_id, version, ai_provider, ai_model, system_prompt, params, tools, context,
+       cid, ctime)
+    VALUES
+      (NEW.id, NEW.version, NEW.ai_provider, NEW.ai_model, NEW.system_prompt, NEW.params,
+       NEW.tools, NEW.context, NEW.mid, NEW.mtime);
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER agent_insert_version AFTER INSERT OR UPDATE ON agent
+  FOR EACH ROW EXECUTE FUNCTION agent_insert_version();
+
+-- Agent Share (the users an agent is shared with, for the 'Shared' and 'Org' visibilities)
+CREATE TABLE agent_share (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  agent_id BIGINT NOT NULL,
+  user_id BIGINT NOT NULL,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE agent_share ADD CONSTRAINT fk_agent_share_agent
+  FOREIGN KEY (agent_id) REFERENCES agent(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE agent_share ADD CONSTRAINT fk_agent_share_user
+  FOREIGN KEY (user_id) REFERENCES "user"(id)
+  ON DELETE CASCADE;
+
+-- One share per agent and user (also the unique key of its upsert)
+ALTER TABLE agent_share ADD CONSTRAINT uk_agent_share_agent_id_user_id
+  UNIQUE (agent_id, user_id);
+
+-- For the agents shared with a user (see `acs::agent_visible_sql`)
+CREATE INDEX idx_agent_share_user_id ON agent_share (user_id, agent_id);
+
+-- Conv Template (of an agent, to start a conv with a title and initial msgs)
+CREATE TABLE conv_template (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  agent_id BIGINT NOT NULL,
+
+  -- Properties
+  name varchar(128) NOT NULL,
+  title_pattern varchar(256), -- with `{{var}}` placeholders (see `ConvBmc::create_from_template`)
+  msgs jsonb NOT NULL default '[]', -- `ConvTemplateMsgs` (the initial System/User msgs)
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE conv_template ADD CONSTRAINT fk_conv_template_agent
+  FOREIGN KEY (agent_id) REFERENCES agent(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE conv_template ADD CONSTRAINT uk_conv_template_agent_id_name
+  UNIQUE (agent_id, name);
+
 -- Conv
 CREATE TYPE conv_kind AS ENUM ('OwnerOnly', 'MultiUsers');
 
@@ -66,11 +222,19 @@ CREATE TABLE conv (
   -- FKs
   owner_id BIGINT NOT NULL,
   agent_id BIGINT NOT NULL,
+  agent_version_id BIGINT NOT NULL, -- set on insert by the trigger below
 
   -- Properties
   title varchar(256),
   kind conv_kind NOT NULL default 'OwnerOnly',
   state conv_state NOT NULL default 'Active',
+  folder_id BIGINT, -- the owner folder (see `ConvBmc::move_to_folder`)
+  -- The leaf msg of the active branch (set by the conv_msg trigger, see `ConvBmc::get_branch`)
+  -- Note: No FK, as conv and conv_msg would reference each other.
+  head_msg_id BIGINT,
+
+  -- Full-text search (see `ConvBmc::search_msgs`)
+  title_tsv tsvector GENERATED ALWAYS AS (to_tsvector('english', coalesce(title, ''))) STORED,
 
   -- Timestamps
   cid bigint NOT NULL,
@@ -83,6 +247,150 @@ ALTER TABLE conv ADD CONSTRAINT fk_conv_agent
   FOREIGN KEY (agent_id) REFERENCES "agent"(id)
   ON DELETE CASCADE;
 
+ALTER TABLE conv ADD CONSTRAINT fk_conv_agent_version
+  FOREIGN KEY (agent_version_id) REFERENCES agent_version(id)
+  ON DELETE CASCADE;
+
+-- The conv runs with the agent version current at its creation.
+CREATE FUNCTION conv_set_agent_version_id() RETURNS trigger AS $$
+BEGIN
+  SELECT v.id INTO NEW.agent_version_id
+  FROM agent a JOIN agent_version v ON v.agent_id = a.id AND v.version = a.version
+  WHERE a.id = NEW.agent_id;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER conv_set_agent_version_id BEFORE INSERT ON conv
+  FOR EACH ROW EXECUTE FUNCTION conv_set_agent_version_id();
+
+
+-- Folder (user defined, flat, to organize the owned convs)
+CREATE TABLE folder (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  owner_id BIGINT NOT NULL,
+
+  -- Properties
+  name varchar(64) NOT NULL,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE folder ADD CONSTRAINT fk_folder_owner
+  FOREIGN KEY (owner_id) REFERENCES "user"(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE folder ADD CONSTRAINT uk_folder_owner_id_name
+  UNIQUE (owner_id, name);
+
+ALTER TABLE conv ADD CONSTRAINT fk_conv_folder
+  FOREIGN KEY (folder_id) REFERENCES folder(id)
+  ON DELETE SET NULL;
+
+CREATE INDEX idx_conv_folder_id ON conv (folder_id);
+
+-- Tag (user defined, many-to-many with conv, through conv_tag)
+CREATE TABLE tag (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  owner_id BIGINT NOT NULL,
+
+  -- Properties
+  name varchar(64) NOT NULL,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE tag ADD CONSTRAINT fk_tag_owner
+  FOREIGN KEY (owner_id) REFERENCES "user"(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE tag ADD CONSTRAINT uk_tag_owner_id_name
+  UNIQUE (owner_id, name);
+
+CREATE TABLE conv_tag (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  conv_id BIGINT NOT NULL,
+  tag_id BIGINT NOT NULL,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE conv_tag ADD CONSTRAINT fk_conv_tag_conv
+  FOREIGN KEY (conv_id) REFERENCES "conv"(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE conv_tag ADD CONSTRAINT fk_conv_tag_tag
+  FOREIGN KEY (tag_id) REFERENCES tag(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE conv_tag ADD CONSTRAINT uk_conv_tag_conv_id_tag_id
+  UNIQUE (conv_id, tag_id);
+
+-- For the `ConvFilter.tag_id` subquery
+CREATE INDEX idx_conv_tag_tag_id ON conv_tag (tag_id, conv_id);
+
+-- User Favorite (the convs and agents pinned by a user, listed first)
+--
+-- Note: Per user, so a `MultiUsers` conv can be pinned by each of its users.
+CREATE TABLE user_favorite (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  user_id BIGINT NOT NULL,
+  -- Exactly one of the conv_id or agent_id
+  conv_id BIGINT,
+  agent_id BIGINT,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL,
+
+  CONSTRAINT ck_user_favorite_conv_id_agent_id
+    CHECK (num_nonnulls(conv_id, agent_id) = 1)
+);
+
+ALTER TABLE user_favorite ADD CONSTRAINT fk_user_favorite_user
+  FOREIGN KEY (user_id) REFERENCES "user"(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE user_favorite ADD CONSTRAINT fk_user_favorite_conv
+  FOREIGN KEY (conv_id) REFERENCES "conv"(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE user_favorite ADD CONSTRAINT fk_user_favorite_agent
+  FOREIGN KEY (agent_id) REFERENCES agent(id)
+  ON DELETE CASCADE;
+
+-- Note: The NULL conv_id/agent_id are distinct (so one key per favorite kind).
+ALTER TABLE user_favorite ADD CONSTRAINT uk_user_favorite_user_id_conv_id
+  UNIQUE (user_id, conv_id);
+
+ALTER TABLE user_favorite ADD CONSTRAINT uk_user_favorite_user_id_agent_id
+  UNIQUE (user_id, agent_id);
 
 -- Conv Participants
 CREATE TABLE conv_user (
@@ -96,6 +404,9 @@ CREATE TABLE conv_user (
   -- Machine User Properties
   auto_respond BOOLEAN NOT NULL DEFAULT false,
 
+  -- Read State (see `ConvBmc::mark_read`)
+  last_read_msg_id BIGINT,
+
   -- Timestamps
   cid bigint NOT NULL,
   ctime timestamp with time zone NOT NULL,
@@ -103,7 +414,15 @@ CREATE TABLE conv_user (
   mtime timestamp with time zone NOT NULL    
 );
 
+-- One conv_user per conv and user (also the unique key of its upsert)
+ALTER TABLE conv_user ADD CONSTRAINT uk_conv_user_conv_id_user_id
+  UNIQUE (conv_id, user_id);
+
 -- Conv Messages
+CREATE TYPE conv_msg_content_type AS ENUM ('Text', 'Markdown', 'Json', 'ToolCall', 'ToolResult');
+
+CREATE TYPE conv_msg_role AS ENUM ('User', 'Assistant', 'System', 'Tool');
+
 CREATE TABLE conv_msg (
   -- PK
   id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
@@ -111,9 +430,19 @@ CREATE TABLE conv_msg (
   -- FKs
   conv_id BIGINT NOT NULL,
   user_id BIGINT NOT NULL, -- should be came as cid
+  parent_msg_id BIGINT, -- set on insert by the trigger below (None for the first msg)
 
   -- Properties
-  content varchar(1024) NOT NULL,
+  role conv_msg_role NOT NULL default 'User',
+  content text NOT NULL,
+  content_type conv_msg_content_type NOT NULL default 'Text',
+  metadata jsonb,
+  -- Edit marker (see `ConvBmc::update_msg`)
+  edit_count int NOT NULL default 0,
+  etime timestamp with time zone, -- last edit time
+
+  -- Full-text search (see `ConvBmc::search_msgs`)
+  content_tsv tsvector GENERATED ALWAYS AS (to_tsvector('english', content)) STORED,
 
   -- Timestamps
   cid bigint NOT NULL,
@@ -126,6 +455,353 @@ ALTER TABLE conv_msg ADD CONSTRAINT fk_conv_msg_conv
   FOREIGN KEY (conv_id) REFERENCES "conv"(id)
   ON DELETE CASCADE;
 
+ALTER TABLE conv_msg ADD CONSTRAINT fk_conv_msg_parent
+  FOREIGN KEY (parent_msg_id) REFERENCES conv_msg(id)
+  ON DELETE CASCADE;
+
+CREATE INDEX idx_conv_msg_parent_msg_id ON conv_msg (parent_msg_id);
+
+-- The conv msgs are a tree: a new msg is a child of the conv head msg
+-- (i.e., appended to the active branch), and becomes the new conv head.
+CREATE FUNCTION conv_msg_set_parent() RETURNS trigger AS $$
+BEGIN
+  SELECT head_msg_id INTO NEW.parent_msg_id FROM conv WHERE id = NEW.conv_id FOR UPDATE;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER conv_msg_set_parent BEFORE INSERT ON conv_msg
+  FOR EACH ROW EXECUTE FUNCTION conv_msg_set_parent();
+
+CREATE FUNCTION conv_msg_set_conv_head() RETURNS trigger AS $$
+BEGIN
+  UPDATE conv SET head_msg_id = NEW.id WHERE id = NEW.conv_id;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER conv_msg_set_conv_head AFTER INSERT ON conv_msg
+  FOR EACH ROW EXECUTE FUNCTION conv_msg_set_conv_head();
+
+-- For the conv_msg list keyset pagination (by conv_id, ordered by id or ctime)
+CREATE INDEX idx_conv_msg_conv_id ON conv_msg (conv_id, id);
+CREATE INDEX idx_conv_msg_conv_id_ctime ON conv_msg (conv_id, ctime, id);
+
+-- For the full-text search
+CREATE INDEX idx_conv_msg_content_tsv ON conv_msg USING GIN (content_tsv);
+CREATE INDEX idx_conv_title_tsv ON conv USING GIN (title_tsv);
+CREATE INDEX idx_conv_owner_id ON conv (owner_id);
+CREATE INDEX idx_conv_user_user_id ON conv_user (user_id);
+
 ALTER TABLE conv_user ADD CONSTRAINT fk_conv_user_conv
   FOREIGN KEY (user_id) REFERENCES "user"(id)
   ON DELETE CASCADE;
+
+-- Conv Message Revisions (the prior versions of the edited messages)
+CREATE TABLE conv_msg_revision (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  msg_id BIGINT NOT NULL,
+
+  -- Properties
+  revision int NOT NULL, -- 1 for the original content
+  content text NOT NULL,
+  content_type conv_msg_content_type NOT NULL,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE conv_msg_revision ADD CONSTRAINT fk_conv_msg_revision_conv_msg
+  FOREIGN KEY (msg_id) REFERENCES conv_msg(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE conv_msg_revision ADD CONSTRAINT uk_conv_msg_revision_msg_id_revision
+  UNIQUE (msg_id, revision);
+
+-- Conv Message Reactions (one per user and emoji)
+CREATE TABLE conv_msg_reaction (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  msg_id BIGINT NOT NULL,
+  user_id BIGINT NOT NULL,
+
+  -- Properties
+  emoji varchar(64) NOT NULL,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE conv_msg_reaction ADD CONSTRAINT fk_conv_msg_reaction_conv_msg
+  FOREIGN KEY (msg_id) REFERENCES conv_msg(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE conv_msg_reaction ADD CONSTRAINT fk_conv_msg_reaction_user
+  FOREIGN KEY (user_id) REFERENCES "user"(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE conv_msg_reaction ADD CONSTRAINT uk_conv_msg_reaction_msg_id_user_id_emoji
+  UNIQUE (msg_id, user_id, emoji);
+
+-- Conv Message Attachments
+CREATE TABLE attachment (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  conv_id BIGINT NOT NULL,
+  msg_id BIGINT NOT NULL,
+  owner_id BIGINT NOT NULL,
+
+  -- Properties
+  name varchar(256) NOT NULL,
+  content_type varchar(256) NOT NULL,
+  size bigint NOT NULL,
+  checksum varchar(64) NOT NULL, -- sha256 (lowercase hex)
+  blob_key varchar(128) NOT NULL UNIQUE,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+-- Note: The blobs of the cascade deleted attachments are not deleted from the blob store.
+ALTER TABLE attachment ADD CONSTRAINT fk_attachment_conv_msg
+  FOREIGN KEY (msg_id) REFERENCES conv_msg(id)
+  ON DELETE CASCADE;
+
+CREATE INDEX idx_attachment_msg_id ON attachment (msg_id, id);
+
+-- Conv Summary
+--
+-- The rolling summaries of the conv msgs which do not fit in the agent context
+-- (see `model::conv_context`). Each summary covers all the msgs up to `up_to_msg_id`.
+CREATE TABLE conv_summary (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  conv_id BIGINT NOT NULL,
+
+  -- Properties
+  up_to_msg_id BIGINT NOT NULL, -- the last summarized msg (inclusive)
+  content text NOT NULL,
+  tokens BIGINT NOT NULL,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE conv_summary ADD CONSTRAINT fk_conv_summary_conv
+  FOREIGN KEY (conv_id) REFERENCES "conv"(id)
+  ON DELETE CASCADE;
+
+CREATE INDEX idx_conv_summary_conv_id ON conv_summary (conv_id, up_to_msg_id);
+
+-- Conv Share
+--
+-- The public read-only links to a snapshot of the conv active branch (see `ConvBmc::create_share`).
+-- The token is the link secret, and the share is unavailable once revoked or expired.
+CREATE TABLE conv_share (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  conv_id BIGINT NOT NULL,
+
+  -- Properties
+  token varchar(64) NOT NULL,
+  snapshot jsonb NOT NULL, -- the `ConvShareSnapshot` at the share creation
+  expires_at timestamp with time zone, -- never expires if null
+  revoked_at timestamp with time zone,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE conv_share ADD CONSTRAINT fk_conv_share_conv
+  FOREIGN KEY (conv_id) REFERENCES "conv"(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE conv_share ADD CONSTRAINT uk_conv_share_token
+  UNIQUE (token);
+
+CREATE INDEX idx_conv_share_conv_id ON conv_share (conv_id);
+
+-- Schedule
+--
+-- The recurring agent runs in a conv: at each `cron` (UTC) occurrence, the `prompt` is posted
+-- as a user msg of the owner, followed by the agent reply (see `ScheduleBmc::run_due`).
+-- The `next_run_at` is null when disabled.
+CREATE TABLE schedule (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  owner_id BIGINT NOT NULL,
+  conv_id BIGINT NOT NULL,
+
+  -- Properties
+  name varchar(128) NOT NULL,
+  cron varchar(128) NOT NULL, -- 5 fields (minute hour day-of-month month day-of-week)
+  prompt text NOT NULL,
+  enabled boolean NOT NULL default true,
+  next_run_at timestamp with time zone,
+  last_run_at timestamp with time zone,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE schedule ADD CONSTRAINT fk_schedule_owner
+  FOREIGN KEY (owner_id) REFERENCES "user"(id)
+  ON DELETE CASCADE;
+
+ALTER TABLE schedule ADD CONSTRAINT fk_schedule_conv
+  FOREIGN KEY (conv_id) REFERENCES "conv"(id)
+  ON DELETE CASCADE;
+
+CREATE INDEX idx_schedule_owner_id ON schedule (owner_id);
+
+-- For the due schedules (see `ScheduleBmc::run_due`)
+CREATE INDEX idx_schedule_next_run_at ON schedule (next_run_at) WHERE next_run_at IS NOT NULL;
+
+-- Schedule Run (the run history of a schedule)
+CREATE TYPE schedule_run_status AS ENUM ('Running', 'Succeeded', 'Failed');
+
+CREATE TABLE schedule_run (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- FKs
+  schedule_id BIGINT NOT NULL,
+
+  -- Properties
+  status schedule_run_status NOT NULL default 'Running',
+  scheduled_at timestamp with time zone NOT NULL, -- the cron occurrence of the run
+  finished_at timestamp with time zone,
+  reply_msg_id BIGINT, -- the agent reply msg (if succeeded)
+  error text, -- the error description (if failed)
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE schedule_run ADD CONSTRAINT fk_schedule_run_schedule
+  FOREIGN KEY (schedule_id) REFERENCES schedule(id)
+  ON DELETE CASCADE;
+
+CREATE INDEX idx_schedule_run_schedule_id ON schedule_run (schedule_id, id);
+
+-- Audit Log
+CREATE TYPE audit_action AS ENUM ('Create', 'Update', 'Delete');
+
+CREATE TABLE audit_log (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- Properties
+  entity varchar(128) NOT NULL,
+  entity_id BIGINT NOT NULL,
+  user_id BIGINT NOT NULL, -- the Ctx user_id which performed the action
+  action audit_action NOT NULL,
+  diff jsonb NOT NULL DEFAULT '{}',
+
+  -- Timestamp
+  -- (append-only, so only the creation time)
+  ctime timestamp with time zone NOT NULL
+);
+
+CREATE INDEX idx_audit_log_entity ON audit_log (entity, entity_id);
+CREATE INDEX idx_audit_log_user ON audit_log (user_id);
+
+-- Append-only
+CREATE TRIGGER audit_log_append_only BEFORE UPDATE OR DELETE ON audit_log
+  FOR EACH STATEMENT EXECUTE FUNCTION reject_append_only_change();
+
+-- Usage
+--
+-- Notes:
+--   - One `usage_record` per AI call of an agent reply (see `ConvBmc::generate_reply`),
+--     with the first `conv_msg` of its reply step.
+--   - No FKs to the user/agent/conv, so that the usage is kept when they are deleted.
+--   - The `cost_micros` are millionths of USD.
+CREATE TABLE usage_record (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- Relations
+  user_id BIGINT NOT NULL, -- the Ctx user_id which requested the reply
+  org_id BIGINT, -- the user org at the time of the reply
+  agent_id BIGINT NOT NULL,
+  conv_id BIGINT NOT NULL,
+  msg_id BIGINT,
+
+  -- Properties
+  ai_provider varchar(256) NOT NULL,
+  ai_model varchar(256) NOT NULL,
+  prompt_tokens BIGINT NOT NULL,
+  completion_tokens BIGINT NOT NULL,
+  cost_micros BIGINT NOT NULL,
+
+  -- Timestamp
+  -- (append-only, so only the creation time)
+  ctime timestamp with time zone NOT NULL
+);
+
+CREATE INDEX idx_usage_record_user_ctime ON usage_record (user_id, ctime);
+CREATE INDEX idx_usage_record_agent_ctime ON usage_record (agent_id, ctime);
+CREATE INDEX idx_usage_record_org_ctime ON usage_record (org_id, ctime);
+CREATE INDEX idx_usage_record_conv_id ON usage_record (conv_id);
+
+-- Append-only
+CREATE TRIGGER usage_record_append_only BEFORE UPDATE OR DELETE ON usage_record
+  FOR EACH STATEMENT EXECUTE FUNCTION reject_append_only_change();
+
+CREATE TYPE usage_scope AS ENUM ('User', 'Agent', 'Org');
+
+-- The daily (UTC) usage limits, per user, agent, or org (NULL max for no limit).
+CREATE TABLE usage_quota (
+  -- PK
+  id BIGINT GENERATED BY DEFAULT AS IDENTITY (START WITH 1000) PRIMARY KEY,
+
+  -- Properties
+  scope usage_scope NOT NULL,
+  scope_id BIGINT NOT NULL,
+  daily_tokens_max BIGINT,
+  daily_cost_micros_max BIGINT,
+
+  -- Timestamps
+  cid bigint NOT NULL,
+  ctime timestamp with time zone NOT NULL,
+  mid bigint NOT NULL,
+  mtime timestamp with time zone NOT NULL
+);
+
+ALTER TABLE usage_quota ADD CONSTRAINT uk_usage_quota_scope_scope_id
+  UNIQUE (scope, scope_id);
@@ -8,8 +8,8 @@ INSERT INTO "user"
     (username, cid, ctime, mid, mtime) VALUES 
     ('demo1',  0,   now(), 0,   now());
 
--- Agent mock-01 (with 'parrot' model) (id: 100)
+-- Agent mock-01 (with 'parrot' model) (id: 100), usable by all the users
 INSERT INTO "agent"    
-    (id,  owner_id, name,      cid, ctime, mid, mtime) VALUES
-    (100, 0,        'mock-01', 0,   now(), 0,   now());
+    (id,  owner_id, name,      visibility, starter_prompts,                    cid, ctime, mid, mtime) VALUES
+    (100, 0,        'mock-01', 'Public',   '["Hello!", "Repeat after me: ..."]', 0,   now(), 0,   now());
 